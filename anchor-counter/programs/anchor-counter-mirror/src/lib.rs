@@ -0,0 +1,110 @@
+//! An anchor counter mirror program.
+//!
+//! It keeps the mirrored count of the anchor counter program `State`
+//! account, updated by the counter program through CPI on every
+//! increment.
+use anchor_lang::prelude::*;
+
+declare_id!("4uHFoZFB9r3kobW7jUNUk4Up44U6N8m4N9aFJP65X5e9");
+
+/// The anchor counter program, which is the only program allowed
+/// to update the mirror.
+pub mod counter {
+    use super::*;
+
+    declare_id!("3GoNh65e4YAHNnadkNNfUGM9VVxeV4U4NxiWQ2TFVG37");
+}
+
+/// An anchor counter mirror program.
+#[program]
+pub mod anchor_counter_mirror {
+    use super::*;
+
+    /// Initialize the `Mirror` for the counter `State` account.
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        let mirror = &mut ctx.accounts.mirror;
+        mirror.counter = ctx.accounts.counter.key();
+        mirror.count = 0;
+        mirror.updates = 0;
+        mirror.bump = *ctx.bumps.get("mirror").unwrap();
+        Ok(())
+    }
+
+    /// Mirror the counter `State::count`, called by the counter
+    /// program on every increment.
+    pub fn on_increment(ctx: Context<OnIncrement>, count: u64) -> Result<()> {
+        let mirror = &mut ctx.accounts.mirror;
+        mirror.count = count;
+        mirror.updates += 1;
+        Ok(())
+    }
+}
+
+/// An initialization instruction accounts to initialize a
+/// `Mirror` account for the counter `State` account.
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    /// A mirror data account of the counter `State` account.
+    #[account(
+        init,
+        payer = payer,
+        space = Mirror::SPACE,
+        seeds = [b"mirror", counter.key().as_ref()],
+        bump
+    )]
+    mirror: Account<'info, Mirror>,
+
+    /// CHECK: A counter `State` account to be mirrored.
+    #[account(owner = counter::ID)]
+    counter: UncheckedAccount<'info>,
+
+    /// A payer of the rent and the transaction fees.
+    #[account(mut)]
+    payer: Signer<'info>,
+
+    /// System program to create a mirror data account.
+    system_program: Program<'info, System>,
+}
+
+/// An increment notification instruction accounts, signed by the
+/// counter program.
+#[derive(Accounts)]
+pub struct OnIncrement<'info> {
+    /// The counter program PDA signer, e.g. `[b"emitter"]`.
+    #[account(seeds = [b"emitter"], bump, seeds::program = counter::ID)]
+    emitter: Signer<'info>,
+
+    /// A mirror of the counter `State` account.
+    #[account(
+        mut,
+        has_one = counter,
+        seeds = [b"mirror", counter.key().as_ref()],
+        bump = mirror.bump
+    )]
+    mirror: Account<'info, Mirror>,
+
+    /// CHECK: A counter `State` account incremented.
+    counter: UncheckedAccount<'info>,
+}
+
+/// A mirror of the counter `State` account.
+#[account]
+pub struct Mirror {
+    /// A counter `State` account to be mirrored.
+    pub counter: Pubkey,
+
+    /// A mirrored `State::count`.
+    pub count: u64,
+
+    /// A number of the increment notifications.
+    pub updates: u64,
+
+    /// A bump of the `Mirror` PDA.
+    pub bump: u8,
+}
+
+impl Mirror {
+    /// 8 bytes for anchor, 32 bytes for `counter`, 8 bytes for `count`,
+    /// 8 bytes for `updates`, and one byte for `bump` member.
+    const SPACE: usize = 8 + 32 + 8 + 8 + 1;
+}
@@ -0,0 +1,87 @@
+//! Instruction builders of the counter program.
+//!
+//! The other programs invoke the counter program through the anchor
+//! generated `cpi` module, available with the `cpi` feature, e.g.
+//!
+//! ```toml
+//! anchor-counter = { path = "../anchor-counter", features = ["cpi"] }
+//! ```
+//!
+//! and these builders take care of the PDAs required by the
+//! instructions, both for the CPI callers and the off-chain clients.
+//!
+//! The CPI caller signs the `increment` instruction by its own PDA,
+//! which should be the authority or the active lessee of the counter
+//! `State` account, and owned by the system program to pay the
+//! increment fee, if any.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::{system_program, InstructionData};
+
+use crate::{accounts, instruction, Vault};
+
+/// Returns the mirror program PDA signer, e.g. `[b"emitter"]`, and
+/// the bump.
+pub fn emitter_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"emitter"], &crate::id())
+}
+
+/// Returns the `Mirror` PDA of the counter `State` account and the bump.
+pub fn mirror_address(state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"mirror", state.as_ref()], &anchor_counter_mirror::id())
+}
+
+/// Returns the `increment` and `increment_by` instruction accounts.
+pub fn increment_accounts(state: Pubkey, signer: Pubkey) -> accounts::Increment {
+    accounts::Increment {
+        state,
+        signer,
+        vault: Vault::address(&state).0,
+        emitter: emitter_address().0,
+        mirror: mirror_address(&state).0,
+        mirror_program: anchor_counter_mirror::id(),
+        system_program: system_program::ID,
+    }
+}
+
+/// Returns the `increment` instruction signed by the `signer`.
+pub fn increment(state: Pubkey, signer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: increment_accounts(state, signer).to_account_metas(None),
+        data: instruction::Increment {}.data(),
+    }
+}
+
+/// Returns the `increment_by` instruction signed by the `signer`.
+pub fn increment_by(state: Pubkey, signer: Pubkey, amount: u64) -> Instruction {
+    Instruction {
+        program_id: crate::id(),
+        accounts: increment_accounts(state, signer).to_account_metas(None),
+        data: instruction::IncrementBy { amount }.data(),
+    }
+}
+
+/// Returns the `batch_increment` instruction of the `states` signed by
+/// the `signer`.
+pub fn batch_increment(states: &[Pubkey], signer: Pubkey) -> Instruction {
+    let mut accounts = accounts::BatchIncrement {
+        signer,
+        emitter: emitter_address().0,
+        mirror_program: anchor_counter_mirror::id(),
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None);
+    for state in states {
+        accounts.extend([
+            AccountMeta::new(*state, false),
+            AccountMeta::new(Vault::address(state).0, false),
+            AccountMeta::new(mirror_address(state).0, false),
+        ]);
+    }
+    Instruction {
+        program_id: crate::id(),
+        accounts,
+        data: instruction::BatchIncrement {}.data(),
+    }
+}
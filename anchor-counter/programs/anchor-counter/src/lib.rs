@@ -1,21 +1,495 @@
 //! An anchor counter program.
+use anchor_counter_mirror::program::AnchorCounterMirror;
+use anchor_counter_mirror::Mirror;
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::solana_program::system_instruction;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+
+pub mod builder;
 
 declare_id!("3GoNh65e4YAHNnadkNNfUGM9VVxeV4U4NxiWQ2TFVG37");
 
+#[error_code]
+pub enum Error {
+    #[msg("Too many authorities for a single batch")]
+    TooManyAuthorities,
+
+    #[msg("Authorities and the remaining accounts length mismatch")]
+    AuthoritiesMismatch,
+
+    #[msg("Invalid counter state address")]
+    InvalidStateAddress,
+
+    #[msg("The signer is neither the authority nor the active lessee")]
+    NotAuthorized,
+
+    #[msg("The lease expiry should be in the future")]
+    InvalidLeaseExpiry,
+
+    #[msg("Counter overflow")]
+    CountOverflow,
+
+    #[msg("The counter state is not in the v1 layout")]
+    NotV1State,
+
+    #[msg("The expiry should be in the future")]
+    InvalidExpiry,
+
+    #[msg("The counter state is not expired yet")]
+    NotExpired,
+
+    #[msg("The history account is not of the counter state")]
+    InvalidHistory,
+
+    #[msg("The remaining accounts are not the counter triples for a single batch")]
+    InvalidBatch,
+
+    #[msg("The vault account is not of the counter state")]
+    InvalidVault,
+
+    #[msg("The signer is not the pending authority")]
+    NotPendingAuthority,
+}
+
+/// Seconds in a UTC day of the `State::tallies` buckets.
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// The number of the days kept in `State::tallies`.
+///
+/// It's not an associated const of `State`, as the IDL only resolves
+/// the array lengths of the crate level consts.
+pub const TALLY_DAYS: usize = 30;
+
 /// An anchor counter program.
 #[program]
 pub mod anchor_counter {
     use super::*;
 
     /// Initialize the counter `State` for the specified address.
-    pub fn initialize(_ctx: Context<Initialize>) -> Result<()> {
+    ///
+    /// The `fee` lamports are charged to the signer on every increment
+    /// and kept in the `Vault` PDA until the authority withdraws those.
+    /// Zero for no fee.
+    ///
+    /// The `mode` decides who can increment the counter, e.g. the
+    /// authority and the lessee only, or anyone.
+    pub fn initialize(ctx: Context<Initialize>, fee: u64, mode: Mode) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        state.authority = ctx.accounts.authority.key();
+        state.fee = fee;
+        state.mode = mode;
+        state.touch()?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.state = state.key();
+        vault.bump = *ctx.bumps.get("vault").unwrap();
+        Ok(())
+    }
+
+    /// Initialize the counter `State` PDAs for the list of authorities
+    /// in a single transaction.
+    ///
+    /// The `State` PDA for each authority, e.g. `[b"state", authority]`,
+    /// should be passed through the `remaining_accounts` in the same
+    /// order as the `authorities`.  The admin pays the rent for all the
+    /// counters.
+    pub fn initialize_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializeMany<'info>>,
+        authorities: Vec<Pubkey>,
+    ) -> Result<()> {
+        require_gte!(
+            State::MAX_BATCH,
+            authorities.len(),
+            Error::TooManyAuthorities
+        );
+        require_eq!(
+            authorities.len(),
+            ctx.remaining_accounts.len(),
+            Error::AuthoritiesMismatch
+        );
+
+        let admin = &ctx.accounts.admin;
+        let rent = Rent::get()?.minimum_balance(State::SPACE);
+        let clock = Clock::get()?;
+        for (authority, state) in authorities.iter().zip(ctx.remaining_accounts) {
+            let (addr, bump) = State::address(authority);
+            require_keys_eq!(addr, state.key(), Error::InvalidStateAddress);
+
+            // Tops up the rent, allocates, and assigns the account
+            // instead of `create_account`, which fails on the `State`
+            // PDA funded by anyone in advance.
+            let accounts = [admin.to_account_info(), state.clone()];
+            let seed = [b"state", authority.as_ref(), &[bump]];
+
+            // CPI.
+            let lamports = rent.saturating_sub(state.lamports());
+            if lamports > 0 {
+                let ix = system_instruction::transfer(&admin.key(), &addr, lamports);
+                invoke(&ix, &accounts)?;
+            }
+            let ix = system_instruction::allocate(&addr, State::SPACE as u64);
+            invoke_signed(&ix, &accounts, &[&seed])?;
+            let ix = system_instruction::assign(&addr, &id());
+            invoke_signed(&ix, &accounts, &[&seed])?;
+
+            let data = State {
+                count: 0,
+                authority: *authority,
+                bump,
+                lessee: Pubkey::default(),
+                lease_until: 0,
+                fee: 0,
+                last_slot: clock.slot,
+                last_timestamp: clock.unix_timestamp,
+                expires_at: 0,
+                beneficiary: Pubkey::default(),
+                mode: Mode::Permissioned,
+                tally_enabled: false,
+                tallies: Default::default(),
+                last_incremented_by: Pubkey::default(),
+                pending_authority: Pubkey::default(),
+            };
+            data.try_serialize(&mut &mut state.try_borrow_mut_data()?[..])?;
+        }
+
         Ok(())
     }
 
+    /// Lease the increment rights to `to` until the `until_ts` unix
+    /// timestamp.
+    ///
+    /// The lease expires automatically, and the lease to the default
+    /// pubkey revokes the current lease.
+    pub fn lease(ctx: Context<Lease>, to: Pubkey, until_ts: i64) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        if to == Pubkey::default() {
+            state.lessee = to;
+            state.lease_until = 0;
+            return state.touch();
+        }
+        require_gt!(
+            until_ts,
+            Clock::get()?.unix_timestamp,
+            Error::InvalidLeaseExpiry
+        );
+        state.lessee = to;
+        state.lease_until = until_ts;
+        state.touch()
+    }
+
     /// Increment the counter `State` by one.
-    pub fn increment(ctx: Context<Increment>) -> Result<()> {
-        ctx.accounts.state.count += 1;
+    ///
+    /// It's allowed to the authority and the active lessee.  It notifies
+    /// the new count to the mirror program through CPI, signed by the
+    /// `[b"emitter"]` PDA.
+    pub fn increment<'info>(ctx: Context<'_, '_, '_, 'info, Increment<'info>>) -> Result<()> {
+        increment_by(ctx, 1)
+    }
+
+    /// Increment the counter `State` by `amount`.
+    ///
+    /// It returns `Error::CountOverflow` instead of wrapping around.
+    ///
+    /// The optional `History` PDA of the `State`, passed as the first
+    /// remaining account, records the snapshot of the new count.
+    pub fn increment_by<'info>(
+        ctx: Context<'_, '_, '_, 'info, Increment<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        // Charges the increment fee to the vault.
+        let fee = ctx.accounts.state.fee;
+        if fee > 0 {
+            let cpi = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.signer.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            );
+            system_program::transfer(cpi, fee)?;
+        }
+
+        let state = &mut ctx.accounts.state;
+        let old_count = state.count;
+        state.count = state
+            .count
+            .checked_add(amount)
+            .ok_or(Error::CountOverflow)?;
+        state.touch()?;
+        state.tally(amount);
+        state.last_incremented_by = ctx.accounts.signer.key();
+
+        if let Some(history) = ctx.remaining_accounts.first() {
+            let mut history: Account<History> = Account::try_from(history)?;
+            require_keys_eq!(history.state, state.key(), Error::InvalidHistory);
+            history.push(state.last_slot, state.count);
+            history.exit(&id())?;
+        }
+
+        emit!(CounterChanged {
+            state: state.key(),
+            old_count,
+            new_count: state.count,
+            signer: ctx.accounts.signer.key(),
+            slot: state.last_slot,
+            last_incremented_by: state.last_incremented_by,
+        });
+
+        let bump = *ctx.bumps.get("emitter").unwrap();
+        let seeds = [b"emitter".as_ref(), &[bump]];
+        let signer = [&seeds[..]];
+        let cpi = CpiContext::new_with_signer(
+            ctx.accounts.mirror_program.to_account_info(),
+            anchor_counter_mirror::cpi::accounts::OnIncrement {
+                emitter: ctx.accounts.emitter.to_account_info(),
+                mirror: ctx.accounts.mirror.to_account_info(),
+                counter: state.to_account_info(),
+            },
+            &signer,
+        );
+        anchor_counter_mirror::cpi::on_increment(cpi, state.count)
+    }
+
+    /// Increment the counter `State`s in a batch by one.
+    ///
+    /// The `(State, Vault, Mirror)` triples of the counters, up to
+    /// `State::MAX_BATCH`, should be passed through the
+    /// `remaining_accounts`.  Each counter is gated and charged the fee
+    /// as in `increment`, but without the `History` snapshot.
+    pub fn batch_increment<'info>(
+        ctx: Context<'_, '_, '_, 'info, BatchIncrement<'info>>,
+    ) -> Result<()> {
+        let triples = ctx.remaining_accounts.chunks_exact(3);
+        require!(
+            triples.remainder().is_empty() && triples.len() <= State::MAX_BATCH,
+            Error::InvalidBatch
+        );
+
+        let signer = &ctx.accounts.signer;
+        let now = Clock::get()?.unix_timestamp;
+        let bump = *ctx.bumps.get("emitter").unwrap();
+        let seeds = [b"emitter".as_ref(), &[bump]];
+        let emitter_signer = [&seeds[..]];
+        for triple in triples {
+            let (state, vault, mirror) = (&triple[0], &triple[1], &triple[2]);
+            let mut state: Account<State> = Account::try_from(state)?;
+            require!(state.can_increment(signer.key, now), Error::NotAuthorized);
+
+            // Charges the increment fee to the vault.
+            if state.fee > 0 {
+                let vault: Account<Vault> = Account::try_from(vault)?;
+                require_keys_eq!(vault.state, state.key(), Error::InvalidVault);
+                let cpi = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: signer.to_account_info(),
+                        to: vault.to_account_info(),
+                    },
+                );
+                system_program::transfer(cpi, state.fee)?;
+            }
+
+            let old_count = state.count;
+            state.count = state.count.checked_add(1).ok_or(Error::CountOverflow)?;
+            state.touch()?;
+            state.tally(1);
+            state.last_incremented_by = signer.key();
+            state.exit(&id())?;
+
+            emit!(CounterChanged {
+                state: state.key(),
+                old_count,
+                new_count: state.count,
+                signer: signer.key(),
+                slot: state.last_slot,
+                last_incremented_by: state.last_incremented_by,
+            });
+
+            let cpi = CpiContext::new_with_signer(
+                ctx.accounts.mirror_program.to_account_info(),
+                anchor_counter_mirror::cpi::accounts::OnIncrement {
+                    emitter: ctx.accounts.emitter.to_account_info(),
+                    mirror: mirror.clone(),
+                    counter: state.to_account_info(),
+                },
+                &emitter_signer,
+            );
+            anchor_counter_mirror::cpi::on_increment(cpi, state.count)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reset the counter `State::count` to zero.
+    ///
+    /// It's only allowed to the authority, and emits `CounterReset`
+    /// with the discarded count.
+    pub fn reset(ctx: Context<Reset>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        let discarded = state.count;
+        state.count = 0;
+        state.touch()?;
+
+        emit!(CounterReset {
+            state: state.key(),
+            authority: state.authority,
+            discarded,
+        });
+        emit!(CounterChanged {
+            state: state.key(),
+            old_count: discarded,
+            new_count: 0,
+            signer: ctx.accounts.authority.key(),
+            slot: state.last_slot,
+            last_incremented_by: state.last_incremented_by,
+        });
+
+        Ok(())
+    }
+
+    /// Initialize the `History` PDA of the counter `State`, which keeps
+    /// the recent `(slot, count)` snapshots.
+    pub fn initialize_history(ctx: Context<InitializeHistory>) -> Result<()> {
+        let history = &mut ctx.accounts.history;
+        history.state = ctx.accounts.state.key();
+        history.bump = *ctx.bumps.get("history").unwrap();
+        Ok(())
+    }
+
+    /// Record the current `(slot, count)` snapshot of the counter
+    /// `State` in the `History`.
+    ///
+    /// It's permissionless, as it only records the current state.
+    pub fn snapshot(ctx: Context<TakeSnapshot>) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        let count = ctx.accounts.state.count;
+        ctx.accounts.history.push(slot, count);
+        Ok(())
+    }
+
+    /// Withdraw the increment fees kept in the `Vault` to the authority.
+    ///
+    /// The vault keeps the rent-exempt lamports.
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>) -> Result<()> {
+        let vault = ctx.accounts.vault.to_account_info();
+        let authority = ctx.accounts.authority.to_account_info();
+        let rent = Rent::get()?.minimum_balance(Vault::SPACE);
+        let fees = vault.lamports().saturating_sub(rent);
+
+        **vault.try_borrow_mut_lamports()? -= fees;
+        **authority.try_borrow_mut_lamports()? += fees;
+        Ok(())
+    }
+
+    /// Propose the `new` authority of the counter `State`, which takes
+    /// effect once the `new` authority accepts it by `accept_authority`.
+    ///
+    /// The default pubkey cancels the pending proposal.
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, new: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        state.pending_authority = new;
+        state.touch()
+    }
+
+    /// Accept the authority of the counter `State` proposed by
+    /// `propose_authority`.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        state.authority = ctx.accounts.pending_authority.key();
+        state.pending_authority = Pubkey::default();
+        state.touch()
+    }
+
+    /// Close the counter `State` and give the rent back to the authority.
+    ///
+    /// It closes the `Vault` as well, if any, with the remaining fees.
+    pub fn close(ctx: Context<Close>) -> Result<()> {
+        Vault::close(&ctx.accounts.vault, &ctx.accounts.authority)
+    }
+
+    /// Set the expiry of the counter `State`, after which anyone can
+    /// `reap` the account for the `beneficiary`.
+    ///
+    /// The zero `expires_at` clears the expiry.
+    pub fn set_expiry(ctx: Context<SetExpiry>, expires_at: i64, beneficiary: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        if expires_at != 0 {
+            require_gt!(
+                expires_at,
+                Clock::get()?.unix_timestamp,
+                Error::InvalidExpiry
+            );
+        }
+        state.expires_at = expires_at;
+        state.beneficiary = beneficiary;
+        state.touch()
+    }
+
+    /// Enable or disable the per-UTC-day tallies of the increments in
+    /// `State::tallies`.
+    pub fn set_tally(ctx: Context<SetTally>, enabled: bool) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        state.tally_enabled = enabled;
+        state.touch()
+    }
+
+    /// Close the expired counter `State` and give the rent back to the
+    /// `State::beneficiary`.
+    ///
+    /// It's permissionless, e.g. for the garbage collection of the
+    /// abandoned counters, and closes the `Vault` as well, if any.
+    pub fn reap(ctx: Context<Reap>) -> Result<()> {
+        Vault::close(&ctx.accounts.vault, &ctx.accounts.beneficiary)
+    }
+
+    /// Migrate the v1 counter `State`, which keeps the `count` in `u8`,
+    /// to the current layout.
+    ///
+    /// The v1 `State` doesn't have the authority, so the `State` account
+    /// key signs the migration and the signed `authority` becomes the
+    /// authority of the counter.  It reallocs the account, paid by the
+    /// authority, and copies the count over.
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let authority = &ctx.accounts.authority;
+        let old = StateV1::try_from_slice(&state.try_borrow_data()?)?;
+
+        let lamports = Rent::get()?
+            .minimum_balance(State::SPACE)
+            .saturating_sub(state.lamports());
+        if lamports > 0 {
+            let cpi = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: authority.to_account_info(),
+                    to: state.to_account_info(),
+                },
+            );
+            system_program::transfer(cpi, lamports)?;
+        }
+        state.realloc(State::SPACE, false)?;
+
+        let clock = Clock::get()?;
+        let data = State {
+            count: old.count as u64,
+            authority: authority.key(),
+            bump: 0,
+            lessee: Pubkey::default(),
+            lease_until: 0,
+            fee: 0,
+            last_slot: clock.slot,
+            last_timestamp: clock.unix_timestamp,
+            expires_at: 0,
+            beneficiary: Pubkey::default(),
+            mode: Mode::Permissioned,
+            tally_enabled: false,
+            tallies: Default::default(),
+            last_incremented_by: Pubkey::default(),
+            pending_authority: Pubkey::default(),
+        };
+        data.try_serialize(&mut &mut state.try_borrow_mut_data()?[..])?;
         Ok(())
     }
 }
@@ -28,6 +502,16 @@ pub struct Initialize<'info> {
     #[account(init, payer = authority, space = State::SPACE)]
     state: Account<'info, State>,
 
+    /// A fee vault PDA of the counter `State` account.
+    #[account(
+        init,
+        payer = authority,
+        space = Vault::SPACE,
+        seeds = [b"vault", state.key().as_ref()],
+        bump,
+    )]
+    vault: Account<'info, Vault>,
+
     /// An authority of the counter `State` account, who
     /// pays the rent and the transaction fees.
     #[account(mut)]
@@ -37,22 +521,550 @@ pub struct Initialize<'info> {
     system_program: Program<'info, System>,
 }
 
+/// A batch initialization instruction accounts to initialize
+/// the counter `State` PDAs passed through the `remaining_accounts`.
+#[derive(Accounts)]
+pub struct InitializeMany<'info> {
+    /// An admin, who pays the rent and the transaction fees.
+    #[account(mut)]
+    admin: Signer<'info>,
+
+    /// System program to create state data accounts.
+    system_program: Program<'info, System>,
+}
+
+/// A lease instruction accounts to lease the increment rights.
+#[derive(Accounts)]
+pub struct Lease<'info> {
+    /// A state data account to lease.
+    #[account(mut, has_one = authority)]
+    state: Account<'info, State>,
+
+    /// An authority of the counter `State` account.
+    authority: Signer<'info>,
+}
+
 /// An increment instruction to counts up the `State::count`
 /// by one.
+///
+/// It's gated by the `State::authority` as well as the active
+/// lessee, hence the `signer` instead of `has_one = authority`,
+/// unless the counter is in the `Mode::Permissionless` mode.
 #[derive(Accounts)]
 pub struct Increment<'info> {
+    #[account(
+        mut,
+        constraint = state.can_increment(
+            signer.key,
+            Clock::get()?.unix_timestamp,
+        ) @ Error::NotAuthorized,
+    )]
+    state: Account<'info, State>,
+
+    /// The authority or the active lessee of the `State` account,
+    /// who pays the increment fee.
+    #[account(mut)]
+    signer: Signer<'info>,
+
+    /// CHECK: A fee vault PDA of the counter `State` account, which
+    /// only exists for the `State` created by `initialize`.
+    #[account(mut, seeds = [b"vault", state.key().as_ref()], bump)]
+    vault: UncheckedAccount<'info>,
+
+    /// CHECK: A PDA signer of the mirror program CPI.
+    #[account(seeds = [b"emitter"], bump)]
+    emitter: UncheckedAccount<'info>,
+
+    /// A mirror of the counter `State` account.
+    #[account(mut)]
+    mirror: Account<'info, Mirror>,
+
+    /// The mirror program to be notified.
+    mirror_program: Program<'info, AnchorCounterMirror>,
+
+    /// System program to transfer the increment fee.
+    system_program: Program<'info, System>,
+}
+
+/// A batch increment instruction accounts to count up the `State`s
+/// passed through the `remaining_accounts`.
+#[derive(Accounts)]
+pub struct BatchIncrement<'info> {
+    /// The authority or the active lessee of all the `State` accounts,
+    /// who pays the increment fees.
+    #[account(mut)]
+    signer: Signer<'info>,
+
+    /// CHECK: A PDA signer of the mirror program CPI.
+    #[account(seeds = [b"emitter"], bump)]
+    emitter: UncheckedAccount<'info>,
+
+    /// The mirror program to be notified.
+    mirror_program: Program<'info, AnchorCounterMirror>,
+
+    /// System program to transfer the increment fees.
+    system_program: Program<'info, System>,
+}
+
+/// A reset instruction accounts to reset the `State::count`.
+#[derive(Accounts)]
+pub struct Reset<'info> {
+    /// A state data account to reset.
+    #[account(mut, has_one = authority)]
+    state: Account<'info, State>,
+
+    /// An authority of the counter `State` account.
+    authority: Signer<'info>,
+}
+
+/// A history initialization instruction accounts to initialize the
+/// `History` PDA of the counter `State` account.
+#[derive(Accounts)]
+pub struct InitializeHistory<'info> {
+    /// A state data account of the history.
+    #[account(has_one = authority)]
+    state: Account<'info, State>,
+
+    /// A history PDA of the counter `State` account.
+    #[account(
+        init,
+        payer = authority,
+        space = History::SPACE,
+        seeds = [b"history", state.key().as_ref()],
+        bump,
+    )]
+    history: Box<Account<'info, History>>,
+
+    /// An authority of the counter `State` account, who pays the rent.
     #[account(mut)]
+    authority: Signer<'info>,
+
+    /// System program to create a history data account.
+    system_program: Program<'info, System>,
+}
+
+/// A snapshot instruction accounts to record the `State::count`.
+#[derive(Accounts)]
+pub struct TakeSnapshot<'info> {
+    /// A state data account to record.
     state: Account<'info, State>,
+
+    /// A history PDA of the counter `State` account.
+    #[account(
+        mut,
+        has_one = state,
+        seeds = [b"history", state.key().as_ref()],
+        bump = history.bump,
+    )]
+    history: Box<Account<'info, History>>,
+}
+
+/// A withdraw instruction accounts to withdraw the increment fees.
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    /// A state data account of the vault.
+    #[account(has_one = authority)]
+    state: Account<'info, State>,
+
+    /// A fee vault PDA of the counter `State` account.
+    #[account(
+        mut,
+        has_one = state,
+        seeds = [b"vault", state.key().as_ref()],
+        bump = vault.bump,
+    )]
+    vault: Account<'info, Vault>,
+
+    /// An authority of the counter `State` account, who gets the fees.
+    #[account(mut)]
+    authority: Signer<'info>,
+}
+
+/// A close instruction accounts to close the counter `State` account.
+#[derive(Accounts)]
+pub struct Close<'info> {
+    /// A state data account to close.
+    #[account(mut, has_one = authority, close = authority)]
+    state: Account<'info, State>,
+
+    /// CHECK: A fee vault PDA of the counter `State` account, which
+    /// only exists for the `State` created by `initialize`.
+    #[account(mut, seeds = [b"vault", state.key().as_ref()], bump)]
+    vault: UncheckedAccount<'info>,
+
+    /// An authority of the counter `State` account, who gets the
+    /// rent back.
+    #[account(mut)]
+    authority: Signer<'info>,
+}
+
+/// An expiry instruction accounts to set the `State::expires_at`.
+#[derive(Accounts)]
+pub struct SetExpiry<'info> {
+    /// A state data account to expire.
+    #[account(mut, has_one = authority)]
+    state: Account<'info, State>,
+
+    /// An authority of the counter `State` account.
+    authority: Signer<'info>,
+}
+
+/// A tally instruction accounts to set the `State::tally_enabled`.
+#[derive(Accounts)]
+pub struct SetTally<'info> {
+    /// A state data account to tally.
+    #[account(mut, has_one = authority)]
+    state: Account<'info, State>,
+
+    /// An authority of the counter `State` account.
+    authority: Signer<'info>,
+}
+
+/// An authority proposal instruction accounts to set the
+/// `State::pending_authority`.
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    /// A state data account to transfer.
+    #[account(mut, has_one = authority)]
+    state: Account<'info, State>,
+
+    /// An authority of the counter `State` account.
+    authority: Signer<'info>,
+}
+
+/// An authority acceptance instruction accounts to take over the
+/// `State::authority`.
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    /// A state data account to transfer.
+    #[account(
+        mut,
+        constraint = state.pending_authority != Pubkey::default()
+            && state.pending_authority == pending_authority.key() @ Error::NotPendingAuthority,
+    )]
+    state: Account<'info, State>,
+
+    /// A pending authority of the counter `State` account.
+    pending_authority: Signer<'info>,
+}
+
+/// A reap instruction accounts to close the expired counter `State`
+/// account.
+#[derive(Accounts)]
+pub struct Reap<'info> {
+    /// An expired state data account to close.
+    #[account(
+        mut,
+        has_one = beneficiary,
+        close = beneficiary,
+        constraint = state.is_expired(Clock::get()?.unix_timestamp) @ Error::NotExpired,
+    )]
+    state: Account<'info, State>,
+
+    /// CHECK: A fee vault PDA of the counter `State` account, which
+    /// only exists for the `State` created by `initialize`.
+    #[account(mut, seeds = [b"vault", state.key().as_ref()], bump)]
+    vault: UncheckedAccount<'info>,
+
+    /// CHECK: A beneficiary of the counter `State` account, who gets
+    /// the rent back.
+    #[account(mut)]
+    beneficiary: UncheckedAccount<'info>,
+}
+
+/// A migration instruction accounts to migrate the v1 counter
+/// `State` account.
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    /// A v1 state data account, validated by the instruction.
+    #[account(mut, owner = id())]
+    state: Signer<'info>,
+
+    /// A new authority of the counter `State` account, who pays the
+    /// additional rent.
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    /// System program to transfer the additional rent.
+    system_program: Program<'info, System>,
 }
 
 /// A state of the counter program.
 #[account]
 pub struct State {
     /// Keep track of the `increment` instruction calls.
-    pub count: u8,
+    pub count: u64,
+
+    /// An authority of the counter.
+    pub authority: Pubkey,
+
+    /// A bump of the `State` PDA, or zero for the non-PDA account.
+    pub bump: u8,
+
+    /// A lessee of the increment rights, or the default pubkey.
+    pub lessee: Pubkey,
+
+    /// A unix timestamp until which the lease is active.
+    pub lease_until: i64,
+
+    /// Lamports charged on every increment, or zero for no fee.
+    pub fee: u64,
+
+    /// A slot of the last mutation.
+    pub last_slot: u64,
+
+    /// A unix timestamp of the last mutation.
+    pub last_timestamp: i64,
+
+    /// A unix timestamp after which anyone can `reap` the account,
+    /// or zero for no expiry.
+    pub expires_at: i64,
+
+    /// A beneficiary of the rent on `reap`.
+    pub beneficiary: Pubkey,
+
+    /// Who can increment the counter.
+    pub mode: Mode,
+
+    /// Tallies the increments per UTC day in `tallies`.
+    pub tally_enabled: bool,
+
+    /// The ring of the per-UTC-day tallies, indexed by the day modulo
+    /// `TALLY_DAYS`.
+    pub tallies: [Tally; TALLY_DAYS],
+
+    /// A signer of the last increment, or the default pubkey.
+    pub last_incremented_by: Pubkey,
+
+    /// An authority proposed by `propose_authority`, or the default
+    /// pubkey.
+    pub pending_authority: Pubkey,
 }
 
 impl State {
-    /// 8 bytes for anchor and one byte for `count` member.
+    /// 8 bytes for anchor, 8 bytes for `count`, 32 bytes for
+    /// `authority`, one byte for `bump`, 32 bytes for `lessee`,
+    /// 8 bytes for `lease_until`, 8 bytes for `fee`, 8 bytes for
+    /// `last_slot`, 8 bytes for `last_timestamp`, 8 bytes for
+    /// `expires_at`, 32 bytes for `beneficiary`, one byte for `mode`,
+    /// one byte for `tally_enabled`, 16 bytes for each `tallies`
+    /// member, 32 bytes for `last_incremented_by`, and 32 bytes for
+    /// `pending_authority`.
+    const SPACE: usize =
+        8 + 8 + 32 + 1 + 32 + 8 + 8 + 8 + 8 + 8 + 32 + 1 + 1 + 16 * TALLY_DAYS + 32 + 32;
+
+    /// The maximum number of counters initialized in a batch.
+    pub const MAX_BATCH: usize = 16;
+
+    /// Stamps the current slot and unix timestamp of the mutation.
+    fn touch(&mut self) -> Result<()> {
+        let clock = Clock::get()?;
+        self.last_slot = clock.slot;
+        self.last_timestamp = clock.unix_timestamp;
+        Ok(())
+    }
+
+    /// Adds the `amount` to the tally of the day of the last mutation,
+    /// e.g. stamped by `touch`, if it's enabled.
+    ///
+    /// The bucket of the same day of the previous rounds is recycled.
+    fn tally(&mut self, amount: u64) {
+        if !self.tally_enabled {
+            return;
+        }
+        let day = self.last_timestamp.div_euclid(SECONDS_PER_DAY);
+        let tally = &mut self.tallies[day.rem_euclid(TALLY_DAYS as i64) as usize];
+        if tally.day != day {
+            *tally = Tally { day, count: 0 };
+        }
+        tally.count = tally.count.saturating_add(amount);
+    }
+
+    /// Returns `true` if the `signer` can increment the counter at `now`.
+    fn can_increment(&self, signer: &Pubkey, now: i64) -> bool {
+        self.mode == Mode::Permissionless
+            || *signer == self.authority
+            || (*signer == self.lessee
+                && self.lessee != Pubkey::default()
+                && now < self.lease_until)
+    }
+
+    /// Returns `true` if the counter is expired at `now`.
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at != 0 && now >= self.expires_at
+    }
+
+    /// Returns the `State` PDA and the bump for the authority.
+    pub fn address(authority: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"state", authority.as_ref()], &id())
+    }
+}
+
+/// A fee vault PDA of the counter `State` account, e.g.
+/// `[b"vault", state]`, which keeps the increment fees.
+#[account]
+pub struct Vault {
+    /// A counter `State` account of the vault.
+    pub state: Pubkey,
+
+    /// A bump of the `Vault` PDA.
+    pub bump: u8,
+}
+
+impl Vault {
+    /// 8 bytes for anchor, 32 bytes for `state`, and one byte for
+    /// `bump` member.
+    const SPACE: usize = 8 + 32 + 1;
+
+    /// Returns the `Vault` PDA of the counter `State` account and the bump.
+    pub fn address(state: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"vault", state.as_ref()], &id())
+    }
+
+    /// Closes the `vault` account, if any, with the remaining fees.
+    fn close(vault: &AccountInfo, to: &AccountInfo) -> Result<()> {
+        if vault.owner != &id() {
+            return Ok(());
+        }
+        let lamports = vault.lamports();
+        **vault.try_borrow_mut_lamports()? = 0;
+        **to.try_borrow_mut_lamports()? += lamports;
+        vault.try_borrow_mut_data()?.fill(0);
+        Ok(())
+    }
+}
+
+/// A history PDA of the counter `State` account, e.g.
+/// `[b"history", state]`, which keeps the recent snapshots in the
+/// ring buffer.
+#[account]
+pub struct History {
+    /// A counter `State` account of the history.
+    pub state: Pubkey,
+
+    /// A bump of the `History` PDA.
+    pub bump: u8,
+
+    /// An index of the next snapshot in `snapshots`.
+    pub head: u8,
+
+    /// The number of the recorded snapshots, up to `History::CAPACITY`.
+    pub len: u8,
+
+    /// The ring buffer of the snapshots.
+    pub snapshots: [Snapshot; 32],
+}
+
+impl History {
+    /// The maximum number of the snapshots kept in the history.
+    pub const CAPACITY: usize = 32;
+
+    /// 8 bytes for anchor, 32 bytes for `state`, one byte for `bump`,
+    /// one byte for `head`, one byte for `len`, and 16 bytes for each
+    /// `snapshots` member.
+    const SPACE: usize = 8 + 32 + 1 + 1 + 1 + 16 * Self::CAPACITY;
+
+    /// Returns the `History` PDA of the counter `State` account and
+    /// the bump.
+    pub fn address(state: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"history", state.as_ref()], &id())
+    }
+
+    /// Appends the snapshot, overwriting the oldest one if it's full.
+    fn push(&mut self, slot: u64, count: u64) {
+        self.snapshots[self.head as usize] = Snapshot { slot, count };
+        self.head = ((self.head as usize + 1) % Self::CAPACITY) as u8;
+        self.len = (self.len as usize + 1).min(Self::CAPACITY) as u8;
+    }
+}
+
+/// An increment mode of the counter `State` account, chosen at
+/// `initialize`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum Mode {
+    /// Only the authority and the active lessee can increment.
+    #[default]
+    Permissioned,
+
+    /// Anyone can increment.
+    Permissionless,
+}
+
+/// An increment tally of the UTC day of the counter `State` account.
+#[derive(Copy, Clone, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct Tally {
+    /// The days since the unix epoch.
+    pub day: i64,
+
+    /// The increments in the day.
+    pub count: u64,
+}
+
+/// A `(slot, count)` snapshot of the counter `State` account.
+#[derive(Copy, Clone, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct Snapshot {
+    /// A slot of the snapshot.
+    pub slot: u64,
+
+    /// The count at the slot.
+    pub count: u64,
+}
+
+/// An event emitted on every `State::count` change, e.g. by the
+/// `increment`, `increment_by`, and `reset` instructions.
+#[event]
+pub struct CounterChanged {
+    /// A counter `State` account changed.
+    pub state: Pubkey,
+
+    /// The count before the change.
+    pub old_count: u64,
+
+    /// The count after the change.
+    pub new_count: u64,
+
+    /// A signer of the instruction.
+    pub signer: Pubkey,
+
+    /// A slot of the change.
+    pub slot: u64,
+
+    /// A signer of the last increment, e.g. the `signer` of the
+    /// increments, kept as is by the `reset`.
+    pub last_incremented_by: Pubkey,
+}
+
+/// An event emitted by the `reset` instruction.
+#[event]
+pub struct CounterReset {
+    /// A counter `State` account reset.
+    pub state: Pubkey,
+
+    /// An authority of the counter.
+    pub authority: Pubkey,
+
+    /// The count discarded by the reset.
+    pub discarded: u64,
+}
+
+/// A v1 state of the counter program, which keeps the `count` in `u8`.
+///
+/// It's only used by the `migrate` instruction.
+struct StateV1 {
+    count: u8,
+}
+
+impl StateV1 {
+    /// The v1 `State::SPACE`, 8 bytes for anchor and one byte for
+    /// `count`.
     const SPACE: usize = 8 + 1;
+
+    fn try_from_slice(data: &[u8]) -> Result<Self> {
+        require_eq!(data.len(), Self::SPACE, Error::NotV1State);
+        require!(data[..8] == State::DISCRIMINATOR, Error::NotV1State);
+        let mut data = &data[8..];
+        Ok(Self {
+            count: AnchorDeserialize::deserialize(&mut data)?,
+        })
+    }
 }
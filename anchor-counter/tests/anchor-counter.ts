@@ -1,8 +1,10 @@
 import * as anchor from "@project-serum/anchor";
 import { Program } from "@project-serum/anchor";
-import { Keypair } from "@solana/web3.js";
+import { Keypair, PublicKey } from "@solana/web3.js";
 import { AnchorCounter } from "../target/types/anchor_counter";
+import { AnchorCounterMirror } from "../target/types/anchor_counter_mirror";
 import { expect } from "chai";
+import { readFileSync } from "fs";
 
 describe("anchor-counter", () => {
   // Configure the client to use the local cluster.
@@ -10,15 +12,31 @@ describe("anchor-counter", () => {
   anchor.setProvider(provider);
 
   const counter = anchor.workspace.AnchorCounter as Program<AnchorCounter>;
+  const mirror = anchor.workspace
+    .AnchorCounterMirror as Program<AnchorCounterMirror>;
   const counterState = Keypair.generate();
+  const [emitter] = PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("emitter")],
+    counter.programId
+  );
+  const [mirrorState] = PublicKey.findProgramAddressSync(
+    [anchor.utils.bytes.utf8.encode("mirror"), counterState.publicKey.toBuffer()],
+    mirror.programId
+  );
+  const vaultOf = (state: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("vault"), state.toBuffer()],
+      counter.programId
+    )[0];
 
   it("Is initialized!", async () => {
     // Add your test here.
     const tx = await counter
       .methods
-      .initialize()
+      .initialize(new anchor.BN(0), { permissioned: {} })
       .accounts({
         state: counterState.publicKey,
+        vault: vaultOf(counterState.publicKey),
         authority: provider.wallet.publicKey,
       })
       .signers([counterState])
@@ -27,7 +45,26 @@ describe("anchor-counter", () => {
     console.log("Initialization transaction signature", tx);
 
     const state = await counter.account.state.fetch(counterState.publicKey);
-    expect(state.count).to.equal(0);
+    expect(state.count.toNumber()).to.equal(0);
+    expect(state.authority).to.eql(provider.wallet.publicKey);
+  });
+
+  it("Is mirrored!", async () => {
+    const tx = await mirror
+      .methods
+      .initialize()
+      .accounts({
+        mirror: mirrorState,
+        counter: counterState.publicKey,
+        payer: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    console.log("Mirror initialization transaction signature", tx);
+
+    const got = await mirror.account.mirror.fetch(mirrorState);
+    expect(got.counter).to.eql(counterState.publicKey);
+    expect(got.count.toNumber()).to.equal(0);
   });
 
   it("is incremented", async () => {
@@ -38,12 +75,672 @@ describe("anchor-counter", () => {
         .increment()
         .accounts({
           state: counterState.publicKey,
+          signer: provider.wallet.publicKey,
+          emitter,
+          mirror: mirrorState,
+          vault: vaultOf(counterState.publicKey),
+          mirrorProgram: mirror.programId,
         })
         .rpc();
 
         console.log("Increment transaction signature", tx);
     }
     const state = await counter.account.state.fetch(counterState.publicKey);
-    expect(state.count).to.equal(count);
+    expect(state.count.toNumber()).to.equal(count);
+
+    // The mirror follows the counter through CPI.
+    const got = await mirror.account.mirror.fetch(mirrorState);
+    expect(got.count.toNumber()).to.equal(count);
+    expect(got.updates.toNumber()).to.equal(count);
+  });
+
+  it("is incremented by amount", async () => {
+    const accounts = {
+      state: counterState.publicKey,
+      signer: provider.wallet.publicKey,
+      emitter,
+      mirror: mirrorState,
+      vault: vaultOf(counterState.publicKey),
+      mirrorProgram: mirror.programId,
+    };
+    let event = null;
+    const listener = counter.addEventListener("CounterChanged", (e) => {
+      event = e;
+    });
+    const before = await counter.account.state.fetch(counterState.publicKey);
+    await counter.methods.incrementBy(new anchor.BN(10)).accounts(accounts).rpc();
+    const after = await counter.account.state.fetch(counterState.publicKey);
+    expect(after.count.toNumber()).to.equal(before.count.toNumber() + 10);
+
+    // The mutation is stamped with the slot and the unix timestamp.
+    expect(after.lastSlot.toNumber()).to.be.greaterThan(before.lastSlot.toNumber());
+    expect(after.lastTimestamp.toNumber()).to.be.at.least(
+      before.lastTimestamp.toNumber()
+    );
+    expect(after.lastIncrementedBy).to.eql(provider.wallet.publicKey);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    await counter.removeEventListener(listener);
+
+    // The change is emitted for the indexers.
+    expect(event.state).to.eql(counterState.publicKey);
+    expect(event.oldCount.toNumber()).to.equal(before.count.toNumber());
+    expect(event.newCount.toNumber()).to.equal(after.count.toNumber());
+    expect(event.signer).to.eql(provider.wallet.publicKey);
+    expect(event.slot.toNumber()).to.equal(after.lastSlot.toNumber());
+    expect(event.lastIncrementedBy).to.eql(provider.wallet.publicKey);
+
+    // It doesn't wrap around.
+    try {
+      await counter.methods
+        .incrementBy(new anchor.BN("ffffffffffffffff", 16))
+        .accounts(accounts)
+        .rpc();
+      expect.fail("increment_by should overflow");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("CountOverflow");
+    }
+  });
+
+  it("records the history snapshots", async () => {
+    const [history] = PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("history"), counterState.publicKey.toBuffer()],
+      counter.programId
+    );
+    await counter
+      .methods
+      .initializeHistory()
+      .accounts({
+        state: counterState.publicKey,
+        history,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    // The increment appends the snapshot with the optional history.
+    await counter
+      .methods
+      .increment()
+      .accounts({
+        state: counterState.publicKey,
+        signer: provider.wallet.publicKey,
+        emitter,
+        mirror: mirrorState,
+        vault: vaultOf(counterState.publicKey),
+        mirrorProgram: mirror.programId,
+      })
+      .remainingAccounts([{ pubkey: history, isWritable: true, isSigner: false }])
+      .rpc();
+    await counter
+      .methods
+      .snapshot()
+      .accounts({ state: counterState.publicKey, history })
+      .rpc();
+
+    const state = await counter.account.state.fetch(counterState.publicKey);
+    const got = await counter.account.history.fetch(history);
+    expect(got.len).to.equal(2);
+    expect(got.head).to.equal(2);
+    expect(got.snapshots[0].count.toNumber()).to.equal(state.count.toNumber());
+    expect(got.snapshots[1].count.toNumber()).to.equal(state.count.toNumber());
+    expect(got.snapshots[1].slot.toNumber()).to.be.at.least(
+      got.snapshots[0].slot.toNumber()
+    );
+  });
+
+  it("leases the increment rights", async () => {
+    const lessee = Keypair.generate();
+    const increment = (signer: Keypair) =>
+      counter
+        .methods
+        .increment()
+        .accounts({
+          state: counterState.publicKey,
+          signer: signer.publicKey,
+          emitter,
+          mirror: mirrorState,
+          vault: vaultOf(counterState.publicKey),
+          mirrorProgram: mirror.programId,
+        })
+        .signers([signer])
+        .rpc();
+
+    // Not allowed before the lease.
+    try {
+      await increment(lessee);
+      expect.fail("increment should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("NotAuthorized");
+    }
+
+    const until = Math.floor(Date.now() / 1000) + 60;
+    await counter
+      .methods
+      .lease(lessee.publicKey, new anchor.BN(until))
+      .accounts({
+        state: counterState.publicKey,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    const before = await counter.account.state.fetch(counterState.publicKey);
+    expect(before.lessee).to.eql(lessee.publicKey);
+    expect(before.leaseUntil.toNumber()).to.equal(until);
+    await increment(lessee);
+    const after = await counter.account.state.fetch(counterState.publicKey);
+    expect(after.count.toNumber()).to.equal(before.count.toNumber() + 1);
+
+    // Revokes the lease.
+    await counter
+      .methods
+      .lease(PublicKey.default, new anchor.BN(0))
+      .accounts({
+        state: counterState.publicKey,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    try {
+      await increment(lessee);
+      expect.fail("increment should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("NotAuthorized");
+    }
+  });
+
+  it("initializes many counters in a batch", async () => {
+    const authorities = [...Array(4)].map(() => Keypair.generate().publicKey);
+    const states = authorities.map(
+      (authority) =>
+        PublicKey.findProgramAddressSync(
+          [anchor.utils.bytes.utf8.encode("state"), authority.toBuffer()],
+          counter.programId
+        )[0]
+    );
+
+    // Anyone can fund the `State` PDA in advance.
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.transfer({
+          fromPubkey: provider.wallet.publicKey,
+          toPubkey: states[0],
+          lamports: 1_000_000,
+        })
+      )
+    );
+
+    const tx = await counter
+      .methods
+      .initializeMany(authorities)
+      .accounts({
+        admin: provider.wallet.publicKey,
+      })
+      .remainingAccounts(
+        states.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false }))
+      )
+      .rpc();
+
+    console.log("Batch initialization transaction signature", tx);
+
+    for (let i = 0; i < authorities.length; i++) {
+      const state = await counter.account.state.fetch(states[i]);
+      expect(state.count.toNumber()).to.equal(0);
+      expect(state.authority).to.eql(authorities[i]);
+    }
+    const info = await provider.connection.getAccountInfo(states[0]);
+    expect(info.owner).to.eql(counter.programId);
+  });
+
+  it("closes the counter and reclaims the rent", async () => {
+    const state = Keypair.generate();
+    const vault = vaultOf(state.publicKey);
+    await counter
+      .methods
+      .initialize(new anchor.BN(0), { permissioned: {} })
+      .accounts({
+        state: state.publicKey,
+        vault,
+        authority: provider.wallet.publicKey,
+      })
+      .signers([state])
+      .rpc();
+
+    const rent =
+      (await provider.connection.getBalance(state.publicKey)) +
+      (await provider.connection.getBalance(vault));
+    const before = await provider.connection.getBalance(provider.wallet.publicKey);
+    await counter
+      .methods
+      .close()
+      .accounts({
+        state: state.publicKey,
+        vault,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    const after = await provider.connection.getBalance(provider.wallet.publicKey);
+
+    // The rent is given back, less the transaction fee.
+    expect(after).to.be.greaterThan(before);
+    expect(after).to.be.at.most(before + rent);
+    expect(await counter.account.state.fetchNullable(state.publicKey)).to.be.null;
+    expect(await counter.account.vault.fetchNullable(vault)).to.be.null;
+  });
+
+  it("reaps the expired counter for the beneficiary", async () => {
+    const state = Keypair.generate();
+    const vault = vaultOf(state.publicKey);
+    const beneficiary = Keypair.generate().publicKey;
+    await counter
+      .methods
+      .initialize(new anchor.BN(0), { permissioned: {} })
+      .accounts({
+        state: state.publicKey,
+        vault,
+        authority: provider.wallet.publicKey,
+      })
+      .signers([state])
+      .rpc();
+
+    const expiresAt = Math.floor(Date.now() / 1000) + 3;
+    await counter
+      .methods
+      .setExpiry(new anchor.BN(expiresAt), beneficiary)
+      .accounts({
+        state: state.publicKey,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    // Anyone can reap, but only after the expiry.
+    const reap = () =>
+      counter
+        .methods
+        .reap()
+        .accounts({ state: state.publicKey, vault, beneficiary })
+        .rpc();
+    try {
+      await reap();
+      expect.fail("reap should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("NotExpired");
+    }
+
+    const rent =
+      (await provider.connection.getBalance(state.publicKey)) +
+      (await provider.connection.getBalance(vault));
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+    await reap();
+    expect(await provider.connection.getBalance(beneficiary)).to.equal(rent);
+    expect(await counter.account.state.fetchNullable(state.publicKey)).to.be.null;
+  });
+
+  it("resets the counter", async () => {
+    const state = Keypair.generate();
+    const accounts = {
+      state: state.publicKey,
+      authority: provider.wallet.publicKey,
+    };
+    await counter
+      .methods
+      .initialize(new anchor.BN(0), { permissioned: {} })
+      .accounts({ ...accounts, vault: vaultOf(state.publicKey) })
+      .signers([state])
+      .rpc();
+
+    // Only the authority can reset.
+    const stranger = Keypair.generate();
+    try {
+      await counter
+        .methods
+        .reset()
+        .accounts({ ...accounts, authority: stranger.publicKey })
+        .signers([stranger])
+        .rpc();
+      expect.fail("reset should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("ConstraintHasOne");
+    }
+
+    let event = null;
+    const listener = counter.addEventListener("CounterReset", (e) => {
+      event = e;
+    });
+    await counter.methods.reset().accounts(accounts).rpc();
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    await counter.removeEventListener(listener);
+
+    const got = await counter.account.state.fetch(state.publicKey);
+    expect(got.count.toNumber()).to.equal(0);
+    expect(event.state).to.eql(state.publicKey);
+    expect(event.discarded.toNumber()).to.equal(0);
+  });
+
+  it("transfers the authority with the two-step accept", async () => {
+    const state = Keypair.generate();
+    await counter
+      .methods
+      .initialize(new anchor.BN(0), { permissioned: {} })
+      .accounts({
+        state: state.publicKey,
+        vault: vaultOf(state.publicKey),
+        authority: provider.wallet.publicKey,
+      })
+      .signers([state])
+      .rpc();
+
+    const newAuthority = Keypair.generate();
+    await counter
+      .methods
+      .proposeAuthority(newAuthority.publicKey)
+      .accounts({
+        state: state.publicKey,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    // The proposal alone doesn't transfer the authority.
+    let got = await counter.account.state.fetch(state.publicKey);
+    expect(got.authority).to.eql(provider.wallet.publicKey);
+    expect(got.pendingAuthority).to.eql(newAuthority.publicKey);
+
+    // Only the pending authority can accept.
+    const stranger = Keypair.generate();
+    try {
+      await counter
+        .methods
+        .acceptAuthority()
+        .accounts({
+          state: state.publicKey,
+          pendingAuthority: stranger.publicKey,
+        })
+        .signers([stranger])
+        .rpc();
+      expect.fail("accept should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("NotPendingAuthority");
+    }
+
+    await counter
+      .methods
+      .acceptAuthority()
+      .accounts({
+        state: state.publicKey,
+        pendingAuthority: newAuthority.publicKey,
+      })
+      .signers([newAuthority])
+      .rpc();
+    got = await counter.account.state.fetch(state.publicKey);
+    expect(got.authority).to.eql(newAuthority.publicKey);
+    expect(got.pendingAuthority).to.eql(PublicKey.default);
+  });
+
+  it("charges the increment fee to the vault", async () => {
+    const fee = 5000;
+    const state = Keypair.generate();
+    const vault = vaultOf(state.publicKey);
+    const [stateMirror] = PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("mirror"), state.publicKey.toBuffer()],
+      mirror.programId
+    );
+    await counter
+      .methods
+      .initialize(new anchor.BN(fee), { permissioned: {} })
+      .accounts({
+        state: state.publicKey,
+        vault,
+        authority: provider.wallet.publicKey,
+      })
+      .signers([state])
+      .rpc();
+    await mirror
+      .methods
+      .initialize()
+      .accounts({
+        mirror: stateMirror,
+        counter: state.publicKey,
+        payer: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    const rent = await provider.connection.getBalance(vault);
+    for (let i = 0; i < 3; i++) {
+      await counter
+        .methods
+        .increment()
+        .accounts({
+          state: state.publicKey,
+          signer: provider.wallet.publicKey,
+          emitter,
+          mirror: stateMirror,
+          vault,
+          mirrorProgram: mirror.programId,
+        })
+        .rpc();
+    }
+    expect(await provider.connection.getBalance(vault)).to.equal(rent + 3 * fee);
+
+    // The authority withdraws the fees, less the vault rent.
+    await counter
+      .methods
+      .withdrawFees()
+      .accounts({
+        state: state.publicKey,
+        vault,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+    expect(await provider.connection.getBalance(vault)).to.equal(rent);
+  });
+
+  it("increments by anyone in the permissionless mode", async () => {
+    const state = Keypair.generate();
+    const vault = vaultOf(state.publicKey);
+    const [stateMirror] = PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("mirror"), state.publicKey.toBuffer()],
+      mirror.programId
+    );
+    await counter
+      .methods
+      .initialize(new anchor.BN(0), { permissionless: {} })
+      .accounts({
+        state: state.publicKey,
+        vault,
+        authority: provider.wallet.publicKey,
+      })
+      .signers([state])
+      .rpc();
+    await mirror
+      .methods
+      .initialize()
+      .accounts({
+        mirror: stateMirror,
+        counter: state.publicKey,
+        payer: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    const stranger = Keypair.generate();
+    await counter
+      .methods
+      .increment()
+      .accounts({
+        state: state.publicKey,
+        signer: stranger.publicKey,
+        emitter,
+        mirror: stateMirror,
+        vault,
+        mirrorProgram: mirror.programId,
+      })
+      .signers([stranger])
+      .rpc();
+
+    const got = await counter.account.state.fetch(state.publicKey);
+    expect(got.mode).to.eql({ permissionless: {} });
+    expect(got.count.toNumber()).to.equal(1);
+  });
+
+  it("increments the counters in a batch", async () => {
+    const states = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    const mirrorOf = (state: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [anchor.utils.bytes.utf8.encode("mirror"), state.toBuffer()],
+        mirror.programId
+      )[0];
+    for (const state of states) {
+      await counter
+        .methods
+        .initialize(new anchor.BN(0), { permissioned: {} })
+        .accounts({
+          state: state.publicKey,
+          vault: vaultOf(state.publicKey),
+          authority: provider.wallet.publicKey,
+        })
+        .signers([state])
+        .rpc();
+      await mirror
+        .methods
+        .initialize()
+        .accounts({
+          mirror: mirrorOf(state.publicKey),
+          counter: state.publicKey,
+          payer: provider.wallet.publicKey,
+        })
+        .rpc();
+    }
+
+    const remainingAccounts = states.flatMap((state) =>
+      [state.publicKey, vaultOf(state.publicKey), mirrorOf(state.publicKey)].map(
+        (pubkey) => ({ pubkey, isWritable: true, isSigner: false })
+      )
+    );
+    await counter
+      .methods
+      .batchIncrement()
+      .accounts({
+        signer: provider.wallet.publicKey,
+        emitter,
+        mirrorProgram: mirror.programId,
+      })
+      .remainingAccounts(remainingAccounts)
+      .rpc();
+    for (const state of states) {
+      const got = await counter.account.state.fetch(state.publicKey);
+      expect(got.count.toNumber()).to.equal(1);
+      const mirrored = await mirror.account.mirror.fetch(mirrorOf(state.publicKey));
+      expect(mirrored.count.toNumber()).to.equal(1);
+    }
+
+    // Not the counter triples.
+    try {
+      await counter
+        .methods
+        .batchIncrement()
+        .accounts({
+          signer: provider.wallet.publicKey,
+          emitter,
+          mirrorProgram: mirror.programId,
+        })
+        .remainingAccounts(remainingAccounts.slice(1))
+        .rpc();
+      expect.fail("batch increment should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("InvalidBatch");
+    }
+  });
+
+  it("tallies the increments per day", async () => {
+    await counter
+      .methods
+      .setTally(true)
+      .accounts({
+        state: counterState.publicKey,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    const count = 3;
+    for (let i = 0; i < count; i++) {
+      await counter
+        .methods
+        .incrementBy(new anchor.BN(2))
+        .accounts({
+          state: counterState.publicKey,
+          signer: provider.wallet.publicKey,
+          emitter,
+          mirror: mirrorState,
+          vault: vaultOf(counterState.publicKey),
+          mirrorProgram: mirror.programId,
+        })
+        .rpc();
+    }
+
+    const state = await counter.account.state.fetch(counterState.publicKey);
+    expect(state.tallyEnabled).to.equal(true);
+    expect(state.tallies).to.have.lengthOf(30);
+    const day = Math.floor(state.lastTimestamp.toNumber() / 86400);
+    const tally = state.tallies[day % 30];
+    expect(tally.day.toNumber()).to.equal(day);
+    expect(tally.count.toNumber()).to.equal(2 * count);
+  });
+
+  it("migrates the v1 counter", async () => {
+    // The 9 bytes v1 state loaded by the test validator, see
+    // `tests/fixtures/state-v1.json` and `Anchor.toml`.
+    const v1State = Keypair.fromSecretKey(
+      Uint8Array.from(
+        JSON.parse(readFileSync("tests/fixtures/v1-state.json", "utf8"))
+      )
+    );
+    const authority = provider.wallet.publicKey;
+
+    const before = await provider.connection.getAccountInfo(v1State.publicKey);
+    expect(before.data).to.have.lengthOf(9);
+
+    // The v1 state key should sign the migration.
+    try {
+      await counter
+        .methods
+        .migrate()
+        .accounts({
+          state: v1State.publicKey,
+          authority,
+        })
+        .rpc();
+      expect.fail("migrate should be signed by the v1 state key");
+    } catch (e) {
+      expect(e.message).to.contain("Signature verification failed");
+    }
+
+    await counter
+      .methods
+      .migrate()
+      .accounts({
+        state: v1State.publicKey,
+        authority,
+      })
+      .signers([v1State])
+      .rpc();
+
+    const state = await counter.account.state.fetch(v1State.publicKey);
+    expect(state.count.toNumber()).to.equal(7);
+    expect(state.authority).to.eql(authority);
+    expect(state.bump).to.equal(0);
+    expect(state.lessee).to.eql(PublicKey.default);
+    expect(state.fee.toNumber()).to.equal(0);
+
+    // Not twice.
+    try {
+      await counter
+        .methods
+        .migrate()
+        .accounts({
+          state: v1State.publicKey,
+          authority,
+        })
+        .signers([v1State])
+        .rpc();
+      expect.fail("migrate should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("NotV1State");
+    }
   });
 });
@@ -0,0 +1,817 @@
+//! A client of the anchor multisig program.
+//!
+//! It provides the instruction builders for the multisig proposals,
+//! e.g. [`propose_transfer`] and [`propose_upgrade`], as well as the
+//! [`approve`] and [`execute`] instructions.  Those take care of the
+//! multisig signer PDA derivation and the remaining accounts assembly
+//! of the execution.
+
+use std::fmt;
+
+use anchor_lang::prelude::{AccountDeserialize, AccountMeta, Pubkey, Rent, ToAccountMetas};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{bpf_loader_upgradeable, system_instruction, system_program};
+use anchor_lang::InstructionData;
+use anchor_multisig::{Multisig, Transaction, TransactionMeta};
+use solana_client::client_error::ClientError;
+use solana_client::rpc_client::RpcClient;
+
+pub use anchor_multisig::ID as PROGRAM_ID;
+
+/// Errors returned by the fetch functions, e.g. [`fetch_transaction`].
+///
+/// Both errors are boxed to keep the `Result` small.
+#[derive(Debug)]
+pub enum Error {
+    /// RPC error.
+    Client(Box<ClientError>),
+
+    /// Invalid multisig program account data.
+    Account(Box<anchor_lang::error::Error>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Client(e) => write!(f, "client error: {e}"),
+            Self::Account(e) => write!(f, "account error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Self::Client(Box::new(e))
+    }
+}
+
+impl From<anchor_lang::error::Error> for Error {
+    fn from(e: anchor_lang::error::Error) -> Self {
+        Self::Account(Box::new(e))
+    }
+}
+
+/// Fetches the `Multisig` account.
+pub fn fetch_multisig(client: &RpcClient, multisig: &Pubkey) -> Result<Multisig, Error> {
+    let data = client.get_account_data(multisig)?;
+    Ok(Multisig::try_deserialize(&mut data.as_slice())?)
+}
+
+/// Fetches the `Transaction` account, e.g. for [`execute`].
+pub fn fetch_transaction(client: &RpcClient, transaction: &Pubkey) -> Result<Transaction, Error> {
+    let data = client.get_account_data(transaction)?;
+    Ok(Transaction::try_deserialize(&mut data.as_slice())?)
+}
+
+/// Returns the multisig signer PDA and the bump.
+pub fn multisig_signer(multisig: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[multisig.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the vault PDA of the multisig and the bump.
+pub fn vault_address(multisig: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[Multisig::VAULT_SEED, multisig.as_ref()], &PROGRAM_ID)
+}
+
+/// Returns the token vault PDA of the multisig for the `mint` and the
+/// bump.
+pub fn token_vault_address(multisig: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[Multisig::TOKEN_VAULT_SEED, multisig.as_ref(), mint.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Returns the transaction PDA of the multisig for the transaction
+/// `count`, e.g. `Multisig::transaction_count`, and the bump.
+pub fn transaction_address(multisig: &Pubkey, count: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"tx", multisig.as_ref(), &count.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+}
+
+/// Builds the `create_account` and the `initialize_multisig`
+/// instructions.
+///
+/// Both the `payer` and the `multisig` should sign the transaction.
+pub fn create_multisig(
+    payer: &Pubkey,
+    multisig: &Pubkey,
+    owners: Vec<Pubkey>,
+    threshold: u64,
+) -> [Instruction; 2] {
+    let space = Multisig::required_space(owners.len());
+    let accounts = anchor_multisig::accounts::InitializeMultisig {
+        multisig: *multisig,
+    };
+    let data = anchor_multisig::instruction::InitializeMultisig {
+        owners,
+        threshold,
+        bump: multisig_signer(multisig).1,
+    };
+    [
+        create_account(payer, multisig, space),
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        },
+    ]
+}
+
+/// Builds the proposal of the `ix` signed by the multisig signer PDA.
+///
+/// It returns the `create_account` instruction of the `transaction`
+/// account, sized for the multisig of `n_owners` owners, followed by
+/// the `initialize_transaction` instruction.  Both the `proposer`,
+/// who pays the rent, and the `transaction` should sign the
+/// transaction.
+pub fn propose(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    ix: Instruction,
+) -> [Instruction; 2] {
+    propose_with_expiration(multisig, n_owners, transaction, proposer, ix, None)
+}
+
+/// Builds the `create_transaction` instruction of the `ix` signed by
+/// the multisig signer PDA, where the transaction account is the PDA
+/// of the multisig transaction `count`, e.g. [`transaction_address`].
+///
+/// The `proposer` pays the rent and should sign the transaction.
+pub fn create_transaction(
+    multisig: &Pubkey,
+    count: u64,
+    proposer: &Pubkey,
+    ix: Instruction,
+) -> Instruction {
+    let (transaction, _) = transaction_address(multisig, count);
+    let accounts = anchor_multisig::accounts::CreateTransaction {
+        multisig: *multisig,
+        transaction,
+        proposer: *proposer,
+        system_program: system_program::ID,
+    };
+    let data = anchor_multisig::instruction::CreateTransaction {
+        tx_program_id: ix.program_id,
+        tx_accounts: ix.accounts.iter().map(transaction_meta).collect(),
+        tx_data: ix.data,
+        expires_at_slot: None,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Builds the proposal of the `ix` same as [`propose`], which
+/// expires at the `expires_at_slot` slot.
+pub fn propose_with_expiration(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    ix: Instruction,
+    expires_at_slot: Option<u64>,
+) -> [Instruction; 2] {
+    initialize_transaction(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        ix,
+        expires_at_slot,
+        String::new(),
+    )
+}
+
+/// Builds the proposal of the `ix` same as [`propose`], with the
+/// `memo` describing the transaction to the approvers.
+pub fn propose_with_memo(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    ix: Instruction,
+    memo: &str,
+) -> [Instruction; 2] {
+    initialize_transaction(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        ix,
+        None,
+        memo.to_string(),
+    )
+}
+
+fn initialize_transaction(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    ix: Instruction,
+    expires_at_slot: Option<u64>,
+    memo: String,
+) -> [Instruction; 2] {
+    let space = Transaction::space(ix.accounts.len(), ix.data.len(), n_owners);
+    let accounts = anchor_multisig::accounts::InitializeTransaction {
+        multisig: *multisig,
+        transaction: *transaction,
+        proposer: *proposer,
+    };
+    let data = anchor_multisig::instruction::InitializeTransaction {
+        tx_program_id: ix.program_id,
+        tx_accounts: ix.accounts.iter().map(transaction_meta).collect(),
+        tx_data: ix.data,
+        expires_at_slot,
+        memo,
+    };
+    [
+        create_account(proposer, transaction, space),
+        Instruction {
+            program_id: PROGRAM_ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        },
+    ]
+}
+
+/// Builds the proposal to transfer `lamports` from the multisig
+/// signer PDA to `to`.
+pub fn propose_transfer(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    to: &Pubkey,
+    lamports: u64,
+) -> [Instruction; 2] {
+    let (signer, _) = multisig_signer(multisig);
+    let ix = system_instruction::transfer(&signer, to, lamports);
+    propose(multisig, n_owners, transaction, proposer, ix)
+}
+
+/// Builds the proposal to transfer `lamports` from the multisig vault
+/// PDA to the `recipient`.
+pub fn propose_sol_transfer(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    recipient: &Pubkey,
+    lamports: u64,
+) -> [Instruction; 2] {
+    let accounts = anchor_multisig::accounts::TransferFromVault {
+        multisig: *multisig,
+        multisig_signer: multisig_signer(multisig).0,
+        vault: vault_address(multisig).0,
+        to: *recipient,
+        system_program: system_program::ID,
+    };
+    let ix = Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::TransferFromVault { lamports }.data(),
+    };
+    propose(multisig, n_owners, transaction, proposer, ix)
+}
+
+/// Builds the proposal to transfer `amount` tokens of the `mint` from
+/// the multisig token vault PDA to the `destination` token account.
+pub fn propose_token_transfer(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> [Instruction; 2] {
+    let accounts = anchor_multisig::accounts::TransferTokens {
+        multisig: *multisig,
+        multisig_signer: multisig_signer(multisig).0,
+        mint: *mint,
+        token_vault: token_vault_address(multisig, mint).0,
+        destination: *destination,
+        token_program: spl_token::ID,
+    };
+    let ix = Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::TransferTokens { amount }.data(),
+    };
+    propose(multisig, n_owners, transaction, proposer, ix)
+}
+
+/// Builds the proposal to change the threshold of the multisig.
+pub fn propose_change_threshold(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    threshold: u64,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::ChangeThreshold { threshold }.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to change the execution delay of the multisig.
+pub fn propose_change_execution_delay(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    delay_slots: u64,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::ChangeExecutionDelay { delay_slots }.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to change the spending limit of the multisig.
+pub fn propose_change_spending_limit(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    lamports: u64,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::ChangeSpendingLimit { lamports }.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to restrict the execution to the owners.
+pub fn propose_set_owners_only_execution(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    enabled: bool,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::SetOwnersOnlyExecution { enabled }.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to freeze the multisig.
+pub fn propose_freeze(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::Freeze {}.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to unfreeze the multisig, the only one the
+/// frozen multisig accepts.
+pub fn propose_unfreeze(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::Unfreeze {}.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to set the `delegate` of the `owner`, or to
+/// remove it with `None`.
+pub fn propose_set_delegate(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    owner: Pubkey,
+    delegate: Option<Pubkey>,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::SetDelegate { owner, delegate }.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to add the `owner` to the multisig.
+pub fn propose_add_owner(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    owner: Pubkey,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::AddOwner { owner }.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to remove the `owner` from the multisig.
+pub fn propose_remove_owner(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    owner: Pubkey,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::RemoveOwner { owner }.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+/// Builds the proposal to replace the `old` owner with the `new` one.
+pub fn propose_replace_owner(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    old: Pubkey,
+    new: Pubkey,
+) -> [Instruction; 2] {
+    let data = anchor_multisig::instruction::ReplaceOwner { old, new }.data();
+    propose(
+        multisig,
+        n_owners,
+        transaction,
+        proposer,
+        auth(multisig, data),
+    )
+}
+
+fn transaction_meta(meta: &AccountMeta) -> TransactionMeta {
+    TransactionMeta {
+        pubkey: meta.pubkey,
+        is_signer: meta.is_signer,
+        is_writable: meta.is_writable,
+    }
+}
+
+/// Builds the instruction signed by the multisig signer PDA, e.g.
+/// the one of the [`anchor_multisig::accounts::Auth`] accounts.
+fn auth(multisig: &Pubkey, data: Vec<u8>) -> Instruction {
+    let (signer, _) = multisig_signer(multisig);
+    let accounts = anchor_multisig::accounts::Auth {
+        multisig: *multisig,
+        multisig_signer: signer,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data,
+    }
+}
+
+/// Builds the proposal to upgrade the `program` with the `buffer`,
+/// where the multisig signer PDA is the upgrade authority.
+///
+/// The `spill` account gets the lamports of the `buffer` account.
+pub fn propose_upgrade(
+    multisig: &Pubkey,
+    n_owners: usize,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    program: &Pubkey,
+    buffer: &Pubkey,
+    spill: &Pubkey,
+) -> [Instruction; 2] {
+    let (signer, _) = multisig_signer(multisig);
+    let ix = bpf_loader_upgradeable::upgrade(program, buffer, &signer, spill);
+    propose(multisig, n_owners, transaction, proposer, ix)
+}
+
+/// Builds the `approve_transaction` instruction signed by the `owner`.
+pub fn approve(multisig: &Pubkey, transaction: &Pubkey, owner: &Pubkey) -> Instruction {
+    let accounts = anchor_multisig::accounts::ApproveTransaction {
+        multisig: *multisig,
+        transaction: *transaction,
+        owner: *owner,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::ApproveTransaction {}.data(),
+    }
+}
+
+/// Builds the `deposit` instruction of `lamports` to the multisig
+/// vault PDA, signed by the `depositor`.
+pub fn deposit(multisig: &Pubkey, depositor: &Pubkey, lamports: u64) -> Instruction {
+    let accounts = anchor_multisig::accounts::Deposit {
+        multisig: *multisig,
+        vault: vault_address(multisig).0,
+        depositor: *depositor,
+        system_program: system_program::ID,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::Deposit { lamports }.data(),
+    }
+}
+
+/// Builds the `create_token_vault` instruction of the `mint`, paid by
+/// the `payer`.
+pub fn create_token_vault(multisig: &Pubkey, mint: &Pubkey, payer: &Pubkey) -> Instruction {
+    let accounts = anchor_multisig::accounts::CreateTokenVault {
+        multisig: *multisig,
+        multisig_signer: multisig_signer(multisig).0,
+        mint: *mint,
+        token_vault: token_vault_address(multisig, mint).0,
+        payer: *payer,
+        token_program: spl_token::ID,
+        system_program: system_program::ID,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::CreateTokenVault {}.data(),
+    }
+}
+
+/// Builds the `spend_within_limit` instruction to transfer `lamports`
+/// from the multisig signer PDA to `to`, signed by the `owner`.
+pub fn spend_within_limit(
+    multisig: &Pubkey,
+    owner: &Pubkey,
+    to: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    let (signer, _) = multisig_signer(multisig);
+    let accounts = anchor_multisig::accounts::SpendWithinLimit {
+        multisig: *multisig,
+        multisig_signer: signer,
+        to: *to,
+        owner: *owner,
+        system_program: system_program::ID,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::SpendWithinLimit { lamports }.data(),
+    }
+}
+
+/// Builds the `revoke_approval` instruction signed by the `owner`.
+pub fn revoke(multisig: &Pubkey, transaction: &Pubkey, owner: &Pubkey) -> Instruction {
+    let accounts = anchor_multisig::accounts::ApproveTransaction {
+        multisig: *multisig,
+        transaction: *transaction,
+        owner: *owner,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::RevokeApproval {}.data(),
+    }
+}
+
+/// Builds the `reject_transaction` instruction signed by the `owner`.
+pub fn reject(multisig: &Pubkey, transaction: &Pubkey, owner: &Pubkey) -> Instruction {
+    let accounts = anchor_multisig::accounts::ApproveTransaction {
+        multisig: *multisig,
+        transaction: *transaction,
+        owner: *owner,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::RejectTransaction {}.data(),
+    }
+}
+
+/// Builds the `attach_tip` instruction of the `lamports`, paid by the
+/// `proposer` to the executor of the `transaction`.
+pub fn attach_tip(transaction: &Pubkey, proposer: &Pubkey, lamports: u64) -> Instruction {
+    let accounts = anchor_multisig::accounts::AttachTip {
+        transaction: *transaction,
+        proposer: *proposer,
+        system_program: system_program::ID,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::AttachTip { lamports }.data(),
+    }
+}
+
+/// Builds the `cancel_transaction` instruction signed by the
+/// `canceller`, which returns the rent back to the `proposer`.
+pub fn cancel(
+    multisig: &Pubkey,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    canceller: &Pubkey,
+) -> Instruction {
+    let accounts = anchor_multisig::accounts::CancelTransaction {
+        multisig: *multisig,
+        transaction: *transaction,
+        proposer: *proposer,
+        canceller: *canceller,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::CancelTransaction {}.data(),
+    }
+}
+
+/// Builds the `close_transaction` instruction signed by the `closer`,
+/// which returns the rent of the executed `transaction` to the
+/// `recipient`.
+pub fn close(
+    multisig: &Pubkey,
+    transaction: &Pubkey,
+    recipient: &Pubkey,
+    closer: &Pubkey,
+) -> Instruction {
+    let accounts = anchor_multisig::accounts::CloseTransaction {
+        multisig: *multisig,
+        transaction: *transaction,
+        recipient: *recipient,
+        closer: *closer,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::CloseTransaction {}.data(),
+    }
+}
+
+/// Builds the permissionless `close_expired` instruction, which returns
+/// the rent of the expired, or the rejected, `transaction` to the
+/// `proposer`.
+pub fn close_expired(transaction: &Pubkey, proposer: &Pubkey) -> Instruction {
+    let accounts = anchor_multisig::accounts::CloseExpired {
+        transaction: *transaction,
+        proposer: *proposer,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::CloseExpired {}.data(),
+    }
+}
+
+/// Builds the `replace_transaction` instruction, which replaces the
+/// instruction of the pending `transaction` with the `ix`, signed by
+/// the `proposer`.
+pub fn replace(
+    multisig: &Pubkey,
+    transaction: &Pubkey,
+    proposer: &Pubkey,
+    ix: Instruction,
+) -> Instruction {
+    let accounts = anchor_multisig::accounts::ReplaceTransaction {
+        multisig: *multisig,
+        transaction: *transaction,
+        proposer: *proposer,
+    };
+    let data = anchor_multisig::instruction::ReplaceTransaction {
+        tx_program_id: ix.program_id,
+        tx_accounts: ix.accounts.iter().map(transaction_meta).collect(),
+        tx_data: ix.data,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Builds the permissionless `expire_transaction` instruction, which
+/// marks the expired `transaction` as expired.
+pub fn expire(transaction: &Pubkey) -> Instruction {
+    let accounts = anchor_multisig::accounts::ExpireTransaction {
+        transaction: *transaction,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: anchor_multisig::instruction::ExpireTransaction {}.data(),
+    }
+}
+
+/// Builds the `purge_stale_transactions` instruction, which closes
+/// the `stale` transactions of the previous owner sets, given as the
+/// `(transaction, proposer)` pairs.
+pub fn purge_stale_transactions(multisig: &Pubkey, stale: &[(Pubkey, Pubkey)]) -> Instruction {
+    let accounts = anchor_multisig::accounts::PurgeStaleTransactions {
+        multisig: *multisig,
+    };
+    let mut accounts = accounts.to_account_metas(None);
+    for (transaction, proposer) in stale {
+        accounts.push(AccountMeta::new(*transaction, false));
+        accounts.push(AccountMeta::new(*proposer, false));
+    }
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: anchor_multisig::instruction::PurgeStaleTransactions {}.data(),
+    }
+}
+
+/// Builds the `execute_transaction` instruction of the `tx` stored in
+/// the `transaction` account, e.g. by [`fetch_transaction`].
+///
+/// The `executor` should sign the transaction and receives the tip,
+/// if any, attached by [`attach_tip`].
+///
+/// The accounts of the proposed instruction as well as its program
+/// are passed as the remaining accounts, where the multisig signer
+/// PDA is signed by the multisig program, not by the client.
+pub fn execute(
+    multisig: &Pubkey,
+    transaction: &Pubkey,
+    executor: &Pubkey,
+    tx: &Transaction,
+) -> Instruction {
+    let (signer, _) = multisig_signer(multisig);
+    let accounts = anchor_multisig::accounts::ExecuteTransaction {
+        multisig: *multisig,
+        transaction: *transaction,
+        multisig_signer: signer,
+        executor: *executor,
+    };
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(tx.accounts.iter().map(|meta| {
+        let is_signer = meta.is_signer && meta.pubkey != signer;
+        if meta.is_writable {
+            AccountMeta::new(meta.pubkey, is_signer)
+        } else {
+            AccountMeta::new_readonly(meta.pubkey, is_signer)
+        }
+    }));
+    metas.push(AccountMeta::new_readonly(tx.program_id, false));
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: metas,
+        data: anchor_multisig::instruction::ExecuteTransaction {}.data(),
+    }
+}
+
+/// Creates the program account of `space` bytes, funded by the `payer`
+/// with the rent-exempt lamports of the default rent.
+fn create_account(payer: &Pubkey, account: &Pubkey, space: usize) -> Instruction {
+    system_instruction::create_account(
+        payer,
+        account,
+        Rent::default().minimum_balance(space),
+        space as u64,
+        &PROGRAM_ID,
+    )
+}
@@ -0,0 +1,1744 @@
+//! The multisig program tests through the `solana-program-test` banks client.
+
+use anchor_lang::error::ERROR_CODE_OFFSET;
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_multisig::{
+    Multisig as MultisigAccount, Transaction as MultisigTransaction, TransactionStatus,
+};
+use solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext};
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+/// 2 of 3 multisig, where the payer is the first owner.
+struct Multisig {
+    banks: BanksClient,
+    payer: Keypair,
+    owners: Vec<Keypair>,
+    multisig: Pubkey,
+}
+
+async fn setup() -> Multisig {
+    setup_with_context().await.1
+}
+
+/// Sets up the multisig with the test context, e.g. to warp the slot.
+async fn setup_with_context() -> (ProgramTestContext, Multisig) {
+    let mut program = ProgramTest::new(
+        "anchor_multisig",
+        anchor_multisig_client::PROGRAM_ID,
+        processor!(anchor_multisig::entry),
+    );
+    // The native token program for the token vaults.
+    program.add_program(
+        "spl_token",
+        spl_token::ID,
+        processor!(spl_token::processor::Processor::process),
+    );
+    let context = program.start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+
+    let owners = vec![Keypair::new(), Keypair::new()];
+    let multisig = Keypair::new();
+    let ixs = anchor_multisig_client::create_multisig(
+        &payer.pubkey(),
+        &multisig.pubkey(),
+        [&payer]
+            .into_iter()
+            .chain(&owners)
+            .map(Keypair::pubkey)
+            .collect(),
+        2,
+    );
+    process(&mut banks, &payer, &ixs, &[&multisig])
+        .await
+        .unwrap();
+
+    // Funds the multisig signer PDA.
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig.pubkey());
+    let ix = system_instruction::transfer(&payer.pubkey(), &signer, 1_000_000_000);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    let multisig = Multisig {
+        banks,
+        payer,
+        owners,
+        multisig: multisig.pubkey(),
+    };
+    (context, multisig)
+}
+
+async fn process(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks.get_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&payer.pubkey()),
+        &[&[payer], signers].concat(),
+        recent_blockhash,
+    );
+    banks.process_transaction(tx).await.map_err(|e| e.unwrap())
+}
+
+async fn fetch_transaction(banks: &mut BanksClient, transaction: Pubkey) -> MultisigTransaction {
+    let account = banks.get_account(transaction).await.unwrap().unwrap();
+    MultisigTransaction::try_deserialize(&mut &account.data[..]).unwrap()
+}
+
+/// Funds the `to` account, e.g. the proposer, which gets the rent back
+/// without paying the transaction fees, so that the refund can be
+/// checked by its balance.
+async fn fund(banks: &mut BanksClient, payer: &Keypair, to: &Pubkey) {
+    let ix = system_instruction::transfer(&payer.pubkey(), to, 1_000_000_000);
+    process(banks, payer, &[ix], &[]).await.unwrap();
+}
+
+fn custom_error(error: anchor_multisig::Error) -> TransactionError {
+    TransactionError::InstructionError(
+        0,
+        InstructionError::Custom(ERROR_CODE_OFFSET + error as u32),
+    )
+}
+
+#[tokio::test]
+async fn initialize_multisig_validation() {
+    let Multisig {
+        mut banks, payer, ..
+    } = setup().await;
+
+    let owner = Pubkey::new_unique();
+    for (owners, threshold, want) in [
+        (vec![], 0, anchor_multisig::Error::NoOwners),
+        (
+            vec![owner, Pubkey::new_unique(), owner],
+            2,
+            anchor_multisig::Error::DuplicateOwner,
+        ),
+        (vec![owner], 0, anchor_multisig::Error::InvalidThreshold),
+        (vec![owner], 2, anchor_multisig::Error::InvalidThreshold),
+    ] {
+        let multisig = Keypair::new();
+        let ixs = anchor_multisig_client::create_multisig(
+            &payer.pubkey(),
+            &multisig.pubkey(),
+            owners,
+            threshold,
+        );
+        let got = process(&mut banks, &payer, &ixs, &[&multisig]).await;
+        assert_eq!(
+            got,
+            Err(TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(ERROR_CODE_OFFSET + want as u32),
+            )),
+            "threshold={threshold}",
+        );
+    }
+}
+
+#[tokio::test]
+async fn propose_approve_execute_transfer() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    // Proposed, and approved, by the payer.
+    let transaction = Keypair::new();
+    let to = Pubkey::new_unique();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &to,
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.signers, [true, true, false]);
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    assert_eq!(banks.get_balance(to).await.unwrap(), 1_000_000);
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert!(tx.executed);
+}
+
+#[tokio::test]
+async fn execute_without_enough_approvals() {
+    let Multisig {
+        mut banks,
+        payer,
+        multisig,
+        ..
+    } = setup().await;
+
+    let transaction = Keypair::new();
+    let to = Pubkey::new_unique();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &to,
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(anchor_multisig::Error::NotEnoughSigners))
+    );
+}
+
+#[tokio::test]
+async fn revoke_approval_before_execution() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let transaction = Keypair::new();
+    let to = Pubkey::new_unique();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &to,
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+
+    // Approved, then revoked.
+    let ixs = [
+        anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey()),
+        anchor_multisig_client::revoke(&multisig, &transaction.pubkey(), &owners[0].pubkey()),
+    ];
+    process(&mut banks, &payer, &ixs, &[&owners[0]])
+        .await
+        .unwrap();
+
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.signers, [true, false, false]);
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(anchor_multisig::Error::NotEnoughSigners))
+    );
+}
+
+#[tokio::test]
+async fn cancel_transaction_by_proposer() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let proposer = &owners[0];
+    fund(&mut banks, &payer, &proposer.pubkey()).await;
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &proposer.pubkey(),
+        &Pubkey::new_unique(),
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction, proposer])
+        .await
+        .unwrap();
+
+    // Not by the other owner.
+    let ix = anchor_multisig_client::cancel(
+        &multisig,
+        &transaction.pubkey(),
+        &proposer.pubkey(),
+        &owners[1].pubkey(),
+    );
+    let got = process(&mut banks, &payer, &[ix], &[&owners[1]]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotCanceller)));
+
+    let rent = banks.get_balance(transaction.pubkey()).await.unwrap();
+    let before = banks.get_balance(proposer.pubkey()).await.unwrap();
+    let ix = anchor_multisig_client::cancel(
+        &multisig,
+        &transaction.pubkey(),
+        &proposer.pubkey(),
+        &proposer.pubkey(),
+    );
+    process(&mut banks, &payer, &[ix], &[proposer])
+        .await
+        .unwrap();
+
+    assert!(banks
+        .get_account(transaction.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+    let after = banks.get_balance(proposer.pubkey()).await.unwrap();
+    assert_eq!(after, before + rent);
+}
+
+#[tokio::test]
+async fn change_threshold_through_transaction() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    for (threshold, want) in [
+        (4, Err(anchor_multisig::Error::InvalidThreshold)),
+        (1, Ok(())),
+    ] {
+        let transaction = Keypair::new();
+        let ixs = anchor_multisig_client::propose_change_threshold(
+            &multisig,
+            3,
+            &transaction.pubkey(),
+            &payer.pubkey(),
+            threshold,
+        );
+        process(&mut banks, &payer, &ixs, &[&transaction])
+            .await
+            .unwrap();
+        let ix =
+            anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+        process(&mut banks, &payer, &[ix], &[&owners[0]])
+            .await
+            .unwrap();
+
+        let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+        let ix =
+            anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+        let got = process(&mut banks, &payer, &[ix], &[]).await;
+        assert_eq!(got, want.map_err(custom_error), "threshold={threshold}");
+    }
+
+    let account = banks.get_account(multisig).await.unwrap().unwrap();
+    let account = MultisigAccount::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(account.threshold, 1);
+}
+
+#[tokio::test]
+async fn add_and_remove_owner_through_transaction() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let removed = owners[1].pubkey();
+    let added = Pubkey::new_unique();
+    for (n_owners, add, owner, want) in [
+        (3, false, added, Err(anchor_multisig::Error::InvalidOwner)),
+        (
+            3,
+            true,
+            removed,
+            Err(anchor_multisig::Error::DuplicateOwner),
+        ),
+        (3, false, removed, Ok(())),
+        (2, true, added, Ok(())),
+    ] {
+        let transaction = Keypair::new();
+        let propose = if add {
+            anchor_multisig_client::propose_add_owner
+        } else {
+            anchor_multisig_client::propose_remove_owner
+        };
+        let ixs = propose(
+            &multisig,
+            n_owners,
+            &transaction.pubkey(),
+            &payer.pubkey(),
+            owner,
+        );
+        process(&mut banks, &payer, &ixs, &[&transaction])
+            .await
+            .unwrap();
+        let ix =
+            anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+        process(&mut banks, &payer, &[ix], &[&owners[0]])
+            .await
+            .unwrap();
+
+        let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+        let ix =
+            anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+        let got = process(&mut banks, &payer, &[ix], &[]).await;
+        assert_eq!(got, want.map_err(custom_error), "owner={owner}");
+    }
+
+    let account = banks.get_account(multisig).await.unwrap().unwrap();
+    let account = MultisigAccount::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(account.owners, [payer.pubkey(), owners[0].pubkey(), added]);
+    assert_eq!(account.threshold, 2);
+    assert_eq!(account.owner_set_seqno, 2);
+}
+
+#[tokio::test]
+async fn close_expired_transaction() {
+    let (
+        mut context,
+        Multisig {
+            mut banks,
+            payer,
+            owners,
+            multisig,
+        },
+    ) = setup_with_context().await;
+
+    let proposer = &owners[0];
+    fund(&mut banks, &payer, &proposer.pubkey()).await;
+    let slot = banks.get_root_slot().await.unwrap();
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    let ix = system_instruction::transfer(&signer, &Pubkey::new_unique(), 1_000_000);
+
+    // Not in the past.
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_with_expiration(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &proposer.pubkey(),
+        ix.clone(),
+        Some(slot),
+    );
+    let got = process(&mut banks, &payer, &ixs, &[&transaction, proposer]).await;
+    assert_eq!(
+        got,
+        Err(TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(ERROR_CODE_OFFSET + anchor_multisig::Error::Expired as u32),
+        ))
+    );
+
+    let ixs = anchor_multisig_client::propose_with_expiration(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &proposer.pubkey(),
+        ix,
+        Some(slot + 100),
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction, proposer])
+        .await
+        .unwrap();
+
+    // Not expired yet.
+    let ix = anchor_multisig_client::close_expired(&transaction.pubkey(), &proposer.pubkey());
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotExpired)));
+
+    context.warp_to_slot(slot + 100).unwrap();
+
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[1].pubkey());
+    let got = process(&mut banks, &payer, &[ix], &[&owners[1]]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::Expired)));
+
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::Expired)));
+
+    // Marked as expired, by anyone, but only once.
+    let ix = anchor_multisig_client::expire(&transaction.pubkey());
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.approvals, 1);
+    assert_eq!(tx.status, TransactionStatus::Expired);
+    let ix = anchor_multisig_client::expire(&transaction.pubkey());
+    let got = process(&mut banks, proposer, &[ix], &[]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotActive)));
+
+    // No signer is required, with the rent back to the proposer.
+    let rent = banks.get_balance(transaction.pubkey()).await.unwrap();
+    let before = banks.get_balance(proposer.pubkey()).await.unwrap();
+    let ix = anchor_multisig_client::close_expired(&transaction.pubkey(), &proposer.pubkey());
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    assert!(banks
+        .get_account(transaction.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+    let after = banks.get_balance(proposer.pubkey()).await.unwrap();
+    assert_eq!(after, before + rent);
+}
+
+#[tokio::test]
+async fn replace_owner_keeps_other_approvals() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    // A pending transfer approved by the payer and the replaced owner.
+    let pending = Keypair::new();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &pending.pubkey(),
+        &payer.pubkey(),
+        &Pubkey::new_unique(),
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&pending])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &pending.pubkey(), &owners[1].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[1]])
+        .await
+        .unwrap();
+
+    let new = Keypair::new();
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_replace_owner(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        owners[1].pubkey(),
+        new.pubkey(),
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    let account = banks.get_account(multisig).await.unwrap().unwrap();
+    let account = MultisigAccount::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(
+        account.owners,
+        [payer.pubkey(), owners[0].pubkey(), new.pubkey()]
+    );
+    assert_eq!(account.owner_set_seqno, 0);
+
+    // The replaced owner's approval doesn't count, though the pending
+    // transaction was not given to the replacement.
+    let tx = fetch_transaction(&mut banks, pending.pubkey()).await;
+    let ix = anchor_multisig_client::execute(&multisig, &pending.pubkey(), &payer.pubkey(), &tx);
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(anchor_multisig::Error::NotEnoughSigners))
+    );
+
+    // Only the replaced owner's approval is cleared, and the new owner
+    // approves it for the execution.
+    let ix = anchor_multisig_client::approve(&multisig, &pending.pubkey(), &new.pubkey());
+    process(&mut banks, &payer, &[ix], &[&new]).await.unwrap();
+    let tx = fetch_transaction(&mut banks, pending.pubkey()).await;
+    assert_eq!(tx.signers, [true, false, true]);
+    let ix = anchor_multisig_client::execute(&multisig, &pending.pubkey(), &new.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[&new]).await.unwrap();
+}
+
+#[tokio::test]
+async fn close_executed_transaction() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &Pubkey::new_unique(),
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+
+    // Not executed yet.
+    let ix = anchor_multisig_client::close(
+        &multisig,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &payer.pubkey(),
+    );
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotExecuted)));
+
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    // Not by the other owner.
+    let recipient = Pubkey::new_unique();
+    let ix = anchor_multisig_client::close(
+        &multisig,
+        &transaction.pubkey(),
+        &recipient,
+        &owners[0].pubkey(),
+    );
+    let got = process(&mut banks, &payer, &[ix], &[&owners[0]]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotCloser)));
+
+    let rent = banks.get_balance(transaction.pubkey()).await.unwrap();
+    let ix = anchor_multisig_client::close(
+        &multisig,
+        &transaction.pubkey(),
+        &recipient,
+        &payer.pubkey(),
+    );
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    assert!(banks
+        .get_account(transaction.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(banks.get_balance(recipient).await.unwrap(), rent);
+}
+
+#[tokio::test]
+async fn create_transaction_pda() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    let to = Pubkey::new_unique();
+    for count in 0..2 {
+        let ix = system_instruction::transfer(&signer, &to, 1_000_000);
+        let ix = anchor_multisig_client::create_transaction(&multisig, count, &payer.pubkey(), ix);
+        process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+        let (transaction, _) = anchor_multisig_client::transaction_address(&multisig, count);
+        let ix = anchor_multisig_client::approve(&multisig, &transaction, &owners[0].pubkey());
+        process(&mut banks, &payer, &[ix], &[&owners[0]])
+            .await
+            .unwrap();
+        let tx = fetch_transaction(&mut banks, transaction).await;
+        assert_eq!(tx.proposer, payer.pubkey());
+        let ix = anchor_multisig_client::execute(&multisig, &transaction, &payer.pubkey(), &tx);
+        process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    }
+
+    let account = banks.get_account(multisig).await.unwrap().unwrap();
+    let account = MultisigAccount::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(account.transaction_count, 2);
+    assert_eq!(banks.get_balance(to).await.unwrap(), 2_000_000);
+
+    // The used nonce can't be reused.
+    let ix = system_instruction::transfer(&signer, &to, 1);
+    let ix = anchor_multisig_client::create_transaction(&multisig, 1, &payer.pubkey(), ix);
+    assert!(process(&mut banks, &payer, &[ix], &[]).await.is_err());
+}
+
+#[tokio::test]
+async fn execute_after_execution_delay() {
+    let (
+        mut context,
+        Multisig {
+            mut banks,
+            payer,
+            owners,
+            multisig,
+        },
+    ) = setup_with_context().await;
+
+    // Sets the 100 slots delay, executed right away without the delay.
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_change_execution_delay(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        100,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    let account = banks.get_account(multisig).await.unwrap().unwrap();
+    let account = MultisigAccount::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(account.execution_delay_slots, 100);
+
+    let transaction = Keypair::new();
+    let to = Pubkey::new_unique();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &to,
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.approved_at_slot, None);
+
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert!(tx.approved_at_slot.is_some());
+
+    // The revocation below the threshold clears the approved slot.
+    let ix = anchor_multisig_client::revoke(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.approved_at_slot, None);
+
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[1].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[1]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let approved_at = tx.approved_at_slot.unwrap();
+
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    let got = process(&mut banks, &payer, std::slice::from_ref(&ix), &[]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(
+            anchor_multisig::Error::ExecutionDelayNotElapsed
+        ))
+    );
+
+    context.warp_to_slot(approved_at + 100).unwrap();
+
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    assert_eq!(banks.get_balance(to).await.unwrap(), 1_000_000);
+}
+
+#[tokio::test]
+async fn propose_with_memo() {
+    let Multisig {
+        mut banks,
+        payer,
+        multisig,
+        ..
+    } = setup().await;
+
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    let ix = system_instruction::transfer(&signer, &Pubkey::new_unique(), 1_000_000);
+
+    let memo = "Pay the Q3 audit invoice";
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_with_memo(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        ix.clone(),
+        memo,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.memo, memo);
+
+    let memo = "x".repeat(MultisigTransaction::MAX_MEMO_LEN + 1);
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_with_memo(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        ix,
+        &memo,
+    );
+    let got = process(&mut banks, &payer, &ixs, &[&transaction]).await;
+    assert_eq!(
+        got,
+        Err(TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(
+                ERROR_CODE_OFFSET + anchor_multisig::Error::MemoTooLong as u32
+            ),
+        ))
+    );
+}
+
+#[tokio::test]
+async fn spend_within_limit() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    // Disabled by default.
+    let to = Pubkey::new_unique();
+    let ix = anchor_multisig_client::spend_within_limit(&multisig, &owners[1].pubkey(), &to, 1);
+    let got = process(&mut banks, &payer, &[ix], &[&owners[1]]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(anchor_multisig::Error::SpendingLimitExceeded))
+    );
+
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_change_spending_limit(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    let ix =
+        anchor_multisig_client::spend_within_limit(&multisig, &owners[1].pubkey(), &to, 1_000_001);
+    let got = process(&mut banks, &payer, &[ix], &[&owners[1]]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(anchor_multisig::Error::SpendingLimitExceeded))
+    );
+
+    // A single owner's signature is enough within the limit.
+    let ix =
+        anchor_multisig_client::spend_within_limit(&multisig, &owners[1].pubkey(), &to, 900_000);
+    process(&mut banks, &payer, &[ix], &[&owners[1]])
+        .await
+        .unwrap();
+    assert_eq!(banks.get_balance(to).await.unwrap(), 900_000);
+
+    // But not over the limit in total within the window.
+    let ix =
+        anchor_multisig_client::spend_within_limit(&multisig, &owners[1].pubkey(), &to, 100_001);
+    let got = process(&mut banks, &payer, &[ix], &[&owners[1]]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(anchor_multisig::Error::SpendingLimitExceeded))
+    );
+
+    // The limit is per owner.
+    let ix =
+        anchor_multisig_client::spend_within_limit(&multisig, &owners[0].pubkey(), &to, 100_000);
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    assert_eq!(banks.get_balance(to).await.unwrap(), 1_000_000);
+
+    let other = Keypair::new();
+    let ix = anchor_multisig_client::spend_within_limit(&multisig, &other.pubkey(), &to, 1);
+    let got = process(&mut banks, &payer, &[ix], &[&other]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::InvalidOwner)));
+    assert_eq!(banks.get_balance(to).await.unwrap(), 1_000_000);
+}
+
+#[tokio::test]
+async fn purge_stale_transactions() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    let transfer = system_instruction::transfer(&signer, &Pubkey::new_unique(), 1_000_000);
+    let proposer = &owners[0];
+    fund(&mut banks, &payer, &proposer.pubkey()).await;
+    let stale = Keypair::new();
+    let ixs = anchor_multisig_client::propose(
+        &multisig,
+        3,
+        &stale.pubkey(),
+        &proposer.pubkey(),
+        transfer.clone(),
+    );
+    process(&mut banks, &payer, &ixs, &[&stale, proposer])
+        .await
+        .unwrap();
+
+    // Bumps the owner set sequence number.
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_remove_owner(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        owners[1].pubkey(),
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    let current = Keypair::new();
+    let ixs =
+        anchor_multisig_client::propose(&multisig, 2, &current.pubkey(), &payer.pubkey(), transfer);
+    process(&mut banks, &payer, &ixs, &[&current])
+        .await
+        .unwrap();
+
+    let ix = anchor_multisig_client::purge_stale_transactions(
+        &multisig,
+        &[(current.pubkey(), payer.pubkey())],
+    );
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotStale)));
+
+    let ix = anchor_multisig_client::purge_stale_transactions(
+        &multisig,
+        &[(stale.pubkey(), payer.pubkey())],
+    );
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(
+        got,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(anchor_lang::error::ErrorCode::ConstraintHasOne as u32),
+        ))
+    );
+
+    // Both the pending and the executed transactions are purged.
+    let rent = banks.get_balance(stale.pubkey()).await.unwrap();
+    let before = banks.get_balance(proposer.pubkey()).await.unwrap();
+    let ix = anchor_multisig_client::purge_stale_transactions(
+        &multisig,
+        &[
+            (stale.pubkey(), proposer.pubkey()),
+            (transaction.pubkey(), payer.pubkey()),
+        ],
+    );
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    for transaction in [stale.pubkey(), transaction.pubkey()] {
+        assert!(banks.get_account(transaction).await.unwrap().is_none());
+    }
+    let after = banks.get_balance(proposer.pubkey()).await.unwrap();
+    assert_eq!(after, before + rent);
+}
+
+#[tokio::test]
+async fn execute_with_tip() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let (proposer, executor) = (&owners[0], &owners[1]);
+    fund(&mut banks, &payer, &proposer.pubkey()).await;
+    fund(&mut banks, &payer, &executor.pubkey()).await;
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    let transfer = system_instruction::transfer(&signer, &Pubkey::new_unique(), 1_000_000);
+
+    let mut transactions = vec![];
+    for _ in 0..2 {
+        let transaction = Keypair::new();
+        let ixs = anchor_multisig_client::propose(
+            &multisig,
+            3,
+            &transaction.pubkey(),
+            &proposer.pubkey(),
+            transfer.clone(),
+        );
+        process(&mut banks, &payer, &ixs, &[&transaction, proposer])
+            .await
+            .unwrap();
+        transactions.push(transaction);
+    }
+
+    let ix = anchor_multisig_client::attach_tip(&transactions[0].pubkey(), &executor.pubkey(), 1);
+    let got = process(&mut banks, &payer, &[ix], &[executor]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotProposer)));
+
+    let tip = 100_000;
+    for transaction in &transactions {
+        let ix = anchor_multisig_client::attach_tip(&transaction.pubkey(), &proposer.pubkey(), tip);
+        process(&mut banks, &payer, &[ix], &[proposer])
+            .await
+            .unwrap();
+        assert_eq!(
+            fetch_transaction(&mut banks, transaction.pubkey())
+                .await
+                .tip,
+            tip
+        );
+    }
+
+    // The tip is paid to the executor.
+    let transaction = transactions[0].pubkey();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction, &payer.pubkey());
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    let rent = banks.get_balance(transaction).await.unwrap() - tip;
+    let before = banks.get_balance(executor.pubkey()).await.unwrap();
+    let tx = fetch_transaction(&mut banks, transaction).await;
+    let ix = anchor_multisig_client::execute(&multisig, &transaction, &executor.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[executor])
+        .await
+        .unwrap();
+    let after = banks.get_balance(executor.pubkey()).await.unwrap();
+    assert_eq!(after, before + tip);
+    assert_eq!(banks.get_balance(transaction).await.unwrap(), rent);
+    assert_eq!(fetch_transaction(&mut banks, transaction).await.tip, 0);
+
+    // The tip is refunded to the proposer with the rent.
+    let transaction = transactions[1].pubkey();
+    let refund = banks.get_balance(transaction).await.unwrap();
+    let before = banks.get_balance(proposer.pubkey()).await.unwrap();
+    let ix = anchor_multisig_client::cancel(
+        &multisig,
+        &transaction,
+        &proposer.pubkey(),
+        &proposer.pubkey(),
+    );
+    process(&mut banks, &payer, &[ix], &[proposer])
+        .await
+        .unwrap();
+    let after = banks.get_balance(proposer.pubkey()).await.unwrap();
+    assert_eq!(after, before + refund);
+    assert_eq!(refund, rent + tip);
+}
+
+#[tokio::test]
+async fn execute_by_owners_only() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_set_owners_only_execution(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        true,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    let account = banks.get_account(multisig).await.unwrap().unwrap();
+    let account = MultisigAccount::try_deserialize(&mut &account.data[..]).unwrap();
+    assert!(account.owners_only_execution);
+
+    let transaction = Keypair::new();
+    let to = Pubkey::new_unique();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &to,
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+
+    // The keeper, not an owner, can't execute it anymore.
+    let keeper = Keypair::new();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &keeper.pubkey(), &tx);
+    let got = process(&mut banks, &payer, &[ix], &[&keeper]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::InvalidOwner)));
+
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &owners[1].pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[&owners[1]])
+        .await
+        .unwrap();
+    assert_eq!(banks.get_balance(to).await.unwrap(), 1_000_000);
+}
+
+#[tokio::test]
+async fn reject_transaction() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let proposer = &owners[0];
+    fund(&mut banks, &payer, &proposer.pubkey()).await;
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &proposer.pubkey(),
+        &Pubkey::new_unique(),
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction, proposer])
+        .await
+        .unwrap();
+
+    // 2 rejections out of 3 owners make the 2 approvals unreachable.
+    let ix = anchor_multisig_client::reject(&multisig, &transaction.pubkey(), &owners[1].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[1]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.rejections, [false, false, true]);
+    assert!(!tx.rejected);
+    assert_eq!(tx.status, TransactionStatus::Active);
+
+    // Paid by the other owner, not to be the same as the closure below.
+    fund(&mut banks, &payer, &owners[1].pubkey()).await;
+    let ix = anchor_multisig_client::close_expired(&transaction.pubkey(), &proposer.pubkey());
+    let got = process(&mut banks, &owners[1], &[ix], &[]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotExpired)));
+
+    // The proposer changes the mind.
+    let ix = anchor_multisig_client::reject(&multisig, &transaction.pubkey(), &proposer.pubkey());
+    process(&mut banks, &payer, &[ix], &[proposer])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.signers, [false, false, false]);
+    assert_eq!(tx.rejections, [false, true, true]);
+    assert!(tx.rejected);
+    assert_eq!(tx.approvals, 0);
+    assert_eq!(tx.status, TransactionStatus::Rejected);
+
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &payer.pubkey());
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::Rejected)));
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::Rejected)));
+
+    // It's closed early by anyone.
+    let rent = banks.get_balance(transaction.pubkey()).await.unwrap();
+    let before = banks.get_balance(proposer.pubkey()).await.unwrap();
+    let ix = anchor_multisig_client::close_expired(&transaction.pubkey(), &proposer.pubkey());
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    assert!(banks
+        .get_account(transaction.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+    let after = banks.get_balance(proposer.pubkey()).await.unwrap();
+    assert_eq!(after, before + rent);
+}
+
+#[tokio::test]
+async fn approve_by_delegate() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let hot = Keypair::new();
+    for (delegate, want) in [
+        (
+            owners[1].pubkey(),
+            Err(anchor_multisig::Error::InvalidDelegate),
+        ),
+        (hot.pubkey(), Ok(())),
+    ] {
+        let transaction = Keypair::new();
+        let ixs = anchor_multisig_client::propose_set_delegate(
+            &multisig,
+            3,
+            &transaction.pubkey(),
+            &payer.pubkey(),
+            owners[0].pubkey(),
+            Some(delegate),
+        );
+        process(&mut banks, &payer, &ixs, &[&transaction])
+            .await
+            .unwrap();
+        let ix =
+            anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[1].pubkey());
+        process(&mut banks, &payer, &[ix], &[&owners[1]])
+            .await
+            .unwrap();
+        let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+        let ix =
+            anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+        let got = process(&mut banks, &payer, &[ix], &[]).await;
+        assert_eq!(got, want.map_err(custom_error), "delegate={delegate}");
+    }
+
+    // The delegate's approval counts as the owner's.
+    let transaction = Keypair::new();
+    let to = Pubkey::new_unique();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &to,
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &hot.pubkey());
+    process(&mut banks, &payer, &[ix], &[&hot]).await.unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.signers, [true, true, false]);
+
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    assert_eq!(banks.get_balance(to).await.unwrap(), 1_000_000);
+}
+
+#[tokio::test]
+async fn freeze_multisig() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    // The pending transaction before the freeze.
+    let pending = Keypair::new();
+    let to = Pubkey::new_unique();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &pending.pubkey(),
+        &payer.pubkey(),
+        &to,
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&pending])
+        .await
+        .unwrap();
+
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_freeze(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    let account = banks.get_account(multisig).await.unwrap().unwrap();
+    let account = MultisigAccount::try_deserialize(&mut &account.data[..]).unwrap();
+    assert!(account.frozen);
+
+    // No new transactions, approvals, or executions.
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &to,
+        1_000_000,
+    );
+    assert_eq!(
+        process(&mut banks, &payer, &ixs, &[&transaction]).await,
+        Err(TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(ERROR_CODE_OFFSET + anchor_multisig::Error::Frozen as u32),
+        ))
+    );
+    let ix = anchor_multisig_client::approve(&multisig, &pending.pubkey(), &owners[0].pubkey());
+    assert_eq!(
+        process(&mut banks, &payer, &[ix], &[&owners[0]]).await,
+        Err(custom_error(anchor_multisig::Error::Frozen))
+    );
+    let ix = anchor_multisig_client::spend_within_limit(&multisig, &owners[1].pubkey(), &to, 1);
+    assert_eq!(
+        process(&mut banks, &payer, &[ix], &[&owners[1]]).await,
+        Err(custom_error(anchor_multisig::Error::Frozen))
+    );
+
+    // But the unfreeze.
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_unfreeze(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    // The pending transaction goes through again.
+    let ix = anchor_multisig_client::approve(&multisig, &pending.pubkey(), &owners[1].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[1]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, pending.pubkey()).await;
+    let ix = anchor_multisig_client::execute(&multisig, &pending.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    assert_eq!(banks.get_balance(to).await.unwrap(), 1_000_000);
+}
+
+#[tokio::test]
+async fn sol_transfer_from_vault() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let (vault, _) = anchor_multisig_client::vault_address(&multisig);
+    let ix = anchor_multisig_client::deposit(&multisig, &payer.pubkey(), 1_000_000_000);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    assert_eq!(banks.get_balance(vault).await.unwrap(), 1_000_000_000);
+
+    let transaction = Keypair::new();
+    let recipient = Pubkey::new_unique();
+    let ixs = anchor_multisig_client::propose_sol_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &recipient,
+        400_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+
+    // Not before the threshold.
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(anchor_multisig::Error::NotEnoughSigners))
+    );
+
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &owners[1].pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[&owners[1]])
+        .await
+        .unwrap();
+
+    assert_eq!(banks.get_balance(recipient).await.unwrap(), 400_000_000);
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.approvals, 2);
+    assert_eq!(tx.status, TransactionStatus::Executed);
+    assert_eq!(banks.get_balance(vault).await.unwrap(), 600_000_000);
+}
+
+/// Creates the `account` of `space` bytes owned by the token program.
+fn create_token_account(rent: u64, payer: &Pubkey, account: &Pubkey, space: usize) -> Instruction {
+    system_instruction::create_account(payer, account, rent, space as u64, &spl_token::ID)
+}
+
+#[tokio::test]
+async fn token_transfer_from_vault() {
+    use spl_token::solana_program::program_pack::Pack;
+
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let rent = banks.get_rent().await.unwrap();
+    let mint = Keypair::new();
+    let destination = Keypair::new();
+    let ixs = [
+        create_token_account(
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            &payer.pubkey(),
+            &mint.pubkey(),
+            spl_token::state::Mint::LEN,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &payer.pubkey(),
+            None,
+            0,
+        )
+        .unwrap(),
+        create_token_account(
+            rent.minimum_balance(spl_token::state::Account::LEN),
+            &payer.pubkey(),
+            &destination.pubkey(),
+            spl_token::state::Account::LEN,
+        ),
+        spl_token::instruction::initialize_account3(
+            &spl_token::ID,
+            &destination.pubkey(),
+            &mint.pubkey(),
+            &owners[1].pubkey(),
+        )
+        .unwrap(),
+    ];
+    process(&mut banks, &payer, &ixs, &[&mint, &destination])
+        .await
+        .unwrap();
+
+    // Creates and funds the token vault.
+    let (vault, _) = anchor_multisig_client::token_vault_address(&multisig, &mint.pubkey());
+    let ixs = [
+        anchor_multisig_client::create_token_vault(&multisig, &mint.pubkey(), &payer.pubkey()),
+        spl_token::instruction::mint_to(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &vault,
+            &payer.pubkey(),
+            &[],
+            1_000,
+        )
+        .unwrap(),
+    ];
+    process(&mut banks, &payer, &ixs, &[]).await.unwrap();
+    let account = banks.get_account(vault).await.unwrap().unwrap();
+    let account = spl_token::state::Account::unpack(&account.data).unwrap();
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    assert_eq!(account.owner, signer);
+    assert_eq!(account.amount, 1_000);
+
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_token_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &mint.pubkey(),
+        &destination.pubkey(),
+        400,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    for (account, want) in [(vault, 600), (destination.pubkey(), 400)] {
+        let account = banks.get_account(account).await.unwrap().unwrap();
+        let account = spl_token::state::Account::unpack(&account.data).unwrap();
+        assert_eq!(account.amount, want);
+    }
+}
+
+#[tokio::test]
+async fn create_funded_token_vault() {
+    use spl_token::solana_program::program_pack::Pack;
+
+    let Multisig {
+        mut banks,
+        payer,
+        multisig,
+        ..
+    } = setup().await;
+
+    let rent = banks.get_rent().await.unwrap();
+    let mint = Keypair::new();
+    let ixs = [
+        create_token_account(
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            &payer.pubkey(),
+            &mint.pubkey(),
+            spl_token::state::Mint::LEN,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &payer.pubkey(),
+            None,
+            0,
+        )
+        .unwrap(),
+    ];
+    process(&mut banks, &payer, &ixs, &[&mint]).await.unwrap();
+
+    // Anyone can fund the token vault PDA before the creation, e.g.
+    // less than the token account rent.
+    let (vault, _) = anchor_multisig_client::token_vault_address(&multisig, &mint.pubkey());
+    let ix = system_instruction::transfer(&payer.pubkey(), &vault, 1_000_000);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+
+    let ix = anchor_multisig_client::create_token_vault(&multisig, &mint.pubkey(), &payer.pubkey());
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    let account = banks.get_account(vault).await.unwrap().unwrap();
+    assert_eq!(account.owner, spl_token::ID);
+    assert_eq!(
+        account.lamports,
+        rent.minimum_balance(spl_token::state::Account::LEN)
+    );
+    let account = spl_token::state::Account::unpack(&account.data).unwrap();
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    assert_eq!(account.owner, signer);
+    assert_eq!(account.mint, mint.pubkey());
+}
+
+#[tokio::test]
+async fn create_recovery_with_duplicate_guardians() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    // The same guardian twice, which meets the threshold alone.
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    let recovery = Keypair::new();
+    let guardian = Pubkey::new_unique();
+    let accounts = anchor_multisig::accounts::CreateRecovery {
+        multisig,
+        recovery: recovery.pubkey(),
+        multisig_signer: signer,
+    };
+    let data = anchor_multisig::instruction::CreateRecovery {
+        guardians: vec![guardian, guardian],
+        threshold: 2,
+        delay: 0,
+    };
+    let ix = Instruction {
+        program_id: anchor_multisig_client::PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    };
+    let transaction = Keypair::new();
+    let ixs =
+        anchor_multisig_client::propose(&multisig, 3, &transaction.pubkey(), &payer.pubkey(), ix);
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[0].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[0]])
+        .await
+        .unwrap();
+
+    let space = anchor_multisig::Recovery::required_space(2);
+    let rent = banks.get_rent().await.unwrap().minimum_balance(space);
+    let ix = system_instruction::create_account(
+        &payer.pubkey(),
+        &recovery.pubkey(),
+        rent,
+        space as u64,
+        &anchor_multisig_client::PROGRAM_ID,
+    );
+    process(&mut banks, &payer, &[ix], &[&recovery])
+        .await
+        .unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    let err = process(&mut banks, &payer, &[ix], &[&recovery])
+        .await
+        .unwrap_err();
+    assert_eq!(err, custom_error(anchor_multisig::Error::DuplicateGuardian));
+}
+
+#[tokio::test]
+async fn replace_transaction_before_approvals() {
+    let Multisig {
+        mut banks,
+        payer,
+        owners,
+        multisig,
+    } = setup().await;
+
+    let transaction = Keypair::new();
+    let ixs = anchor_multisig_client::propose_transfer(
+        &multisig,
+        3,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        &Pubkey::new_unique(),
+        1_000_000,
+    );
+    process(&mut banks, &payer, &ixs, &[&transaction])
+        .await
+        .unwrap();
+
+    // Replaced with the other recipient by the proposer only.
+    let (signer, _) = anchor_multisig_client::multisig_signer(&multisig);
+    let to = Pubkey::new_unique();
+    let transfer = system_instruction::transfer(&signer, &to, 2_000_000);
+    let ix = anchor_multisig_client::replace(
+        &multisig,
+        &transaction.pubkey(),
+        &owners[0].pubkey(),
+        transfer.clone(),
+    );
+    let got = process(&mut banks, &payer, &[ix], &[&owners[0]]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotProposer)));
+
+    let ix = anchor_multisig_client::replace(
+        &multisig,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        transfer.clone(),
+    );
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    assert_eq!(tx.data, transfer.data);
+    assert_eq!(tx.signers, [true, false, false]);
+    assert_eq!(tx.approvals, 1);
+
+    // No more replacement once the other owner approved.
+    let ix = anchor_multisig_client::approve(&multisig, &transaction.pubkey(), &owners[1].pubkey());
+    process(&mut banks, &payer, &[ix], &[&owners[1]])
+        .await
+        .unwrap();
+    let ix = anchor_multisig_client::replace(
+        &multisig,
+        &transaction.pubkey(),
+        &owners[1].pubkey(),
+        system_instruction::transfer(&signer, &Pubkey::new_unique(), 1_000_000),
+    );
+    let got = process(&mut banks, &payer, &[ix], &[&owners[1]]).await;
+    assert_eq!(got, Err(custom_error(anchor_multisig::Error::NotProposer)));
+    let ix = anchor_multisig_client::replace(
+        &multisig,
+        &transaction.pubkey(),
+        &payer.pubkey(),
+        system_instruction::transfer(&signer, &Pubkey::new_unique(), 1_000_000),
+    );
+    let got = process(&mut banks, &payer, &[ix], &[]).await;
+    assert_eq!(
+        got,
+        Err(custom_error(anchor_multisig::Error::AlreadyApproved))
+    );
+
+    let tx = fetch_transaction(&mut banks, transaction.pubkey()).await;
+    let ix =
+        anchor_multisig_client::execute(&multisig, &transaction.pubkey(), &payer.pubkey(), &tx);
+    process(&mut banks, &payer, &[ix], &[]).await.unwrap();
+    assert_eq!(banks.get_balance(to).await.unwrap(), 2_000_000);
+}
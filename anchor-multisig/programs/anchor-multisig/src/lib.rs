@@ -12,11 +12,16 @@
 //! [coral-xyz]: https://github.com/coral-xyz/multisig/blob/master/programs/multisig/src/lib.rs
 //! [anchor tests]: https://github.com/coral-xyz/anchor/blob/master/tests/multisig/programs/multisig/src/lib.rs
 
+use std::iter;
+use std::mem;
 use std::ops::Deref;
 
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program;
 use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
 
 declare_id!("EYg7btAzuDC6MoYeCN9YzZcWu3T25Xqt7SEhcTbdbnG2");
 
@@ -30,6 +35,111 @@ pub enum Error {
 
     #[msg("There is not enough signers approved.")]
     NotEnoughSigners,
+
+    #[msg("Too many owners for the multisig account.")]
+    TooManyOwners,
+
+    #[msg("The threshold should be between 1 and the number of owners.")]
+    InvalidThreshold,
+
+    #[msg("The multisig account is too small for the owners.")]
+    AccountTooSmall,
+
+    #[msg("The template amount offset is out of the instruction data.")]
+    InvalidAmountOffset,
+
+    #[msg("The transaction data is still being written.")]
+    NotFinalized,
+
+    #[msg("The transaction had been already finalized.")]
+    AlreadyFinalized,
+
+    #[msg("The transaction account is too small for the data.")]
+    TransactionTooLarge,
+
+    #[msg("Too many guardians for the recovery account.")]
+    TooManyGuardians,
+
+    #[msg("The given guardian is not part of this recovery.")]
+    InvalidGuardian,
+
+    #[msg("There is a recovery proposal pending already.")]
+    RecoveryPending,
+
+    #[msg("There is no recovery proposal pending.")]
+    NoRecoveryPending,
+
+    #[msg("The recovery delay has not elapsed yet.")]
+    RecoveryDelayNotElapsed,
+
+    #[msg("The transaction account is missing in the remaining accounts.")]
+    MissingAccount,
+
+    #[msg("The transaction account should be writable.")]
+    WritabilityMismatch,
+
+    #[msg("The owner set had been changed after the transaction creation.")]
+    StaleOwnerSet,
+
+    #[msg("Only the proposer or the multisig signer can cancel the transaction.")]
+    NotCanceller,
+
+    #[msg("The given owner is already part of this multisig.")]
+    DuplicateOwner,
+
+    #[msg("The transaction had been expired.")]
+    Expired,
+
+    #[msg("The transaction has not been expired yet.")]
+    NotExpired,
+
+    #[msg("The transaction doesn't belong to this multisig.")]
+    InvalidTransaction,
+
+    #[msg("The transaction has not been executed yet.")]
+    NotExecuted,
+
+    #[msg("Only the proposer or the multisig signer can close the transaction.")]
+    NotCloser,
+
+    #[msg("The execution delay has not elapsed yet.")]
+    ExecutionDelayNotElapsed,
+
+    #[msg("The transaction memo is too long.")]
+    MemoTooLong,
+
+    #[msg("The amount exceeds the spending limit of the multisig.")]
+    SpendingLimitExceeded,
+
+    #[msg("The transaction is for the current owner set.")]
+    NotStale,
+
+    #[msg("The multisig should have at least one owner.")]
+    NoOwners,
+
+    #[msg("Only the proposer can attach the tip to, or replace, the transaction.")]
+    NotProposer,
+
+    #[msg("The transaction had been rejected.")]
+    Rejected,
+
+    #[msg("The delegate should be neither an owner nor the other owner's delegate.")]
+    InvalidDelegate,
+
+    #[msg("The multisig is frozen.")]
+    Frozen,
+
+    #[msg("The transaction is not active.")]
+    NotActive,
+
+    #[msg("The other owners had already approved the transaction.")]
+    AlreadyApproved,
+
+    #[msg("The recovery delay should not be negative.")]
+    InvalidDelay,
+
+    #[msg("The given guardian is already part of this recovery.")]
+    DuplicateGuardian,
 }
 
 #[program]
@@ -44,38 +154,266 @@ pub mod anchor_multisig {
     ) -> Result<()> {
         let multisig = &mut ctx.accounts.multisig;
 
+        Multisig::validate_owners(multisig, &owners)?;
+        require!(
+            threshold > 0 && threshold <= owners.len() as u64,
+            Error::InvalidThreshold
+        );
+
         multisig.owners = owners;
         multisig.threshold = threshold;
         multisig.bump = bump;
         multisig.owner_set_seqno = 0;
+        multisig.transaction_count = 0;
+        multisig.execution_delay_slots = 0;
+        multisig.spending_limit = 0;
+        multisig.owners_only_execution = false;
+        multisig.delegates = vec![];
+        multisig.frozen = false;
+        multisig.spends = vec![];
+        multisig.owner_epochs = vec![0; multisig.owners.len()];
 
         Ok(())
     }
 
+    /// Initializes a transaction with the `memo`, up to
+    /// [`Transaction::MAX_MEMO_LEN`] bytes, which tells the approvers
+    /// what the transaction is for.
     pub fn initialize_transaction(
         ctx: Context<InitializeTransaction>,
         tx_program_id: Pubkey,
         tx_accounts: Vec<TransactionMeta>,
         tx_data: Vec<u8>,
+        expires_at_slot: Option<u64>,
+        memo: String,
     ) -> Result<()> {
-        // Signers vector, set `true` for the proposer.
-        let signers: Vec<_> = ctx
-            .accounts
-            .multisig
-            .owners
-            .iter()
-            .map(|key| key == ctx.accounts.proposer.key)
-            .collect();
+        let accounts = ctx.accounts;
+        accounts.transaction.init(
+            &accounts.multisig,
+            accounts.proposer.key,
+            tx_program_id,
+            tx_accounts,
+            tx_data,
+            expires_at_slot,
+        )?;
+        require_gte!(Transaction::MAX_MEMO_LEN, memo.len(), Error::MemoTooLong);
+        accounts.transaction.memo = memo;
+        emit!(TransactionCreated {
+            multisig: accounts.multisig.key(),
+            transaction: accounts.transaction.key(),
+            proposer: accounts.proposer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Creates a transaction in the PDA derived from the multisig
+    /// transaction count, paid by the `proposer`, so that the client
+    /// doesn't need to prepare the transaction account keypair.
+    pub fn create_transaction(
+        ctx: Context<CreateTransaction>,
+        tx_program_id: Pubkey,
+        tx_accounts: Vec<TransactionMeta>,
+        tx_data: Vec<u8>,
+        expires_at_slot: Option<u64>,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        accounts.transaction.init(
+            &accounts.multisig,
+            accounts.proposer.key,
+            tx_program_id,
+            tx_accounts,
+            tx_data,
+            expires_at_slot,
+        )?;
+        accounts.multisig.transaction_count += 1;
+
+        emit!(TransactionCreated {
+            multisig: accounts.multisig.key(),
+            transaction: accounts.transaction.key(),
+            proposer: accounts.proposer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Initializes a transaction with the empty instruction data,
+    /// which will be written by `append_tx_data` incrementally for
+    /// the data exceeding the single transaction size.
+    ///
+    /// The transaction is not approvable until `finalize_transaction`.
+    pub fn initialize_transaction_buffered(
+        ctx: Context<InitializeTransaction>,
+        tx_program_id: Pubkey,
+        tx_accounts: Vec<TransactionMeta>,
+        expires_at_slot: Option<u64>,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        accounts.transaction.init(
+            &accounts.multisig,
+            accounts.proposer.key,
+            tx_program_id,
+            tx_accounts,
+            vec![],
+            expires_at_slot,
+        )?;
+        accounts.transaction.finalized = false;
+        accounts.transaction.approved_at_slot = None;
+        emit!(TransactionCreated {
+            multisig: accounts.multisig.key(),
+            transaction: accounts.transaction.key(),
+            proposer: accounts.proposer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Appends the `chunk` to the buffered transaction data.
+    pub fn append_tx_data(ctx: Context<BufferTransaction>, chunk: Vec<u8>) -> Result<()> {
+        let transaction = &mut ctx.accounts.transaction;
+        transaction.data.extend_from_slice(&chunk);
+        require_gte!(
+            transaction.to_account_info().data_len(),
+            transaction.required_space(),
+            Error::TransactionTooLarge
+        );
+
+        Ok(())
+    }
+
+    /// Finalizes the buffered transaction data and makes it approvable.
+    pub fn finalize_transaction(ctx: Context<BufferTransaction>) -> Result<()> {
+        let threshold = ctx.accounts.multisig.threshold;
+        ctx.accounts.transaction.finalized = true;
+        ctx.accounts.transaction.update_approved_at(threshold)?;
+
+        Ok(())
+    }
+
+    /// Attaches the `lamports` tip, escrowed in the transaction
+    /// account, which is paid to the executor of the transaction.
+    ///
+    /// The tip goes back to the proposer with the rent, in case the
+    /// transaction is cancelled or closed without the execution.
+    pub fn attach_tip(ctx: Context<AttachTip>, lamports: u64) -> Result<()> {
+        require!(!ctx.accounts.transaction.executed, Error::AlreadyExecuted);
+
+        let accounts = system_program::Transfer {
+            from: ctx.accounts.proposer.to_account_info(),
+            to: ctx.accounts.transaction.to_account_info(),
+        };
+        system_program::transfer(
+            CpiContext::new(ctx.accounts.system_program.to_account_info(), accounts),
+            lamports,
+        )?;
+
+        ctx.accounts.transaction.tip += lamports;
+
+        Ok(())
+    }
+
+    /// Replaces the instruction of the pending transaction by the
+    /// proposer, as long as no other owner has approved it, instead of
+    /// cancelling and proposing it again with the new rent.
+    ///
+    /// The new instruction should fit in the transaction account, and
+    /// the rejections so far, if any, are kept as is.
+    pub fn replace_transaction(
+        ctx: Context<ReplaceTransaction>,
+        tx_program_id: Pubkey,
+        tx_accounts: Vec<TransactionMeta>,
+        tx_data: Vec<u8>,
+    ) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let transaction = &mut ctx.accounts.transaction;
+        require!(
+            transaction.status == TransactionStatus::Active,
+            Error::NotActive
+        );
+        require!(transaction.finalized, Error::NotFinalized);
+        require!(!transaction.is_expired(Clock::get()?.slot), Error::Expired);
+        transaction.sync_owner_epochs(multisig)?;
+        let proposer = transaction.proposer;
+        require!(
+            multisig
+                .owners
+                .iter()
+                .zip(&transaction.signers)
+                .all(|(owner, approved)| !approved || *owner == proposer),
+            Error::AlreadyApproved
+        );
 
-        // Initialize the transaction account.
-        let tx = &mut ctx.accounts.transaction;
-        tx.program_id = tx_program_id;
-        tx.accounts = tx_accounts;
-        tx.data = tx_data;
-        tx.signers = signers;
-        tx.multisig = ctx.accounts.multisig.key();
-        tx.executed = false;
-        tx.owner_set_seqno = ctx.accounts.multisig.owner_set_seqno;
+        transaction.program_id = tx_program_id;
+        transaction.accounts = tx_accounts;
+        transaction.data = tx_data;
+        require_gte!(
+            transaction.to_account_info().data_len(),
+            transaction.required_space(),
+            Error::TransactionTooLarge
+        );
+        multisig.require_unfrozen(transaction)?;
+
+        // Restarts the execution delay for the new instruction.
+        transaction.approved_at_slot = None;
+        transaction.update_approved_at(multisig.threshold)
+    }
+
+    /// Creates a reusable transaction template.
+    ///
+    /// The `tx_data` is the instruction data skeleton with the
+    /// little endian `u64` amount placeholder at `amount_offset`,
+    /// which will be replaced by `initialize_from_template`.
+    pub fn create_template(
+        ctx: Context<CreateTemplate>,
+        tx_program_id: Pubkey,
+        tx_accounts: Vec<TransactionMeta>,
+        tx_data: Vec<u8>,
+        amount_offset: u32,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts
+                .multisig
+                .owners
+                .contains(ctx.accounts.proposer.key),
+            Error::InvalidOwner
+        );
+        require_gte!(
+            tx_data.len(),
+            amount_offset as usize + 8,
+            Error::InvalidAmountOffset
+        );
+
+        let template = &mut ctx.accounts.template;
+        template.multisig = ctx.accounts.multisig.key();
+        template.program_id = tx_program_id;
+        template.accounts = tx_accounts;
+        template.data = tx_data;
+        template.amount_offset = amount_offset;
+
+        Ok(())
+    }
+
+    /// Initializes a transaction from the template with the `amount`.
+    pub fn initialize_from_template(
+        ctx: Context<InitializeFromTemplate>,
+        amount: u64,
+        expires_at_slot: Option<u64>,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        let template = &accounts.template;
+        accounts.transaction.init(
+            &accounts.multisig,
+            accounts.proposer.key,
+            template.program_id,
+            template.accounts.clone(),
+            template.data_with_amount(amount),
+            expires_at_slot,
+        )?;
+        emit!(TransactionCreated {
+            multisig: accounts.multisig.key(),
+            transaction: accounts.transaction.key(),
+            proposer: accounts.proposer.key(),
+        });
 
         Ok(())
     }
@@ -84,16 +422,99 @@ pub mod anchor_multisig {
         if ctx.accounts.transaction.executed {
             return Err(Error::AlreadyExecuted.into());
         }
+        require!(ctx.accounts.transaction.finalized, Error::NotFinalized);
+        require!(!ctx.accounts.transaction.rejected, Error::Rejected);
+        require!(
+            !ctx.accounts.transaction.is_expired(Clock::get()?.slot),
+            Error::Expired
+        );
+        ctx.accounts
+            .multisig
+            .require_unfrozen(&ctx.accounts.transaction)?;
+        ctx.accounts
+            .transaction
+            .sync_owner_epochs(&ctx.accounts.multisig)?;
 
         let owner_index = ctx
             .accounts
             .multisig
-            .owners
-            .iter()
-            .position(|a| a == ctx.accounts.owner.key)
-            .ok_or(Error::InvalidOwner)?;
+            .approver_index(ctx.accounts.owner.key)?;
 
         ctx.accounts.transaction.signers[owner_index] = true;
+        ctx.accounts.transaction.rejections[owner_index] = false;
+        ctx.accounts
+            .transaction
+            .update_approved_at(ctx.accounts.multisig.threshold)?;
+
+        emit!(TransactionApproved {
+            multisig: ctx.accounts.multisig.key(),
+            transaction: ctx.accounts.transaction.key(),
+            owner: ctx.accounts.multisig.owners[owner_index],
+            approvals: ctx.accounts.transaction.count_approvals(),
+            threshold: ctx.accounts.multisig.threshold,
+        });
+
+        Ok(())
+    }
+
+    /// Revokes the owner's approval of the transaction, as long as
+    /// the transaction has not been executed.
+    pub fn revoke_approval(ctx: Context<ApproveTransaction>) -> Result<()> {
+        if ctx.accounts.transaction.executed {
+            return Err(Error::AlreadyExecuted.into());
+        }
+        require!(ctx.accounts.transaction.finalized, Error::NotFinalized);
+        ctx.accounts
+            .transaction
+            .sync_owner_epochs(&ctx.accounts.multisig)?;
+
+        let owner_index = ctx
+            .accounts
+            .multisig
+            .approver_index(ctx.accounts.owner.key)?;
+
+        ctx.accounts.transaction.signers[owner_index] = false;
+        ctx.accounts
+            .transaction
+            .update_approved_at(ctx.accounts.multisig.threshold)?;
+
+        Ok(())
+    }
+
+    /// Rejects the transaction by the owner, which revokes the owner's
+    /// approval as well.
+    ///
+    /// The transaction is rejected once the threshold can't be reached
+    /// anymore, e.g. `owners.len() - threshold + 1` rejections, so that
+    /// it can be closed early by `close_expired`.
+    pub fn reject_transaction(ctx: Context<ApproveTransaction>) -> Result<()> {
+        if ctx.accounts.transaction.executed {
+            return Err(Error::AlreadyExecuted.into());
+        }
+        require!(ctx.accounts.transaction.finalized, Error::NotFinalized);
+        require!(!ctx.accounts.transaction.rejected, Error::Rejected);
+
+        let multisig = &ctx.accounts.multisig;
+        let owner_index = multisig.approver_index(ctx.accounts.owner.key)?;
+
+        let transaction = &mut ctx.accounts.transaction;
+        transaction.sync_owner_epochs(multisig)?;
+        transaction.signers[owner_index] = false;
+        transaction.rejections[owner_index] = true;
+        transaction.update_approved_at(multisig.threshold)?;
+        let rejections = transaction.rejections();
+        transaction.rejected = rejections > multisig.owners.len() as u64 - multisig.threshold;
+        if transaction.rejected {
+            transaction.status = TransactionStatus::Rejected;
+        }
+
+        emit!(TransactionRejected {
+            multisig: multisig.key(),
+            transaction: transaction.key(),
+            owner: multisig.owners[owner_index],
+            rejections,
+            rejected: transaction.rejected,
+        });
 
         Ok(())
     }
@@ -102,150 +523,1474 @@ pub mod anchor_multisig {
         if ctx.accounts.transaction.executed {
             return Err(Error::AlreadyExecuted.into());
         }
+        require!(ctx.accounts.transaction.finalized, Error::NotFinalized);
+        require!(!ctx.accounts.transaction.rejected, Error::Rejected);
+        require!(
+            !ctx.accounts.transaction.is_expired(Clock::get()?.slot),
+            Error::Expired
+        );
+        ctx.accounts
+            .multisig
+            .require_unfrozen(&ctx.accounts.transaction)?;
+
+        if ctx.accounts.multisig.owners_only_execution {
+            require!(
+                ctx.accounts
+                    .multisig
+                    .owners
+                    .contains(ctx.accounts.executor.key),
+                Error::InvalidOwner
+            );
+        }
 
         // check if we have enough approvers.
-        let approved = ctx
-            .accounts
+        ctx.accounts
             .transaction
-            .signers
-            .iter()
-            .filter(|&approved| *approved)
-            .count() as u64;
-        if approved < ctx.accounts.multisig.threshold {
+            .sync_owner_epochs(&ctx.accounts.multisig)?;
+        if ctx.accounts.transaction.count_approvals() < ctx.accounts.multisig.threshold {
             return Err(Error::NotEnoughSigners.into());
         }
+        require!(
+            ctx.accounts.transaction.is_delay_elapsed(
+                ctx.accounts.multisig.execution_delay_slots,
+                Clock::get()?.slot
+            ),
+            Error::ExecutionDelayNotElapsed
+        );
+
+        // Validates the remaining accounts before the invocation.
+        let accounts = ctx.remaining_accounts;
+        ctx.accounts.transaction.validate_accounts(accounts)?;
 
         // Execute the transaction signed by the multisig.
         let ix: Instruction = (*ctx.accounts.transaction).deref().into();
         let multisig_key = ctx.accounts.multisig.key();
         let seeds = &[multisig_key.as_ref(), &[ctx.accounts.multisig.bump]];
         let signer = &[&seeds[..]];
-        let accounts = ctx.remaining_accounts;
 
         solana_program::program::invoke_signed(&ix, accounts, signer)?;
 
         ctx.accounts.transaction.executed = true;
+        ctx.accounts.transaction.status = TransactionStatus::Executed;
+
+        // Pays the escrowed tip to the executor.
+        let tip = mem::take(&mut ctx.accounts.transaction.tip);
+        **ctx
+            .accounts
+            .transaction
+            .to_account_info()
+            .try_borrow_mut_lamports()? -= tip;
+        **ctx.accounts.executor.try_borrow_mut_lamports()? += tip;
+
+        emit!(TransactionExecuted {
+            multisig: multisig_key,
+            transaction: ctx.accounts.transaction.key(),
+            executor: ctx.accounts.executor.key(),
+            tip,
+        });
 
         Ok(())
     }
 
-    pub fn set_owners(ctx: Context<Auth>, owners: Vec<Pubkey>) -> Result<()> {
-        let multisig = &mut ctx.accounts.multisig;
-
-        let owners_len = owners.len() as u64;
-        if owners_len < multisig.threshold {
-            multisig.threshold = owners_len;
+    /// Cancels the pending transaction and returns the rent back to
+    /// the proposer.
+    ///
+    /// It's allowed to the proposer, or to the multisig signer, e.g.
+    /// through the other transaction approved by the threshold.
+    pub fn cancel_transaction(ctx: Context<CancelTransaction>) -> Result<()> {
+        if ctx.accounts.transaction.executed {
+            return Err(Error::AlreadyExecuted.into());
         }
-        multisig.owners = owners;
-        multisig.owner_set_seqno += 1;
+
+        let canceller = ctx.accounts.canceller.key;
+        let multisig_signer = Multisig::signer(&ctx.accounts.multisig)?;
+        require!(
+            *canceller == ctx.accounts.transaction.proposer || *canceller == multisig_signer,
+            Error::NotCanceller
+        );
+
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct InitializeMultisig<'info> {
-    #[account(zero, signer)]
-    multisig: Box<Account<'info, Multisig>>,
-}
+    /// Closes the executed transaction and returns the rent to the
+    /// `recipient`.
+    ///
+    /// It's allowed to the proposer, or to the multisig signer, same
+    /// as `cancel_transaction`.
+    pub fn close_transaction(ctx: Context<CloseTransaction>) -> Result<()> {
+        require!(ctx.accounts.transaction.executed, Error::NotExecuted);
 
-#[derive(Accounts)]
-pub struct InitializeTransaction<'info> {
-    /// A multisig account this transaction is under.
-    multisig: Box<Account<'info, Multisig>>,
+        let closer = ctx.accounts.closer.key;
+        let multisig_signer = Multisig::signer(&ctx.accounts.multisig)?;
+        require!(
+            *closer == ctx.accounts.transaction.proposer || *closer == multisig_signer,
+            Error::NotCloser
+        );
 
-    /// A transaction account to be executed in the future.
-    #[account(zero, signer)]
-    transaction: Box<Account<'info, Transaction>>,
+        Ok(())
+    }
 
-    /// One of the owners of the multisig account.
-    proposer: Signer<'info>,
-}
+    /// Marks the expired transaction as [`TransactionStatus::Expired`],
+    /// by anyone, e.g. for the clients watching the status before the
+    /// transaction is closed by `close_expired`.
+    ///
+    /// The status is client-driven.  The approval and the execution
+    /// of the expired transaction fail with [`Error::Expired`], which
+    /// rolls back any status update in the same instruction.
+    pub fn expire_transaction(ctx: Context<ExpireTransaction>) -> Result<()> {
+        let transaction = &mut ctx.accounts.transaction;
+        require!(
+            transaction.status == TransactionStatus::Active,
+            Error::NotActive
+        );
+        require!(
+            transaction.is_expired(Clock::get()?.slot),
+            Error::NotExpired
+        );
+        transaction.status = TransactionStatus::Expired;
 
-#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
-pub struct TransactionMeta {
-    pub pubkey: Pubkey,
-    pub is_signer: bool,
-    pub is_writable: bool,
-}
+        Ok(())
+    }
 
-impl From<&TransactionMeta> for AccountMeta {
-    fn from(tx: &TransactionMeta) -> Self {
-        if tx.is_writable {
-            Self::new(tx.pubkey, tx.is_signer)
-        } else {
-            Self::new_readonly(tx.pubkey, tx.is_signer)
-        }
+    /// Closes the expired, or the rejected, transaction and returns
+    /// the rent back to the proposer.
+    ///
+    /// It's permissionless, as the expired or the rejected transaction
+    /// can't be approved nor executed anymore.
+    pub fn close_expired(ctx: Context<CloseExpired>) -> Result<()> {
+        let transaction = &ctx.accounts.transaction;
+        require!(
+            transaction.rejected || transaction.is_expired(Clock::get()?.slot),
+            Error::NotExpired
+        );
+
+        Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct ApproveTransaction<'info> {
-    /// A multisig account to manage the transaction to.
-    #[account(constraint = multisig.owner_set_seqno == transaction.owner_set_seqno)]
-    multisig: Box<Account<'info, Multisig>>,
+    /// Closes the transactions created for the previous owner sets
+    /// and returns the rent back to the proposers.
+    ///
+    /// The `(transaction, proposer)` pairs should be passed as the
+    /// remaining accounts.  It's permissionless, as the stale
+    /// transaction can't be executed anymore.
+    pub fn purge_stale_transactions<'info>(
+        ctx: Context<'_, '_, '_, 'info, PurgeStaleTransactions<'info>>,
+    ) -> Result<()> {
+        let pairs = ctx.remaining_accounts.chunks_exact(2);
+        require!(
+            pairs.remainder().is_empty(),
+            ErrorCode::AccountNotEnoughKeys
+        );
 
-    /// A transaction to approve.
-    #[account(mut, has_one = multisig)]
-    transaction: Box<Account<'info, Transaction>>,
+        let multisig = &ctx.accounts.multisig;
+        for pair in pairs {
+            let (transaction, proposer) = (&pair[0], &pair[1]);
+            let transaction: Account<Transaction> = Account::try_from(transaction)?;
+            require_keys_eq!(
+                transaction.multisig,
+                multisig.key(),
+                Error::InvalidTransaction
+            );
+            require_keys_eq!(
+                transaction.proposer,
+                proposer.key(),
+                ErrorCode::ConstraintHasOne
+            );
+            require_neq!(
+                transaction.owner_set_seqno,
+                multisig.owner_set_seqno,
+                Error::NotStale
+            );
+            transaction.close(proposer.clone())?;
+        }
 
-    /// One of the multisig owners.
-    owner: Signer<'info>,
-}
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct ExecuteTransaction<'info> {
-    #[account(constraint = multisig.owner_set_seqno == transaction.owner_set_seqno)]
-    multisig: Box<Account<'info, Multisig>>,
+    /// Creates the social recovery of the multisig, signed by
+    /// the multisig signer, e.g. executed through the transaction.
+    ///
+    /// The guardians reaching the `threshold` can replace the entire
+    /// owner set after the `delay` seconds.
+    pub fn create_recovery(
+        ctx: Context<CreateRecovery>,
+        guardians: Vec<Pubkey>,
+        threshold: u64,
+        delay: i64,
+    ) -> Result<()> {
+        require_gte!(
+            Recovery::MAX_GUARDIANS,
+            guardians.len(),
+            Error::TooManyGuardians
+        );
+        for (i, guardian) in guardians.iter().enumerate() {
+            require!(!guardians[..i].contains(guardian), Error::DuplicateGuardian);
+        }
+        require!(
+            threshold > 0 && threshold <= guardians.len() as u64,
+            Error::InvalidThreshold
+        );
+        require_gte!(delay, 0, Error::InvalidDelay);
 
-    #[account(mut, has_one = multisig)]
-    transaction: Box<Account<'info, Transaction>>,
+        let recovery = &mut ctx.accounts.recovery;
+        recovery.multisig = ctx.accounts.multisig.key();
+        recovery.approvals = vec![false; guardians.len()];
+        recovery.guardians = guardians;
+        recovery.threshold = threshold;
+        recovery.delay = delay;
+        recovery.proposed_owners = vec![];
+        recovery.proposed_at = 0;
 
-    /// CHECK: multisig_signer is a PDA program signer.  Data is never read or written to.
-    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
-    multisig_signer: UncheckedAccount<'info>,
-}
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct Auth<'info> {
-    #[account(mut)]
-    multisig: Box<Account<'info, Multisig>>,
-    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
-    multisig_signer: Signer<'info>,
-}
+    /// Proposes the new owner set by one of the guardians.
+    pub fn propose_recovery(ctx: Context<GuardRecovery>, owners: Vec<Pubkey>) -> Result<()> {
+        let recovery = &mut ctx.accounts.recovery;
+        require!(!recovery.is_pending(), Error::RecoveryPending);
+        require_gte!(Multisig::MAX_OWNERS, owners.len(), Error::TooManyOwners);
+        require!(!owners.is_empty(), Error::NoOwners);
 
-#[account]
-#[derive(Debug, Default)]
-pub struct Multisig {
-    pub owners: Vec<Pubkey>,
-    pub threshold: u64,
-    pub bump: u8,
-    pub owner_set_seqno: u32,
-}
+        let index = recovery.guardian_index(ctx.accounts.guardian.key)?;
+        recovery
+            .approvals
+            .iter_mut()
+            .for_each(|approved| *approved = false);
+        recovery.approvals[index] = true;
+        recovery.proposed_owners = owners;
+        recovery.proposed_at = Clock::get()?.unix_timestamp;
 
-/// Transaction account, maintained by the `Multisig` account.
-#[account]
-pub struct Transaction {
-    /// A multisig account this transaction belongs to.
-    pub multisig: Pubkey,
+        Ok(())
+    }
 
-    /// A target program ID to execute against.
-    pub program_id: Pubkey,
+    /// Approves the pending recovery proposal by one of the guardians.
+    pub fn approve_recovery(ctx: Context<GuardRecovery>) -> Result<()> {
+        let recovery = &mut ctx.accounts.recovery;
+        require!(recovery.is_pending(), Error::NoRecoveryPending);
 
-    /// Accounts required for the transaction.
-    pub accounts: Vec<TransactionMeta>,
+        let index = recovery.guardian_index(ctx.accounts.guardian.key)?;
+        recovery.approvals[index] = true;
 
-    /// Instruction data for the transaction.
-    pub data: Vec<u8>,
+        Ok(())
+    }
 
-    /// Signers[index] is true if multisig.owners[index] signed
-    /// the transaction.
-    pub signers: Vec<bool>,
+    /// Replaces the owner set with the recovery proposal approved by
+    /// the guardian quorum after the delay.
+    pub fn execute_recovery(ctx: Context<ExecuteRecovery>) -> Result<()> {
+        let recovery = &mut ctx.accounts.recovery;
+        require!(recovery.is_pending(), Error::NoRecoveryPending);
+        let approved = recovery
+            .approvals
+            .iter()
+            .filter(|&approved| *approved)
+            .count() as u64;
+        require_gte!(approved, recovery.threshold, Error::NotEnoughSigners);
+        let now = Clock::get()?.unix_timestamp;
+        require_gte!(
+            now,
+            recovery.proposed_at.saturating_add(recovery.delay),
+            Error::RecoveryDelayNotElapsed
+        );
 
-    /// Boolean ensuring one time execution.
-    pub executed: bool,
+        let owners = mem::take(&mut recovery.proposed_owners);
+        recovery.clear();
 
-    /// Owner set sequence number.
+        let multisig = &mut ctx.accounts.multisig;
+        Multisig::validate_owners(multisig, &owners)?;
+        let owners_len = owners.len() as u64;
+        if owners_len < multisig.threshold {
+            multisig.threshold = owners_len;
+        }
+        multisig.owners = owners;
+        multisig.prune_owner_entries();
+        multisig.owner_set_seqno += 1;
+        multisig.owner_epochs = vec![0; multisig.owners.len()];
+
+        Ok(())
+    }
+
+    /// Cancels the pending recovery proposal, signed by the multisig
+    /// signer, e.g. by the current owner quorum during the delay.
+    pub fn cancel_recovery(ctx: Context<CancelRecovery>) -> Result<()> {
+        let recovery = &mut ctx.accounts.recovery;
+        require!(recovery.is_pending(), Error::NoRecoveryPending);
+        recovery.clear();
+
+        Ok(())
+    }
+
+    pub fn set_owners(ctx: Context<Auth>, owners: Vec<Pubkey>) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+
+        Multisig::validate_owners(multisig, &owners)?;
+
+        let owners_len = owners.len() as u64;
+        if owners_len < multisig.threshold {
+            multisig.threshold = owners_len;
+        }
+        multisig.owners = owners;
+        multisig.prune_owner_entries();
+        multisig.owner_set_seqno += 1;
+        multisig.owner_epochs = vec![0; multisig.owners.len()];
+        Ok(())
+    }
+
+    /// Changes the threshold of the multisig, signed by the multisig
+    /// signer, e.g. executed through the transaction.
+    pub fn change_threshold(ctx: Context<Auth>, threshold: u64) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(
+            threshold > 0 && threshold <= multisig.owners.len() as u64,
+            Error::InvalidThreshold
+        );
+
+        multisig.threshold = threshold;
+        Ok(())
+    }
+
+    /// Changes the execution delay of the multisig, signed by the
+    /// multisig signer.  It gives the owners the review window between
+    /// the threshold approvals and the execution of the transaction.
+    pub fn change_execution_delay(ctx: Context<Auth>, delay_slots: u64) -> Result<()> {
+        ctx.accounts.multisig.execution_delay_slots = delay_slots;
+        Ok(())
+    }
+
+    /// Changes the spending limit of the multisig, signed by the
+    /// multisig signer.  Zero disables `spend_within_limit`.
+    pub fn change_spending_limit(ctx: Context<Auth>, lamports: u64) -> Result<()> {
+        ctx.accounts.multisig.spending_limit = lamports;
+        Ok(())
+    }
+
+    /// Restricts the execution of the transactions to the owners,
+    /// signed by the multisig signer.  Anyone can execute the
+    /// transaction reached the threshold otherwise.
+    pub fn set_owners_only_execution(ctx: Context<Auth>, enabled: bool) -> Result<()> {
+        ctx.accounts.multisig.owners_only_execution = enabled;
+        Ok(())
+    }
+
+    /// Freezes the multisig, signed by the multisig signer, as the
+    /// kill switch on the incident, e.g. the compromised owner keys.
+    ///
+    /// The frozen multisig can't create, approve, or execute the
+    /// transactions other than the one to `unfreeze` it, nor spend
+    /// within the limit.
+    pub fn freeze(ctx: Context<Auth>) -> Result<()> {
+        ctx.accounts.multisig.frozen = true;
+        Ok(())
+    }
+
+    /// Unfreezes the multisig, signed by the multisig signer.
+    pub fn unfreeze(ctx: Context<Auth>) -> Result<()> {
+        ctx.accounts.multisig.frozen = false;
+        Ok(())
+    }
+
+    /// Sets the `delegate` of the `owner`, signed by the multisig
+    /// signer, e.g. the hot key approving on behalf of the owner's
+    /// cold key.  `None` removes the delegate.
+    ///
+    /// The delegate's approval, revocation, and rejection count as the
+    /// owner's.
+    pub fn set_delegate(ctx: Context<Auth>, owner: Pubkey, delegate: Option<Pubkey>) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(multisig.owners.contains(&owner), Error::InvalidOwner);
+
+        multisig.delegates.retain(|d| d.owner != owner);
+        if let Some(delegate) = delegate {
+            require!(
+                !multisig.owners.contains(&delegate)
+                    && !multisig.delegates.iter().any(|d| d.delegate == delegate),
+                Error::InvalidDelegate
+            );
+            multisig.delegates.push(Delegate { owner, delegate });
+        }
+        Ok(())
+    }
+
+    /// Transfers `lamports` from the multisig signer PDA to `to`,
+    /// signed by any single owner, as long as the owner's total within
+    /// the spending window is within the spending limit of the
+    /// multisig, e.g. without the threshold approvals.
+    pub fn spend_within_limit(ctx: Context<SpendWithinLimit>, lamports: u64) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(!multisig.frozen, Error::Frozen);
+        let owner = ctx.accounts.owner.key();
+        require!(multisig.owners.contains(&owner), Error::InvalidOwner);
+        multisig.record_spend(owner, lamports, Clock::get()?.slot)?;
+
+        let multisig_key = multisig.key();
+        let seeds = &[multisig_key.as_ref(), &[multisig.bump]];
+        let signer = &[&seeds[..]];
+        let accounts = system_program::Transfer {
+            from: ctx.accounts.multisig_signer.to_account_info(),
+            to: ctx.accounts.to.to_account_info(),
+        };
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                accounts,
+                signer,
+            ),
+            lamports,
+        )?;
+
+        emit!(SpentWithinLimit {
+            multisig: multisig_key,
+            owner: ctx.accounts.owner.key(),
+            to: ctx.accounts.to.key(),
+            lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Deposits `lamports` to the multisig vault PDA, by anyone.
+    pub fn deposit(ctx: Context<Deposit>, lamports: u64) -> Result<()> {
+        let accounts = system_program::Transfer {
+            from: ctx.accounts.depositor.to_account_info(),
+            to: ctx.accounts.vault.to_account_info(),
+        };
+        system_program::transfer(
+            CpiContext::new(ctx.accounts.system_program.to_account_info(), accounts),
+            lamports,
+        )?;
+
+        emit!(Deposited {
+            multisig: ctx.accounts.multisig.key(),
+            depositor: ctx.accounts.depositor.key(),
+            lamports,
+        });
+
+        Ok(())
+    }
+
+    /// Transfers `lamports` from the multisig vault PDA to `to`, signed
+    /// by the multisig signer, e.g. executed through the transaction.
+    pub fn transfer_from_vault(ctx: Context<TransferFromVault>, lamports: u64) -> Result<()> {
+        let multisig_key = ctx.accounts.multisig.key();
+        let bump = *ctx.bumps.get("vault").unwrap();
+        let seeds = &[Multisig::VAULT_SEED, multisig_key.as_ref(), &[bump]];
+        let signer = &[&seeds[..]];
+        let accounts = system_program::Transfer {
+            from: ctx.accounts.vault.to_account_info(),
+            to: ctx.accounts.to.to_account_info(),
+        };
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                accounts,
+                signer,
+            ),
+            lamports,
+        )
+    }
+
+    /// Creates the token vault PDA of the `mint`, owned by the multisig
+    /// signer, paid by anyone.
+    pub fn create_token_vault(ctx: Context<CreateTokenVault>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let multisig_key = accounts.multisig.key();
+        let mint_key = accounts.mint.key();
+        let bump = *ctx.bumps.get("token_vault").unwrap();
+        let seeds = &[
+            Multisig::TOKEN_VAULT_SEED,
+            multisig_key.as_ref(),
+            mint_key.as_ref(),
+            &[bump],
+        ];
+        // Tops up the rent instead of `create_account`, which fails on
+        // the token vault funded by anyone in advance.
+        let space = spl_token::state::Account::LEN;
+        let lamports = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(accounts.token_vault.lamports());
+        if lamports > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: accounts.payer.to_account_info(),
+                        to: accounts.token_vault.to_account_info(),
+                    },
+                ),
+                lamports,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                accounts.system_program.to_account_info(),
+                system_program::Allocate {
+                    account_to_allocate: accounts.token_vault.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                accounts.system_program.to_account_info(),
+                system_program::Assign {
+                    account_to_assign: accounts.token_vault.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            &spl_token::id(),
+        )?;
+
+        let ix = spl_token::instruction::initialize_account3(
+            &spl_token::id(),
+            accounts.token_vault.key,
+            accounts.mint.key,
+            accounts.multisig_signer.key,
+        )?;
+        solana_program::program::invoke(
+            &ix,
+            &[
+                accounts.token_vault.to_account_info(),
+                accounts.mint.to_account_info(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Transfers `amount` tokens from the token vault PDA of the mint
+    /// to the `destination` token account, signed by the multisig
+    /// signer, e.g. executed through the transaction.
+    pub fn transfer_tokens(ctx: Context<TransferTokens>, amount: u64) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let ix = spl_token::instruction::transfer(
+            &spl_token::id(),
+            accounts.token_vault.key,
+            accounts.destination.key,
+            accounts.multisig_signer.key,
+            &[],
+            amount,
+        )?;
+        solana_program::program::invoke(
+            &ix,
+            &[
+                accounts.token_vault.to_account_info(),
+                accounts.destination.to_account_info(),
+                accounts.multisig_signer.to_account_info(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Adds the `owner` to the multisig, signed by the multisig signer.
+    pub fn add_owner(ctx: Context<Auth>, owner: Pubkey) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(!multisig.owners.contains(&owner), Error::DuplicateOwner);
+
+        let mut owners = multisig.owners.clone();
+        owners.push(owner);
+        Multisig::validate_owners(multisig, &owners)?;
+
+        multisig.owners = owners;
+        multisig.prune_owner_entries();
+        multisig.owner_set_seqno += 1;
+        multisig.owner_epochs = vec![0; multisig.owners.len()];
+        Ok(())
+    }
+
+    /// Removes the `owner` from the multisig, signed by the multisig
+    /// signer.
+    ///
+    /// The threshold is clamped to the remaining owners and the last
+    /// owner can't be removed.
+    pub fn remove_owner(ctx: Context<Auth>, owner: Pubkey) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        let index = multisig
+            .owners
+            .iter()
+            .position(|a| a == &owner)
+            .ok_or(Error::InvalidOwner)?;
+        require_gt!(multisig.owners.len(), 1, Error::InvalidThreshold);
+
+        multisig.owners.remove(index);
+        multisig.prune_owner_entries();
+        let owners_len = multisig.owners.len() as u64;
+        if owners_len < multisig.threshold {
+            multisig.threshold = owners_len;
+        }
+        multisig.owner_set_seqno += 1;
+        multisig.owner_epochs = vec![0; multisig.owners.len()];
+        Ok(())
+    }
+
+    /// Replaces the `old` owner with the `new` one in place, signed by
+    /// the multisig signer, e.g. for the key rotation.
+    ///
+    /// Unlike `set_owners`, it keeps the owner set sequence number, so
+    /// that the other owners' approvals of the pending transactions
+    /// stand.  The `old` owner's approvals and rejections are dropped
+    /// by the owner slot epoch, not to be counted as the `new` owner's
+    /// ones.
+    pub fn replace_owner(ctx: Context<Auth>, old: Pubkey, new: Pubkey) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(!multisig.owners.contains(&new), Error::DuplicateOwner);
+        let index = multisig
+            .owners
+            .iter()
+            .position(|a| a == &old)
+            .ok_or(Error::InvalidOwner)?;
+
+        multisig.owners[index] = new;
+        multisig.owner_epochs[index] = multisig.owner_epochs[index].wrapping_add(1);
+        multisig.prune_owner_entries();
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeMultisig<'info> {
+    #[account(zero, signer)]
+    multisig: Box<Account<'info, Multisig>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTransaction<'info> {
+    /// A multisig account this transaction is under.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A transaction account to be executed in the future.
+    #[account(zero, signer)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// One of the owners of the multisig account.
+    proposer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(tx_program_id: Pubkey, tx_accounts: Vec<TransactionMeta>, tx_data: Vec<u8>)]
+pub struct CreateTransaction<'info> {
+    /// A multisig account this transaction is under.
+    #[account(mut)]
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A transaction PDA account to be executed in the future.
+    #[account(
+        init,
+        payer = proposer,
+        space = Transaction::space(tx_accounts.len(), tx_data.len(), multisig.owners.len()),
+        seeds = [
+            b"tx",
+            multisig.key().as_ref(),
+            &multisig.transaction_count.to_le_bytes(),
+        ],
+        bump,
+    )]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// One of the owners of the multisig account, who pays the rent.
+    #[account(mut)]
+    proposer: Signer<'info>,
+
+    /// The system program to create the transaction account.
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BufferTransaction<'info> {
+    /// A multisig account this transaction is under.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A buffered transaction account being written.
+    #[account(
+        mut,
+        has_one = multisig,
+        constraint = !transaction.finalized @ Error::AlreadyFinalized,
+        constraint = transaction.is_proposer(&multisig, proposer.key) @ Error::InvalidOwner,
+    )]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// The proposer of the transaction.
+    proposer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTemplate<'info> {
+    /// A multisig account this template is under.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A template account to be instantiated in the future.
+    #[account(zero, signer)]
+    template: Box<Account<'info, Template>>,
+
+    /// One of the owners of the multisig account.
+    proposer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFromTemplate<'info> {
+    /// A multisig account this transaction is under.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A template of the transaction.
+    #[account(has_one = multisig)]
+    template: Box<Account<'info, Template>>,
+
+    /// A transaction account to be executed in the future.
+    #[account(zero, signer)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// One of the owners of the multisig account.
+    proposer: Signer<'info>,
+}
+
+/// A delegate approving on behalf of the owner.
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct Delegate {
+    pub owner: Pubkey,
+    pub delegate: Pubkey,
+}
+
+/// Lamports spent by the owner through `spend_within_limit` in the
+/// spending window started at `window_start_slot`.
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct Spend {
+    pub owner: Pubkey,
+    pub window_start_slot: u64,
+    pub spent: u64,
+}
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct TransactionMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl From<&TransactionMeta> for AccountMeta {
+    fn from(tx: &TransactionMeta) -> Self {
+        if tx.is_writable {
+            Self::new(tx.pubkey, tx.is_signer)
+        } else {
+            Self::new_readonly(tx.pubkey, tx.is_signer)
+        }
+    }
+}
+
+#[derive(Accounts)]
+pub struct ApproveTransaction<'info> {
+    /// A multisig account to manage the transaction to.
+    #[account(constraint = multisig.owner_set_seqno == transaction.owner_set_seqno)]
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A transaction to approve.
+    #[account(mut, has_one = multisig)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// One of the multisig owners.
+    owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTransaction<'info> {
+    #[account(
+        constraint = multisig.owner_set_seqno == transaction.owner_set_seqno
+            @ Error::StaleOwnerSet,
+    )]
+    multisig: Box<Account<'info, Multisig>>,
+
+    #[account(mut, has_one = multisig)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// CHECK: multisig_signer is a PDA program signer.  Data is never read or written to.
+    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
+    multisig_signer: UncheckedAccount<'info>,
+
+    /// The executor of the transaction, who receives the tip.  It
+    /// should be one of the owners if `owners_only_execution` is set.
+    #[account(mut)]
+    executor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AttachTip<'info> {
+    /// A transaction to attach the tip to.
+    #[account(mut, has_one = proposer @ Error::NotProposer)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// The proposer of the transaction, who pays the tip.
+    #[account(mut)]
+    proposer: Signer<'info>,
+
+    /// The system program to transfer the tip.
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReplaceTransaction<'info> {
+    /// A multisig account this transaction is under.
+    #[account(
+        constraint = multisig.owner_set_seqno == transaction.owner_set_seqno
+            @ Error::StaleOwnerSet,
+    )]
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A pending transaction to replace.
+    #[account(mut, has_one = multisig, has_one = proposer @ Error::NotProposer)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// The proposer of the transaction.
+    proposer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTransaction<'info> {
+    /// A multisig account the transaction is under.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A pending transaction to be cancelled.
+    #[account(mut, has_one = multisig, has_one = proposer, close = proposer)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// CHECK: The proposer of the transaction to receive the rent,
+    /// checked by `has_one`.
+    #[account(mut)]
+    proposer: UncheckedAccount<'info>,
+
+    /// The proposer or the multisig PDA signer.
+    canceller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTransaction<'info> {
+    /// A multisig account the transaction is under.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// An executed transaction to be closed.
+    #[account(mut, has_one = multisig, close = recipient)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// CHECK: A recipient of the rent, chosen by the closer.
+    #[account(mut)]
+    recipient: UncheckedAccount<'info>,
+
+    /// The proposer or the multisig PDA signer.
+    closer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireTransaction<'info> {
+    /// An expired transaction to be marked.
+    #[account(mut)]
+    transaction: Box<Account<'info, Transaction>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseExpired<'info> {
+    /// An expired transaction to be closed.
+    #[account(mut, has_one = proposer, close = proposer)]
+    transaction: Box<Account<'info, Transaction>>,
+
+    /// CHECK: The proposer of the transaction to receive the rent,
+    /// checked by `has_one`.
+    #[account(mut)]
+    proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PurgeStaleTransactions<'info> {
+    /// A multisig account the stale transactions are under.
+    multisig: Box<Account<'info, Multisig>>,
+}
+
+#[derive(Accounts)]
+pub struct Auth<'info> {
+    #[account(mut)]
+    multisig: Box<Account<'info, Multisig>>,
+    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
+    multisig_signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SpendWithinLimit<'info> {
+    /// A multisig account to spend from.
+    #[account(mut)]
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// CHECK: multisig_signer is a PDA program signer, which holds the lamports.
+    #[account(mut, seeds = [multisig.key().as_ref()], bump = multisig.bump)]
+    multisig_signer: UncheckedAccount<'info>,
+
+    /// CHECK: The recipient of the lamports.
+    #[account(mut)]
+    to: UncheckedAccount<'info>,
+
+    /// One of the owners of the multisig account.
+    owner: Signer<'info>,
+
+    /// The system program to transfer the lamports.
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    /// A multisig account of the vault.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// The multisig vault PDA to deposit to.
+    #[account(mut, seeds = [Multisig::VAULT_SEED, multisig.key().as_ref()], bump)]
+    vault: SystemAccount<'info>,
+
+    /// The depositor, who pays the lamports.
+    #[account(mut)]
+    depositor: Signer<'info>,
+
+    /// The system program to transfer the lamports.
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TransferFromVault<'info> {
+    /// A multisig account of the vault.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// The multisig PDA signer.
+    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
+    multisig_signer: Signer<'info>,
+
+    /// The multisig vault PDA to transfer from.
+    #[account(mut, seeds = [Multisig::VAULT_SEED, multisig.key().as_ref()], bump)]
+    vault: SystemAccount<'info>,
+
+    /// CHECK: The recipient of the lamports.
+    #[account(mut)]
+    to: UncheckedAccount<'info>,
+
+    /// The system program to transfer the lamports.
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTokenVault<'info> {
+    /// A multisig account of the token vault.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// CHECK: multisig_signer is a PDA program signer, the owner of the
+    /// token vault.  Data is never read or written to.
+    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
+    multisig_signer: UncheckedAccount<'info>,
+
+    /// CHECK: A mint of the token vault, validated by the token program.
+    mint: UncheckedAccount<'info>,
+
+    /// CHECK: The token vault PDA to be created.
+    #[account(
+        mut,
+        seeds = [Multisig::TOKEN_VAULT_SEED, multisig.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    token_vault: UncheckedAccount<'info>,
+
+    /// The payer of the token vault rent.
+    #[account(mut)]
+    payer: Signer<'info>,
+
+    /// CHECK: The token program to initialize the token vault.
+    #[account(address = spl_token::id())]
+    token_program: UncheckedAccount<'info>,
+
+    /// The system program to create the token vault.
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TransferTokens<'info> {
+    /// A multisig account of the token vault.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// The multisig PDA signer.
+    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
+    multisig_signer: Signer<'info>,
+
+    /// CHECK: A mint of the token vault.
+    mint: UncheckedAccount<'info>,
+
+    /// CHECK: The token vault PDA to transfer from, validated by the
+    /// token program.
+    #[account(
+        mut,
+        seeds = [Multisig::TOKEN_VAULT_SEED, multisig.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    token_vault: UncheckedAccount<'info>,
+
+    /// CHECK: The destination token account of the mint, validated by
+    /// the token program.
+    #[account(mut)]
+    destination: UncheckedAccount<'info>,
+
+    /// CHECK: The token program to transfer the tokens.
+    #[account(address = spl_token::id())]
+    token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRecovery<'info> {
+    /// A multisig account to recover.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A recovery account of the multisig.
+    #[account(zero, signer)]
+    recovery: Box<Account<'info, Recovery>>,
+
+    /// The multisig PDA signer.
+    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
+    multisig_signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GuardRecovery<'info> {
+    /// A recovery account.
+    #[account(mut)]
+    recovery: Box<Account<'info, Recovery>>,
+
+    /// One of the guardians of the recovery account.
+    guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRecovery<'info> {
+    /// A multisig account to replace the owners.
+    #[account(mut)]
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A recovery account of the multisig.
+    #[account(mut, has_one = multisig)]
+    recovery: Box<Account<'info, Recovery>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRecovery<'info> {
+    /// A multisig account of the recovery.
+    multisig: Box<Account<'info, Multisig>>,
+
+    /// A recovery account of the multisig.
+    #[account(mut, has_one = multisig)]
+    recovery: Box<Account<'info, Recovery>>,
+
+    /// The multisig PDA signer.
+    #[account(seeds = [multisig.key().as_ref()], bump = multisig.bump)]
+    multisig_signer: Signer<'info>,
+}
+
+#[account]
+#[derive(Debug, Default)]
+pub struct Multisig {
+    pub owners: Vec<Pubkey>,
+    pub threshold: u64,
+    pub bump: u8,
+    pub owner_set_seqno: u32,
+
+    /// Number of the transactions created by `create_transaction`,
+    /// used as the nonce of the transaction PDA.
+    pub transaction_count: u64,
+
+    /// Number of the slots to wait for after the transaction reaches
+    /// the threshold, before the execution.
+    pub execution_delay_slots: u64,
+
+    /// Maximum lamports each owner can transfer from the multisig
+    /// signer PDA by `spend_within_limit` in total within
+    /// [`Self::SPENDING_WINDOW_SLOTS`].
+    pub spending_limit: u64,
+
+    /// True if only the owners can execute the transactions.
+    pub owners_only_execution: bool,
+
+    /// Delegates of the owners, up to one for each owner.
+    pub delegates: Vec<Delegate>,
+
+    /// True while the multisig is frozen by `freeze`.
+    pub frozen: bool,
+
+    /// Lamports spent by the owners in the current spending window.
+    pub spends: Vec<Spend>,
+
+    /// Owner_epochs[index] is bumped when multisig.owners[index] is
+    /// replaced by `replace_owner`.
+    pub owner_epochs: Vec<u32>,
+}
+
+impl Multisig {
+    /// A maximum number of owners of the multisig account.
+    pub const MAX_OWNERS: usize = 32;
+
+    /// Number of the slots of the spending window, e.g. about a day.
+    pub const SPENDING_WINDOW_SLOTS: u64 = 216_000;
+
+    /// A seed of the vault PDA, followed by the multisig address.
+    pub const VAULT_SEED: &'static [u8] = b"vault";
+
+    /// A seed of the token vault PDA, followed by the multisig and the
+    /// mint addresses.
+    pub const TOKEN_VAULT_SEED: &'static [u8] = b"token_vault";
+
+    /// Returns the account space required for `n_owners` owners.
+    pub const fn required_space(n_owners: usize) -> usize {
+        let owners = 4 + 32 * n_owners;
+        let delegates = 4 + 64 * n_owners;
+        let spends = 4 + 48 * n_owners;
+        let owner_epochs = 4 + 4 * n_owners;
+        8 + owners + 8 + 1 + 4 + 8 + 8 + 8 + 1 + delegates + 1 + spends + owner_epochs
+    }
+
+    /// Returns the multisig PDA signer address.
+    fn signer(multisig: &Account<Self>) -> Result<Pubkey> {
+        let key = multisig.key();
+        Pubkey::create_program_address(&[key.as_ref(), &[multisig.bump]], &id())
+            .map_err(|_| error!(ErrorCode::ConstraintSeeds))
+    }
+
+    /// Returns the index of the owner the `key` approves for, e.g. the
+    /// owner itself or the owner's delegate.
+    fn approver_index(&self, key: &Pubkey) -> Result<usize> {
+        let owner = self
+            .delegates
+            .iter()
+            .find(|d| d.delegate == *key)
+            .map_or(key, |d| &d.owner);
+        self.owners
+            .iter()
+            .position(|a| a == owner)
+            .ok_or_else(|| Error::InvalidOwner.into())
+    }
+
+    /// Fails while the multisig is frozen, unless the `transaction`
+    /// unfreezes it.
+    fn require_unfrozen(&self, transaction: &Transaction) -> Result<()> {
+        require!(!self.frozen || transaction.is_unfreeze(), Error::Frozen);
+        Ok(())
+    }
+
+    /// Drops the delegates of the removed owners, as well as the ones
+    /// became the owners, and the spends of the removed owners.
+    fn prune_owner_entries(&mut self) {
+        let owners = &self.owners;
+        self.delegates
+            .retain(|d| owners.contains(&d.owner) && !owners.contains(&d.delegate));
+        self.spends.retain(|s| owners.contains(&s.owner));
+    }
+
+    /// Adds the `lamports` to the `owner`'s spend in the current window
+    /// at the `slot`, and fails if the total exceeds the spending limit.
+    ///
+    /// The window restarts at the first spend after the previous one
+    /// had elapsed.
+    fn record_spend(&mut self, owner: Pubkey, lamports: u64, slot: u64) -> Result<()> {
+        let limit = self.spending_limit;
+        let index = match self.spends.iter().position(|s| s.owner == owner) {
+            Some(index) => index,
+            None => {
+                self.spends.push(Spend {
+                    owner,
+                    window_start_slot: slot,
+                    spent: 0,
+                });
+                self.spends.len() - 1
+            }
+        };
+        let spend = &mut self.spends[index];
+        if slot
+            >= spend
+                .window_start_slot
+                .saturating_add(Self::SPENDING_WINDOW_SLOTS)
+        {
+            spend.window_start_slot = slot;
+            spend.spent = 0;
+        }
+        let spent = spend
+            .spent
+            .checked_add(lamports)
+            .ok_or(Error::SpendingLimitExceeded)?;
+        require_gte!(limit, spent, Error::SpendingLimitExceeded);
+        spend.spent = spent;
+        Ok(())
+    }
+
+    /// Validates the number of owners against [`Self::MAX_OWNERS`]
+    /// as well as the multisig account space, and rejects the empty
+    /// or the duplicate owners.
+    fn validate_owners(multisig: &Account<Self>, owners: &[Pubkey]) -> Result<()> {
+        require!(!owners.is_empty(), Error::NoOwners);
+        require_gte!(Self::MAX_OWNERS, owners.len(), Error::TooManyOwners);
+        for (i, owner) in owners.iter().enumerate() {
+            require!(!owners[..i].contains(owner), Error::DuplicateOwner);
+        }
+        require_gte!(
+            multisig.to_account_info().data_len(),
+            Self::required_space(owners.len()),
+            Error::AccountTooSmall
+        );
+        Ok(())
+    }
+}
+
+/// Transaction account, maintained by the `Multisig` account.
+#[account]
+pub struct Transaction {
+    /// A multisig account this transaction belongs to.
+    pub multisig: Pubkey,
+
+    /// A target program ID to execute against.
+    pub program_id: Pubkey,
+
+    /// Accounts required for the transaction.
+    pub accounts: Vec<TransactionMeta>,
+
+    /// Instruction data for the transaction.
+    pub data: Vec<u8>,
+
+    /// Signers[index] is true if multisig.owners[index] signed
+    /// the transaction.
+    pub signers: Vec<bool>,
+
+    /// Boolean ensuring one time execution.
+    pub executed: bool,
+
+    /// Owner set sequence number.
     pub owner_set_seqno: u32,
+
+    /// False while the instruction data is being buffered.
+    pub finalized: bool,
+
+    /// The proposer of the transaction, who gets the rent back on
+    /// the cancellation.
+    pub proposer: Pubkey,
+
+    /// The slot the transaction expires at, if any.
+    pub expires_at_slot: Option<u64>,
+
+    /// The slot the transaction reached the threshold at, if any.
+    pub approved_at_slot: Option<u64>,
+
+    /// A short description of the transaction given by the proposer,
+    /// e.g. "Pay the Q3 audit invoice".
+    pub memo: String,
+
+    /// Lamports escrowed in the account for the executor, on top of
+    /// the rent.
+    pub tip: u64,
+
+    /// Rejections[index] is true if multisig.owners[index] rejected
+    /// the transaction.
+    pub rejections: Vec<bool>,
+
+    /// True once the rejections make the threshold unreachable.
+    pub rejected: bool,
+
+    /// Number of the owners approved the transaction.
+    pub approvals: u8,
+
+    /// A status of the transaction.
+    pub status: TransactionStatus,
+
+    /// Owner_epochs[index] is the epoch of multisig.owners[index] the
+    /// approval or the rejection at the index was given at.
+    pub owner_epochs: Vec<u32>,
+}
+
+/// A status of the [`Transaction`], maintained by the handlers.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum TransactionStatus {
+    /// Pending for the approvals or the execution.
+    #[default]
+    Active,
+
+    /// Executed by `execute_transaction`.
+    Executed,
+
+    /// Rejected by `reject_transaction`.
+    Rejected,
+
+    /// Expired, marked only by `expire_transaction`.
+    ///
+    /// The transaction past `expires_at_slot` stays `Active` until
+    /// someone calls `expire_transaction`, so the clients should check
+    /// the slot as well.
+    Expired,
+}
+
+impl Transaction {
+    /// A maximum length of the memo in bytes.
+    pub const MAX_MEMO_LEN: usize = 128;
+
+    /// Initializes the transaction under the `multisig` account,
+    /// approved by the `proposer`.
+    ///
+    /// The `expires_at_slot`, if any, should be in the future.
+    fn init(
+        &mut self,
+        multisig: &Account<Multisig>,
+        proposer: &Pubkey,
+        program_id: Pubkey,
+        accounts: Vec<TransactionMeta>,
+        data: Vec<u8>,
+        expires_at_slot: Option<u64>,
+    ) -> Result<()> {
+        if let Some(slot) = expires_at_slot {
+            require_gt!(slot, Clock::get()?.slot, Error::Expired);
+        }
+
+        // Signers vector, set `true` for the proposer.
+        let signers: Vec<_> = multisig.owners.iter().map(|key| key == proposer).collect();
+
+        self.program_id = program_id;
+        self.accounts = accounts;
+        self.data = data;
+        self.rejections = vec![false; signers.len()];
+        self.rejected = false;
+        self.signers = signers;
+        self.multisig = multisig.key();
+        self.executed = false;
+        self.owner_set_seqno = multisig.owner_set_seqno;
+        self.finalized = true;
+        self.proposer = *proposer;
+        self.expires_at_slot = expires_at_slot;
+        self.approved_at_slot = None;
+        self.memo = String::new();
+        self.tip = 0;
+        self.status = TransactionStatus::Active;
+        self.owner_epochs = multisig.owner_epochs.clone();
+        multisig.require_unfrozen(self)?;
+        self.update_approved_at(multisig.threshold)
+    }
+
+    /// Drops the approvals and the rejections given by the owners
+    /// replaced since then, and updates the approval state against the
+    /// `multisig` threshold.
+    fn sync_owner_epochs(&mut self, multisig: &Multisig) -> Result<()> {
+        let slots = self
+            .owner_epochs
+            .iter_mut()
+            .zip(self.signers.iter_mut().zip(&mut self.rejections));
+        for (current, (epoch, (approved, rejected))) in multisig.owner_epochs.iter().zip(slots) {
+            if epoch != current {
+                *epoch = *current;
+                *approved = false;
+                *rejected = false;
+            }
+        }
+        self.update_approved_at(multisig.threshold)
+    }
+
+    /// Returns the number of the owners approved the transaction.
+    fn count_approvals(&self) -> u64 {
+        self.signers.iter().filter(|&approved| *approved).count() as u64
+    }
+
+    /// Returns the number of the owners rejected the transaction.
+    fn rejections(&self) -> u64 {
+        self.rejections.iter().filter(|&rejected| *rejected).count() as u64
+    }
+
+    /// Records the current slot when the approvals reach the
+    /// `threshold`, or clears it when they fall below, as well as the
+    /// number of the approvals.
+    ///
+    /// The slot is kept while the approvals stay at the threshold, so
+    /// that the additional approvals don't restart the delay.
+    fn update_approved_at(&mut self, threshold: u64) -> Result<()> {
+        let approvals = self.count_approvals();
+        self.approvals = approvals as u8;
+        if approvals < threshold {
+            self.approved_at_slot = None;
+        } else if self.approved_at_slot.is_none() {
+            self.approved_at_slot = Some(Clock::get()?.slot);
+        }
+        Ok(())
+    }
+
+    /// Returns true if the execution `delay` had elapsed at the `slot`
+    /// since the transaction reached the threshold.
+    fn is_delay_elapsed(&self, delay: u64, slot: u64) -> bool {
+        delay == 0
+            || self
+                .approved_at_slot
+                .is_some_and(|approved_at| slot >= approved_at.saturating_add(delay))
+    }
+
+    /// Returns true if the transaction had been expired at the `slot`.
+    fn is_expired(&self, slot: u64) -> bool {
+        self.expires_at_slot
+            .is_some_and(|expires_at| slot >= expires_at)
+    }
+
+    /// Validates the `accounts` given for the execution against the
+    /// stored metas and the program ID.
+    ///
+    /// It logs the offending account, as the error can't carry it.
+    fn validate_accounts(&self, accounts: &[AccountInfo]) -> Result<()> {
+        let program = TransactionMeta {
+            pubkey: self.program_id,
+            is_signer: false,
+            is_writable: false,
+        };
+        for meta in self.accounts.iter().chain(iter::once(&program)) {
+            let account = match accounts.iter().find(|account| *account.key == meta.pubkey) {
+                Some(account) => account,
+                None => {
+                    msg!("missing account: {}", meta.pubkey);
+                    return Err(Error::MissingAccount.into());
+                }
+            };
+            if meta.is_writable && !account.is_writable {
+                msg!("account should be writable: {}", meta.pubkey);
+                return Err(Error::WritabilityMismatch.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the transaction unfreezes the multisig it
+    /// belongs to, which is allowed while the multisig is frozen.
+    fn is_unfreeze(&self) -> bool {
+        self.program_id == id()
+            && self.data == instruction::Unfreeze::DISCRIMINATOR
+            && self
+                .accounts
+                .first()
+                .is_some_and(|meta| meta.pubkey == self.multisig)
+    }
+
+    /// Returns true if the `key` is the proposer of the transaction,
+    /// e.g. the only owner approved before the finalization.
+    fn is_proposer(&self, multisig: &Multisig, key: &Pubkey) -> bool {
+        multisig
+            .owners
+            .iter()
+            .position(|owner| owner == key)
+            .is_some_and(|index| self.signers[index])
+    }
+
+    /// Returns the account space required for the current state.
+    fn required_space(&self) -> usize {
+        Self::space(self.accounts.len(), self.data.len(), self.signers.len())
+    }
+
+    /// Returns the account space required for the transaction of
+    /// `n_accounts` accounts and `data_len` bytes of the instruction
+    /// data under the multisig of `n_owners` owners, with the room
+    /// for the memo up to [`Self::MAX_MEMO_LEN`] bytes, the tip, the
+    /// rejections, the approval count, the status, and the owner
+    /// epochs.
+    pub const fn space(n_accounts: usize, data_len: usize, n_owners: usize) -> usize {
+        let header = 8 + 32 + 32;
+        let instruction = 4 + 34 * n_accounts + 4 + data_len;
+        let state = 4 + n_owners + 1 + 4 + 1 + 32 + 9 + 9;
+        let memo = 4 + Self::MAX_MEMO_LEN;
+        let rejections = 4 + n_owners + 1;
+        let owner_epochs = 4 + 4 * n_owners;
+        header + instruction + state + memo + 8 + rejections + 1 + 1 + owner_epochs
+    }
 }
 
 impl From<&Transaction> for Instruction {
@@ -257,3 +2002,150 @@ impl From<&Transaction> for Instruction {
         }
     }
 }
+
+/// Template account, a reusable transaction skeleton for the
+/// recurring operations, e.g. monthly vendor payments.
+#[account]
+pub struct Template {
+    /// A multisig account this template belongs to.
+    pub multisig: Pubkey,
+
+    /// A target program ID to execute against.
+    pub program_id: Pubkey,
+
+    /// Accounts required for the transaction.
+    pub accounts: Vec<TransactionMeta>,
+
+    /// Instruction data skeleton with the amount placeholder.
+    pub data: Vec<u8>,
+
+    /// Byte offset of the little endian `u64` amount placeholder
+    /// in the `data`.
+    pub amount_offset: u32,
+}
+
+impl Template {
+    /// Returns the instruction data with the `amount` filled in.
+    fn data_with_amount(&self, amount: u64) -> Vec<u8> {
+        let mut data = self.data.clone();
+        let offset = self.amount_offset as usize;
+        data[offset..offset + 8].copy_from_slice(&amount.to_le_bytes());
+        data
+    }
+}
+
+/// Recovery account, the social recovery configuration of the
+/// `Multisig` account as well as the pending proposal.
+#[account]
+pub struct Recovery {
+    /// A multisig account this recovery belongs to.
+    pub multisig: Pubkey,
+
+    /// Guardians of the multisig, distinct from the owners.
+    pub guardians: Vec<Pubkey>,
+
+    /// A number of the guardian approvals to replace the owners.
+    pub threshold: u64,
+
+    /// A delay in seconds from the proposal to the execution.
+    pub delay: i64,
+
+    /// Proposed owner set, or empty if there is no proposal.
+    pub proposed_owners: Vec<Pubkey>,
+
+    /// Approvals[index] is true if guardians[index] approved
+    /// the proposal.
+    pub approvals: Vec<bool>,
+
+    /// A unix timestamp of the proposal.
+    pub proposed_at: i64,
+}
+
+impl Recovery {
+    /// A maximum number of guardians of the recovery account.
+    pub const MAX_GUARDIANS: usize = 10;
+
+    /// Returns the account space required for `n_guardians` guardians.
+    pub const fn required_space(n_guardians: usize) -> usize {
+        8 + 32 + 4 + 32 * n_guardians + 8 + 8 + 4 + 32 * Multisig::MAX_OWNERS + 4 + n_guardians + 8
+    }
+
+    fn is_pending(&self) -> bool {
+        !self.proposed_owners.is_empty()
+    }
+
+    fn guardian_index(&self, guardian: &Pubkey) -> Result<usize> {
+        self.guardians
+            .iter()
+            .position(|key| key == guardian)
+            .ok_or_else(|| Error::InvalidGuardian.into())
+    }
+
+    fn clear(&mut self) {
+        self.proposed_owners.clear();
+        self.approvals
+            .iter_mut()
+            .for_each(|approved| *approved = false);
+        self.proposed_at = 0;
+    }
+}
+
+/// An event emitted by the [`anchor_multisig::initialize_transaction`],
+/// [`anchor_multisig::initialize_transaction_buffered`], and
+/// [`anchor_multisig::initialize_from_template`] instructions.
+#[event]
+pub struct TransactionCreated {
+    pub multisig: Pubkey,
+    pub transaction: Pubkey,
+    pub proposer: Pubkey,
+}
+
+/// An event emitted by the [`anchor_multisig::approve_transaction`]
+/// instruction, with the current number of the approvals.
+#[event]
+pub struct TransactionApproved {
+    pub multisig: Pubkey,
+    pub transaction: Pubkey,
+    pub owner: Pubkey,
+    pub approvals: u64,
+    pub threshold: u64,
+}
+
+/// An event emitted by the [`anchor_multisig::execute_transaction`]
+/// instruction.
+#[event]
+pub struct TransactionExecuted {
+    pub multisig: Pubkey,
+    pub transaction: Pubkey,
+    pub executor: Pubkey,
+    pub tip: u64,
+}
+
+/// An event emitted by the [`anchor_multisig::reject_transaction`]
+/// instruction, with the current number of the rejections.
+#[event]
+pub struct TransactionRejected {
+    pub multisig: Pubkey,
+    pub transaction: Pubkey,
+    pub owner: Pubkey,
+    pub rejections: u64,
+    pub rejected: bool,
+}
+
+/// An event emitted by the [`anchor_multisig::spend_within_limit`]
+/// instruction.
+#[event]
+pub struct SpentWithinLimit {
+    pub multisig: Pubkey,
+    pub owner: Pubkey,
+    pub to: Pubkey,
+    pub lamports: u64,
+}
+
+/// An event emitted by the [`anchor_multisig::deposit`] instruction.
+#[event]
+pub struct Deposited {
+    pub multisig: Pubkey,
+    pub depositor: Pubkey,
+    pub lamports: u64,
+}
@@ -29,15 +29,35 @@ describe("anchor-multisig", () => {
     program.programId
   );
 
-  it("Creates and initialize a multisig account", async () => {
-    // The size is not tuned yet and will come back how
-    // to adjust to the actual size, e.g. number of
-    // owners of this multisig account.
-    const accountKeypair = multisigKeypair;
-    const accountSize = 200;
+  // The multisig account size for the owners, in line with
+  // `Multisig::required_space` of the program.
+  const multisigSize = (nOwners: number) => {
+    const owners = 4 + 32 * nOwners;
+    const delegates = 4 + 64 * nOwners;
+    const spends = 4 + 48 * nOwners;
+    const ownerEpochs = 4 + 4 * nOwners;
+    return (
+      8 +
+      owners +
+      8 +
+      1 +
+      4 +
+      8 +
+      8 +
+      8 +
+      1 +
+      delegates +
+      1 +
+      spends +
+      ownerEpochs
+    );
+  };
 
+  it("Creates and initialize a multisig account", async () => {
     // A, B, and C is the original owner.
+    const accountKeypair = multisigKeypair;
     const owners = [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey];
+    const accountSize = multisigSize(owners.length);
     const threshold = new anchor.BN(2);
 
     const tx = await program.rpc.initializeMultisig(owners, threshold, bump, {
@@ -64,6 +84,38 @@ describe("anchor-multisig", () => {
     assert.strictEqual(got.ownerSetSeqno, 0);
   });
 
+  it("Rejects too many owners", async () => {
+    const accountKeypair = anchor.web3.Keypair.generate();
+    const [_signer, bump] = anchor.web3.PublicKey.findProgramAddressSync(
+      [accountKeypair.publicKey.toBuffer()],
+      program.programId
+    );
+    // MAX_OWNERS + 1 owners.
+    const owners = [];
+    for (let i = 0; i < 33; i++) {
+      owners.push(anchor.web3.Keypair.generate().publicKey);
+    }
+    const accountSize = multisigSize(owners.length);
+
+    try {
+      await program.rpc.initializeMultisig(owners, new anchor.BN(2), bump, {
+        accounts: {
+          multisig: accountKeypair.publicKey,
+        },
+        instructions: [
+          await program.account.multisig.createInstruction(
+            accountKeypair,
+            accountSize
+          ),
+        ],
+        signers: [accountKeypair],
+      });
+      assert.fail("too many owners should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("TooManyOwners");
+    }
+  });
+
   it("Creates and initializes a transaction", async () => {
     // A new transaction keypair and size.
     //
@@ -90,10 +142,13 @@ describe("anchor-multisig", () => {
       owners: [ownerA.publicKey, ownerB.publicKey, ownerD.publicKey],
     });
 
+    const memo = "Replace the owner C with D";
     const tx = await program.rpc.initializeTransaction(
       program.programId,
       accounts,
       data,
+      null,
+      memo,
       {
         accounts: {
           multisig: multisigKeypair.publicKey,
@@ -125,7 +180,9 @@ describe("anchor-multisig", () => {
     assert.isNotTrue(got.signers[1]); // ownerB.
     assert.isNotTrue(got.signers[2]); // ownerC.
     assert.isNotTrue(got.executed);
+    assert.isTrue(got.finalized);
     assert.strictEqual(got.ownerSetSeqno, 0);
+    assert.strictEqual(got.memo, memo);
   });
 
   it("Approves the transaction", async () => {
@@ -136,11 +193,22 @@ describe("anchor-multisig", () => {
     };
     const signers = [ownerB];
 
+    let event = null;
+    const listener = program.addEventListener("TransactionApproved", (e) => {
+      event = e;
+    });
     const tx = await program.rpc.approveTransaction({
       accounts,
       signers,
     });
     console.log("Approve transaction succeeded", tx);
+    await program.removeEventListener(listener);
+
+    expect(event.multisig).to.eql(multisigKeypair.publicKey);
+    expect(event.transaction).to.eql(transactionKeypair.publicKey);
+    expect(event.owner).to.eql(ownerB.publicKey);
+    expect(event.approvals.toNumber()).to.equal(2);
+    expect(event.threshold.toNumber()).to.equal(2);
 
     const got = await program.account.transaction.fetch(
       transactionKeypair.publicKey
@@ -160,6 +228,7 @@ describe("anchor-multisig", () => {
         multisig: multisigKeypair.publicKey,
         multisigSigner,
         transaction: transactionKeypair.publicKey,
+        executor: program.provider.publicKey,
       },
       remainingAccounts: program.instruction.setOwners
         .accounts({
@@ -195,4 +264,313 @@ describe("anchor-multisig", () => {
     assert.isTrue(got.owners[1].equals(ownerB.publicKey));
     assert.isTrue(got.owners[2].equals(ownerD.publicKey));
   });
+
+  it("Initializes a transaction from the template", async () => {
+    const templateKeypair = anchor.web3.Keypair.generate();
+    const vendor = anchor.web3.Keypair.generate().publicKey;
+    const accounts = [
+      {
+        pubkey: multisigSigner,
+        isWritable: true,
+        isSigner: true,
+      },
+      {
+        pubkey: vendor,
+        isWritable: true,
+        isSigner: false,
+      },
+    ];
+
+    // System transfer instruction data with the placeholder amount,
+    // e.g. 4 bytes of the instruction index followed by the lamports.
+    const skeleton = anchor.web3.SystemProgram.transfer({
+      fromPubkey: multisigSigner,
+      toPubkey: vendor,
+      lamports: 0,
+    }).data;
+    const amountOffset = 4;
+
+    let tx = await program.rpc.createTemplate(
+      anchor.web3.SystemProgram.programId,
+      accounts,
+      skeleton,
+      amountOffset,
+      {
+        accounts: {
+          multisig: multisigKeypair.publicKey,
+          template: templateKeypair.publicKey,
+          proposer: ownerA.publicKey,
+        },
+        instructions: [
+          await program.account.template.createInstruction(
+            templateKeypair,
+            500
+          ),
+        ],
+        signers: [templateKeypair, ownerA],
+      }
+    );
+    console.log("Template had been created", tx);
+
+    const transactionKeypair = anchor.web3.Keypair.generate();
+    const amount = 1_000_000;
+    tx = await program.rpc.initializeFromTemplate(new anchor.BN(amount), null, {
+      accounts: {
+        multisig: multisigKeypair.publicKey,
+        template: templateKeypair.publicKey,
+        transaction: transactionKeypair.publicKey,
+        proposer: ownerB.publicKey,
+      },
+      instructions: [
+        await program.account.transaction.createInstruction(
+          transactionKeypair,
+          1000
+        ),
+      ],
+      signers: [transactionKeypair, ownerB],
+    });
+    console.log("Transaction from the template had been created", tx);
+
+    const got = await program.account.transaction.fetch(
+      transactionKeypair.publicKey
+    );
+    const want = anchor.web3.SystemProgram.transfer({
+      fromPubkey: multisigSigner,
+      toPubkey: vendor,
+      lamports: amount,
+    }).data;
+
+    assert.isTrue(got.multisig.equals(multisigKeypair.publicKey));
+    assert.isTrue(got.programId.equals(anchor.web3.SystemProgram.programId));
+    assert.deepEqual(got.accounts, accounts);
+    assert.deepEqual(got.data, want);
+    assert.isNotTrue(got.signers[0]); // ownerA.
+    assert.isTrue(got.signers[1]); // ownerB.
+    assert.isNotTrue(got.executed);
+  });
+
+  it("Writes the buffered transaction data", async () => {
+    const transactionKeypair = anchor.web3.Keypair.generate();
+    const data = Buffer.from(Array.from({ length: 1500 }, (_, i) => i % 256));
+    const chunkSize = 500;
+
+    let tx = await program.rpc.initializeTransactionBuffered(
+      program.programId,
+      [],
+      null,
+      {
+        accounts: {
+          multisig: multisigKeypair.publicKey,
+          transaction: transactionKeypair.publicKey,
+          proposer: ownerA.publicKey,
+        },
+        instructions: [
+          await program.account.transaction.createInstruction(
+            transactionKeypair,
+            2000
+          ),
+        ],
+        signers: [transactionKeypair, ownerA],
+      }
+    );
+    console.log("Buffered transaction had been created", tx);
+
+    // It's not approvable until finalized.
+    try {
+      await program.rpc.approveTransaction({
+        accounts: {
+          multisig: multisigKeypair.publicKey,
+          transaction: transactionKeypair.publicKey,
+          owner: ownerB.publicKey,
+        },
+        signers: [ownerB],
+      });
+      assert.fail("approved the buffered transaction");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("NotFinalized");
+    }
+
+    const accounts = {
+      multisig: multisigKeypair.publicKey,
+      transaction: transactionKeypair.publicKey,
+      proposer: ownerA.publicKey,
+    };
+    for (let i = 0; i < data.length; i += chunkSize) {
+      await program.rpc.appendTxData(data.subarray(i, i + chunkSize), {
+        accounts,
+        signers: [ownerA],
+      });
+    }
+    tx = await program.rpc.finalizeTransaction({
+      accounts,
+      signers: [ownerA],
+    });
+    console.log("Buffered transaction had been finalized", tx);
+
+    const got = await program.account.transaction.fetch(
+      transactionKeypair.publicKey
+    );
+    assert.deepEqual(got.data, data);
+    assert.isTrue(got.finalized);
+
+    // No more data after the finalization.
+    try {
+      await program.rpc.appendTxData(Buffer.from([0]), {
+        accounts,
+        signers: [ownerA],
+      });
+      assert.fail("appended to the finalized transaction");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("AlreadyFinalized");
+    }
+  });
+
+  it("Replaces the owners through the social recovery", async () => {
+    const recoveryKeypair = anchor.web3.Keypair.generate();
+    const recoveryTransaction = anchor.web3.Keypair.generate();
+    const guardians = [ownerC.publicKey, ownerE.publicKey];
+
+    // The recovery is created through the multisig transaction.
+    const accounts = [
+      {
+        pubkey: multisigKeypair.publicKey,
+        isWritable: false,
+        isSigner: false,
+      },
+      {
+        pubkey: recoveryKeypair.publicKey,
+        isWritable: true,
+        isSigner: true,
+      },
+      {
+        pubkey: multisigSigner,
+        isWritable: false,
+        isSigner: true,
+      },
+    ];
+    const data = program.coder.instruction.encode("create_recovery", {
+      guardians,
+      threshold: new anchor.BN(2),
+      delay: new anchor.BN(0),
+    });
+    await program.rpc.initializeTransaction(program.programId, accounts, data, null, "", {
+      accounts: {
+        multisig: multisigKeypair.publicKey,
+        transaction: recoveryTransaction.publicKey,
+        proposer: ownerA.publicKey,
+      },
+      instructions: [
+        await program.account.transaction.createInstruction(
+          recoveryTransaction,
+          1000
+        ),
+      ],
+      signers: [recoveryTransaction, ownerA],
+    });
+    await program.rpc.approveTransaction({
+      accounts: {
+        multisig: multisigKeypair.publicKey,
+        transaction: recoveryTransaction.publicKey,
+        owner: ownerB.publicKey,
+      },
+      signers: [ownerB],
+    });
+    const execute = async (remainingAccounts) =>
+      program.rpc.executeTransaction({
+        accounts: {
+          multisig: multisigKeypair.publicKey,
+          multisigSigner,
+          transaction: recoveryTransaction.publicKey,
+          executor: program.provider.publicKey,
+        },
+        remainingAccounts,
+        instructions: [
+          await program.account.recovery.createInstruction(
+            recoveryKeypair,
+            1000
+          ),
+        ],
+        signers: [recoveryKeypair],
+      });
+    const remainingAccounts = accounts.map((meta) =>
+      meta.pubkey.equals(multisigSigner) ? { ...meta, isSigner: false } : meta
+    );
+
+    // The accounts are validated before the execution.
+    try {
+      await execute(remainingAccounts);
+      assert.fail("the missing program should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("MissingAccount");
+    }
+    const programAccount = {
+      pubkey: program.programId,
+      isWritable: false,
+      isSigner: false,
+    };
+    await execute(remainingAccounts.concat(programAccount));
+
+    let recovery = await program.account.recovery.fetch(
+      recoveryKeypair.publicKey
+    );
+    assert.deepEqual(recovery.guardians, guardians);
+    assert.isTrue(recovery.threshold.eq(new anchor.BN(2)));
+
+    // The empty owner set can't be proposed.
+    try {
+      await program.rpc.proposeRecovery([], {
+        accounts: {
+          recovery: recoveryKeypair.publicKey,
+          guardian: ownerE.publicKey,
+        },
+        signers: [ownerE],
+      });
+      assert.fail("the empty owner set should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("NoOwners");
+    }
+
+    // Guardians propose and approve the new owner set.
+    const owners = [ownerA.publicKey, ownerB.publicKey, ownerE.publicKey];
+    await program.rpc.proposeRecovery(owners, {
+      accounts: {
+        recovery: recoveryKeypair.publicKey,
+        guardian: ownerE.publicKey,
+      },
+      signers: [ownerE],
+    });
+
+    // It's not executable without the guardian quorum.
+    const executeRecovery = () =>
+      program.rpc.executeRecovery({
+        accounts: {
+          multisig: multisigKeypair.publicKey,
+          recovery: recoveryKeypair.publicKey,
+        },
+      });
+    try {
+      await executeRecovery();
+      assert.fail("recovery should not be executed");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("NotEnoughSigners");
+    }
+
+    await program.rpc.approveRecovery({
+      accounts: {
+        recovery: recoveryKeypair.publicKey,
+        guardian: ownerC.publicKey,
+      },
+      signers: [ownerC],
+    });
+    const before = await program.account.multisig.fetch(
+      multisigKeypair.publicKey
+    );
+    await executeRecovery();
+
+    const got = await program.account.multisig.fetch(multisigKeypair.publicKey);
+    assert.deepEqual(got.owners, owners);
+    assert.strictEqual(got.ownerSetSeqno, before.ownerSetSeqno + 1);
+    recovery = await program.account.recovery.fetch(recoveryKeypair.publicKey);
+    assert.lengthOf(recovery.proposedOwners, 0);
+  });
 });
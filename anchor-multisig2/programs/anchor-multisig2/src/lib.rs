@@ -3,6 +3,8 @@
 use std::collections::HashSet;
 
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
 
 declare_id!("6ihHMp67G1RVdkSUC7ZgFccbLA5Ar19hn7wst11RjnQu");
 
@@ -26,52 +28,258 @@ pub enum Error {
 
     #[msg("The transaction queue is full")]
     TransactionQueueFull,
+
+    #[msg("The transaction was created under the old signer set")]
+    StaleTransaction,
+
+    #[msg("The multisig account is in an unknown layout")]
+    UnknownLayout,
+
+    #[msg("The transaction is not in the pending upload")]
+    TransactionNotPending,
+
+    #[msg("The transaction account is too small for the data")]
+    TransactionTooLarge,
+
+    #[msg("The capacity can't be shrunk")]
+    InvalidCapacity,
+
+    #[msg("The threshold should be at least one")]
+    ThresholdTooLow,
 }
 
 /// A Multisig PDA account.
+///
+/// The `payer` is placed right after the account discriminator
+/// so that clients can filter the multisig accounts by the payer
+/// through the `getProgramAccounts` memcmp filter at
+/// [`Multisig::PAYER_OFFSET`].
 #[account]
 pub struct Multisig {
+    /// A payer, e.g. creator, of the account.
+    payer: Pubkey,
+
     /// PDA bump of the account.
     bump: u8,
 
     /// threshold, e.g. `m` of `m/n` Multisig.
     m: u8,
 
-    /// Number of signers in `signers` array.
+    /// Number of signers in `signers`.
     n: u8,
 
     /// Current queued transactions.
     tx_queued: u8,
 
-    /// An array of queued transactions.
-    txs: [Pubkey; 10], // [Pubkey; Self::MAX_TRANSACTIONS]
+    /// Queued transactions, up to `max_transactions`.
+    txs: Vec<Pubkey>,
+
+    /// Signer's Pubkeys, up to `max_signers`.
+    signers: Vec<Pubkey>,
+
+    /// Signer set sequence number, incremented by the
+    /// [`anchor_multisig2::set_signers`] and the
+    /// [`anchor_multisig2::migrate`] instructions.
+    ///
+    /// The transactions stamped with the older number, e.g. the one
+    /// uploaded across the signer set change, can be neither committed
+    /// nor approved.
+    owner_set_seqno: u32,
+
+    /// Capacity of `signers`, grown by the
+    /// [`anchor_multisig2::grow_capacity`] instruction.
+    max_signers: u8,
+
+    /// Capacity of `txs`, grown by the
+    /// [`anchor_multisig2::grow_capacity`] instruction.
+    max_transactions: u8,
 
-    /// An array of signer's Pubkey.
-    signers: [Pubkey; 11], // [Pubkey; Self::MAX_SIGNERS]
+    /// A payer chosen seed of the PDA, so that a payer can open
+    /// multiple independent multisig accounts.
+    ///
+    /// The seed `0` is derived without the seed bytes, which keeps
+    /// the address of the accounts opened before this field.
+    seed: u64,
 }
 
 impl Multisig {
+    /// An offset of the `payer` field for the memcmp filter.
+    pub const PAYER_OFFSET: usize = 8;
+
     /// A minimum signers required for the account.
     const MIN_SIGNERS: usize = 2;
 
-    /// A maximum signers allowed to managed by the account.
-    const MAX_SIGNERS: usize = 11;
+    /// A default capacity of the signers, which is the size of the
+    /// fixed signer array before the capacity.
+    const DEFAULT_SIGNERS: u8 = 11;
 
-    /// A maximum pending transactions.
-    const MAX_TRANSACTIONS: usize = 10;
+    /// A default capacity of the pending transactions, which is the
+    /// size of the fixed transaction array before the capacity.
+    const DEFAULT_TRANSACTIONS: u8 = 10;
 
-    /// A space of the [`Multisig`] account.
-    const SPACE: usize = 8 + 1 + 1 + 1 + 1 + 32 * Self::MAX_SIGNERS + 32 * Self::MAX_TRANSACTIONS;
+    /// A space of the [`Multisig`] account in the fixed array layout,
+    /// e.g. `[Pubkey; 10]` for `txs` and `[Pubkey; 11]` for `signers`.
+    const ARRAY_SPACE: usize = 8 + 32 + 1 + 1 + 1 + 1 + 32 * 10 + 32 * 11 + 4;
+
+    /// A space of the [`Multisig`] account in the fixed array layout
+    /// before `owner_set_seqno`.
+    const LEGACY_SPACE: usize = Self::ARRAY_SPACE - 4;
+
+    /// A space of the [`Multisig`] account in the original fixed array
+    /// layout, before `payer` as well.
+    const BASELINE_SPACE: usize = Self::LEGACY_SPACE - 32;
+
+    /// Returns true if the account of `len` bytes is in one of the
+    /// fixed array layouts.
+    fn is_array_layout(len: usize) -> bool {
+        [Self::ARRAY_SPACE, Self::LEGACY_SPACE, Self::BASELINE_SPACE].contains(&len)
+    }
+
+    /// Returns the space of the [`Multisig`] account for the capacity.
+    fn space(max_signers: u8, max_transactions: u8) -> usize {
+        let (txs, signers) = (max_transactions as usize, max_signers as usize);
+        8 + 32 + 1 + 1 + 1 + 1 + (4 + 32 * txs) + (4 + 32 * signers) + 4 + 1 + 1 + 8
+    }
+
+    /// Returns the PDA seed bytes of the `seed`.
+    ///
+    /// It's empty for the seed `0`, e.g. `[b"multisig", payer]`, which
+    /// is the address of the accounts in the fixed array layouts.
+    fn seed_bytes(seed: u64) -> Vec<u8> {
+        match seed {
+            0 => vec![],
+            seed => seed.to_le_bytes().to_vec(),
+        }
+    }
+
+    /// Decodes the [`Multisig`] account in the fixed array layouts,
+    /// e.g. [`Multisig::ARRAY_SPACE`], [`Multisig::LEGACY_SPACE`], and
+    /// [`Multisig::BASELINE_SPACE`].
+    ///
+    /// The baseline layout doesn't have the `payer`, which is taken
+    /// from the `payer` the account is derived from instead.
+    ///
+    /// The queued transactions are dropped and the `owner_set_seqno`
+    /// is incremented, as those transactions are in the fixed array
+    /// layout as well.
+    fn from_array_layout(data: &[u8], payer: &Pubkey) -> Result<Self> {
+        require!(Self::is_array_layout(data.len()), Error::UnknownLayout);
+        require!(data[..8] == Self::DISCRIMINATOR, Error::UnknownLayout);
+        let baseline = data.len() == Self::BASELINE_SPACE;
+        let mut data = &data[8..];
+        let payer = match baseline {
+            true => *payer,
+            false => Pubkey::deserialize(&mut data)?,
+        };
+        let bump = u8::deserialize(&mut data)?;
+        let m = u8::deserialize(&mut data)?;
+        let n = u8::deserialize(&mut data)?;
+        let _tx_queued = u8::deserialize(&mut data)?;
+        let _txs = <[Pubkey; 10]>::deserialize(&mut data)?;
+        let signers = <[Pubkey; 11]>::deserialize(&mut data)?;
+        let owner_set_seqno = match data.is_empty() {
+            true => 0,
+            false => u32::deserialize(&mut data)?,
+        };
+        Ok(Self {
+            payer,
+            bump,
+            m,
+            n,
+            tx_queued: 0,
+            txs: vec![],
+            signers: signers[..n as usize].to_vec(),
+            owner_set_seqno: owner_set_seqno.wrapping_add(1),
+            max_signers: Self::DEFAULT_SIGNERS,
+            max_transactions: Self::DEFAULT_TRANSACTIONS,
+            seed: 0,
+        })
+    }
+
+    /// Returns the index of the `signer` in the `signers` array.
+    fn signer_index(&self, signer: &Pubkey) -> Result<usize> {
+        self.signers
+            .iter()
+            .position(|pubkey| pubkey == signer)
+            .ok_or_else(|| Error::InvalidSigner.into())
+    }
+
+    /// Pushes the `transaction` to the transaction queue.
+    fn push_transaction(&mut self, transaction: Pubkey) -> Result<()> {
+        // The queue should not be full.
+        require!(
+            self.txs.len() < self.max_transactions as usize,
+            Error::TransactionQueueFull,
+        );
+        self.txs.push(transaction);
+        self.tx_queued = self.txs.len() as u8;
+        Ok(())
+    }
+
+    /// Removes the `transaction` from the transaction queue.
+    ///
+    /// The following transactions are shifted to keep the queue
+    /// order and `tx_queued` in sync with the queue length.  The
+    /// account space beyond the serialized queue is stale, which
+    /// is never read, as `txs` is length prefixed.
+    fn remove_transaction(&mut self, transaction: &Pubkey) -> Result<()> {
+        let index = self
+            .txs
+            .iter()
+            .position(|tx| tx == transaction)
+            .ok_or(Error::InvalidTransaction)?;
+        self.txs.remove(index);
+        self.tx_queued = self.txs.len() as u8;
+        Ok(())
+    }
+
+    /// Drops all the transactions from the transaction queue.
+    fn clear_transactions(&mut self) {
+        self.txs.clear();
+        self.tx_queued = 0;
+    }
+
+    /// Sets the signers and the threshold, `m`.
+    ///
+    /// The `payer` is always added to the signers.
+    fn set_signers(&mut self, payer: Pubkey, m: u8, signers: Vec<Pubkey>) -> Result<()> {
+        // Checks duplicate signers.
+        let mut signers: HashSet<_> = signers.into_iter().collect();
+        signers.insert(payer);
+
+        // Makes sure we have a valid number of sighers,
+        // as well as the valid threshold, 1 <= m <= signers.len().
+        require_gte!(signers.len(), Self::MIN_SIGNERS, Error::NotEnoughSigners);
+        require_gte!(
+            self.max_signers as usize,
+            signers.len(),
+            Error::TooManySigners
+        );
+        let threshold = m as usize;
+        require!(threshold >= 1, Error::ThresholdTooLow);
+        require_gte!(signers.len(), threshold, Error::ThresholdTooHigh);
+
+        self.m = m;
+        self.n = signers.len() as u8;
+        self.signers = signers.into_iter().collect();
+
+        Ok(())
+    }
 }
 
 /// A transaction account managed by Multisig account.
+///
+/// The `multisig` is placed right after the account discriminator
+/// so that clients can filter the pending transactions of the multisig
+/// through the `getProgramAccounts` memcmp filter at
+/// [`Transaction::MULTISIG_OFFSET`].
 #[account]
 pub struct Transaction {
     /// A multisig account.
     pub multisig: Pubkey,
 
-    /// Indices of the signers.
-    pub signers: [bool; 11],
+    /// Approvals indexed by the [`Multisig`] signers.
+    pub signers: Vec<bool>,
 
     /// A target program ID.
     pub program_id: Pubkey,
@@ -81,6 +289,30 @@ pub struct Transaction {
 
     /// An instruction data.
     pub data: Vec<u8>,
+
+    /// [`Multisig`] signer set sequence number at the creation.
+    ///
+    /// The transactions enqueued before this field reads zero
+    /// from the zero initialized account space.
+    pub owner_set_seqno: u32,
+
+    /// True while the `data` is being uploaded by the
+    /// [`anchor_multisig2::enqueue_append`] instruction.
+    ///
+    /// It's false for the transactions enqueued before this field,
+    /// same as `owner_set_seqno` above.
+    pub pending: bool,
+}
+
+impl Transaction {
+    /// An offset of the `multisig` field for the memcmp filter.
+    pub const MULTISIG_OFFSET: usize = 8;
+
+    /// Returns the account space required for the current state.
+    fn required_space(&self) -> usize {
+        let (signers, accounts, data) = (self.signers.len(), self.accounts.len(), self.data.len());
+        8 + 32 + (4 + signers) + 32 + (4 + 34 * accounts) + (4 + data) + 4 + 1
+    }
 }
 
 #[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
@@ -100,9 +332,86 @@ impl From<TransactionMeta> for AccountMeta {
     }
 }
 
+/// An approval status returned by the [`anchor_multisig2::approve`]
+/// instruction.
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct ApprovalStatus {
+    /// Number of the approvals of the transaction.
+    pub approvals: u8,
+
+    /// Threshold, e.g. `m`, of the multisig account.
+    pub threshold: u8,
+}
+
+impl ApprovalStatus {
+    fn new(tx: &Transaction, multisig: &Multisig) -> Self {
+        Self {
+            approvals: tx.signers.iter().filter(|&signer| *signer).count() as u8,
+            threshold: multisig.m,
+        }
+    }
+
+    /// Returns `true` if the transaction got the enough approvals.
+    pub fn is_executable(&self) -> bool {
+        self.approvals >= self.threshold
+    }
+}
+
+/// An event emitted by the [`anchor_multisig2::open`] instruction.
+#[event]
+pub struct MultisigOpened {
+    pub multisig: Pubkey,
+    pub payer: Pubkey,
+    pub seed: u64,
+    pub m: u8,
+    pub n: u8,
+}
+
+/// An event emitted by the [`anchor_multisig2::enqueue`] instruction.
+#[event]
+pub struct TransactionEnqueued {
+    pub multisig: Pubkey,
+    pub transaction: Pubkey,
+    pub signer: Pubkey,
+}
+
+/// An event emitted by the [`anchor_multisig2::approve`] instruction.
+#[event]
+pub struct TransactionApproved {
+    pub multisig: Pubkey,
+    pub transaction: Pubkey,
+    pub signer: Pubkey,
+    pub approvals: u8,
+    pub threshold: u8,
+}
+
+/// An event emitted by the [`anchor_multisig2::set_signers`] instruction.
+#[event]
+pub struct SignersChanged {
+    pub multisig: Pubkey,
+    pub m: u8,
+    pub n: u8,
+    pub owner_set_seqno: u32,
+}
+
+/// An event emitted by the [`anchor_multisig2::dequeue`] instruction.
+#[event]
+pub struct TransactionDequeued {
+    pub multisig: Pubkey,
+    pub transaction: Pubkey,
+    pub tx_queued: u8,
+}
+
+/// An event emitted by the [`anchor_multisig2::close`] instruction.
+#[event]
+pub struct MultisigClosed {
+    pub multisig: Pubkey,
+    pub payer: Pubkey,
+}
+
 /// Accounts required for the [`anchor_multisig2::open`] instruction.
 #[derive(Accounts)]
-#[instruction(bump: u8)]
+#[instruction(bump: u8, seed: u64)]
 pub struct Open<'info> {
     /// A [`Multisig`] account payer, as well as the signer
     /// of the [`Transaction`]s.
@@ -113,8 +422,8 @@ pub struct Open<'info> {
     #[account(
         init,
         payer = payer,
-        space = Multisig::SPACE,
-        seeds = [b"multisig", payer.key.as_ref()],
+        space = Multisig::space(Multisig::DEFAULT_SIGNERS, Multisig::DEFAULT_TRANSACTIONS),
+        seeds = [b"multisig", payer.key.as_ref(), &Multisig::seed_bytes(seed)],
         bump
     )]
     pub multisig: Account<'info, Multisig>,
@@ -139,6 +448,26 @@ pub struct Enqueue<'info> {
     pub transaction: Box<Account<'info, Transaction>>,
 }
 
+/// Accounts required for the [`anchor_multisig2::enqueue_append`]
+/// and [`anchor_multisig2::enqueue_commit`] instructions.
+#[derive(Accounts)]
+pub struct EnqueueUpload<'info> {
+    /// The payer of the [`anchor_multisig2::enqueue_begin`] instruction.
+    pub payer: Signer<'info>,
+
+    /// The multisig account to be enqueued under.
+    #[account(mut)]
+    pub multisig: Box<Account<'info, Multisig>>,
+
+    /// The transaction being uploaded.
+    #[account(
+        mut,
+        has_one = multisig,
+        constraint = transaction.pending @ Error::TransactionNotPending
+    )]
+    pub transaction: Box<Account<'info, Transaction>>,
+}
+
 /// Approves the transaction managed under multisig account.
 #[derive(Accounts)]
 pub struct Approve<'info> {
@@ -155,6 +484,84 @@ pub struct Approve<'info> {
     pub transaction: Box<Account<'info, Transaction>>,
 }
 
+/// Accounts required for the [`anchor_multisig2::set_signers`] instruction.
+#[derive(Accounts)]
+pub struct SetSigners<'info> {
+    /// The original payer of the [`Multisig`] account.
+    pub payer: Signer<'info>,
+
+    /// The [`Multisig`] account to change the signers.
+    #[account(
+        mut,
+        seeds = [b"multisig", payer.key().as_ref(), &Multisig::seed_bytes(multisig.seed)],
+        bump = multisig.bump
+    )]
+    pub multisig: Account<'info, Multisig>,
+}
+
+/// Accounts required for the [`anchor_multisig2::dequeue`] instruction.
+#[derive(Accounts)]
+pub struct Dequeue<'info> {
+    /// The original payer of the [`Multisig`] account.
+    pub payer: Signer<'info>,
+
+    /// The [`Multisig`] account to dequeue the transaction from.
+    #[account(
+        mut,
+        seeds = [b"multisig", payer.key().as_ref(), &Multisig::seed_bytes(multisig.seed)],
+        bump = multisig.bump
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    /// The transaction to be dequeued.
+    #[account(has_one = multisig)]
+    pub transaction: Box<Account<'info, Transaction>>,
+}
+
+/// Accounts required for the [`anchor_multisig2::grow_capacity`] instruction.
+#[derive(Accounts)]
+pub struct GrowCapacity<'info> {
+    /// The original payer of the [`Multisig`] account, who pays
+    /// the additional rent.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The [`Multisig`] account to grow.
+    #[account(
+        mut,
+        seeds = [b"multisig", payer.key().as_ref(), &Multisig::seed_bytes(multisig.seed)],
+        bump = multisig.bump
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    /// The SystemProgram to transfer the additional rent.
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for the [`anchor_multisig2::migrate`] instruction.
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    /// The original payer of the [`Multisig`] account, who pays
+    /// the additional rent.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: The [`Multisig`] account in the legacy layout, which
+    /// can't be deserialized as [`Multisig`] yet.
+    ///
+    /// The legacy accounts are always derived without the seed.
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"multisig", payer.key().as_ref()],
+        bump
+    )]
+    pub multisig: UncheckedAccount<'info>,
+
+    /// The SystemProgram to transfer the additional rent.
+    pub system_program: Program<'info, System>,
+}
+
 /// Accounts required for the [`anchor_multisig2::close`] instruction.
 #[derive(Accounts)]
 pub struct Close<'info> {
@@ -166,8 +573,8 @@ pub struct Close<'info> {
     #[account(
         mut,
         close = payer,
-        seeds = [b"multisig", payer.key().as_ref()],
-        bump
+        seeds = [b"multisig", payer.key().as_ref(), &Multisig::seed_bytes(multisig.seed)],
+        bump = multisig.bump
     )]
     pub multisig: Account<'info, Multisig>,
 
@@ -181,34 +588,35 @@ pub mod anchor_multisig2 {
     use super::*;
 
     /// Creates new Multisig account.
-    pub fn open(ctx: Context<Open>, bump: u8, m: u8, signers: Vec<Pubkey>) -> Result<()> {
+    ///
+    /// The `seed` distinguishes the multisig accounts of the same payer.
+    pub fn open(
+        ctx: Context<Open>,
+        bump: u8,
+        seed: u64,
+        m: u8,
+        signers: Vec<Pubkey>,
+    ) -> Result<()> {
         let multisig = &mut ctx.accounts.multisig;
         let payer = &ctx.accounts.payer;
 
-        // Checks duplicate signers.
-        let mut signers: HashSet<_> = signers.into_iter().collect();
-        signers.insert(payer.key());
-
-        // Makes sure we have a valid number of sighers,
-        // as well as the valid threshold, m <= signers.len().
-        require_gte!(
-            signers.len(),
-            Multisig::MIN_SIGNERS,
-            Error::NotEnoughSigners
-        );
-        require!(signers.len() < Multisig::MAX_SIGNERS, Error::TooManySigners);
-        let threshold = m as usize;
-        require_gte!(signers.len(), threshold, Error::ThresholdTooHigh);
-
         // Initializes the multisig PDA account.
+        multisig.max_signers = Multisig::DEFAULT_SIGNERS;
+        multisig.max_transactions = Multisig::DEFAULT_TRANSACTIONS;
+        multisig.set_signers(payer.key(), m, signers)?;
+        multisig.payer = payer.key();
         multisig.bump = bump;
-        multisig.m = m;
-        multisig.n = signers.len() as u8;
-        signers
-            .into_iter()
-            .enumerate()
-            .for_each(|(i, signer)| multisig.signers[i] = signer);
+        multisig.seed = seed;
         multisig.tx_queued = 0;
+        multisig.owner_set_seqno = 0;
+
+        emit!(MultisigOpened {
+            multisig: multisig.key(),
+            payer: payer.key(),
+            seed,
+            m: multisig.m,
+            n: multisig.n,
+        });
 
         Ok(())
     }
@@ -228,79 +636,354 @@ pub mod anchor_multisig2 {
 
         // The payer of the transaction should be one of
         // the Multisig account this transaction belongs to.
-        let index = match multisig
-            .signers
-            .iter()
-            .position(|pubkey| *pubkey == payer.key())
-        {
-            None => return Err(Error::InvalidSigner.into()),
-            Some(index) => index,
-        };
-
-        // The queue should not be full.
-        let tx_queued = multisig.tx_queued as usize;
-        require!(
-            tx_queued < Multisig::MAX_TRANSACTIONS,
-            Error::TransactionQueueFull,
-        );
+        let index = multisig.signer_index(payer.key)?;
 
         // Initialize the transaction and enqueue
         // the tx pubkey to multisig account.
         let tx = &mut ctx.accounts.transaction;
+        multisig.push_transaction(tx.key())?;
         tx.multisig = multisig.key();
         tx.program_id = tx_program_id;
         tx.accounts = tx_accounts;
         tx.data = tx_data;
+        tx.signers = vec![false; multisig.signers.len()];
+        tx.signers[index] = true;
+        tx.owner_set_seqno = multisig.owner_set_seqno;
+        tx.pending = false;
+
+        emit!(TransactionEnqueued {
+            multisig: multisig.key(),
+            transaction: tx.key(),
+            signer: payer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Begins the chunked upload of the new Transaction under the
+    /// Multisig account.
+    ///
+    /// The instruction data, which doesn't fit in a single
+    /// transaction, e.g. upgrade buffers, is uploaded through
+    /// [`enqueue_append`] and the transaction is enqueued by
+    /// [`enqueue_commit`].  It's not approvable until then.
+    pub fn enqueue_begin(
+        ctx: Context<Enqueue>,
+        tx_program_id: Pubkey,
+        tx_accounts: Vec<TransactionMeta>,
+    ) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let payer = &ctx.accounts.payer;
+
+        let index = multisig.signer_index(payer.key)?;
+
+        let tx = &mut ctx.accounts.transaction;
+        tx.multisig = multisig.key();
+        tx.program_id = tx_program_id;
+        tx.accounts = tx_accounts;
+        tx.data = vec![];
+        tx.signers = vec![false; multisig.signers.len()];
         tx.signers[index] = true;
-        multisig.txs[tx_queued] = tx.key();
-        multisig.tx_queued += 1;
+        tx.owner_set_seqno = multisig.owner_set_seqno;
+        tx.pending = true;
+
+        Ok(())
+    }
+
+    /// Appends the `data_chunk` to the pending transaction data.
+    pub fn enqueue_append(ctx: Context<EnqueueUpload>, data_chunk: Vec<u8>) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let payer = &ctx.accounts.payer;
+        let tx = &mut ctx.accounts.transaction;
+
+        // Only the signer who began the upload can append.
+        let index = multisig.signer_index(payer.key)?;
+        require!(tx.signers.get(index) == Some(&true), Error::InvalidSigner);
+
+        tx.data.extend_from_slice(&data_chunk);
+        require_gte!(
+            tx.to_account_info().data_len(),
+            tx.required_space(),
+            Error::TransactionTooLarge
+        );
+
+        Ok(())
+    }
+
+    /// Commits the pending transaction and enqueues it under
+    /// the Multisig account.
+    pub fn enqueue_commit(ctx: Context<EnqueueUpload>) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        let payer = &ctx.accounts.payer;
+        let tx = &mut ctx.accounts.transaction;
+
+        let index = multisig.signer_index(payer.key)?;
+        require!(tx.signers.get(index) == Some(&true), Error::InvalidSigner);
+
+        // The signer set may have changed during the upload.
+        require_eq!(
+            tx.owner_set_seqno,
+            multisig.owner_set_seqno,
+            Error::StaleTransaction
+        );
+
+        multisig.push_transaction(tx.key())?;
+        tx.pending = false;
+
+        emit!(TransactionEnqueued {
+            multisig: multisig.key(),
+            transaction: tx.key(),
+            signer: payer.key(),
+        });
 
         Ok(())
     }
 
     /// Approves transaction queued in Multisig account.
-    pub fn approve(ctx: Context<Approve>) -> Result<()> {
+    ///
+    /// It returns the [`ApprovalStatus`] through the return data, so
+    /// that the clients and the CPI callers know if the transaction
+    /// became executable without fetching the account.
+    pub fn approve(ctx: Context<Approve>) -> Result<ApprovalStatus> {
         let multisig = &mut ctx.accounts.multisig;
         let payer = &ctx.accounts.payer;
 
         // The payer of the transaction should be one of
         // the Multisig account this transaction belongs to.
-        let index = match multisig
-            .signers
-            .iter()
-            .position(|pubkey| *pubkey == payer.key())
-        {
-            None => return Err(Error::InvalidSigner.into()),
-            Some(index) => index,
-        };
+        let index = multisig.signer_index(payer.key)?;
 
         // The transaction should be managed under the
-        // multisig account.
+        // multisig account, e.g. not pending the upload.
         let tx = &mut ctx.accounts.transaction;
         require!(multisig.txs.contains(&tx.key()), Error::InvalidTransaction);
 
+        // The approvals are expired by the signer set change.
+        require_eq!(
+            tx.owner_set_seqno,
+            multisig.owner_set_seqno,
+            Error::StaleTransaction
+        );
+
         // Nothing to do if it's already approved by the
         // same signer.
         if tx.signers[index] == true {
-            return Ok(());
+            return Ok(ApprovalStatus::new(tx, multisig));
         }
         tx.signers[index] = true;
 
-        // Counts the signers and executes the transaction
-        // if it got the enough signatures.
-        let signers = tx.signers.iter().filter(|&signer| *signer).count();
-        let threshold = multisig.m as usize;
-        if signers < threshold {
+        // Counts the signers.
+        let status = ApprovalStatus::new(tx, multisig);
+
+        emit!(TransactionApproved {
+            multisig: multisig.key(),
+            transaction: tx.key(),
+            signer: payer.key(),
+            approvals: status.approvals,
+            threshold: status.threshold,
+        });
+
+        Ok(status)
+    }
+
+    /// Changes the signers and the threshold of the multisig account.
+    ///
+    /// It stamps the new `owner_set_seqno` and drops the pending
+    /// transactions from the queue, as the approvals under the old
+    /// signer set are no longer valid.
+    pub fn set_signers(ctx: Context<SetSigners>, m: u8, signers: Vec<Pubkey>) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        let payer = &ctx.accounts.payer;
+
+        multisig.set_signers(payer.key(), m, signers)?;
+        multisig.owner_set_seqno = multisig.owner_set_seqno.wrapping_add(1);
+        multisig.clear_transactions();
+
+        emit!(SignersChanged {
+            multisig: multisig.key(),
+            m: multisig.m,
+            n: multisig.n,
+            owner_set_seqno: multisig.owner_set_seqno,
+        });
+
+        Ok(())
+    }
+
+    /// Dequeues the transaction from the multisig account, e.g. to
+    /// make a room for the new transaction.
+    pub fn dequeue(ctx: Context<Dequeue>) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        let tx = &ctx.accounts.transaction;
+
+        multisig.remove_transaction(&tx.key())?;
+
+        emit!(TransactionDequeued {
+            multisig: multisig.key(),
+            transaction: tx.key(),
+            tx_queued: multisig.tx_queued,
+        });
+
+        Ok(())
+    }
+
+    /// Grows the capacity of the signers and the pending transactions.
+    ///
+    /// The payer covers the additional rent.
+    pub fn grow_capacity(
+        ctx: Context<GrowCapacity>,
+        max_signers: u8,
+        max_transactions: u8,
+    ) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(
+            max_signers >= multisig.max_signers && max_transactions >= multisig.max_transactions,
+            Error::InvalidCapacity
+        );
+
+        let space = Multisig::space(max_signers, max_transactions);
+        realloc(
+            &multisig.to_account_info(),
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            space,
+        )?;
+        multisig.max_signers = max_signers;
+        multisig.max_transactions = max_transactions;
+
+        Ok(())
+    }
+
+    /// Migrates the multisig account in the fixed array layouts, e.g.
+    /// the baseline one as well as before and after the
+    /// `owner_set_seqno` field, to the current layout with the default
+    /// capacity.
+    ///
+    /// The transactions queued before the migration are dropped, as
+    /// those are in the fixed array layout as well.
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+
+        // Nothing to do for the migrated account.
+        if !Multisig::is_array_layout(multisig.data_len()) {
             return Ok(());
         }
+        let migrated =
+            Multisig::from_array_layout(&multisig.try_borrow_data()?, ctx.accounts.payer.key)?;
+
+        let space = Multisig::space(migrated.max_signers, migrated.max_transactions);
+        realloc(
+            multisig,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            space,
+        )?;
+        migrated.try_serialize(&mut &mut multisig.try_borrow_mut_data()?[..])?;
 
         Ok(())
     }
 
-    /// Closes the multisig account.
+    /// Closes the multisig account and gives the rent back to the
+    /// original payer.
     ///
-    /// It requires `m - 1` signers to approve this operation.
-    pub fn close(_ctx: Context<Close>) -> Result<()> {
+    /// It's only allowed to the payer, without the signer approvals.
+    /// The queued transaction accounts are left as is.
+    pub fn close(ctx: Context<Close>) -> Result<()> {
+        emit!(MultisigClosed {
+            multisig: ctx.accounts.multisig.key(),
+            payer: ctx.accounts.payer.key(),
+        });
+
         Ok(())
     }
 }
+
+/// Reallocates the `account` to the `space`, paid by the `payer`.
+fn realloc<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = rent.saturating_sub(account.lamports());
+    if lamports > 0 {
+        let cpi = CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: account.clone(),
+            },
+        );
+        system_program::transfer(cpi, lamports)?;
+    }
+    account.realloc(space, false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Multisig, Pubkey};
+
+    fn multisig(max_transactions: u8) -> Multisig {
+        Multisig {
+            payer: Pubkey::new_unique(),
+            bump: 255,
+            m: 2,
+            n: 2,
+            tx_queued: 0,
+            txs: vec![],
+            signers: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            owner_set_seqno: 0,
+            max_signers: Multisig::DEFAULT_SIGNERS,
+            max_transactions,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn remove_transaction_keeps_order() {
+        let mut multisig = multisig(4);
+        let txs: Vec<_> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        txs.iter()
+            .for_each(|tx| multisig.push_transaction(*tx).unwrap());
+        assert!(multisig.push_transaction(Pubkey::new_unique()).is_err());
+
+        multisig.remove_transaction(&txs[1]).unwrap();
+        assert_eq!(multisig.txs, [txs[0], txs[2], txs[3]]);
+        assert_eq!(multisig.tx_queued, 3);
+
+        // Not in the queue anymore.
+        let got = multisig.remove_transaction(&txs[1]);
+        assert_eq!(got, Err(Error::InvalidTransaction.into()));
+        assert_eq!(multisig.tx_queued, 3);
+
+        multisig.remove_transaction(&txs[3]).unwrap();
+        multisig.remove_transaction(&txs[0]).unwrap();
+        assert_eq!(multisig.txs, [txs[2]]);
+        assert_eq!(multisig.tx_queued, 1);
+    }
+
+    #[test]
+    fn remove_transaction_cycles() {
+        let mut multisig = multisig(3);
+        let mut want = vec![];
+        for i in 0..20 {
+            // Fills up the queue.
+            while want.len() < 3 {
+                let tx = Pubkey::new_unique();
+                multisig.push_transaction(tx).unwrap();
+                want.push(tx);
+            }
+            assert!(multisig.push_transaction(Pubkey::new_unique()).is_err());
+
+            // Removes the one in the rotating position.
+            let tx = want.remove(i % want.len());
+            multisig.remove_transaction(&tx).unwrap();
+            assert_eq!(multisig.txs, want);
+            assert_eq!(multisig.tx_queued as usize, want.len());
+        }
+
+        multisig.clear_transactions();
+        assert!(multisig.txs.is_empty());
+        assert_eq!(multisig.tx_queued, 0);
+    }
+}
@@ -0,0 +1,458 @@
+//! The multisig program tests through the `solana-program-test` banks client.
+
+use anchor_lang::error::ERROR_CODE_OFFSET;
+use anchor_lang::{
+    AccountDeserialize, AnchorDeserialize, AnchorSerialize, Discriminator, InstructionData,
+    ToAccountMetas,
+};
+use anchor_multisig2::{ApprovalStatus, Error, Multisig, Transaction as MultisigTransaction};
+use solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestBanksClientExt};
+use solana_sdk::account::Account;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::system_program;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+/// A capacity of the signers, including the payer, of the newly
+/// opened multisig account.
+const MAX_SIGNERS: usize = 11;
+
+/// A capacity of the transaction queue of the newly opened multisig
+/// account.
+const MAX_TRANSACTIONS: usize = 10;
+
+struct Env {
+    banks: BanksClient,
+    payer: Keypair,
+}
+
+impl Env {
+    async fn new() -> Self {
+        Self::with_accounts(vec![]).await
+    }
+
+    /// Starts with the `accounts`, e.g. the multisig accounts in the
+    /// legacy layouts.
+    async fn with_accounts(accounts: Vec<(Pubkey, Account)>) -> Self {
+        let mut program = ProgramTest::new(
+            "anchor_multisig2",
+            anchor_multisig2::ID,
+            processor!(anchor_multisig2::entry),
+        );
+        for (pubkey, account) in accounts {
+            program.add_account(pubkey, account);
+        }
+        let (banks, payer, _) = program.start().await;
+        Self { banks, payer }
+    }
+
+    /// Opens the `m` of `n` multisig account of the `seed`, where the
+    /// payer is added to the `signers`.
+    async fn open(
+        &mut self,
+        seed: u64,
+        m: u8,
+        signers: &[Pubkey],
+    ) -> Result<Pubkey, TransactionError> {
+        let seed_bytes = match seed {
+            0 => vec![],
+            seed => seed.to_le_bytes().to_vec(),
+        };
+        let (multisig, bump) = Pubkey::find_program_address(
+            &[b"multisig", self.payer.pubkey().as_ref(), &seed_bytes],
+            &anchor_multisig2::ID,
+        );
+        let accounts = anchor_multisig2::accounts::Open {
+            payer: self.payer.pubkey(),
+            multisig,
+            system_program: system_program::ID,
+        };
+        let data = anchor_multisig2::instruction::Open {
+            bump,
+            seed,
+            m,
+            signers: signers.to_vec(),
+        };
+        let ix = Instruction {
+            program_id: anchor_multisig2::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        };
+        self.process(&[ix], &[]).await?;
+        Ok(multisig)
+    }
+
+    /// Enqueues the empty system program instruction under the
+    /// `multisig` account, signed by the `signer`.
+    async fn enqueue(
+        &mut self,
+        multisig: &Pubkey,
+        n: usize,
+        signer: &Keypair,
+    ) -> Result<Pubkey, TransactionError> {
+        let transaction = Keypair::new();
+        let space = 8 + 32 + (4 + n) + 32 + 4 + 4 + 4 + 1;
+        let create = system_instruction::create_account(
+            &self.payer.pubkey(),
+            &transaction.pubkey(),
+            Rent::default().minimum_balance(space),
+            space as u64,
+            &anchor_multisig2::ID,
+        );
+        let accounts = anchor_multisig2::accounts::Enqueue {
+            payer: signer.pubkey(),
+            multisig: *multisig,
+            transaction: transaction.pubkey(),
+        };
+        let data = anchor_multisig2::instruction::Enqueue {
+            tx_program_id: system_program::ID,
+            tx_accounts: vec![],
+            tx_data: vec![],
+        };
+        let enqueue = Instruction {
+            program_id: anchor_multisig2::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        };
+        self.process(&[create, enqueue], &[&transaction, signer])
+            .await?;
+        Ok(transaction.pubkey())
+    }
+
+    /// Approves the `transaction` by the `signer`.
+    ///
+    /// The [`ApprovalStatus`] is taken from the simulation, as the
+    /// banks client doesn't return the return data of the processed
+    /// transaction.
+    async fn approve(
+        &mut self,
+        multisig: &Pubkey,
+        transaction: &Pubkey,
+        signer: &Keypair,
+    ) -> Result<ApprovalStatus, TransactionError> {
+        let accounts = anchor_multisig2::accounts::Approve {
+            payer: signer.pubkey(),
+            multisig: *multisig,
+            transaction: *transaction,
+        };
+        let ix = Instruction {
+            program_id: anchor_multisig2::ID,
+            accounts: accounts.to_account_metas(None),
+            data: anchor_multisig2::instruction::Approve {}.data(),
+        };
+        let tx = self.transaction(&[ix], &[signer]).await;
+        let simulation = self.banks.simulate_transaction(tx.clone()).await.unwrap();
+        simulation.result.unwrap()?;
+        let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+        self.banks
+            .process_transaction(tx)
+            .await
+            .map_err(|e| e.unwrap())?;
+        Ok(ApprovalStatus::try_from_slice(&return_data.data).unwrap())
+    }
+
+    /// Waits for the new blockhash, so that the same instruction can be
+    /// sent again.
+    async fn next_blockhash(&mut self) {
+        let blockhash = self.banks.get_latest_blockhash().await.unwrap();
+        self.banks
+            .get_new_latest_blockhash(&blockhash)
+            .await
+            .unwrap();
+    }
+
+    /// Signs the transaction by the payer and the `signers`, where the
+    /// payer is skipped in the `signers`.
+    async fn transaction(&mut self, ixs: &[Instruction], signers: &[&Keypair]) -> Transaction {
+        let blockhash = self.banks.get_latest_blockhash().await.unwrap();
+        let signers: Vec<_> = signers
+            .iter()
+            .filter(|signer| signer.pubkey() != self.payer.pubkey())
+            .copied()
+            .collect();
+        Transaction::new_signed_with_payer(
+            ixs,
+            Some(&self.payer.pubkey()),
+            &[&[&self.payer], signers.as_slice()].concat(),
+            blockhash,
+        )
+    }
+
+    async fn process(
+        &mut self,
+        ixs: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        let tx = self.transaction(ixs, signers).await;
+        self.banks
+            .process_transaction(tx)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    /// Migrates the `payer`'s multisig account in the legacy layout.
+    async fn migrate(&mut self, payer: &Keypair) -> Result<Pubkey, TransactionError> {
+        let (multisig, _) = Pubkey::find_program_address(
+            &[b"multisig", payer.pubkey().as_ref()],
+            &anchor_multisig2::ID,
+        );
+        let accounts = anchor_multisig2::accounts::Migrate {
+            payer: payer.pubkey(),
+            multisig,
+            system_program: system_program::ID,
+        };
+        let ix = Instruction {
+            program_id: anchor_multisig2::ID,
+            accounts: accounts.to_account_metas(None),
+            data: anchor_multisig2::instruction::Migrate {}.data(),
+        };
+        self.process(&[ix], &[payer]).await?;
+        Ok(multisig)
+    }
+
+    async fn fetch_transaction(&mut self, transaction: &Pubkey) -> MultisigTransaction {
+        let account = self.banks.get_account(*transaction).await.unwrap().unwrap();
+        MultisigTransaction::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+}
+
+/// Returns the custom program error of the `index`th instruction.
+fn custom_error(index: u8, error: Error) -> TransactionError {
+    TransactionError::InstructionError(
+        index,
+        InstructionError::Custom(ERROR_CODE_OFFSET + error as u32),
+    )
+}
+
+/// Legacy space of the multisig account in the baseline fixed array
+/// layout, e.g. `[Pubkey; 10]` queue and `[Pubkey; 11]` signers.
+const BASELINE_SPACE: usize = 684;
+
+/// Legacy space with the `payer` field.
+const LEGACY_SPACE: usize = BASELINE_SPACE + 32;
+
+/// Legacy space with the `payer` and the `owner_set_seqno` fields.
+const ARRAY_SPACE: usize = LEGACY_SPACE + 4;
+
+/// Returns the `payer`'s `m` of `signers` multisig account of `space`
+/// bytes in the fixed array layout, with a queued transaction.
+fn legacy_multisig(space: usize, payer: &Pubkey, m: u8, signers: &[Pubkey]) -> (Pubkey, Account) {
+    let (multisig, bump) =
+        Pubkey::find_program_address(&[b"multisig", payer.as_ref()], &anchor_multisig2::ID);
+    let mut txs = [Pubkey::default(); 10];
+    txs[0] = Pubkey::new_unique();
+    let mut padded = [Pubkey::default(); 11];
+    padded[..signers.len()].copy_from_slice(signers);
+
+    let mut data = Multisig::DISCRIMINATOR.to_vec();
+    if space != BASELINE_SPACE {
+        payer.serialize(&mut data).unwrap();
+    }
+    (bump, m, signers.len() as u8, 1u8, txs, padded)
+        .serialize(&mut data)
+        .unwrap();
+    if space == ARRAY_SPACE {
+        // The owner_set_seqno.
+        5u32.serialize(&mut data).unwrap();
+    }
+    assert_eq!(data.len(), space);
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: anchor_multisig2::ID,
+        ..Account::default()
+    };
+    (multisig, account)
+}
+
+fn keypairs(n: usize) -> Vec<Keypair> {
+    (0..n).map(|_| Keypair::new()).collect()
+}
+
+fn pubkeys(keypairs: &[Keypair]) -> Vec<Pubkey> {
+    keypairs.iter().map(Keypair::pubkey).collect()
+}
+
+#[tokio::test]
+async fn open_threshold_edge_cases() {
+    let mut env = Env::new().await;
+    let payer = env.payer.pubkey();
+
+    for (seed, m, signers, want) in [
+        // m == n, including the payer.
+        (1, 3, pubkeys(&keypairs(2)), Ok(())),
+        (2, 4, pubkeys(&keypairs(2)), Err(Error::ThresholdTooHigh)),
+        // The payer alone, or with itself, is not enough.
+        (3, 1, vec![], Err(Error::NotEnoughSigners)),
+        (4, 1, vec![payer, payer], Err(Error::NotEnoughSigners)),
+        // The duplicate signers are counted once.
+        (5, 2, vec![payer, Pubkey::new_unique()], Ok(())),
+        (
+            6,
+            3,
+            vec![payer, Pubkey::new_unique()],
+            Err(Error::ThresholdTooHigh),
+        ),
+        // Up to the capacity, including the payer.
+        (7, 2, pubkeys(&keypairs(MAX_SIGNERS - 1)), Ok(())),
+        (
+            8,
+            2,
+            pubkeys(&keypairs(MAX_SIGNERS)),
+            Err(Error::TooManySigners),
+        ),
+        // At least one approval.
+        (9, 0, pubkeys(&keypairs(2)), Err(Error::ThresholdTooLow)),
+    ] {
+        let got = env.open(seed, m, &signers).await.map(|_| ());
+        assert_eq!(got, want.map_err(|e| custom_error(0, e)), "seed={seed}");
+    }
+}
+
+#[tokio::test]
+async fn approve_m_equals_n() {
+    let mut env = Env::new().await;
+    let signers = keypairs(2);
+    let multisig = env.open(0, 3, &pubkeys(&signers)).await.unwrap();
+
+    let payer = Keypair::from_bytes(&env.payer.to_bytes()).unwrap();
+    let transaction = env.enqueue(&multisig, 3, &payer).await.unwrap();
+    for (signer, want) in signers.iter().zip([(2, false), (3, true)]) {
+        let got = env.approve(&multisig, &transaction, signer).await.unwrap();
+        assert_eq!((got.approvals, got.is_executable()), want);
+        assert_eq!(got.threshold, 3);
+    }
+    let tx = env.fetch_transaction(&transaction).await;
+    assert!(tx.signers.iter().all(|approved| *approved));
+}
+
+#[tokio::test]
+async fn approve_duplicate() {
+    let mut env = Env::new().await;
+    let signers = keypairs(2);
+    let multisig = env.open(0, 2, &pubkeys(&signers)).await.unwrap();
+
+    // The proposer approves on the enqueue.
+    let payer = Keypair::from_bytes(&env.payer.to_bytes()).unwrap();
+    let transaction = env.enqueue(&multisig, 3, &payer).await.unwrap();
+    let got = env.approve(&multisig, &transaction, &payer).await.unwrap();
+    assert_eq!((got.approvals, got.threshold), (1, 2));
+    assert!(!got.is_executable());
+
+    for _ in 0..2 {
+        let got = env
+            .approve(&multisig, &transaction, &signers[0])
+            .await
+            .unwrap();
+        assert_eq!((got.approvals, got.threshold), (2, 2));
+        assert!(got.is_executable());
+        env.next_blockhash().await;
+    }
+    let tx = env.fetch_transaction(&transaction).await;
+    assert_eq!(tx.signers.iter().filter(|approved| **approved).count(), 2);
+}
+
+#[tokio::test]
+async fn approve_by_non_signer() {
+    let mut env = Env::new().await;
+    let multisig = env.open(0, 2, &[Pubkey::new_unique()]).await.unwrap();
+
+    let other = Keypair::new();
+    let got = env.enqueue(&multisig, 2, &other).await;
+    assert_eq!(got, Err(custom_error(1, Error::InvalidSigner)));
+
+    let payer = Keypair::from_bytes(&env.payer.to_bytes()).unwrap();
+    let transaction = env.enqueue(&multisig, 2, &payer).await.unwrap();
+    let got = env.approve(&multisig, &transaction, &other).await;
+    assert_eq!(got.err(), Some(custom_error(0, Error::InvalidSigner)));
+    let tx = env.fetch_transaction(&transaction).await;
+    assert_eq!(tx.signers.iter().filter(|approved| **approved).count(), 1);
+}
+
+#[tokio::test]
+async fn enqueue_full_queue() {
+    let mut env = Env::new().await;
+    let signers = keypairs(1);
+    let multisig = env.open(0, 2, &pubkeys(&signers)).await.unwrap();
+
+    let mut transactions = vec![];
+    for _ in 0..MAX_TRANSACTIONS {
+        let transaction = env.enqueue(&multisig, 2, &signers[0]).await.unwrap();
+        transactions.push(transaction);
+    }
+    let got = env.enqueue(&multisig, 2, &signers[0]).await;
+    assert_eq!(got, Err(custom_error(1, Error::TransactionQueueFull)));
+
+    // The queued transactions are still approvable.
+    let payer = Keypair::from_bytes(&env.payer.to_bytes()).unwrap();
+    let last = transactions.last().unwrap();
+    let got = env.approve(&multisig, last, &payer).await.unwrap();
+    assert!(got.is_executable());
+}
+
+#[tokio::test]
+async fn approve_by_signers_at_max() {
+    let mut env = Env::new().await;
+    let signers = keypairs(MAX_SIGNERS - 1);
+    let m = MAX_SIGNERS as u8;
+    let multisig = env.open(0, m, &pubkeys(&signers)).await.unwrap();
+
+    let payer = Keypair::from_bytes(&env.payer.to_bytes()).unwrap();
+    let transaction = env.enqueue(&multisig, MAX_SIGNERS, &payer).await.unwrap();
+    for (i, signer) in signers.iter().enumerate() {
+        let got = env.approve(&multisig, &transaction, signer).await.unwrap();
+        assert_eq!(got.approvals as usize, i + 2);
+        assert_eq!(got.threshold, m);
+        assert_eq!(got.is_executable(), i + 2 == MAX_SIGNERS);
+    }
+}
+
+#[tokio::test]
+async fn migrate_legacy_layouts() {
+    let layouts = [BASELINE_SPACE, LEGACY_SPACE, ARRAY_SPACE];
+    let legacy_payers = keypairs(layouts.len());
+    let signers = keypairs(2);
+    let mut accounts = vec![];
+    for (space, legacy_payer) in layouts.iter().zip(&legacy_payers) {
+        let funded = Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        };
+        let mut owners = pubkeys(&signers);
+        owners.push(legacy_payer.pubkey());
+        accounts.push((legacy_payer.pubkey(), funded));
+        accounts.push(legacy_multisig(*space, &legacy_payer.pubkey(), 2, &owners));
+    }
+    let mut env = Env::with_accounts(accounts).await;
+
+    for (space, legacy_payer) in layouts.iter().zip(&legacy_payers) {
+        let multisig = env.migrate(legacy_payer).await.unwrap();
+        let account = env.banks.get_account(multisig).await.unwrap().unwrap();
+        let offset = Multisig::PAYER_OFFSET;
+        assert_eq!(
+            account.data[offset..offset + 32],
+            legacy_payer.pubkey().to_bytes(),
+            "space={space}"
+        );
+
+        // The legacy signers and the threshold carry over, without the
+        // queued transaction.
+        let transaction = env.enqueue(&multisig, 3, &signers[0]).await.unwrap();
+        let got = env
+            .approve(&multisig, &transaction, legacy_payer)
+            .await
+            .unwrap();
+        assert_eq!((got.approvals, got.threshold), (2, 2), "space={space}");
+        assert!(got.is_executable());
+
+        // Nothing to do for the migrated account.
+        let want = env.banks.get_account(multisig).await.unwrap().unwrap();
+        env.next_blockhash().await;
+        env.migrate(legacy_payer).await.unwrap();
+        let got = env.banks.get_account(multisig).await.unwrap().unwrap();
+        assert_eq!(got.data, want.data, "space={space}");
+    }
+}
@@ -28,7 +28,7 @@ describe("anchor-multisig2", () => {
   beforeEach(async () => {
     // Opens an account.
     await program.methods
-      .open(bump, threshold, signers)
+      .open(bump, new anchor.BN(0), threshold, signers)
       .accounts({ payer: payer.publicKey, multisig })
       .rpc();
   });
@@ -48,13 +48,18 @@ describe("anchor-multisig2", () => {
   it("Check the account initial state", async () => {
     // check the on-chain multisig account.
     const account = await program.account.multisig.fetch(multisig);
+    expect(account.payer).to.deep.equal(payer.publicKey);
     expect(account.bump).to.equal(bump);
     expect(account.m).to.equal(threshold);
     expect(account.n).to.equal(signers.length);
     expect(account.signers).to.include.deep.members(signers);
-    expect(account.signers).to.have.lengthOf(11);
+    expect(account.signers).to.have.lengthOf(signers.length);
     expect(account.txQueued).to.equal(0);
-    expect(account.txs).to.have.lengthOf(10);
+    expect(account.txs).to.have.lengthOf(0);
+    expect(account.ownerSetSeqno).to.equal(0);
+    expect(account.maxSigners).to.equal(11);
+    expect(account.maxTransactions).to.equal(10);
+    expect(account.seed.toNumber()).to.equal(0);
   });
 
   it("Creates a transaction", async () => {
@@ -84,5 +89,293 @@ describe("anchor-multisig2", () => {
       ],
       signers: [payer, txKeypair],
     });
+
+    // Filters the pending transactions by the multisig account.
+    const txs = await program.account.transaction.all([
+      { memcmp: { offset: 8, bytes: multisig.toBase58() } },
+    ]);
+    expect(txs.map((tx) => tx.publicKey)).to.deep.include(txKeypair.publicKey);
+  });
+
+  it("Expires the approvals on the signer set change", async () => {
+    // Queue the transaction instruction.
+    const ix = web3.SystemProgram.transfer({
+      fromPubkey: payer.publicKey,
+      toPubkey: signerA.publicKey,
+      lamports: 10,
+    });
+    const txKeypair = web3.Keypair.generate();
+    await program.rpc.enqueue(ix.programId, ix.keys, ix.data, {
+      accounts: {
+        payer: payer.publicKey,
+        multisig,
+        transaction: txKeypair.publicKey,
+      },
+      instructions: [
+        web3.SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          lamports: web3.LAMPORTS_PER_SOL,
+          newAccountPubkey: txKeypair.publicKey,
+          programId: program.programId,
+          space: 300,
+        }),
+      ],
+      signers: [payer, txKeypair],
+    });
+
+    // Rotates the signerB out.
+    const signerC = web3.Keypair.generate();
+    await program.methods
+      .setSigners(threshold, [signerA.publicKey, signerC.publicKey])
+      .accounts({ payer: payer.publicKey, multisig })
+      .rpc();
+
+    const account = await program.account.multisig.fetch(multisig);
+    expect(account.ownerSetSeqno).to.equal(1);
+    expect(account.txQueued).to.equal(0);
+    expect(account.signers).to.include.deep.members([signerC.publicKey]);
+    expect(account.signers).to.not.include.deep.members([signerB.publicKey]);
+
+    // The old transaction can't be approved anymore.
+    try {
+      await program.methods
+        .approve()
+        .accounts({
+          payer: signerA.publicKey,
+          multisig,
+          transaction: txKeypair.publicKey,
+        })
+        .signers([signerA])
+        .rpc();
+      expect.fail("stale transaction should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("InvalidTransaction");
+    }
+  });
+
+  it("Uploads the large transaction data in chunks", async () => {
+    // Large enough data not to fit in a single transaction.
+    const data = Buffer.alloc(3000, 0xab);
+    const chunkSize = 800;
+    const txKeypair = web3.Keypair.generate();
+    await program.methods
+      .enqueueBegin(web3.SystemProgram.programId, [])
+      .accounts({
+        payer: payer.publicKey,
+        multisig,
+        transaction: txKeypair.publicKey,
+      })
+      .preInstructions([
+        web3.SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          lamports: web3.LAMPORTS_PER_SOL,
+          newAccountPubkey: txKeypair.publicKey,
+          programId: program.programId,
+          space: 4000,
+        }),
+      ])
+      .signers([txKeypair])
+      .rpc();
+
+    // It's not approvable during the upload.
+    try {
+      await program.methods
+        .approve()
+        .accounts({
+          payer: signerA.publicKey,
+          multisig,
+          transaction: txKeypair.publicKey,
+        })
+        .signers([signerA])
+        .rpc();
+      expect.fail("pending transaction should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("InvalidTransaction");
+    }
+
+    const accounts = {
+      payer: payer.publicKey,
+      multisig,
+      transaction: txKeypair.publicKey,
+    };
+    for (let i = 0; i < data.length; i += chunkSize) {
+      await program.methods
+        .enqueueAppend(data.subarray(i, i + chunkSize))
+        .accounts(accounts)
+        .rpc();
+    }
+    await program.methods.enqueueCommit().accounts(accounts).rpc();
+
+    const tx = await program.account.transaction.fetch(txKeypair.publicKey);
+    expect(tx.data).to.deep.equal(data);
+    expect(tx.pending).to.be.false;
+    const account = await program.account.multisig.fetch(multisig);
+    expect(account.txQueued).to.equal(1);
+    expect(account.txs[0]).to.deep.equal(txKeypair.publicKey);
+
+    // No more data after the commit.
+    try {
+      await program.methods
+        .enqueueAppend(Buffer.from([0]))
+        .accounts(accounts)
+        .rpc();
+      expect.fail("committed transaction should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("TransactionNotPending");
+    }
+  });
+
+  it("Returns the approval count from approve", async () => {
+    const ix = web3.SystemProgram.transfer({
+      fromPubkey: payer.publicKey,
+      toPubkey: signerA.publicKey,
+      lamports: 10,
+    });
+    const txKeypair = web3.Keypair.generate();
+    await program.rpc.enqueue(ix.programId, ix.keys, ix.data, {
+      accounts: {
+        payer: payer.publicKey,
+        multisig,
+        transaction: txKeypair.publicKey,
+      },
+      instructions: [
+        web3.SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          lamports: web3.LAMPORTS_PER_SOL,
+          newAccountPubkey: txKeypair.publicKey,
+          programId: program.programId,
+          space: 300,
+        }),
+      ],
+      signers: [payer, txKeypair],
+    });
+
+    // The approval status is in the return data, which is
+    // logged as `Program return: <program id> <base64 data>`.
+    const sim = await program.methods
+      .approve()
+      .accounts({
+        payer: signerA.publicKey,
+        multisig,
+        transaction: txKeypair.publicKey,
+      })
+      .signers([signerA])
+      .simulate();
+    const prefix = `Program return: ${program.programId} `;
+    const log = sim.raw.find((log) => log.startsWith(prefix));
+    const status = Buffer.from(log.slice(prefix.length), "base64");
+    expect(status[0]).to.equal(1); // approvals
+    expect(status[1]).to.equal(threshold);
+  });
+
+  it("Dequeues the transaction", async () => {
+    const ix = web3.SystemProgram.transfer({
+      fromPubkey: payer.publicKey,
+      toPubkey: signerA.publicKey,
+      lamports: 10,
+    });
+    const txKeypair = web3.Keypair.generate();
+    await program.rpc.enqueue(ix.programId, ix.keys, ix.data, {
+      accounts: {
+        payer: payer.publicKey,
+        multisig,
+        transaction: txKeypair.publicKey,
+      },
+      instructions: [
+        web3.SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          lamports: web3.LAMPORTS_PER_SOL,
+          newAccountPubkey: txKeypair.publicKey,
+          programId: program.programId,
+          space: 300,
+        }),
+      ],
+      signers: [payer, txKeypair],
+    });
+    const before = await program.account.multisig.fetch(multisig);
+
+    await program.methods
+      .dequeue()
+      .accounts({
+        payer: payer.publicKey,
+        multisig,
+        transaction: txKeypair.publicKey,
+      })
+      .rpc();
+
+    const after = await program.account.multisig.fetch(multisig);
+    expect(after.txQueued).to.equal(before.txQueued - 1);
+    expect(after.txs).to.eql(
+      before.txs.filter((tx) => !tx.equals(txKeypair.publicKey))
+    );
+  });
+
+  it("Grows the capacity", async () => {
+    const before = await provider.connection.getAccountInfo(multisig);
+    await program.methods
+      .growCapacity(20, 32)
+      .accounts({ payer: payer.publicKey, multisig })
+      .rpc();
+
+    const after = await provider.connection.getAccountInfo(multisig);
+    expect(after.data.length).to.be.greaterThan(before.data.length);
+    const account = await program.account.multisig.fetch(multisig);
+    expect(account.maxSigners).to.equal(20);
+    expect(account.maxTransactions).to.equal(32);
+
+    // More signers than the default capacity.
+    const more = [...Array(15)].map(() => web3.Keypair.generate().publicKey);
+    await program.methods
+      .setSigners(threshold, more)
+      .accounts({ payer: payer.publicKey, multisig })
+      .rpc();
+    const got = await program.account.multisig.fetch(multisig);
+    expect(got.n).to.equal(more.length + 1);
+
+    // It can't be shrunk.
+    try {
+      await program.methods
+        .growCapacity(11, 10)
+        .accounts({ payer: payer.publicKey, multisig })
+        .rpc();
+      expect.fail("capacity should not be shrunk");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("InvalidCapacity");
+    }
+  });
+
+  it("Opens another multisig with the seed", async () => {
+    const seed = new anchor.BN(7);
+    const [another, anotherBump] = web3.PublicKey.findProgramAddressSync(
+      [
+        anchor.utils.bytes.utf8.encode("multisig"),
+        payer.publicKey.toBuffer(),
+        seed.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    expect(another).to.not.deep.equal(multisig);
+
+    await program.methods
+      .open(anotherBump, seed, threshold, [signerA.publicKey])
+      .accounts({ payer: payer.publicKey, multisig: another })
+      .rpc();
+    const account = await program.account.multisig.fetch(another);
+    expect(account.seed.toNumber()).to.equal(7);
+    expect(account.n).to.equal(2);
+
+    // Both multisig accounts are independent.
+    await program.methods
+      .setSigners(threshold, signers)
+      .accounts({ payer: payer.publicKey, multisig: another })
+      .rpc();
+    const first = await program.account.multisig.fetch(multisig);
+    expect(first.ownerSetSeqno).to.equal(0);
+
+    await program.methods
+      .close()
+      .accounts({ payer: payer.publicKey, multisig: another })
+      .rpc();
+    expect(await provider.connection.getAccountInfo(another)).to.be.null;
   });
 });
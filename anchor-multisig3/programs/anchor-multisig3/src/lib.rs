@@ -1,11 +1,27 @@
 //! A native SOL multisig wallet program.
+//!
+//! The other programs can deposit lamports to the multisig fund
+//! through the [`anchor_multisig3::deposit`] instruction, with the
+//! `cpi` feature of this crate, e.g.
+//!
+//! ```ignore
+//! let accounts = anchor_multisig3::cpi::accounts::Deposit {
+//!     depositor: ctx.accounts.payer.to_account_info(),
+//!     state: ctx.accounts.multisig_state.to_account_info(),
+//!     fund: ctx.accounts.multisig_fund.to_account_info(),
+//!     system_program: ctx.accounts.system_program.to_account_info(),
+//! };
+//! let program = ctx.accounts.multisig_program.to_account_info();
+//! anchor_multisig3::cpi::deposit(CpiContext::new(program, accounts), lamports)?;
+//! ```
 
 use std::collections::{HashMap, HashSet};
 use std::ops::DerefMut;
 
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::program::{invoke, invoke_signed};
-use anchor_lang::solana_program::system_instruction;
+use anchor_lang::solana_program::sysvar::instructions::load_instruction_at_checked;
+use anchor_lang::solana_program::{ed25519_program, hash, system_instruction};
 
 declare_id!("3LuouAGwBeueVADEviTaKLsgwkrinvfXKCNKPWcmbAQX");
 
@@ -49,6 +65,33 @@ pub enum Error {
 
     #[msg("There is not enough fund remains")]
     NotEnoughFund,
+
+    #[msg("Invalid ed25519 signature verification instruction")]
+    InvalidSignatureInstruction,
+
+    #[msg("Multisig account closure is not approved by m signers")]
+    CloseNotApproved,
+
+    #[msg("The recipient should not be the multisig fund account")]
+    RecipientIsFund,
+
+    #[msg("The recipient should not be the multisig state account")]
+    RecipientIsState,
+
+    #[msg("The same transfer is already queued")]
+    DuplicateTransfer,
+
+    #[msg("The multisig account exists with the different parameters")]
+    StateMismatch,
+
+    #[msg("Missing or out of order transfer AccountInfo")]
+    MissingTransferAccountInfo,
+
+    #[msg("Threshold should be at least one")]
+    ThresholdTooLow,
+
+    #[msg("The close vote is for the stale close proposal")]
+    StaleCloseVote,
 }
 
 /// A multisig state PDA account.
@@ -74,6 +117,44 @@ pub struct State {
 
     /// An array of the pending transactions.
     pub queue: Vec<Pubkey>,
+
+    /// Cumulative lamports disbursed to each recipient, up to
+    /// [`State::MAX_RECIPIENTS`] recipients.
+    pub disbursed: Vec<Disbursed>,
+
+    /// Cumulative lamports disbursed to the recipients not tracked
+    /// in `disbursed`.
+    pub other_disbursed: u64,
+
+    /// A close proposal sequence number each signer approved, e.g.
+    /// only the votes for the current `close_seqno` count.
+    pub close_votes: Vec<u64>,
+
+    /// Digests of the queued transfers, e.g. [`Transfer::digest`],
+    /// in the same order as `queue`.
+    pub queue_digests: Vec<[u8; 8]>,
+
+    /// Allows the same transfer, e.g. the same recipient and the
+    /// lamports, to be queued more than once.
+    pub allow_duplicates: bool,
+
+    /// A nonce of the [`State::approval_message`], bumped on every
+    /// transfer execution.
+    pub nonce: u64,
+
+    /// A current close proposal sequence number, bumped on the signer
+    /// changes.
+    pub close_seqno: u64,
+}
+
+/// Cumulative lamports disbursed to the recipient.
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct Disbursed {
+    /// A recipient of the transfers.
+    pub recipient: Pubkey,
+
+    /// Total lamports transferred to the recipient.
+    pub lamports: u64,
 }
 
 impl State {
@@ -89,10 +170,33 @@ impl State {
     /// A maximum transaction queue.
     const MAX_QUEUE: u8 = u8::MAX;
 
+    /// A maximum recipients tracked in the disbursement report.
+    const MAX_RECIPIENTS: usize = 16;
+
     fn space(signers: &[Pubkey], q: u8) -> usize {
         let n = Self::valid_n(signers.len() as u8) as usize;
         let q = Self::valid_q(q) as usize;
-        8 + 1 + 4 + 32 * n + 4 + n + 32 + 8 + 1 + 4 + 32 * q
+        let r = Self::MAX_RECIPIENTS;
+        8 + 1
+            + 4
+            + 32 * n
+            + 4
+            + n
+            + 32
+            + 8
+            + 1
+            + 4
+            + 32 * q
+            + 4
+            + (32 + 8) * r
+            + 8
+            + 4
+            + 8 * n
+            + 4
+            + 8 * q
+            + 1
+            + 8
+            + 8
     }
 
     /// Returns the valid n, number of signers.
@@ -124,6 +228,62 @@ impl State {
         self.signed.iter().any(|signed| *signed)
     }
 
+    /// Checks if the multisig account can be closed by the `funder`.
+    ///
+    /// It requires m approvals of the close proposal, unless the
+    /// funder is the only signer of the multisig account.
+    pub fn is_closable(&self, funder: &Pubkey) -> bool {
+        if self.signers.iter().all(|signer| signer == funder) {
+            return true;
+        }
+        let signed = self
+            .close_votes
+            .iter()
+            .filter(|&seqno| *seqno == self.close_seqno)
+            .count();
+        signed >= self.m as usize
+    }
+
+    /// Returns the message to be signed by the signers for the
+    /// [`anchor_multisig3::approve_bundle`] instruction.
+    ///
+    /// It's the state account address followed by the hash of the
+    /// nonce and the current transfer queue, e.g. the transfer address
+    /// and its [`Transfer::digest`], so that the signature only approves
+    /// the transfers the signer had seen.  The nonce is bumped on every
+    /// execution, so that the signature can't be replayed against the
+    /// transfer re-created on the same address.
+    pub fn approval_message(&self, state_key: &Pubkey) -> [u8; 64] {
+        let nonce = self.nonce.to_le_bytes();
+        let mut queue: Vec<&[u8]> = vec![&nonce];
+        for (transfer, digest) in self.queue.iter().zip(&self.queue_digests) {
+            queue.push(transfer.as_ref());
+            queue.push(digest);
+        }
+        let mut message = [0u8; 64];
+        message[..32].copy_from_slice(state_key.as_ref());
+        message[32..].copy_from_slice(hash::hashv(&queue).as_ref());
+        message
+    }
+
+    /// Records the lamports disbursed to the recipient.
+    ///
+    /// The recipients beyond [`State::MAX_RECIPIENTS`] are accounted
+    /// in `other_disbursed`.
+    fn record_disbursed(&mut self, recipient: &Pubkey, lamports: u64) {
+        let disbursed = &mut self.disbursed;
+        if let Some(entry) = disbursed.iter_mut().find(|d| d.recipient == *recipient) {
+            entry.lamports = entry.lamports.saturating_add(lamports);
+        } else if disbursed.len() < Self::MAX_RECIPIENTS {
+            disbursed.push(Disbursed {
+                recipient: *recipient,
+                lamports,
+            });
+        } else {
+            self.other_disbursed = self.other_disbursed.saturating_add(lamports);
+        }
+    }
+
     /// Validates the multisig queue.
     pub fn validate_queue(&self) -> Result<()> {
         require!(!self.is_full(), Error::AccountFull);
@@ -153,21 +313,83 @@ impl State {
         Ok(())
     }
 
-    /// Creates a fund account.
-    fn create_fund_account<'info>(
+    /// Initializes the multisig state account.
+    ///
+    /// The close proposal sequence starts from the current slot, so
+    /// that the close votes for the account closed before won't count
+    /// on the one re-created on the same address.
+    fn initialize(&mut self, m: u8, signers: Vec<Pubkey>, q: u8, fund: Pubkey) -> Result<()> {
+        // Checks the uniqueness of signer's address.
+        let signers: HashSet<_> = signers.into_iter().collect();
+        require_gte!(signers.len(), Self::MIN_SIGNERS as usize, Error::NoSigners,);
+        require_gte!(
+            Self::MAX_SIGNERS as usize,
+            signers.len(),
+            Error::TooManySigners
+        );
+
+        let threshold = m as usize;
+        require_gte!(threshold, 1, Error::ThresholdTooLow);
+        require_gte!(signers.len(), threshold, Error::ThresholdTooHigh);
+
+        self.m = m;
+        self.signers = signers.into_iter().collect();
+        self.signed = vec![false; self.signers.len()];
+        self.fund = fund;
+        self.balance = 0;
+        self.q = Self::valid_q(q);
+        self.disbursed = Vec::new();
+        self.other_disbursed = 0;
+        self.close_seqno = Clock::get()?.slot.max(1);
+        self.close_votes = vec![0; self.signers.len()];
+        self.queue_digests = Vec::new();
+        self.allow_duplicates = false;
+        self.nonce = 0;
+
+        Ok(())
+    }
+
+    /// Checks if the state is created with the parameters.
+    fn is_created_with(&self, m: u8, signers: &[Pubkey], q: u8) -> bool {
+        let signers: HashSet<_> = signers.iter().collect();
+        self.m == m
+            && self.q == Self::valid_q(q)
+            && self.signers.len() == signers.len()
+            && self.signers.iter().all(|signer| signers.contains(signer))
+    }
+
+    /// Creates a fund account, unless it's already created.
+    ///
+    /// Anyone can send lamports to the fund PDA before the creation,
+    /// which makes `create_account` fail forever.  In that case, it
+    /// tops up the rent and assigns the account to the program instead.
+    fn ensure_fund_account<'info>(
         state: &Account<'info, Self>,
         fund: &UncheckedAccount<'info>,
         funder: &Signer<'info>,
         bump: u8,
     ) -> Result<()> {
-        let lamports = Rent::get()?.minimum_balance(0);
-        let ix = system_instruction::create_account(&funder.key(), &fund.key(), lamports, 0, &id());
+        if fund.owner == &id() {
+            return Ok(());
+        }
+        let rent = Rent::get()?.minimum_balance(0);
         let state_key = state.key();
         let accounts = [funder.to_account_info(), fund.to_account_info()];
         let seed = [b"fund", state_key.as_ref(), &[bump]];
 
         // CPI.
-        invoke_signed(&ix, &accounts, &[&seed])?;
+        if fund.lamports() == 0 {
+            let ix = system_instruction::create_account(&funder.key(), &fund.key(), rent, 0, &id());
+            invoke_signed(&ix, &accounts, &[&seed])?;
+            return Ok(());
+        }
+        let lamports = rent.saturating_sub(fund.lamports());
+        if lamports > 0 {
+            let ix = system_instruction::transfer(&funder.key(), &fund.key(), lamports);
+            invoke(&ix, &accounts)?;
+        }
+        let ix = system_instruction::assign(&fund.key(), &id());
+        invoke_signed(&ix, &[fund.to_account_info()], &[&seed])?;
 
         Ok(())
     }
@@ -203,6 +425,88 @@ impl State {
 
         Ok(())
     }
+
+    /// Executes the queued transfers in case m approvals are met.
+    ///
+    /// The transfer and the recipient accounts should be provided
+    /// through the `remaining_accounts`.
+    fn execute_queue<'a, 'b>(
+        state: &mut Account<'a, Self>,
+        fund: &UncheckedAccount<'a>,
+        remaining_accounts: &[AccountInfo<'b>],
+        fund_bump: u8,
+    ) -> Result<()> {
+        let remaining_accounts: HashMap<_, _> = remaining_accounts
+            .iter()
+            .map(|account| (account.key, account))
+            .collect();
+
+        // Checks the threshold.
+        let signed = state.signed.iter().filter(|&signed| *signed).count() as u8;
+        if signed < state.m {
+            return Ok(());
+        }
+
+        // Finds out the executable transactions.
+        let mut executable = Vec::new();
+        let mut remaining = Vec::new();
+        let mut remaining_digests = Vec::new();
+        for (transfer_addr, digest) in state.queue.iter().zip(&state.queue_digests) {
+            let transfer_info = match remaining_accounts.get(transfer_addr) {
+                Some(transfer) => transfer,
+                None => {
+                    remaining.push(*transfer_addr);
+                    remaining_digests.push(*digest);
+                    continue;
+                }
+            };
+            let mut ref_data = transfer_info.try_borrow_mut_data()?;
+            let mut transfer_data: &[u8] = ref_data.deref_mut();
+            let tx = Transfer::try_deserialize(&mut transfer_data)?;
+            let to = match remaining_accounts.get(&tx.recipient) {
+                None => return Err(Error::MissingRecipientAccountInfo.into()),
+                Some(recipient) => recipient,
+            };
+            executable.push((transfer_info, to, tx));
+        }
+
+        // There is no executable account info.  Just returns the success.
+        //
+        // This is a case that the approver approved the multisig but didn't
+        // provide the account info.
+        if executable.is_empty() {
+            return Ok(());
+        }
+
+        // Executes the queued transfers.
+        let fund = fund.to_account_info();
+        for (transfer, to, tx) in executable {
+            // Fund to the recipient and closes the transfer account.
+            State::transfer_fund(&state, &fund, &to, tx.lamports, fund_bump)?;
+            state.record_disbursed(to.key, tx.lamports);
+            emit!(TransferExecuted {
+                state: state.key(),
+                transfer: transfer.key(),
+                recipient: tx.recipient,
+                lamports: tx.lamports,
+                content_hash: tx.content_hash,
+            });
+            let lamports = transfer.lamports();
+            State::transfer_fund(&state, &transfer, &fund, lamports, fund_bump)?;
+        }
+
+        // Update the queue and invalidates the approval messages.
+        state.queue = remaining;
+        state.queue_digests = remaining_digests;
+        state.nonce = state.nonce.wrapping_add(1);
+
+        // Reset the signed status once the queue is empty.
+        if State::is_empty(&state) {
+            state.signed.iter_mut().for_each(|signed| *signed = false);
+        }
+
+        Ok(())
+    }
 }
 
 /// A transfer transaction queued under the State account.
@@ -217,10 +521,96 @@ pub struct Transfer {
 
     /// A lamports to transfer.
     lamports: u64,
+
+    /// A hash of the off-chain proposal document, e.g. SHA-256,
+    /// so that the signers can verify they approve the same
+    /// document referenced off-chain.
+    content_hash: [u8; 32],
 }
 
 impl Transfer {
-    const SPACE: usize = 8 + 32 + 32 + 8;
+    const SPACE: usize = 8 + 32 + 32 + 8 + 32;
+
+    /// Returns the digest of the `recipient` and the `lamports` to
+    /// detect the duplicate transfers.
+    fn digest(recipient: &Pubkey, lamports: u64) -> [u8; 8] {
+        let hash = hash::hashv(&[recipient.as_ref(), &lamports.to_le_bytes()]);
+        let mut digest = [0u8; 8];
+        digest.copy_from_slice(&hash.as_ref()[..8]);
+        digest
+    }
+}
+
+/// A queued transfer returned by the [`anchor_multisig3::get_queue`]
+/// instruction.
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct QueuedTransfer {
+    /// A transfer account.
+    pub transfer: Pubkey,
+
+    /// A creator of the transfer.
+    pub creator: Pubkey,
+
+    /// A recipient of the transfer.
+    pub recipient: Pubkey,
+
+    /// A lamports to transfer.
+    pub lamports: u64,
+}
+
+/// A page of the transfer queue returned by the
+/// [`anchor_multisig3::get_queue`] instruction.
+///
+/// The approvals are for the entire queue, as the queued transfers
+/// are executed together once `m` signers approve.
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct QueueStatus {
+    /// Number of the signers approved the queue.
+    pub approvals: u8,
+
+    /// A threshold.
+    pub m: u8,
+
+    /// Number of the queued transfers.
+    pub queued: u8,
+
+    /// Queued transfers, starting from the requested offset.
+    pub transfers: Vec<QueuedTransfer>,
+}
+
+impl QueueStatus {
+    /// A maximum transfers in a page, to fit in the 1024 bytes
+    /// return data.
+    pub const MAX_TRANSFERS: usize = 9;
+}
+
+/// An event emitted by the [`anchor_multisig3::create_transfer`] instruction.
+#[event]
+pub struct TransferCreated {
+    pub state: Pubkey,
+    pub transfer: Pubkey,
+    pub creator: Pubkey,
+    pub recipient: Pubkey,
+    pub lamports: u64,
+    pub content_hash: [u8; 32],
+}
+
+/// An event emitted by the [`anchor_multisig3::deposit`] instruction.
+#[event]
+pub struct Deposited {
+    pub state: Pubkey,
+    pub depositor: Pubkey,
+    pub lamports: u64,
+}
+
+/// An event emitted on the queued transfer execution.
+#[event]
+pub struct TransferExecuted {
+    pub state: Pubkey,
+    pub transfer: Pubkey,
+    pub recipient: Pubkey,
+    pub lamports: u64,
+    pub content_hash: [u8; 32],
 }
 
 #[derive(Accounts)]
@@ -250,6 +640,56 @@ pub struct Create<'info> {
     pub system_program: Program<'info, System>,
 }
 
+/// Creates the multisig account, if it's not yet.
+#[derive(Accounts)]
+#[instruction(m: u8, signers: Vec<Pubkey>, q: u8, state_bump: u8, fund_bump: u8)]
+pub struct EnsureCreated<'info> {
+    /// A funder of the multisig account.
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    /// A multisig state PDA account.
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = State::space(&signers, q),
+        seeds = [b"state", funder.key.as_ref()],
+        bump,
+    )]
+    pub state: Account<'info, State>,
+
+    /// A multisig fund account.
+    ///
+    /// CHECK: Checked by the handler.
+    #[account(mut, seeds = [b"fund", state.key().as_ref()], bump = fund_bump)]
+    pub fund: UncheckedAccount<'info>,
+
+    /// The system program to create a multisig PDA accounts.
+    pub system_program: Program<'info, System>,
+}
+
+/// Repairs the half-initialized multisig account.
+#[derive(Accounts)]
+#[instruction(state_bump: u8, fund_bump: u8)]
+pub struct Repair<'info> {
+    /// An original funder of the multisig account.
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    /// A multisig state PDA account.
+    #[account(mut, seeds = [b"state", funder.key.as_ref()], bump = state_bump)]
+    pub state: Box<Account<'info, State>>,
+
+    /// A multisig fund account.
+    ///
+    /// CHECK: Checked by the handler.
+    #[account(mut, seeds = [b"fund", state.key().as_ref()], bump = fund_bump)]
+    pub fund: UncheckedAccount<'info>,
+
+    /// The system program to create a multisig fund account.
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 #[instruction(lamports: u64, state_bump: u8, fund_bump: u8)]
 pub struct Fund<'info> {
@@ -273,6 +713,31 @@ pub struct Fund<'info> {
     pub system_program: Program<'info, System>,
 }
 
+/// Deposits lamports to the multisig fund account.
+///
+/// Unlike [`Fund`], the state account is not derived from the
+/// depositor, so that anyone, e.g. the other programs through CPI,
+/// can deposit to the multisig fund.
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    /// A depositor of the lamports.
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// A multisig state PDA account.
+    #[account(mut, has_one = fund @ Error::InvalidFundAddress)]
+    pub state: Box<Account<'info, State>>,
+
+    /// A multisig fund PDA account.
+    ///
+    /// CHECK: Checked by the `has_one` constraint of the state.
+    #[account(mut)]
+    pub fund: UncheckedAccount<'info>,
+
+    /// The system program to make the transfer of the deposit.
+    pub system_program: Program<'info, System>,
+}
+
 /// Create and queue the new transfer under the multisig account.
 #[derive(Accounts)]
 #[instruction(recipient: Pubkey, lamports: u64, fund_bump: u8)]
@@ -332,6 +797,74 @@ pub struct Approve<'info> {
     pub system_program: Program<'info, System>,
 }
 
+/// Configures the multisig account.
+#[derive(Accounts)]
+#[instruction(state_bump: u8)]
+pub struct Configure<'info> {
+    /// An original funder of the multisig account.
+    pub funder: Signer<'info>,
+
+    /// A multisig state PDA account.
+    #[account(mut, seeds = [b"state", funder.key.as_ref()], bump = state_bump)]
+    pub state: Box<Account<'info, State>>,
+}
+
+/// Approves the close proposal of the multisig account.
+///
+/// The multisig account can be closed by the funder once m signers
+/// approve the proposal.
+#[derive(Accounts)]
+pub struct ApproveClose<'info> {
+    /// An approver of the close proposal.
+    pub signer: Signer<'info>,
+
+    /// A multisig state PDA account.
+    #[account(mut)]
+    pub state: Box<Account<'info, State>>,
+}
+
+/// Approves the multisig account with the pre-signed ed25519 signatures.
+///
+/// The signatures of the [`State::approval_message`] should be verified by
+/// the ed25519 program instructions in the same transaction.
+#[derive(Accounts)]
+#[instruction(fund_bump: u8)]
+pub struct ApproveBundle<'info> {
+    /// A submitter of the signature bundle, not necessary one of the
+    /// multisig signers.
+    #[account(mut)]
+    pub submitter: Signer<'info>,
+
+    /// A multisig state PDA account.
+    #[account(mut)]
+    pub state: Box<Account<'info, State>>,
+
+    /// A multisig fund account.
+    ///
+    /// CHECK: Checked by the handler.
+    #[account(mut, seeds = [b"fund", state.key().as_ref()], bump = fund_bump)]
+    pub fund: UncheckedAccount<'info>,
+
+    /// The instructions sysvar to introspect the ed25519 program instructions.
+    ///
+    /// CHECK: Checked by the address constraint.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    /// The system program to create a transfer account.
+    pub system_program: Program<'info, System>,
+}
+
+/// Reads the transfer queue of the multisig account.
+///
+/// The transfer accounts should be given as the remaining accounts
+/// in the queue order, starting from the requested offset.
+#[derive(Accounts)]
+pub struct GetQueue<'info> {
+    /// A multisig state PDA account.
+    pub state: Box<Account<'info, State>>,
+}
+
 #[derive(Accounts)]
 #[instruction(state_bump: u8, fund_bump: u8)]
 pub struct Close<'info> {
@@ -376,28 +909,72 @@ pub mod anchor_multisig3 {
         // Validate the multisig fund account.
         State::validate_fund(&state, &fund, fund_bump)?;
 
-        // Checks the uniqueness of signer's address.
-        let signers: HashSet<_> = signers.into_iter().collect();
-        require_gte!(signers.len(), State::MIN_SIGNERS as usize, Error::NoSigners,);
-        require_gte!(
-            State::MAX_SIGNERS as usize,
-            signers.len(),
-            Error::TooManySigners
-        );
-
-        let threshold = m as usize;
-        require_gte!(signers.len(), threshold, Error::ThresholdTooHigh);
-
         // Creates a fund account.
-        State::create_fund_account(&state, &fund, &funder, fund_bump)?;
+        State::ensure_fund_account(&state, &fund, &funder, fund_bump)?;
 
         // Initializes the multisig state account.
-        state.m = m;
-        state.signers = signers.into_iter().collect();
-        state.signed = vec![false; state.signers.len()];
-        state.fund = fund.key();
-        state.balance = 0;
-        state.q = State::valid_q(q);
+        state.initialize(m, signers, q, fund.key())
+    }
+
+    /// Creates the multisig account, or succeeds without any change
+    /// in case it's already created with the same parameters, e.g.
+    /// the client retry of [`anchor_multisig3::create`].
+    pub fn ensure_created(
+        ctx: Context<EnsureCreated>,
+        m: u8,
+        signers: Vec<Pubkey>,
+        q: u8,
+        _state_bump: u8,
+        fund_bump: u8,
+    ) -> Result<()> {
+        let funder = &mut ctx.accounts.funder;
+        let state = &mut ctx.accounts.state;
+        let fund = &mut ctx.accounts.fund;
+
+        // Validate the multisig fund account.
+        State::validate_fund(&state, &fund, fund_bump)?;
+
+        // Creates a fund account, if it's not yet.
+        State::ensure_fund_account(&state, &fund, &funder, fund_bump)?;
+
+        // The newly created state account doesn't have any signer.
+        if state.signers.is_empty() {
+            return state.initialize(m, signers, q, fund.key());
+        }
+        require!(state.is_created_with(m, &signers, q), Error::StateMismatch);
+
+        Ok(())
+    }
+
+    /// Repairs the half-initialized multisig account.
+    ///
+    /// It creates the missing fund account and resets the approval
+    /// states not in line with the signers.
+    pub fn repair(ctx: Context<Repair>, _state_bump: u8, fund_bump: u8) -> Result<()> {
+        let funder = &ctx.accounts.funder;
+        let state = &mut ctx.accounts.state;
+        let fund = &ctx.accounts.fund;
+
+        // Validate the multisig fund account.
+        State::validate_fund(&state, &fund, fund_bump)?;
+        require!(!state.signers.is_empty(), Error::NoSigners);
+        if state.fund == Pubkey::default() {
+            state.fund = fund.key();
+        }
+        require_keys_eq!(state.fund, fund.key(), Error::InvalidFundAddress);
+
+        // Creates a fund account, if it's not yet.
+        State::ensure_fund_account(&state, &fund, &funder, fund_bump)?;
+
+        // Resets the approvals in case of the length mismatch.
+        let n = state.signers.len();
+        if state.signed.len() != n {
+            state.signed = vec![false; n];
+        }
+        if state.close_votes.len() != n {
+            state.close_votes = vec![0; n];
+            state.close_seqno = state.close_seqno.wrapping_add(1);
+        }
 
         Ok(())
     }
@@ -424,15 +1001,45 @@ pub mod anchor_multisig3 {
         Ok(())
     }
 
+    /// Deposits lamports to the multisig account.
+    ///
+    /// It's open to anyone, e.g. the other programs through CPI, to
+    /// pay the proceeds directly into the multisig fund.
+    pub fn deposit(ctx: Context<Deposit>, lamports: u64) -> Result<()> {
+        let depositor = &ctx.accounts.depositor;
+        let state = &mut ctx.accounts.state;
+        let fund = &ctx.accounts.fund;
+
+        // CPI to transfer the deposit to the multisig fund account.
+        let ix = system_instruction::transfer(&depositor.key(), &fund.key(), lamports);
+        let accounts = [depositor.to_account_info(), fund.to_account_info()];
+        invoke(&ix, &accounts)?;
+
+        // Update the balance.
+        state.balance += lamports;
+
+        emit!(Deposited {
+            state: state.key(),
+            depositor: depositor.key(),
+            lamports,
+        });
+
+        Ok(())
+    }
+
     /// Creates a queued transfer lamports to the recipient.
     ///
     /// Transfer account creation fee will be given back to the
     /// creator of the transfer from the multisig fund.
+    ///
+    /// The `content_hash` anchors the off-chain proposal document
+    /// to the transfer.  It's all zero if there is no such document.
     pub fn create_transfer(
         ctx: Context<CreateTransfer>,
         recipient: Pubkey,
         lamports: u64,
         fund_bump: u8,
+        content_hash: [u8; 32],
     ) -> Result<()> {
         let creator = &ctx.accounts.creator;
         let state = &mut ctx.accounts.state;
@@ -453,6 +1060,16 @@ pub mod anchor_multisig3 {
         // Check the current transfer queue.
         state.validate_queue()?;
 
+        // Checks the recipient as well as the duplicate transfer,
+        // which double-debits the balance by accident.
+        require_keys_neq!(recipient, fund.key(), Error::RecipientIsFund);
+        require_keys_neq!(recipient, state.key(), Error::RecipientIsState);
+        let digest = Transfer::digest(&recipient, lamports);
+        require!(
+            state.allow_duplicates || !state.queue_digests.contains(&digest),
+            Error::DuplicateTransfer
+        );
+
         // Checks the multisig fund balance.
         require_gte!(state.balance, lamports, Error::NotEnoughFund);
 
@@ -468,23 +1085,67 @@ pub mod anchor_multisig3 {
         transfer.creator = creator_key;
         transfer.recipient = recipient;
         transfer.lamports = lamports;
+        transfer.content_hash = content_hash;
         state.balance -= lamports;
         state.queue.push(transfer.key());
+        state.queue_digests.push(digest);
+
+        emit!(TransferCreated {
+            state: state.key(),
+            transfer: transfer.key(),
+            creator: creator_key,
+            recipient,
+            lamports,
+            content_hash,
+        });
 
         Ok(())
     }
 
+    /// Returns the page of the transfer queue from the `offset`,
+    /// up to [`QueueStatus::MAX_TRANSFERS`] transfers, through the
+    /// return data, so that the wallets get the queue in one call.
+    pub fn get_queue<'info>(
+        ctx: Context<'_, '_, '_, 'info, GetQueue<'info>>,
+        offset: u8,
+    ) -> Result<QueueStatus> {
+        let state = &ctx.accounts.state;
+        let keys = state
+            .queue
+            .iter()
+            .skip(offset as usize)
+            .take(QueueStatus::MAX_TRANSFERS);
+
+        let mut transfers = Vec::with_capacity(QueueStatus::MAX_TRANSFERS);
+        let mut infos = ctx.remaining_accounts.iter();
+        for key in keys {
+            let info = infos
+                .next()
+                .filter(|info| info.key == key)
+                .ok_or(Error::MissingTransferAccountInfo)?;
+            let tx: Account<Transfer> = Account::try_from(info)?;
+            transfers.push(QueuedTransfer {
+                transfer: *key,
+                creator: tx.creator,
+                recipient: tx.recipient,
+                lamports: tx.lamports,
+            });
+        }
+
+        Ok(QueueStatus {
+            approvals: state.signed.iter().filter(|&signed| *signed).count() as u8,
+            m: state.m,
+            queued: state.queue.len() as u8,
+            transfers,
+        })
+    }
+
     /// Approves the transactions and executes the transfer
     /// in case m approvals are met.
     pub fn approve(ctx: Context<Approve>, fund_bump: u8) -> Result<()> {
         let signer = &ctx.accounts.signer;
         let state = &mut ctx.accounts.state;
         let fund = &mut ctx.accounts.fund;
-        let remaining_accounts: HashMap<_, _> = ctx
-            .remaining_accounts
-            .iter()
-            .map(|account| (account.key, account))
-            .collect();
 
         // Validate the multisig fund account.
         State::validate_fund(&state, &fund, fund_bump)?;
@@ -506,57 +1167,84 @@ pub mod anchor_multisig3 {
             state.signed[signer_index] = true;
         }
 
-        // Checks the threshold.
-        let signed = state.signed.iter().filter(|&signed| *signed).count() as u8;
-        if signed < state.m {
-            return Ok(());
-        }
+        // Executes the transfers in case m approvals are met.
+        State::execute_queue(state, fund, ctx.remaining_accounts, fund_bump)
+    }
 
-        // Finds out the executable transactions.
-        let mut executable = Vec::new();
-        let mut remaining = Vec::new();
-        for transfer_addr in &state.queue {
-            let transfer_info = match remaining_accounts.get(transfer_addr) {
-                Some(transfer) => transfer,
-                None => {
-                    remaining.push(*transfer_addr);
-                    continue;
-                }
-            };
-            let mut ref_data = transfer_info.try_borrow_mut_data()?;
-            let mut transfer_data: &[u8] = ref_data.deref_mut();
-            let tx = Transfer::try_deserialize(&mut transfer_data)?;
-            let to = match remaining_accounts.get(&tx.recipient) {
-                None => return Err(Error::MissingRecipientAccountInfo.into()),
-                Some(recipient) => recipient,
-            };
-            executable.push((transfer_info, to, tx.lamports));
-        }
+    /// Approves the transactions with the multiple signer's signatures
+    /// in a single transaction and executes the transfer in case m
+    /// approvals are met.
+    ///
+    /// Each signer signs the [`State::approval_message`] off-chain and
+    /// the submitter puts those signatures in the ed25519 program
+    /// instructions ahead of this instruction.
+    pub fn approve_bundle(ctx: Context<ApproveBundle>, fund_bump: u8) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        let fund = &mut ctx.accounts.fund;
+        let instructions = &ctx.accounts.instructions;
 
-        // There is no executable account info.  Just returns the success.
-        //
-        // This is a case that the approver approved the multisig but didn't
-        // provide the account info.
-        if executable.is_empty() {
-            return Ok(());
-        }
+        // Validate the multisig fund account.
+        State::validate_fund(&state, &fund, fund_bump)?;
 
-        // Executes the queued transfers.
-        let fund = fund.to_account_info();
-        for (transfer, to, lamports) in executable {
-            // Fund to the recipient and closes the transfer account.
-            State::transfer_fund(&state, &fund, &to, lamports, fund_bump)?;
-            let lamports = transfer.lamports();
-            State::transfer_fund(&state, &transfer, &fund, lamports, fund_bump)?;
+        // Nothing to approve.
+        require!(!state.is_empty(), Error::AccountEmpty);
+
+        // Marks the approval of the signers verified by the ed25519
+        // program instructions.
+        let message = state.approval_message(&state.key());
+        let mut index = 0;
+        while let Ok(ix) = load_instruction_at_checked(index, instructions) {
+            index += 1;
+            if ix.program_id != ed25519_program::ID {
+                continue;
+            }
+            for signer_key in ed25519_signers(&ix.data, &message)? {
+                let signer_index = match state.signers.iter().position(|key| *key == signer_key) {
+                    None => return Err(Error::InvalidSigner.into()),
+                    Some(signer_index) => signer_index,
+                };
+                state.signed[signer_index] = true;
+            }
         }
 
-        // Update the queue.
-        state.queue = remaining;
+        // Executes the transfers in case m approvals are met.
+        State::execute_queue(state, fund, ctx.remaining_accounts, fund_bump)
+    }
 
-        // Reset the signed status once the queue is empty.
-        if State::is_empty(&state) {
-            state.signed.iter_mut().for_each(|signed| *signed = false);
-        }
+    /// Allows or disallows the duplicate transfers, e.g. the same
+    /// recipient and the lamports, in the queue.
+    ///
+    /// It's only allowed to the multisig funder, and disallowed by
+    /// default.
+    pub fn set_allow_duplicates(
+        ctx: Context<Configure>,
+        _state_bump: u8,
+        allow_duplicates: bool,
+    ) -> Result<()> {
+        ctx.accounts.state.allow_duplicates = allow_duplicates;
+        Ok(())
+    }
+
+    /// Approves the close proposal of the multisig account.
+    ///
+    /// The `seqno` should be the current [`State::close_seqno`], so
+    /// that the vote only counts for the proposal the signer had seen.
+    pub fn approve_close(ctx: Context<ApproveClose>, seqno: u64) -> Result<()> {
+        let signer = &ctx.accounts.signer;
+        let state = &mut ctx.accounts.state;
+
+        // Checks the signer.
+        let signer_key = signer.key();
+        let signer_index = match state
+            .signers
+            .iter()
+            .position(|pubkey| *pubkey == signer_key)
+        {
+            None => return Err(Error::InvalidSigner.into()),
+            Some(signer_index) => signer_index,
+        };
+        require_eq!(seqno, state.close_seqno, Error::StaleCloseVote);
+        state.close_votes[signer_index] = seqno;
 
         Ok(())
     }
@@ -564,7 +1252,9 @@ pub mod anchor_multisig3 {
     /// Closes a multisig account.
     ///
     /// It cleans up all the remaining accounts and return back to the
-    /// funder.
+    /// funder, once the close proposal is approved by m signers through
+    /// [`anchor_multisig3::approve_close`].  The funder can close it
+    /// alone only when the funder is the only signer.
     pub fn close(ctx: Context<Close>, _state_bump: u8, fund_bump: u8) -> Result<()> {
         let funder = &mut ctx.accounts.funder;
         let state = &mut ctx.accounts.state;
@@ -578,6 +1268,9 @@ pub mod anchor_multisig3 {
         // Validate the multisig fund account.
         State::validate_fund(&state, &fund, fund_bump)?;
 
+        // Checks the close proposal approvals.
+        require!(state.is_closable(funder.key), Error::CloseNotApproved);
+
         // Closes the transfer accounts by transfering the
         // rent fee back to the fund account.
         let to = fund.to_account_info();
@@ -600,3 +1293,57 @@ pub mod anchor_multisig3 {
         Ok(())
     }
 }
+
+/// Returns the signers of the `message` verified by the ed25519 program
+/// instruction.
+///
+/// It only accepts the instruction which carries the signatures, the public
+/// keys, and the messages in the instruction itself, as described in the
+/// [ed25519 program] document.
+///
+/// [ed25519 program]: https://docs.solana.com/developing/runtime-facilities/programs#ed25519-program
+fn ed25519_signers(data: &[u8], message: &[u8]) -> Result<Vec<Pubkey>> {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_SIZE: usize = 14;
+    const PUBKEY_SIZE: usize = 32;
+    const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+    let read_u16 = |offset: usize| -> Result<u16> {
+        data.get(offset..offset + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(|| Error::InvalidSignatureInstruction.into())
+    };
+
+    let count = *data.first().ok_or(Error::InvalidSignatureInstruction)? as usize;
+    let mut signers = Vec::with_capacity(count);
+    for i in 0..count {
+        let offsets = OFFSETS_START + i * OFFSETS_SIZE;
+        let signature_instruction_index = read_u16(offsets + 2)?;
+        let public_key_offset = read_u16(offsets + 4)? as usize;
+        let public_key_instruction_index = read_u16(offsets + 6)?;
+        let message_data_offset = read_u16(offsets + 8)? as usize;
+        let message_data_size = read_u16(offsets + 10)? as usize;
+        let message_instruction_index = read_u16(offsets + 12)?;
+
+        require!(
+            signature_instruction_index == CURRENT_INSTRUCTION
+                && public_key_instruction_index == CURRENT_INSTRUCTION
+                && message_instruction_index == CURRENT_INSTRUCTION,
+            Error::InvalidSignatureInstruction
+        );
+        let signed_message = data
+            .get(message_data_offset..message_data_offset + message_data_size)
+            .ok_or(Error::InvalidSignatureInstruction)?;
+        require!(
+            signed_message == message,
+            Error::InvalidSignatureInstruction
+        );
+        let public_key = data
+            .get(public_key_offset..public_key_offset + PUBKEY_SIZE)
+            .and_then(|key| Pubkey::try_from(key).ok())
+            .ok_or(Error::InvalidSignatureInstruction)?;
+        signers.push(public_key);
+    }
+
+    Ok(signers)
+}
@@ -2,7 +2,8 @@ import * as anchor from "@project-serum/anchor";
 import { web3, Program } from "@project-serum/anchor";
 import { AnchorMultisig3 } from "../target/types/anchor_multisig3";
 import { expect } from "chai";
-const { Keypair, PublicKey, LAMPORTS_PER_SOL } = web3;
+import { createHash } from "crypto";
+const { Ed25519Program, Keypair, PublicKey, LAMPORTS_PER_SOL } = web3;
 
 describe("anchor-multisig3", () => {
   // Configure the client to use the local cluster.
@@ -68,11 +69,24 @@ describe("anchor-multisig3", () => {
       .rpc();
   });
 
+  // Approves the close proposal by m signers.
+  const approveClose = async () => {
+    const ms = await program.account.state.fetch(state);
+    for (const signer of signers.slice(0, threshold)) {
+      await program.methods
+        .approveClose(ms.closeSeqno)
+        .accounts({ signer: signer.publicKey, state })
+        .signers([signer])
+        .rpc();
+    }
+  };
+
   afterEach(async () => {
     // Since we use the PDA based on the wallet pubkey,
     // we need to close the account in every test, or
     // it interfears the later tests.
     try {
+      await approveClose();
       const ms = await program.account.state.fetch(state);
       const remainingAccounts = ms.queue.map((transfer) => {
         return {
@@ -125,6 +139,59 @@ describe("anchor-multisig3", () => {
   });
 
   it("Checks the account closure", async () => {
+    // The funder can't close it alone.
+    try {
+      await program.methods
+        .close(stateBump, fundBump)
+        .accounts({
+          funder: wallet.publicKey,
+          state,
+          fund,
+        })
+        .signers([wallet.payer])
+        .rpc();
+      expect.fail("it should require m approvals");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("CloseNotApproved");
+    }
+
+    // Nor with the stale votes.
+    const ms = await program.account.state.fetch(state);
+    try {
+      await program.methods
+        .approveClose(ms.closeSeqno.subn(1))
+        .accounts({ signer: signers[0].publicKey, state })
+        .signers([signers[0]])
+        .rpc();
+      expect.fail("it should reject the stale vote");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("StaleCloseVote");
+    }
+
+    // Nor with m - 1 approvals.
+    for (const signer of signers.slice(0, threshold - 1)) {
+      await program.methods
+        .approveClose(ms.closeSeqno)
+        .accounts({ signer: signer.publicKey, state })
+        .signers([signer])
+        .rpc();
+    }
+    try {
+      await program.methods
+        .close(stateBump, fundBump)
+        .accounts({
+          funder: wallet.publicKey,
+          state,
+          fund,
+        })
+        .signers([wallet.payer])
+        .rpc();
+      expect.fail("it should require m approvals");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("CloseNotApproved");
+    }
+
+    await approveClose();
     await program.methods
       .close(stateBump, fundBump)
       .accounts({
@@ -143,6 +210,43 @@ describe("anchor-multisig3", () => {
     }
   });
 
+  it("Checks the zero threshold", async () => {
+    const funder = Keypair.generate();
+    const airdrop = await provider.connection.requestAirdrop(
+      funder.publicKey,
+      LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(airdrop);
+    const [otherState, otherStateBump] = PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("state"), funder.publicKey.toBuffer()],
+      program.programId
+    );
+    const [otherFund, otherFundBump] = PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("fund"), otherState.toBuffer()],
+      program.programId
+    );
+    try {
+      await program.methods
+        .create(
+          0,
+          signers.map((pair) => pair.publicKey),
+          queueDepth,
+          otherStateBump,
+          otherFundBump
+        )
+        .accounts({
+          funder: funder.publicKey,
+          state: otherState,
+          fund: otherFund,
+        })
+        .signers([funder])
+        .rpc();
+      expect.fail("it should require at least one approval");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("ThresholdTooLow");
+    }
+  });
+
   it("Checks 1,000,000 SOL funding", async () => {
     const before = await provider.connection.getBalance(fund);
     const lamports = 1000000 * LAMPORTS_PER_SOL;
@@ -164,6 +268,49 @@ describe("anchor-multisig3", () => {
     expect(balance - before).to.equal(lamports);
   });
 
+  it("Checks the deposit by anyone", async () => {
+    // A depositor, neither the funder nor the signers.
+    const depositor = Keypair.generate();
+    const tx = await provider.connection.requestAirdrop(
+      depositor.publicKey,
+      2 * LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(tx);
+
+    const before = await provider.connection.getBalance(fund);
+    const lamports = 1 * LAMPORTS_PER_SOL;
+    await program.methods
+      .deposit(new anchor.BN(lamports))
+      .accounts({
+        depositor: depositor.publicKey,
+        state,
+        fund,
+      })
+      .signers([depositor])
+      .rpc();
+
+    const ms = await program.account.state.fetch(state);
+    expect(ms.balance.eq(new anchor.BN(lamports))).to.be.true;
+    const balance = await provider.connection.getBalance(fund);
+    expect(balance - before).to.equal(lamports);
+
+    // The fund account should be the one of the state.
+    try {
+      await program.methods
+        .deposit(new anchor.BN(lamports))
+        .accounts({
+          depositor: depositor.publicKey,
+          state,
+          fund: depositor.publicKey,
+        })
+        .signers([depositor])
+        .rpc();
+      expect.fail("it should reject the other fund account");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("InvalidFundAddress");
+    }
+  });
+
   it("Checks multiple queued transactions", async () => {
     let balance = 1000000 * LAMPORTS_PER_SOL;
     await program.methods
@@ -181,8 +328,11 @@ describe("anchor-multisig3", () => {
       const lamports = 100 * index * LAMPORTS_PER_SOL;
       const lamportsBN = new anchor.BN(lamports);
       const signer = signers[index % signers.length];
+      const contentHash = createHash("sha256")
+        .update(`proposal #${index}`)
+        .digest();
       const tx = await program.methods
-        .createTransfer(payee.publicKey, lamportsBN, fundBump)
+        .createTransfer(payee.publicKey, lamportsBN, fundBump, [...contentHash])
         .accounts({
           creator: signer.publicKey,
           state,
@@ -192,6 +342,10 @@ describe("anchor-multisig3", () => {
         .signers([signer, transfer])
         .rpc();
 
+      // The content hash is anchored to the transfer.
+      const got = await program.account.transfer.fetch(transfer.publicKey);
+      expect(Buffer.from(got.contentHash)).to.deep.equal(contentHash);
+
       balance -= lamports;
     }
 
@@ -208,6 +362,134 @@ describe("anchor-multisig3", () => {
     }
   });
 
+  it("Checks the queue pages through the return data", async () => {
+    await program.methods
+      .fund(new anchor.BN(1000 * LAMPORTS_PER_SOL), stateBump, fundBump)
+      .accounts({
+        funder: wallet.publicKey,
+        state,
+        fund,
+      })
+      .signers([wallet.payer])
+      .rpc();
+
+    const transfers = [];
+    for (const [index, payee] of payees.entries()) {
+      const transfer = Keypair.generate();
+      const signer = signers[index % signers.length];
+      await program.methods
+        .createTransfer(
+          payee.publicKey,
+          new anchor.BN((index + 1) * LAMPORTS_PER_SOL),
+          fundBump,
+          new Array(32).fill(0)
+        )
+        .accounts({
+          creator: signer.publicKey,
+          state,
+          fund,
+          transfer: transfer.publicKey,
+        })
+        .signers([signer, transfer])
+        .rpc();
+      transfers.push(transfer.publicKey);
+    }
+    await program.methods
+      .approve(fundBump)
+      .accounts({ signer: signers[0].publicKey, state, fund })
+      .signers([signers[0]])
+      .rpc();
+
+    // Reads the queue in two pages, as it's bounded by the
+    // return data size.
+    const got = [];
+    for (const offset of [0, 9]) {
+      const page = await program.methods
+        .getQueue(offset)
+        .accounts({ state })
+        .remainingAccounts(
+          transfers.slice(offset, offset + 9).map((pubkey) => {
+            return { pubkey, isWritable: false, isSigner: false };
+          })
+        )
+        .view();
+      expect(page.approvals).to.equal(1);
+      expect(page.m).to.equal(threshold);
+      expect(page.queued).to.equal(payees.length);
+      got.push(...page.transfers);
+    }
+    expect(got).to.have.lengthOf(payees.length);
+    for (const [index, transfer] of got.entries()) {
+      expect(transfer.transfer).to.eql(transfers[index]);
+      expect(transfer.creator).to.eql(signers[index % signers.length].publicKey);
+      expect(transfer.recipient).to.eql(payees[index].publicKey);
+      expect(transfer.lamports.toNumber()).to.equal(
+        (index + 1) * LAMPORTS_PER_SOL
+      );
+    }
+  });
+
+  it("Checks the rejected recipients and duplicate transfers", async () => {
+    await program.methods
+      .fund(new anchor.BN(10 * LAMPORTS_PER_SOL), stateBump, fundBump)
+      .accounts({
+        funder: wallet.publicKey,
+        state,
+        fund,
+      })
+      .signers([wallet.payer])
+      .rpc();
+
+    const createTransfer = async (recipient: web3.PublicKey) => {
+      const transfer = Keypair.generate();
+      await program.methods
+        .createTransfer(
+          recipient,
+          new anchor.BN(LAMPORTS_PER_SOL),
+          fundBump,
+          new Array(32).fill(0)
+        )
+        .accounts({
+          creator: signers[0].publicKey,
+          state,
+          fund,
+          transfer: transfer.publicKey,
+        })
+        .signers([signers[0], transfer])
+        .rpc();
+    };
+    const expectError = async (recipient: web3.PublicKey, code: string) => {
+      try {
+        await createTransfer(recipient);
+        expect.fail(`${code} should be returned`);
+      } catch (e) {
+        expect(e.error.errorCode.code).to.equal(code);
+      }
+    };
+
+    // No transfer to the multisig accounts themselves.
+    await expectError(fund, "RecipientIsFund");
+    await expectError(state, "RecipientIsState");
+
+    // The same transfer is rejected by default.
+    await createTransfer(payees[0].publicKey);
+    await expectError(payees[0].publicKey, "DuplicateTransfer");
+
+    // Unless the funder allows it.
+    await program.methods
+      .setAllowDuplicates(stateBump, true)
+      .accounts({ funder: wallet.publicKey, state })
+      .signers([wallet.payer])
+      .rpc();
+    await createTransfer(payees[0].publicKey);
+
+    const ms = await program.account.state.fetch(state);
+    expect(ms.allowDuplicates).to.be.true;
+    expect(ms.queue).to.have.lengthOf(2);
+    expect(ms.queueDigests).to.have.lengthOf(2);
+    expect(ms.balance.toNumber()).to.equal(8 * LAMPORTS_PER_SOL);
+  });
+
   it("Checks the approval and the transfer execution", async () => {
     let balance = 1000000 * LAMPORTS_PER_SOL;
     await program.methods
@@ -226,7 +508,7 @@ describe("anchor-multisig3", () => {
       const lamportsBN = new anchor.BN(lamports);
       const signer = signers[index % signers.length];
       const tx = await program.methods
-        .createTransfer(payee.publicKey, lamportsBN, fundBump)
+        .createTransfer(payee.publicKey, lamportsBN, fundBump, new Array(32).fill(0))
         .accounts({
           creator: signer.publicKey,
           state,
@@ -289,5 +571,157 @@ describe("anchor-multisig3", () => {
       const balance = await provider.connection.getBalance(payee.publicKey);
       expect(balance).to.equal(expected);
     }
+
+    // Checks the disbursement report.
+    for (const [index, payee] of payees.entries()) {
+      const expected = new anchor.BN(1000 * index * LAMPORTS_PER_SOL);
+      const disbursed = ms.disbursed.find((d) =>
+        d.recipient.equals(payee.publicKey)
+      );
+      expect(disbursed.lamports.eq(expected)).to.be.true;
+    }
+    expect(ms.otherDisbursed.toNumber()).to.equal(0);
+  });
+
+  it("Checks the bundled approval and the transfer execution", async () => {
+    const lamports = 10 * LAMPORTS_PER_SOL;
+    await program.methods
+      .fund(new anchor.BN(lamports), stateBump, fundBump)
+      .accounts({
+        funder: wallet.publicKey,
+        state,
+        fund,
+      })
+      .signers([wallet.payer])
+      .rpc();
+
+    const payee = payees[0];
+    const transfer = Keypair.generate();
+    await program.methods
+      .createTransfer(
+        payee.publicKey,
+        new anchor.BN(lamports),
+        fundBump,
+        new Array(32).fill(0)
+      )
+      .accounts({
+        creator: signers[0].publicKey,
+        state,
+        fund,
+        transfer: transfer.publicKey,
+      })
+      .signers([signers[0], transfer])
+      .rpc();
+
+    // The approval message is the state address followed by
+    // the hash of the nonce and the queued transfers.
+    const ms = await program.account.state.fetch(state);
+    const queueHash = createHash("sha256");
+    queueHash.update(ms.nonce.toArrayLike(Buffer, "le", 8));
+    for (const transfer of ms.queue) {
+      const tx = await program.account.transfer.fetch(transfer);
+      const digest = createHash("sha256")
+        .update(tx.recipient.toBuffer())
+        .update(tx.lamports.toArrayLike(Buffer, "le", 8))
+        .digest()
+        .subarray(0, 8);
+      queueHash.update(transfer.toBuffer());
+      queueHash.update(digest);
+    }
+    const message = Buffer.concat([state.toBuffer(), queueHash.digest()]);
+
+    // Collects the signatures offline and submits those at once.
+    const ed25519Ixs = signers.slice(0, threshold).map((signer) =>
+      Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: signer.secretKey,
+        message,
+      })
+    );
+    const before = await provider.connection.getBalance(payee.publicKey);
+    await program.methods
+      .approveBundle(fundBump)
+      .accounts({
+        submitter: wallet.publicKey,
+        state,
+        fund,
+        instructions: web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+      })
+      .remainingAccounts([
+        { pubkey: payee.publicKey, isWritable: true, isSigner: false },
+        { pubkey: transfer.publicKey, isWritable: true, isSigner: false },
+      ])
+      .preInstructions(ed25519Ixs)
+      .rpc();
+
+    const after = await provider.connection.getBalance(payee.publicKey);
+    expect(after - before).to.equal(lamports);
+    const got = await program.account.state.fetch(state);
+    expect(got.queue).to.have.lengthOf(0);
+    expect(got.signed.filter(Boolean)).to.have.lengthOf(0);
+    expect(got.nonce.eq(ms.nonce.addn(1))).to.be.true;
+  });
+
+  it("Checks the idempotent creation and the repair", async () => {
+    const keys = signers.map((pair) => pair.publicKey);
+    const accounts = { funder: wallet.publicKey, state, fund };
+
+    // The retry succeeds without any change.
+    await program.methods
+      .ensureCreated(threshold, keys, queueDepth, stateBump, fundBump)
+      .accounts(accounts)
+      .rpc();
+    await program.methods.repair(stateBump, fundBump).accounts(accounts).rpc();
+    const ms = await program.account.state.fetch(state);
+    expect(ms.m).to.equal(threshold);
+    expect(ms.signed).to.have.lengthOf(signers.length);
+
+    // But not with the different parameters.
+    try {
+      await program.methods
+        .ensureCreated(threshold - 1, keys, queueDepth, stateBump, fundBump)
+        .accounts(accounts)
+        .rpc();
+      expect.fail("ensureCreated should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("StateMismatch");
+    }
+
+    // The fund PDA funded before the creation.
+    const funder = Keypair.generate();
+    const airdrop = await provider.connection.requestAirdrop(
+      funder.publicKey,
+      LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(airdrop);
+    const [otherState, otherStateBump] = PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("state"), funder.publicKey.toBuffer()],
+      program.programId
+    );
+    const [otherFund, otherFundBump] = PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("fund"), otherState.toBuffer()],
+      program.programId
+    );
+    await provider.sendAndConfirm(
+      new web3.Transaction().add(
+        web3.SystemProgram.transfer({
+          fromPubkey: wallet.publicKey,
+          toPubkey: otherFund,
+          lamports: 1000,
+        })
+      )
+    );
+    await program.methods
+      .ensureCreated(1, [funder.publicKey], 1, otherStateBump, otherFundBump)
+      .accounts({ funder: funder.publicKey, state: otherState, fund: otherFund })
+      .signers([funder])
+      .rpc();
+    const info = await provider.connection.getAccountInfo(otherFund);
+    expect(info.owner).to.deep.equal(program.programId);
+
+    await program.methods
+      .close(otherStateBump, otherFundBump)
+      .accounts({ funder: funder.publicKey, state: otherState, fund: otherFund })
+      .signers([funder])
+      .rpc();
   });
 });
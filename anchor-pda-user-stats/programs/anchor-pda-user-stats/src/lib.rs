@@ -1,4 +1,7 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
 
 declare_id!("3EQwhZWFzX1MCUbYpckbErrrLmt5n9PEQdjHUpXc8as7");
 
@@ -6,6 +9,42 @@ declare_id!("3EQwhZWFzX1MCUbYpckbErrrLmt5n9PEQdjHUpXc8as7");
 pub enum Error {
     #[msg("User name is too long")]
     NameTooLong,
+
+    #[msg("User can't refer themselves")]
+    SelfReferral,
+
+    #[msg("Referral count overflow")]
+    ReferralCountOverflow,
+
+    #[msg("Too many user stats accounts")]
+    TooManyAccounts,
+
+    #[msg("Too many programs for the allowlist")]
+    TooManyPrograms,
+
+    #[msg("The caller program is not in the allowlist")]
+    CallerNotAllowed,
+
+    #[msg("Activity points overflow")]
+    PointsOverflow,
+
+    #[msg("User stats updated too frequently")]
+    TooFrequent,
+
+    #[msg("Stats payload is too large")]
+    PayloadTooLarge,
+
+    #[msg("Prior stats payload doesn't match the stored hash")]
+    PayloadHashMismatch,
+
+    #[msg("Stats payload update count overflow")]
+    UpdateCountOverflow,
+
+    #[msg("Only the program upgrade authority can be the admin")]
+    NotUpgradeAuthority,
+
+    #[msg("User stats is not in the legacy layout")]
+    NotLegacyUserStats,
 }
 
 #[program]
@@ -24,6 +63,178 @@ pub mod anchor_pda_user_stats {
         Ok(())
     }
 
+    /// Opens a `UserStats` account referred by the existing user,
+    /// and counts up the referrer's `referral_count`.
+    pub fn open_with_referrer(
+        ctx: Context<OpenWithReferrer>,
+        name: String,
+        referrer: Pubkey,
+    ) -> Result<()> {
+        let user_stats = &mut ctx.accounts.user_stats;
+        let referrer_stats = &mut ctx.accounts.referrer_stats;
+
+        if name.len() > UserStats::NAME_MAX {
+            Err(Error::NameTooLong)?;
+        }
+        user_stats.name = name;
+        user_stats.bump = *ctx.bumps.get("user_stats").unwrap();
+        user_stats.referrer = Some(referrer);
+
+        referrer_stats.referral_count = referrer_stats
+            .referral_count
+            .checked_add(1)
+            .ok_or(Error::ReferralCountOverflow)?;
+
+        Ok(())
+    }
+
+    /// Migrates the legacy `UserStats` account, which only has the
+    /// `name` and the `bump`, to the current layout.
+    ///
+    /// It reallocs the account, paid by the user, and copies the old
+    /// values over.
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let user_stats = &ctx.accounts.user_stats;
+        let user = &ctx.accounts.user;
+        let old = UserStatsV1::try_from_slice(&user_stats.try_borrow_data()?)?;
+
+        let lamports = Rent::get()?
+            .minimum_balance(UserStats::SPACE)
+            .saturating_sub(user_stats.lamports());
+        if lamports > 0 {
+            let cpi = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: user.to_account_info(),
+                    to: user_stats.to_account_info(),
+                },
+            );
+            system_program::transfer(cpi, lamports)?;
+        }
+        user_stats.realloc(UserStats::SPACE, false)?;
+
+        let data = UserStats {
+            name: old.name,
+            bump: old.bump,
+            referrer: None,
+            referral_count: 0,
+            points: 0,
+            last_update_slot: 0,
+        };
+        data.try_serialize(&mut &mut user_stats.try_borrow_mut_data()?[..])?;
+        Ok(())
+    }
+
+    /// Returns the summaries of the `UserStats` accounts given through
+    /// the `remaining_accounts`, in the same order, as the return data.
+    ///
+    /// It's up to [`UserStatsSummary::MAX_ACCOUNTS`] accounts to fit
+    /// in the return data.
+    pub fn get_many(ctx: Context<GetMany>) -> Result<Vec<UserStatsSummary>> {
+        require_gte!(
+            UserStatsSummary::MAX_ACCOUNTS,
+            ctx.remaining_accounts.len(),
+            Error::TooManyAccounts
+        );
+
+        ctx.remaining_accounts
+            .iter()
+            .map(|info| {
+                require_keys_eq!(*info.owner, id(), ErrorCode::AccountOwnedByWrongProgram);
+                let user_stats = UserStats::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+                Ok(UserStatsSummary {
+                    user_stats: info.key(),
+                    name: user_stats.name,
+                    referral_count: user_stats.referral_count,
+                })
+            })
+            .collect()
+    }
+
+    /// Initializes the `Allowlist` of the caller programs of the
+    /// `record_activity` instruction.
+    ///
+    /// It's only allowed to the upgrade authority of the program, so
+    /// that no one can take the admin before the deployer.
+    pub fn init_allowlist(ctx: Context<InitAllowlist>, programs: Vec<Pubkey>) -> Result<()> {
+        let allowlist = &mut ctx.accounts.allowlist;
+        allowlist.admin = ctx.accounts.admin.key();
+        allowlist.bump = *ctx.bumps.get("allowlist").unwrap();
+        allowlist.set_programs(programs)
+    }
+
+    /// Replaces the caller programs of the `Allowlist`.
+    pub fn set_allowlist(ctx: Context<SetAllowlist>, programs: Vec<Pubkey>) -> Result<()> {
+        ctx.accounts.allowlist.set_programs(programs)
+    }
+
+    /// Awards the activity `points` to the user.
+    ///
+    /// It's only callable by the allowlisted programs through CPI,
+    /// signed by the caller program PDA, e.g. `[b"user-stats-caller"]`,
+    /// and at most once per the `Allowlist` cooldown slots.
+    pub fn record_activity(ctx: Context<RecordActivity>, points: u64) -> Result<()> {
+        let cooldown_slots = ctx.accounts.allowlist.cooldown_slots;
+        let user_stats = &mut ctx.accounts.user_stats;
+        let slot = Clock::get()?.slot;
+        require!(
+            user_stats.is_cooled_down(slot, cooldown_slots),
+            Error::TooFrequent
+        );
+        user_stats.last_update_slot = slot;
+        user_stats.points = user_stats
+            .points
+            .checked_add(points)
+            .ok_or(Error::PointsOverflow)?;
+
+        Ok(())
+    }
+
+    /// Sets the minimum number of slots between the updates of each
+    /// user, to limit the damage by the compromised caller programs.
+    ///
+    /// It's only allowed to the `Allowlist` admin.
+    pub fn set_cooldown(ctx: Context<SetAllowlist>, cooldown_slots: u64) -> Result<()> {
+        ctx.accounts.allowlist.cooldown_slots = cooldown_slots;
+        Ok(())
+    }
+
+    /// Opens a `CompressedUserStats` account, which only stores the
+    /// hash of the stats `payload` and leaves the payload itself to
+    /// the off-chain storage.
+    pub fn open_compressed(ctx: Context<OpenCompressed>, payload: Vec<u8>) -> Result<()> {
+        let user_stats = &mut ctx.accounts.user_stats;
+        user_stats.bump = *ctx.bumps.get("user_stats").unwrap();
+        user_stats.set_payload(&payload)
+    }
+
+    /// Replaces the compressed stats payload with the new `payload`,
+    /// after verifying the `prior_payload` against the stored hash.
+    pub fn update_with_proof(
+        ctx: Context<UpdateWithProof>,
+        prior_payload: Vec<u8>,
+        payload: Vec<u8>,
+    ) -> Result<()> {
+        let user_stats = &mut ctx.accounts.user_stats;
+        require!(
+            user_stats.verify_payload(&prior_payload),
+            Error::PayloadHashMismatch
+        );
+        user_stats.set_payload(&payload)?;
+        user_stats.update_count = user_stats
+            .update_count
+            .checked_add(1)
+            .ok_or(Error::UpdateCountOverflow)?;
+
+        Ok(())
+    }
+
+    /// Closes the `CompressedUserStats` account and gives the rent back
+    /// to the user.
+    pub fn close_compressed(_ctx: Context<CloseCompressed>) -> Result<()> {
+        Ok(())
+    }
+
     pub fn close(ctx: Context<Close>) -> Result<()> {
         let user_stats = &mut ctx.accounts.user_stats;
         let lamports = **user_stats.to_account_info().lamports.borrow();
@@ -43,13 +254,157 @@ pub struct UserStats {
 
     /// A PDA bump.
     bump: u8,
+
+    /// A user who referred this user, if any.
+    referrer: Option<Pubkey>,
+
+    /// Number of users referred by this user.
+    referral_count: u64,
+
+    /// Activity points awarded by the allowlisted programs.
+    points: u64,
+
+    /// A slot of the last update, or zero if never updated.
+    last_update_slot: u64,
 }
 
 impl UserStats {
     /// A space for the UserStats
-    const SPACE: usize = Self::DESCRIMINATOR + 4 + Self::NAME_MAX + 1;
+    const SPACE: usize = Self::DESCRIMINATOR + 4 + Self::NAME_MAX + 1 + 1 + 32 + 8 + 8 + 8;
     const DESCRIMINATOR: usize = 8;
     const NAME_MAX: usize = 32;
+
+    /// Returns true if the `cooldown_slots` had elapsed since the last
+    /// update at the `slot`.
+    fn is_cooled_down(&self, slot: u64, cooldown_slots: u64) -> bool {
+        self.last_update_slot == 0 || slot >= self.last_update_slot.saturating_add(cooldown_slots)
+    }
+}
+
+/// A legacy `UserStats`, which only has the `name` and the `bump`.
+///
+/// It's only used by the `migrate` instruction.
+#[derive(AnchorDeserialize)]
+struct UserStatsV1 {
+    name: String,
+    bump: u8,
+}
+
+impl UserStatsV1 {
+    /// The legacy `UserStats::SPACE`.
+    const SPACE: usize = UserStats::DESCRIMINATOR + 4 + UserStats::NAME_MAX + 1;
+
+    fn try_from_slice(data: &[u8]) -> Result<Self> {
+        require_eq!(data.len(), Self::SPACE, Error::NotLegacyUserStats);
+        require!(
+            data[..8] == UserStats::DISCRIMINATOR,
+            Error::NotLegacyUserStats
+        );
+        let mut data = &data[8..];
+        Ok(Self::deserialize(&mut data)?)
+    }
+}
+
+/// A compressed user stats, which keeps only the hash of the stats
+/// payload on-chain, e.g. for the large deployments.
+///
+/// The payload itself, e.g. the borsh serialized stats, is kept
+/// off-chain and given to the `update_with_proof` instruction as the
+/// proof of the current state.
+#[account]
+pub struct CompressedUserStats {
+    /// A PDA bump.
+    bump: u8,
+
+    /// A SHA-256 hash of the current stats payload.
+    payload_hash: [u8; 32],
+
+    /// Number of the payload updates.
+    update_count: u64,
+}
+
+impl CompressedUserStats {
+    /// A space for the CompressedUserStats.
+    const SPACE: usize = 8 + 1 + 32 + 8;
+
+    /// A maximum length of the stats payload, to fit in a transaction
+    /// together with the prior payload.
+    ///
+    /// The `update_with_proof` transaction takes about 220 bytes other
+    /// than the payloads, e.g. a signature, three account keys, the
+    /// blockhash, and the instruction header, so that the two 480 bytes
+    /// payloads fit in the 1232 bytes packet with some room for the
+    /// other instruction, e.g. the compute budget.
+    pub const PAYLOAD_MAX: usize = 480;
+
+    fn set_payload(&mut self, payload: &[u8]) -> Result<()> {
+        require_gte!(Self::PAYLOAD_MAX, payload.len(), Error::PayloadTooLarge);
+        self.payload_hash = hash::hash(payload).to_bytes();
+        Ok(())
+    }
+
+    fn verify_payload(&self, payload: &[u8]) -> bool {
+        hash::hash(payload).to_bytes() == self.payload_hash
+    }
+}
+
+/// An allowlist of the caller programs of the `record_activity`
+/// instruction.
+#[account]
+pub struct Allowlist {
+    /// An admin of the allowlist.
+    admin: Pubkey,
+
+    /// Allowlisted caller programs.
+    programs: Vec<Pubkey>,
+
+    /// A PDA bump.
+    bump: u8,
+
+    /// Minimum number of slots between the updates of each user.
+    cooldown_slots: u64,
+}
+
+impl Allowlist {
+    /// A space for the Allowlist.
+    const SPACE: usize = 8 + 32 + 4 + 32 * Self::MAX_PROGRAMS + 1 + 8;
+
+    /// A maximum number of the allowlisted programs.
+    pub const MAX_PROGRAMS: usize = 8;
+
+    /// A seed of the caller program PDA signer.
+    pub const CALLER_SEED: &'static [u8] = b"user-stats-caller";
+
+    fn set_programs(&mut self, programs: Vec<Pubkey>) -> Result<()> {
+        require_gte!(
+            Self::MAX_PROGRAMS,
+            programs.len(),
+            Error::TooManyPrograms
+        );
+        self.programs = programs;
+        Ok(())
+    }
+}
+
+/// A summary of the `UserStats` account returned by the `get_many`
+/// instruction.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct UserStatsSummary {
+    /// A `UserStats` PDA account.
+    pub user_stats: Pubkey,
+
+    /// A user name.
+    pub name: String,
+
+    /// Number of users referred by this user.
+    pub referral_count: u64,
+}
+
+impl UserStatsSummary {
+    /// A maximum number of the accounts to fit in the 1024 bytes
+    /// return data, e.g. 4 bytes for the vector length and up to
+    /// 32 + 4 + 32 + 8 bytes for each summary.
+    pub const MAX_ACCOUNTS: usize = 13;
 }
 
 #[derive(Accounts)]
@@ -72,6 +427,35 @@ pub struct Open<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(name: String, referrer: Pubkey)]
+pub struct OpenWithReferrer<'info> {
+    /// A user, who pays for the `UserStats` account.
+    #[account(mut, constraint = user.key() != referrer @ Error::SelfReferral)]
+    pub user: Signer<'info>,
+
+    /// A `UserStats` PDA account.
+    #[account(
+        init,
+        payer = user,
+        space = UserStats::SPACE,
+        seeds = [b"user-stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+
+    /// An existing `UserStats` PDA account of the referrer.
+    #[account(
+        mut,
+        seeds = [b"user-stats", referrer.as_ref()],
+        bump = referrer_stats.bump
+    )]
+    pub referrer_stats: Account<'info, UserStats>,
+
+    /// SystemProgram to create PDA account.
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct Close<'info> {
     /// A user account to get the rent back.
@@ -89,3 +473,141 @@ pub struct Close<'info> {
     /// SystemProgram to close the PDA account.
     pub system_program: Program<'info, System>,
 }
+
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    /// A user, who pays for the additional rent.
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: A legacy `UserStats` PDA account, validated by the
+    /// instruction.
+    #[account(
+        mut,
+        owner = id(),
+        seeds = [b"user-stats", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: UncheckedAccount<'info>,
+
+    /// SystemProgram to transfer the additional rent.
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct OpenCompressed<'info> {
+    /// A user, who pays for the `CompressedUserStats` account.
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// A `CompressedUserStats` PDA account.
+    #[account(
+        init,
+        payer = user,
+        space = CompressedUserStats::SPACE,
+        seeds = [b"user-stats-hash", user.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, CompressedUserStats>,
+
+    /// SystemProgram to create PDA account.
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateWithProof<'info> {
+    /// A user of the compressed stats.
+    pub user: Signer<'info>,
+
+    /// A `CompressedUserStats` PDA account of the user.
+    #[account(
+        mut,
+        seeds = [b"user-stats-hash", user.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Account<'info, CompressedUserStats>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCompressed<'info> {
+    /// A user account to get the rent back.
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// A `CompressedUserStats` PDA account to be closed.
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"user-stats-hash", user.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Account<'info, CompressedUserStats>,
+}
+
+#[derive(Accounts)]
+pub struct GetMany {}
+
+#[derive(Accounts)]
+pub struct InitAllowlist<'info> {
+    /// An admin, who pays for the `Allowlist` account.
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// This program.
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::AnchorPdaUserStats>,
+
+    /// A program data account of this program, to check the admin
+    /// against the upgrade authority.
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ Error::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// An `Allowlist` PDA account.
+    #[account(
+        init,
+        payer = admin,
+        space = Allowlist::SPACE,
+        seeds = [b"allowlist"],
+        bump
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+
+    /// SystemProgram to create PDA account.
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAllowlist<'info> {
+    /// An admin of the `Allowlist` account.
+    pub admin: Signer<'info>,
+
+    /// An `Allowlist` PDA account.
+    #[account(mut, has_one = admin, seeds = [b"allowlist"], bump = allowlist.bump)]
+    pub allowlist: Account<'info, Allowlist>,
+}
+
+#[derive(Accounts)]
+pub struct RecordActivity<'info> {
+    /// A `UserStats` PDA account to award the points.
+    #[account(mut)]
+    pub user_stats: Account<'info, UserStats>,
+
+    /// An `Allowlist` PDA account of the caller programs.
+    #[account(
+        seeds = [b"allowlist"],
+        bump = allowlist.bump,
+        constraint = allowlist.programs.contains(caller_program.key) @ Error::CallerNotAllowed
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+
+    /// CHECK: An allowlisted caller program.
+    #[account(executable)]
+    pub caller_program: UncheckedAccount<'info>,
+
+    /// A caller program PDA signer, e.g. `[b"user-stats-caller"]`.
+    #[account(seeds = [Allowlist::CALLER_SEED], bump, seeds::program = caller_program.key())]
+    pub caller: Signer<'info>,
+}
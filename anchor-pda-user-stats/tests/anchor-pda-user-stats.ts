@@ -2,6 +2,8 @@ import * as anchor from "@project-serum/anchor";
 import { AnchorError, Program, web3 } from "@project-serum/anchor";
 import { AnchorPdaUserStats } from "../target/types/anchor_pda_user_stats";
 import { expect } from 'chai';
+import { createHash } from "crypto";
+import { readFileSync } from "fs";
 
 describe("anchor-pda-user-stats", () => {
   // Configure the client to use the local cluster.
@@ -53,4 +55,320 @@ describe("anchor-pda-user-stats", () => {
     }
     expect(resp).to.be.instanceof(Error);
   });
+
+  it("open a user stats with a referrer", async () => {
+    const referrer = provider.wallet.publicKey;
+    const [referrerStatsPda, _referrerBump] = web3.PublicKey
+      .findProgramAddressSync(
+        [anchor.utils.bytes.utf8.encode("user-stats"), referrer.toBuffer()],
+        program.programId
+      );
+    await program.methods
+      .open("keith")
+      .accounts({
+        user: referrer,
+        userStats: referrerStatsPda,
+      })
+      .rpc();
+
+    // A new user referred by the wallet.
+    const user = web3.Keypair.generate();
+    const sig = await provider.connection.requestAirdrop(
+      user.publicKey,
+      web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(sig);
+    const [userStatsPda, _userBump] = web3.PublicKey
+      .findProgramAddressSync(
+        [anchor.utils.bytes.utf8.encode("user-stats"), user.publicKey.toBuffer()],
+        program.programId
+      );
+    await program.methods
+      .openWithReferrer("noguchi", referrer)
+      .accounts({
+        user: user.publicKey,
+        userStats: userStatsPda,
+        referrerStats: referrerStatsPda,
+      })
+      .signers([user])
+      .rpc();
+
+    const userStats = await program.account.userStats.fetch(userStatsPda);
+    expect(userStats.referrer).to.deep.equal(referrer);
+    const referrerStats = await program.account.userStats.fetch(referrerStatsPda);
+    expect(referrerStats.referralCount.toNumber()).to.equal(1);
+  });
+
+  it("gets many user stats", async () => {
+    // The referrer and the referred user stats opened by the previous test.
+    const referrer = provider.wallet.publicKey;
+    const [referrerStatsPda] = web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("user-stats"), referrer.toBuffer()],
+      program.programId
+    );
+    const referred = await program.account.userStats.all();
+    const accounts = [referrerStatsPda].concat(
+      referred
+        .map((account) => account.publicKey)
+        .filter((pubkey) => !pubkey.equals(referrerStatsPda))
+    );
+
+    const got = await program.methods
+      .getMany()
+      .remainingAccounts(
+        accounts.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
+      .view();
+
+    expect(got).to.have.lengthOf(accounts.length);
+    expect(got[0].userStats).to.deep.equal(referrerStatsPda);
+    expect(got[0].name).to.equal("keith");
+    expect(got[0].referralCount.toNumber()).to.equal(1);
+  });
+
+  it("rejects the activity not from the allowlisted programs", async () => {
+    const [allowlist] = web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("allowlist")],
+      program.programId
+    );
+    const [userStats] = web3.PublicKey.findProgramAddressSync(
+      [
+        anchor.utils.bytes.utf8.encode("user-stats"),
+        provider.wallet.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+    const [programData] = web3.PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+
+    // Only the upgrade authority, e.g. the deployer, can be the admin.
+    const other = web3.Keypair.generate();
+    const airdrop = await provider.connection.requestAirdrop(
+      other.publicKey,
+      web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(airdrop);
+    try {
+      await program.methods
+        .initAllowlist([])
+        .accounts({
+          admin: other.publicKey,
+          allowlist,
+          program: program.programId,
+          programData,
+        })
+        .signers([other])
+        .rpc();
+      expect.fail("init_allowlist should be rejected");
+    } catch (e) {
+      expect(e).to.be.instanceof(AnchorError);
+      expect(e.error.errorCode.code).to.equal("NotUpgradeAuthority");
+    }
+
+    await program.methods
+      .initAllowlist([])
+      .accounts({
+        admin: provider.wallet.publicKey,
+        allowlist,
+        program: program.programId,
+        programData,
+      })
+      .rpc();
+
+    const recordActivity = (caller: web3.Keypair) =>
+      program.methods
+        .recordActivity(new anchor.BN(10))
+        .accounts({
+          userStats,
+          allowlist,
+          callerProgram: web3.SystemProgram.programId,
+          caller: caller.publicKey,
+        })
+        .signers([caller])
+        .rpc();
+
+    // Not in the allowlist.
+    try {
+      await recordActivity(web3.Keypair.generate());
+      expect.fail("record_activity should be rejected");
+    } catch (e) {
+      expect(e).to.be.instanceof(AnchorError);
+      expect(e.error.errorCode.code).to.equal("CallerNotAllowed");
+    }
+
+    // In the allowlist, but not signed by the caller program PDA.
+    await program.methods
+      .setAllowlist([web3.SystemProgram.programId])
+      .accounts({ admin: provider.wallet.publicKey, allowlist })
+      .rpc();
+    try {
+      await recordActivity(web3.Keypair.generate());
+      expect.fail("record_activity should be rejected");
+    } catch (e) {
+      expect(e).to.be.instanceof(AnchorError);
+      expect(e.error.errorCode.code).to.equal("ConstraintSeeds");
+    }
+    const got = await program.account.userStats.fetch(userStats);
+    expect(got.points.toNumber()).to.equal(0);
+  });
+
+  it("sets the cooldown of the updates by the admin", async () => {
+    const [allowlist] = web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("allowlist")],
+      program.programId
+    );
+    await program.methods
+      .setCooldown(new anchor.BN(150))
+      .accounts({ admin: provider.wallet.publicKey, allowlist })
+      .rpc();
+
+    const got = await program.account.allowlist.fetch(allowlist);
+    expect(got.cooldownSlots.toNumber()).to.equal(150);
+
+    // Not by the user.
+    const user = web3.Keypair.generate();
+    try {
+      await program.methods
+        .setCooldown(new anchor.BN(0))
+        .accounts({ admin: user.publicKey, allowlist })
+        .signers([user])
+        .rpc();
+      expect.fail("set_cooldown should be rejected");
+    } catch (e) {
+      expect(e).to.be.instanceof(AnchorError);
+      expect(e.error.errorCode.code).to.equal("ConstraintHasOne");
+    }
+  });
+
+  it("updates the compressed user stats with the prior payload", async () => {
+    const [userStats] = web3.PublicKey.findProgramAddressSync(
+      [
+        anchor.utils.bytes.utf8.encode("user-stats-hash"),
+        provider.wallet.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+    const payload = (stats: object) => Buffer.from(JSON.stringify(stats));
+    const sha256 = (data: Buffer) => [...createHash("sha256").update(data).digest()];
+
+    const first = payload({ name: "keith", points: 0 });
+    await program.methods
+      .openCompressed(first)
+      .accounts({ user: provider.wallet.publicKey, userStats })
+      .rpc();
+    let got = await program.account.compressedUserStats.fetch(userStats);
+    expect(got.payloadHash).to.deep.equal(sha256(first));
+
+    // The stale payload is rejected.
+    const second = payload({ name: "keith", points: 10 });
+    try {
+      await program.methods
+        .updateWithProof(second, second)
+        .accounts({ user: provider.wallet.publicKey, userStats })
+        .rpc();
+      expect.fail("update_with_proof should be rejected");
+    } catch (e) {
+      expect(e).to.be.instanceof(AnchorError);
+      expect(e.error.errorCode.code).to.equal("PayloadHashMismatch");
+    }
+
+    await program.methods
+      .updateWithProof(first, second)
+      .accounts({ user: provider.wallet.publicKey, userStats })
+      .rpc();
+    got = await program.account.compressedUserStats.fetch(userStats);
+    expect(got.payloadHash).to.deep.equal(sha256(second));
+    expect(got.updateCount.toNumber()).to.equal(1);
+
+    // Two payloads of the maximum length fit in a transaction.
+    const PAYLOAD_MAX = 480;
+    const third = Buffer.alloc(PAYLOAD_MAX, 3);
+    const fourth = Buffer.alloc(PAYLOAD_MAX, 4);
+    await program.methods
+      .updateWithProof(second, third)
+      .accounts({ user: provider.wallet.publicKey, userStats })
+      .rpc();
+    await program.methods
+      .updateWithProof(third, fourth)
+      .accounts({ user: provider.wallet.publicKey, userStats })
+      .rpc();
+    got = await program.account.compressedUserStats.fetch(userStats);
+    expect(got.payloadHash).to.deep.equal(sha256(fourth));
+    expect(got.updateCount.toNumber()).to.equal(3);
+
+    // But not beyond the maximum.
+    try {
+      await program.methods
+        .updateWithProof(fourth, Buffer.alloc(PAYLOAD_MAX + 1))
+        .accounts({ user: provider.wallet.publicKey, userStats })
+        .rpc();
+      expect.fail("update_with_proof should be rejected");
+    } catch (e) {
+      expect(e).to.be.instanceof(AnchorError);
+      expect(e.error.errorCode.code).to.equal("PayloadTooLarge");
+    }
+
+    await program.methods
+      .closeCompressed()
+      .accounts({ user: provider.wallet.publicKey, userStats })
+      .rpc();
+    expect(await provider.connection.getAccountInfo(userStats)).to.be.null;
+  });
+
+  it("migrates the legacy user stats", async () => {
+    // The 45 bytes legacy user stats loaded by the test validator,
+    // see `tests/fixtures/legacy-user-stats.json` and `Anchor.toml`.
+    const user = web3.Keypair.fromSecretKey(
+      Uint8Array.from(
+        JSON.parse(readFileSync("tests/fixtures/legacy-user.json", "utf8"))
+      )
+    );
+    const [userStats, bump] = web3.PublicKey.findProgramAddressSync(
+      [anchor.utils.bytes.utf8.encode("user-stats"), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const airdrop = await provider.connection.requestAirdrop(
+      user.publicKey,
+      web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(airdrop);
+
+    const before = await provider.connection.getAccountInfo(userStats);
+    expect(before.data).to.have.lengthOf(45);
+
+    await program.methods
+      .migrate()
+      .accounts({ user: user.publicKey, userStats })
+      .signers([user])
+      .rpc();
+
+    const got = await program.account.userStats.fetch(userStats);
+    expect(got.name).to.equal("legacy");
+    expect(got.bump).to.equal(bump);
+    expect(got.referrer).to.be.null;
+    expect(got.referralCount.toNumber()).to.equal(0);
+    expect(got.points.toNumber()).to.equal(0);
+
+    // Not twice.
+    try {
+      await program.methods
+        .migrate()
+        .accounts({ user: user.publicKey, userStats })
+        .signers([user])
+        .rpc();
+      expect.fail("migrate should be rejected");
+    } catch (e) {
+      expect(e).to.be.instanceof(AnchorError);
+      expect(e.error.errorCode.code).to.equal("NotLegacyUserStats");
+    }
+
+    // The migrated user stats can be closed.
+    await program.methods
+      .close()
+      .accounts({ user: user.publicKey, userStats })
+      .signers([user])
+      .rpc();
+  });
 });
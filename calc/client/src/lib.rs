@@ -0,0 +1,143 @@
+//! A client of the calc program.
+//!
+//! It provides the instruction builders for each calc program
+//! instruction, as well as the [`fetch_result`] helper to get the
+//! typed calculation result out of the `Calculator` account.
+
+use std::fmt;
+
+use anchor_lang::prelude::{AccountDeserialize, Pubkey, ToAccountMetas};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{system_program, InstructionData};
+use calc::{Calculator, RoundingMode};
+use solana_client::client_error::ClientError;
+use solana_client::rpc_client::RpcClient;
+
+pub use calc::ID as PROGRAM_ID;
+
+/// A calculation result stored in the `Calculator` account.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CalcResult {
+    /// The result of the last calculation.
+    pub result: i64,
+
+    /// The absolute remainder of the last division.
+    pub remainder: i64,
+}
+
+impl CalcResult {
+    /// Deserializes the `Calculator` account data.
+    pub fn try_from_account_data(mut data: &[u8]) -> anchor_lang::Result<Self> {
+        let calculator = Calculator::try_deserialize(&mut data)?;
+        Ok(Self {
+            result: calculator.result,
+            remainder: calculator.remainder,
+        })
+    }
+}
+
+/// Errors returned by the [`fetch_result`] function.
+#[derive(Debug)]
+pub enum Error {
+    /// RPC error.
+    Client(ClientError),
+
+    /// Invalid `Calculator` account data.
+    Account(anchor_lang::error::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Client(e) => write!(f, "client error: {e}"),
+            Self::Account(e) => write!(f, "account error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Self::Client(e)
+    }
+}
+
+impl From<anchor_lang::error::Error> for Error {
+    fn from(e: anchor_lang::error::Error) -> Self {
+        Self::Account(e)
+    }
+}
+
+/// Fetches the `Calculator` account and returns the calculation result.
+pub fn fetch_result(client: &RpcClient, calculator: &Pubkey) -> Result<CalcResult, Error> {
+    let data = client.get_account_data(calculator)?;
+    Ok(CalcResult::try_from_account_data(&data)?)
+}
+
+/// Builds the `create` instruction.
+///
+/// Both the `calculator` and the `user` should sign the transaction.
+pub fn create(calculator: &Pubkey, user: &Pubkey, greeting: String) -> Instruction {
+    let accounts = calc::accounts::Create {
+        calculator: *calculator,
+        user: *user,
+        system_program: system_program::ID,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: calc::instruction::Create { greeting }.data(),
+    }
+}
+
+/// Builds the `add` instruction.
+pub fn add(calculator: &Pubkey, a: i64, b: i64) -> Instruction {
+    calc_instruction(calculator, calc::instruction::Add { a, b }.data())
+}
+
+/// Builds the `sub` instruction.
+pub fn sub(calculator: &Pubkey, a: i64, b: i64) -> Instruction {
+    calc_instruction(calculator, calc::instruction::Sub { a, b }.data())
+}
+
+/// Builds the `mul` instruction.
+pub fn mul(calculator: &Pubkey, a: i64, b: i64) -> Instruction {
+    calc_instruction(calculator, calc::instruction::Mul { a, b }.data())
+}
+
+/// Builds the `div` instruction.
+pub fn div(calculator: &Pubkey, a: i64, b: i64) -> Instruction {
+    calc_instruction(calculator, calc::instruction::Div { a, b }.data())
+}
+
+/// Builds the `div_round` instruction.
+pub fn div_round(calculator: &Pubkey, a: i64, b: i64, mode: RoundingMode) -> Instruction {
+    calc_instruction(
+        calculator,
+        calc::instruction::DivRound { a, b, mode }.data(),
+    )
+}
+
+/// Builds the `add_big` instruction.
+pub fn add_big(calculator: &Pubkey, a: u128, b: u128) -> Instruction {
+    let (a, b) = (a.to_le_bytes(), b.to_le_bytes());
+    calc_instruction(calculator, calc::instruction::AddBig { a, b }.data())
+}
+
+/// Builds the `mul_big` instruction.
+pub fn mul_big(calculator: &Pubkey, a: u128, b: u128) -> Instruction {
+    let (a, b) = (a.to_le_bytes(), b.to_le_bytes());
+    calc_instruction(calculator, calc::instruction::MulBig { a, b }.data())
+}
+
+fn calc_instruction(calculator: &Pubkey, data: Vec<u8>) -> Instruction {
+    let accounts = calc::accounts::CalcCtx {
+        calculator: *calculator,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data,
+    }
+}
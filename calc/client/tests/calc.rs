@@ -0,0 +1,216 @@
+//! The calc program tests through the `solana-program-test` banks client.
+
+use anchor_lang::error::ERROR_CODE_OFFSET;
+use anchor_lang::{AccountDeserialize, AccountSerialize};
+use calc::{Calculator, RoundingMode};
+use calc_client::CalcResult;
+use solana_program_test::{processor, BanksClient, ProgramTest};
+use solana_sdk::account::Account;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+async fn setup() -> (BanksClient, Keypair, Hash, Pubkey) {
+    setup_with_greeting("test").await
+}
+
+/// Sets up the calculator account of `Calculator::SPACE` bytes with
+/// the `greeting`.
+async fn setup_with_greeting(greeting: &str) -> (BanksClient, Keypair, Hash, Pubkey) {
+    let mut program = ProgramTest::new("calc", calc_client::PROGRAM_ID, processor!(calc::entry));
+
+    // The native `create` instruction can't be tested here, as the
+    // program-test doesn't support the account data resizing through
+    // the system program CPI.  Let's add the calculator account directly.
+    let calculator = Pubkey::new_unique();
+    let mut data = Vec::with_capacity(Calculator::SPACE);
+    Calculator {
+        greeting: greeting.to_string(),
+        result: 0,
+        remainder: 0,
+        rounding_mode: RoundingMode::default(),
+        big_result: [0; 32],
+    }
+    .try_serialize(&mut data)
+    .unwrap();
+    data.resize(Calculator::SPACE, 0);
+    program.add_account(
+        calculator,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: calc_client::PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+    let (banks, payer, recent_blockhash) = program.start().await;
+
+    (banks, payer, recent_blockhash, calculator)
+}
+
+async fn process(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: Hash,
+    ix: Instruction,
+) -> Result<(), TransactionError> {
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&payer.pubkey()),
+        &[payer],
+        recent_blockhash,
+    );
+    banks.process_transaction(tx).await.map_err(|e| e.unwrap())
+}
+
+async fn fetch_result(banks: &mut BanksClient, calculator: Pubkey) -> CalcResult {
+    let account = banks.get_account(calculator).await.unwrap().unwrap();
+    CalcResult::try_from_account_data(&account.data).unwrap()
+}
+
+fn custom_error(error: calc::Error) -> TransactionError {
+    TransactionError::InstructionError(
+        0,
+        InstructionError::Custom(ERROR_CODE_OFFSET + error as u32),
+    )
+}
+
+#[tokio::test]
+async fn add_sub_mul_div() {
+    let (mut banks, payer, hash, calculator) = setup().await;
+
+    let ix = calc_client::add(&calculator, 1, 2);
+    process(&mut banks, &payer, hash, ix).await.unwrap();
+    let got = fetch_result(&mut banks, calculator).await;
+    assert_eq!(got.result, 3);
+
+    let ix = calc_client::sub(&calculator, 1, 2);
+    process(&mut banks, &payer, hash, ix).await.unwrap();
+    let got = fetch_result(&mut banks, calculator).await;
+    assert_eq!(got.result, -1);
+
+    let ix = calc_client::mul(&calculator, 3, -4);
+    process(&mut banks, &payer, hash, ix).await.unwrap();
+    let got = fetch_result(&mut banks, calculator).await;
+    assert_eq!(got.result, -12);
+
+    let ix = calc_client::div(&calculator, -7, 2);
+    process(&mut banks, &payer, hash, ix).await.unwrap();
+    let got = fetch_result(&mut banks, calculator).await;
+    assert_eq!(
+        got,
+        CalcResult {
+            result: -3,
+            remainder: 1
+        }
+    );
+}
+
+#[tokio::test]
+async fn div_round() {
+    let (mut banks, payer, hash, calculator) = setup().await;
+
+    let tests = [
+        (-7, 2, RoundingMode::Floor, -4, 1),
+        (-7, 2, RoundingMode::Ceil, -3, 1),
+        (-7, 2, RoundingMode::Nearest, -4, 1),
+        (-7, 2, RoundingMode::TowardZero, -3, 1),
+        (7, 2, RoundingMode::Floor, 3, 1),
+        (7, 2, RoundingMode::Ceil, 4, 1),
+        (5, 3, RoundingMode::Nearest, 2, 1),
+        (4, 3, RoundingMode::Nearest, 1, 1),
+        (-5, -3, RoundingMode::Nearest, 2, 1),
+        (6, -3, RoundingMode::Floor, -2, 0),
+        (i64::MIN, i64::MAX, RoundingMode::Floor, -2, i64::MAX - 1),
+        (i64::MAX, i64::MIN, RoundingMode::Nearest, -1, 1),
+    ];
+    for (i, (a, b, mode, result, remainder)) in tests.into_iter().enumerate() {
+        // Makes each transaction unique.
+        let ix = calc_client::add(&calculator, i as i64, 0);
+        process(&mut banks, &payer, hash, ix).await.unwrap();
+
+        let ix = calc_client::div_round(&calculator, a, b, mode);
+        process(&mut banks, &payer, hash, ix).await.unwrap();
+        let got = fetch_result(&mut banks, calculator).await;
+        assert_eq!(got, CalcResult { result, remainder }, "{a}/{b} {mode:?}");
+    }
+
+    // The mode is stored in the account.
+    let account = banks.get_account(calculator).await.unwrap().unwrap();
+    let got = Calculator::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(got.rounding_mode, RoundingMode::Nearest);
+}
+
+#[tokio::test]
+async fn div_by_zero() {
+    let (mut banks, payer, hash, calculator) = setup().await;
+
+    let ix = calc_client::div(&calculator, 1, 0);
+    let got = process(&mut banks, &payer, hash, ix).await;
+    assert_eq!(got, Err(custom_error(calc::Error::DivideByZero)));
+
+    let ix = calc_client::div_round(&calculator, 1, 0, RoundingMode::Floor);
+    let got = process(&mut banks, &payer, hash, ix).await;
+    assert_eq!(got, Err(custom_error(calc::Error::DivideByZero)));
+}
+
+#[tokio::test]
+async fn overflow() {
+    let (mut banks, payer, hash, calculator) = setup().await;
+
+    let ixs = [
+        calc_client::add(&calculator, i64::MAX, 1),
+        calc_client::sub(&calculator, i64::MIN, 1),
+        calc_client::mul(&calculator, i64::MAX, 2),
+        calc_client::div(&calculator, i64::MIN, -1),
+    ];
+    for ix in ixs {
+        let got = process(&mut banks, &payer, hash, ix).await;
+        assert_eq!(got, Err(custom_error(calc::Error::Overflow)));
+    }
+}
+
+#[tokio::test]
+async fn add_mul_big() {
+    let (mut banks, payer, hash, calculator) = setup().await;
+
+    let max = u128::MAX;
+    let tests = [
+        (calc_client::add_big(&calculator, 1, 2), (3, 0)),
+        (calc_client::add_big(&calculator, max, max), (max - 1, 1)),
+        (calc_client::mul_big(&calculator, 3, 4), (12, 0)),
+        (calc_client::mul_big(&calculator, 1 << 64, 1 << 64), (0, 1)),
+        (calc_client::mul_big(&calculator, max, max), (1, max - 1)),
+    ];
+    for (ix, (lo, hi)) in tests {
+        process(&mut banks, &payer, hash, ix).await.unwrap();
+        let account = banks.get_account(calculator).await.unwrap().unwrap();
+        let got = Calculator::try_deserialize(&mut &account.data[..]).unwrap();
+        let (got_lo, got_hi) = got.big_result.split_at(16);
+        assert_eq!(u128::from_le_bytes(got_lo.try_into().unwrap()), lo);
+        assert_eq!(u128::from_le_bytes(got_hi.try_into().unwrap()), hi);
+    }
+}
+
+#[tokio::test]
+async fn mul_big_with_the_longest_greeting() {
+    let greeting = "a".repeat(Calculator::GREETING_MAX);
+    let (mut banks, payer, hash, calculator) = setup_with_greeting(&greeting).await;
+
+    // The 32-byte result fits in the account with the longest greeting.
+    let ix = calc_client::mul_big(&calculator, u128::MAX, u128::MAX);
+    process(&mut banks, &payer, hash, ix).await.unwrap();
+    let account = banks.get_account(calculator).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), Calculator::SPACE);
+    let got = Calculator::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(got.greeting, greeting);
+    let (got_lo, got_hi) = got.big_result.split_at(16);
+    assert_eq!(u128::from_le_bytes(got_lo.try_into().unwrap()), 1);
+    assert_eq!(
+        u128::from_le_bytes(got_hi.try_into().unwrap()),
+        u128::MAX - 1
+    );
+}
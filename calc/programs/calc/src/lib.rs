@@ -2,41 +2,164 @@ use anchor_lang::prelude::*;
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+#[error_code]
+pub enum Error {
+    #[msg("Divide by zero")]
+    DivideByZero,
+
+    #[msg("Arithmetic overflow")]
+    Overflow,
+
+    #[msg("Greeting is too long")]
+    GreetingTooLong,
+}
+
 #[program]
 pub mod calc {
     use super::*;
 
     pub fn create(ctx: Context<Create>, greeting: String) -> Result<()> {
+        require_gte!(
+            Calculator::GREETING_MAX,
+            greeting.len(),
+            Error::GreetingTooLong
+        );
         let calc = &mut ctx.accounts.calculator;
         calc.greeting = greeting;
+        // The zero-filled account reads as the first variant, `Floor`.
+        calc.rounding_mode = RoundingMode::default();
         Ok(())
     }
 
     pub fn add(ctx: Context<CalcCtx>, a: i64, b: i64) -> Result<()> {
         let calc = &mut ctx.accounts.calculator;
-        calc.result = a + b;
+        calc.result = a.checked_add(b).ok_or(Error::Overflow)?;
         Ok(())
     }
 
     pub fn sub(ctx: Context<CalcCtx>, a: i64, b: i64) -> Result<()> {
         let calc = &mut ctx.accounts.calculator;
-        calc.result = a - b;
+        calc.result = a.checked_sub(b).ok_or(Error::Overflow)?;
         Ok(())
     }
 
     pub fn mul(ctx: Context<CalcCtx>, a: i64, b: i64) -> Result<()> {
         let calc = &mut ctx.accounts.calculator;
-        calc.result = a * b;
+        calc.result = a.checked_mul(b).ok_or(Error::Overflow)?;
         Ok(())
     }
 
     pub fn div(ctx: Context<CalcCtx>, a: i64, b: i64) -> Result<()> {
         let calc = &mut ctx.accounts.calculator;
-        calc.result = a / b;
-        let rem = a % b;
+        require!(b != 0, Error::DivideByZero);
+        calc.result = a.checked_div(b).ok_or(Error::Overflow)?;
+        let rem = a.checked_rem(b).ok_or(Error::Overflow)?;
         calc.remainder = if rem < 0 { -rem } else { rem };
         Ok(())
     }
+
+    /// Divides `a` by `b` with the rounding `mode`, which is stored
+    /// in the `Calculator` account.
+    ///
+    /// The `remainder` is the absolute value of `a - result * b`.
+    pub fn div_round(ctx: Context<CalcCtx>, a: i64, b: i64, mode: RoundingMode) -> Result<()> {
+        let calc = &mut ctx.accounts.calculator;
+        require!(b != 0, Error::DivideByZero);
+        let result = mode.div(a, b)?;
+        let rem = a as i128 - result as i128 * b as i128;
+        calc.result = result;
+        calc.remainder = rem.unsigned_abs() as i64;
+        calc.rounding_mode = mode;
+        Ok(())
+    }
+
+    /// Adds the 16-byte little-endian unsigned `a` and `b`, and stores
+    /// the 32-byte little-endian sum in the `big_result` field.
+    pub fn add_big(ctx: Context<CalcCtx>, a: [u8; 16], b: [u8; 16]) -> Result<()> {
+        let calc = &mut ctx.accounts.calculator;
+        let (a, b) = (u128::from_le_bytes(a), u128::from_le_bytes(b));
+        let (lo, carry) = a.overflowing_add(b);
+        calc.big_result = to_le_bytes(lo, carry as u128);
+        Ok(())
+    }
+
+    /// Multiplies the 16-byte little-endian unsigned `a` and `b`, and
+    /// stores the 32-byte little-endian product in the `big_result`
+    /// field.
+    pub fn mul_big(ctx: Context<CalcCtx>, a: [u8; 16], b: [u8; 16]) -> Result<()> {
+        let calc = &mut ctx.accounts.calculator;
+        let (a, b) = (u128::from_le_bytes(a), u128::from_le_bytes(b));
+        let (lo, hi) = widening_mul(a, b);
+        calc.big_result = to_le_bytes(lo, hi);
+        Ok(())
+    }
+}
+
+/// Multiplies `a` and `b` and returns the low and the high 128 bits
+/// of the 256-bit product, with the 64-bit limbs.
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a0, a1) = (a & MASK, a >> 64);
+    let (b0, b1) = (b & MASK, b >> 64);
+    let (p00, p01, p10, p11) = (a0 * b0, a0 * b1, a1 * b0, a1 * b1);
+    // It's less than 3 * 2^64, which never overflows.
+    let mid = (p00 >> 64) + (p01 & MASK) + (p10 & MASK);
+    let lo = (p00 & MASK) | (mid << 64);
+    let hi = p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64);
+    (lo, hi)
+}
+
+/// Returns the 32-byte little-endian representation of the `lo` and
+/// the `hi` 128 bits.
+fn to_le_bytes(lo: u128, hi: u128) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[..16].copy_from_slice(&lo.to_le_bytes());
+    bytes[16..].copy_from_slice(&hi.to_le_bytes());
+    bytes
+}
+
+/// A rounding mode of the `div_round` instruction.
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Rounds toward the negative infinity.
+    Floor,
+
+    /// Rounds toward the positive infinity.
+    Ceil,
+
+    /// Rounds to the nearest, ties away from zero.
+    Nearest,
+
+    /// Rounds toward zero, same as the `div` instruction.
+    #[default]
+    TowardZero,
+}
+
+impl RoundingMode {
+    /// Divides `a` by non-zero `b` with the `i128` intermediates.
+    pub fn div(self, a: i64, b: i64) -> Result<i64> {
+        require!(b != 0, Error::DivideByZero);
+        let (a, b) = (a as i128, b as i128);
+        let (q, r) = (a / b, a % b);
+        let negative = (a < 0) != (b < 0);
+        let q = if r == 0 {
+            q
+        } else {
+            match self {
+                Self::Floor if negative => q - 1,
+                Self::Ceil if !negative => q + 1,
+                Self::Nearest if 2 * r.abs() >= b.abs() => {
+                    if negative {
+                        q - 1
+                    } else {
+                        q + 1
+                    }
+                }
+                _ => q,
+            }
+        };
+        i64::try_from(q).map_err(|_| Error::Overflow.into())
+    }
 }
 
 #[account]
@@ -44,11 +167,27 @@ pub struct Calculator {
     pub greeting: String,
     pub result: i64,
     pub remainder: i64,
+    pub rounding_mode: RoundingMode,
+    /// A 32-byte little-endian result of `add_big` and `mul_big`.
+    pub big_result: [u8; 32],
+}
+
+impl Calculator {
+    /// A maximum length of the greeting in bytes.
+    pub const GREETING_MAX: usize = 200;
+
+    /// A space for the Calculator account.
+    pub const SPACE: usize = 8 // discriminator
+        + 4 + Self::GREETING_MAX // greeting
+        + 8 // result
+        + 8 // remainder
+        + 1 // rounding_mode
+        + 32; // big_result
 }
 
 #[derive(Accounts)]
 pub struct Create<'info> {
-    #[account(init, payer = user, space = 264)] // 256 + 8?
+    #[account(init, payer = user, space = Calculator::SPACE)]
     pub calculator: Account<'info, Calculator>,
     #[account(mut)]
     pub user: Signer<'info>,
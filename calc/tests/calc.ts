@@ -28,6 +28,26 @@ describe("calc", () => {
       .calculator
       .fetch(calcKeypair.publicKey);
     expect(got.greeting).to.equal("Welcome to Solana!");
+    expect(got.roundingMode).to.eql({ towardZero: {} });
+  });
+
+  it("rejects the long greeting", async () => {
+    const calcKeypair = anchor.web3.Keypair.generate();
+
+    try {
+      await program.methods
+        .create("a".repeat(201))
+        .accounts({
+          calculator: calcKeypair.publicKey,
+          user: wallet.publicKey,
+          system_program: program.programId,
+        })
+        .signers([calcKeypair])
+        .rpc();
+      expect.fail("create should be rejected");
+    } catch (e) {
+      expect(e.error.errorCode.code).to.equal("GreetingTooLong");
+    }
   });
 
   it("addition", async () => {
@@ -131,4 +151,64 @@ describe("calc", () => {
     expect(got.result).to.eql(new anchor.BN(2));
     expect(got.remainder).to.eql(new anchor.BN(3));
   });
+
+  it("division with rounding", async () => {
+    const calcKeypair = anchor.web3.Keypair.generate();
+    await program.methods
+      .create("division with rounding test")
+      .accounts({
+        calculator: calcKeypair.publicKey,
+        user: wallet.publicKey,
+        system_program: program.programId,
+      })
+      .signers([calcKeypair])
+      .rpc();
+
+    await program.methods
+      .divRound(new anchor.BN(-19), new anchor.BN(8), { floor: {} })
+      .accounts({
+        calculator: calcKeypair.publicKey,
+      })
+      .rpc();
+
+    const got = await program
+      .account
+      .calculator
+      .fetch(calcKeypair.publicKey);
+    expect(got.result).to.eql(new anchor.BN(-3));
+    expect(got.remainder).to.eql(new anchor.BN(5));
+    expect(got.roundingMode).to.eql({ floor: {} });
+  });
+
+  it("big multiplication", async () => {
+    const calcKeypair = anchor.web3.Keypair.generate();
+    await program.methods
+      .create("big multiplication test")
+      .accounts({
+        calculator: calcKeypair.publicKey,
+        user: wallet.publicKey,
+        system_program: program.programId,
+      })
+      .signers([calcKeypair])
+      .rpc();
+
+    // (2^128 - 1)^2 = 2^256 - 2^129 + 1.
+    const max = new Array(16).fill(0xff);
+    await program.methods
+      .mulBig(max, max)
+      .accounts({
+        calculator: calcKeypair.publicKey,
+      })
+      .rpc();
+
+    const got = await program
+      .account
+      .calculator
+      .fetch(calcKeypair.publicKey);
+    const want = new anchor.BN(1)
+      .shln(256)
+      .sub(new anchor.BN(1).shln(129))
+      .addn(1);
+    expect(new anchor.BN(got.bigResult, "le")).to.eql(want);
+  });
 });
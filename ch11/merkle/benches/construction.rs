@@ -0,0 +1,64 @@
+//! MerkleTree construction benchmark of the depth 20 tree.
+//!
+//! It also reports the number of the heap allocations per
+//! construction through the counting global allocator, as the tree
+//! construction should only allocate the node pages and the padding
+//! nodes, regardless of the number of the parent hashes.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use merkle::{MerkleTree, Padding};
+use sha3::Sha3_256;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::iter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const NR_LEAVES: usize = 524_288; // 2 ^ 19, e.g. 20 depth tree.
+
+/// The global allocator counting the allocations.
+struct Counter;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counter = Counter;
+
+fn build(leaves: &[[u8; 32]], padding: Padding) -> MerkleTree<Sha3_256> {
+    MerkleTree::with_padding(leaves, padding)
+}
+
+fn construction(c: &mut Criterion) {
+    let leaves: Vec<_> = iter::repeat_n([0xabu8; 32], NR_LEAVES).collect();
+    let mut group = c.benchmark_group("construction");
+    group.sample_size(10);
+    for (name, padding) in [
+        ("duplicate", Padding::Duplicate),
+        ("empty_subtree", Padding::EmptySubtree),
+    ] {
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let tree = build(&leaves, padding);
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+        println!(
+            "construction/{name}: {allocations} allocations for {} leaves",
+            tree.leaves().count()
+        );
+        drop(tree);
+
+        group.bench_function(name, |b| {
+            b.iter_batched(|| (), |_| build(&leaves, padding), BatchSize::PerIteration)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, construction);
+criterion_main!(benches);
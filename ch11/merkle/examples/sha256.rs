@@ -1,5 +1,5 @@
 //! Merkle Tree
-use merkle::MerkleTree;
+use merkle::{verify_batch, BatchMode, HashDisplay, MerkleTree};
 use sha3::Sha3_256;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -22,14 +22,15 @@ fn main() {
 
     let tree: MerkleTree<Sha3_256> = std::iter::repeat([0xabu8; 32]).take(nr_leaves).collect();
     for (i, leave) in tree.leaves().take(4).enumerate() {
-        println!("leaf[{i}]={:02x?}", leave);
+        println!("leaf[{i}]={}", HashDisplay::new(leave));
     }
     if tree.leaves().count() > 4 {
         println!("truncated {} leaves...", tree.leaves().count() - 4);
     }
     let root = tree.root();
     println!("tree.root.len={}", root.len());
-    println!("tree.root={:02x?}", root);
+    println!("tree.root={}", tree.root_display());
+    println!("tree.root.base58={}", tree.root_display().to_base58());
 
     // merkle proof and verification.
     println!("create merkle tree for {} leaves", nr_leaves);
@@ -51,10 +52,16 @@ fn main() {
         for leaves_chunk in chunks {
             let tree = tree0.clone();
             spawner.spawn(move |_| {
-                for (i, leaf) in leaves_chunk {
-                    let proof = tree.proof(*i).unwrap();
-                    assert_eq!(proof.verify(leaf).as_ref(), tree.root());
-                }
+                let proofs = leaves_chunk
+                    .iter()
+                    .map(|(i, leaf)| (tree.proof(*i as u64).unwrap(), leaf));
+                let result = verify_batch(tree.root(), proofs, BatchMode::Strict);
+                assert!(result.is_ok(), "failed proofs: {:?}", result.failures());
+                println!(
+                    "verified {} proofs in {:?}",
+                    result.verified(),
+                    result.elapsed(),
+                );
             });
         }
     })
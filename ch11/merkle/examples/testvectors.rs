@@ -0,0 +1,30 @@
+//! Merkle tree test vectors
+//!
+//! Usage: testvectors [legacy|rfc6962] [seed] [leaves]
+use merkle::testvectors::{generate, Mode};
+use sha3::Sha3_256;
+use std::str::FromStr;
+
+const SEED: u64 = 0;
+const NR_LEAVES: usize = 8;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mode = match args.next().as_deref() {
+        None | Some("legacy") => Mode::Legacy,
+        Some("rfc6962") => Mode::Rfc6962,
+        Some(mode) => panic!("unsupported mode: {mode}"),
+    };
+    let seed = args
+        .next()
+        .as_ref()
+        .and_then(|v| u64::from_str(v).ok())
+        .unwrap_or(SEED);
+    let nr_leaves = args
+        .next()
+        .as_ref()
+        .and_then(|v| usize::from_str(v).ok())
+        .unwrap_or(NR_LEAVES);
+
+    println!("{}", generate::<Sha3_256>(mode, seed, nr_leaves).to_json());
+}
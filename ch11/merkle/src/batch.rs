@@ -0,0 +1,137 @@
+//! Bulk MerkleProof verification.
+use super::MerkleProof;
+use digest::Digest;
+use std::borrow::Borrow;
+use std::time::{Duration, Instant};
+
+/// Verification mode of the [`verify_batch`] function.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BatchMode {
+    /// Stops at the first mismatch.
+    #[default]
+    Strict,
+
+    /// Verifies all the proofs and collects every mismatch.
+    Lenient,
+}
+
+/// BatchResult type to be returned by the [`verify_batch`] function.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchResult {
+    checked: usize,
+    verified: usize,
+    failures: Vec<usize>,
+    elapsed: Duration,
+}
+
+impl BatchResult {
+    /// Returns `true` if all the checked proofs are verified.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Number of the proofs checked before returning.
+    ///
+    /// It's less than the number of the given proofs in case
+    /// of the early exit in [`BatchMode::Strict`] mode.
+    pub fn checked(&self) -> usize {
+        self.checked
+    }
+
+    /// Number of the verified proofs.
+    pub fn verified(&self) -> usize {
+        self.verified
+    }
+
+    /// Positions of the failed proofs in the given iterator.
+    pub fn failures(&self) -> &[usize] {
+        &self.failures
+    }
+
+    /// Time spent for the verification.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Verifies many `(proof, leaf)` pairs against a single merkle root.
+pub fn verify_batch<B, I, P, T>(root: &[u8], proofs_and_leaves: I, mode: BatchMode) -> BatchResult
+where
+    B: Digest,
+    I: IntoIterator<Item = (P, T)>,
+    P: Borrow<MerkleProof<B>>,
+    T: AsRef<[u8]>,
+{
+    let start = Instant::now();
+    let mut result = BatchResult::default();
+
+    for (i, (proof, leaf)) in proofs_and_leaves.into_iter().enumerate() {
+        result.checked += 1;
+        if proof.borrow().verify(leaf).as_ref() == root {
+            result.verified += 1;
+            continue;
+        }
+        result.failures.push(i);
+        if mode == BatchMode::Strict {
+            break;
+        }
+    }
+    result.elapsed = start.elapsed();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_batch, BatchMode};
+    use crate::MerkleTree;
+    use sha3::Sha3_256;
+
+    #[test]
+    fn verify_batch_all_verified() {
+        let tree: MerkleTree<Sha3_256> = (0..16).map(|i| [0x11u8 * i as u8; 32]).collect();
+        let proofs =
+            (0..16).map(|i| (tree.proof(i as u64).unwrap(), tree.leaves().nth(i).unwrap()));
+
+        let got = verify_batch(tree.root(), proofs, BatchMode::Strict);
+        assert!(got.is_ok());
+        assert_eq!(got.checked(), 16);
+        assert_eq!(got.verified(), 16);
+        assert!(got.failures().is_empty());
+    }
+
+    #[test]
+    fn verify_batch_strict_early_exit() {
+        let tree: MerkleTree<Sha3_256> = (0..16).map(|i| [0x11u8 * i as u8; 32]).collect();
+        let bad = [0xffu8; 32];
+        let proofs = (0..16).map(|i| {
+            let leaf = match i {
+                3 | 7 => &bad[..],
+                i => tree.leaves().nth(i).unwrap(),
+            };
+            (tree.proof(i as u64).unwrap(), leaf)
+        });
+
+        let got = verify_batch(tree.root(), proofs, BatchMode::Strict);
+        assert!(!got.is_ok());
+        assert_eq!(got.checked(), 4);
+        assert_eq!(got.verified(), 3);
+        assert_eq!(got.failures(), &[3]);
+    }
+
+    #[test]
+    fn verify_batch_lenient_collects_failures() {
+        let tree: MerkleTree<Sha3_256> = (0..16).map(|i| [0x11u8 * i as u8; 32]).collect();
+        let bad = [0xffu8; 32];
+        let proofs: Vec<_> = (0..16).map(|i| tree.proof(i).unwrap()).collect();
+        let leaves = (0..16).map(|i| match i {
+            3 | 7 => &bad[..],
+            i => tree.leaves().nth(i).unwrap(),
+        });
+
+        let got = verify_batch(tree.root(), proofs.iter().zip(leaves), BatchMode::Lenient);
+        assert!(!got.is_ok());
+        assert_eq!(got.checked(), 16);
+        assert_eq!(got.verified(), 14);
+        assert_eq!(got.failures(), &[3, 7]);
+    }
+}
@@ -0,0 +1,122 @@
+//! MerkleProof LRU cache for the hot leaves.
+use super::{MerkleProof, MerkleTree};
+use digest::Digest;
+use hashlink::LruCache;
+use std::io::Result;
+
+/// MerkleTree with the LRU cache of the proofs keyed by the leaf index.
+///
+/// It's for the servers answering many repeated proof requests, e.g.
+/// the airdrop program backend, to avoid rebuilding the identical
+/// sibling lists.  All the cached proofs are invalidated on [`set`],
+/// as the updated leaf changes the sibling of every other proof.
+///
+/// [`set`]: CachedMerkleTree::set
+#[derive(Debug)]
+pub struct CachedMerkleTree<B>
+where
+    B: Digest,
+{
+    tree: MerkleTree<B>,
+    cache: LruCache<u64, MerkleProof<B>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl<B> CachedMerkleTree<B>
+where
+    B: Digest,
+{
+    /// Wraps the `tree` with the cache holding up to `capacity` proofs.
+    pub fn new(tree: MerkleTree<B>, capacity: usize) -> Self {
+        assert!(capacity != 0, "zero capacity cache is not supported");
+        Self {
+            tree,
+            cache: LruCache::new(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn root(&self) -> &[u8] {
+        self.tree.root()
+    }
+
+    pub fn leaves(&self) -> impl Iterator<Item = &[u8]> {
+        self.tree.leaves()
+    }
+
+    /// Returns the proof of the leaf, from the cache if it's there.
+    pub fn proof(&mut self, index: u64) -> Result<MerkleProof<B>> {
+        if let Some(proof) = self.cache.get(&index) {
+            self.hits += 1;
+            return Ok(proof.clone());
+        }
+        let proof = self.tree.proof(index)?;
+        self.misses += 1;
+        self.cache.insert(index, proof.clone());
+        Ok(proof)
+    }
+
+    /// Sets the leaf hash and invalidates the cached proofs.
+    pub fn set(&mut self, index: u64, hash: &[u8]) -> Result<()> {
+        let root = self.tree.root().to_vec();
+        self.tree.set(index, hash)?;
+        if self.tree.root() != root.as_slice() {
+            self.cache.clear();
+        }
+        Ok(())
+    }
+
+    /// Number of the proofs served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Number of the proofs built from the tree.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns the inner tree.
+    pub fn into_inner(self) -> MerkleTree<B> {
+        self.tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CachedMerkleTree;
+    use crate::MerkleTree;
+    use sha3::Sha3_256;
+
+    #[test]
+    fn cached_proof() {
+        let tree: MerkleTree<Sha3_256> = (0..8).map(|i| [i as u8; 32]).collect();
+        let mut cached = CachedMerkleTree::new(tree.clone(), 2);
+
+        for index in [0, 0, 1, 0, 2, 1] {
+            let got = cached.proof(index).unwrap();
+            let leaf = tree.leaves().nth(index as usize).unwrap();
+            assert_eq!(got.verify(leaf).as_ref(), tree.root());
+        }
+        // 0 and 1 are hit once each, and 1 is evicted by 2.
+        assert_eq!(cached.hits(), 2);
+        assert_eq!(cached.misses(), 4);
+        assert!(cached.proof(8).is_err());
+    }
+
+    #[test]
+    fn cached_proof_invalidated_on_set() {
+        let tree: MerkleTree<Sha3_256> = (0..8).map(|i| [i as u8; 32]).collect();
+        let mut cached = CachedMerkleTree::new(tree, 8);
+        let _ = cached.proof(1).unwrap();
+
+        cached.set(0, &[0xff; 32]).unwrap();
+        let got = cached.proof(1).unwrap();
+        assert_eq!(cached.hits(), 0);
+        assert_eq!(cached.misses(), 2);
+        let leaf = cached.leaves().nth(1).unwrap();
+        assert_eq!(got.verify(leaf).as_ref(), cached.root());
+    }
+}
@@ -0,0 +1,106 @@
+//! Structural equality and hashing of the merkle trees and proofs.
+//!
+//! The trees are compared by the padding and the leaves, as the root
+//! alone can't tell the padding apart, e.g. `[a, b, c]` with
+//! [`Padding::ZeroHash`] and `[a, b, c, 0]` with [`Padding::Duplicate`].
+//!
+//! [`Padding::ZeroHash`]: super::Padding::ZeroHash
+//! [`Padding::Duplicate`]: super::Padding::Duplicate
+use super::{MerkleProof, MerkleProofData, MerkleTree};
+use digest::{Digest, OutputSizeUser};
+use std::hash::{Hash, Hasher};
+
+impl<B> PartialEq for MerkleTree<B>
+where
+    B: Digest,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.padding == other.padding && self.leaves().eq(other.leaves())
+    }
+}
+
+impl<B> Eq for MerkleTree<B> where B: Digest {}
+
+impl<B> Hash for MerkleTree<B>
+where
+    B: Digest,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.padding.hash(state);
+        self.leaf_range.len().hash(state);
+        self.leaves().for_each(|leaf| leaf.hash(state));
+    }
+}
+
+impl<B> PartialEq for MerkleProof<B>
+where
+    B: OutputSizeUser,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<B> Eq for MerkleProof<B> where B: OutputSizeUser {}
+
+impl<B> Hash for MerkleProof<B>
+where
+    B: OutputSizeUser,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<B> PartialEq for MerkleProofData<B>
+where
+    B: OutputSizeUser,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.kind() == other.kind()
+            && self.sibling() == other.sibling()
+            && self.sibling_index() == other.sibling_index()
+    }
+}
+
+impl<B> Eq for MerkleProofData<B> where B: OutputSizeUser {}
+
+impl<B> Hash for MerkleProofData<B>
+where
+    B: OutputSizeUser,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind().hash(state);
+        self.sibling().hash(state);
+        self.sibling_index().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{MerkleTree, Padding};
+    use sha3::Sha3_256;
+    use std::collections::HashSet;
+
+    #[test]
+    fn cmp_tree_and_proof() {
+        let leaves = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        let a: MerkleTree<Sha3_256> = leaves.iter().collect();
+        let b: MerkleTree<Sha3_256> = leaves.iter().collect();
+        let c: MerkleTree<Sha3_256> = leaves.iter().chain(&[[0u8; 32]]).collect();
+        let d = MerkleTree::<Sha3_256>::with_padding(leaves.iter(), Padding::ZeroHash);
+
+        // The same root, but the different padding.
+        assert_eq!(c.root(), d.root());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(c, d);
+        let trees: HashSet<_> = [&a, &b, &c, &d].into_iter().collect();
+        assert_eq!(trees.len(), 3);
+
+        let proofs: HashSet<_> = (0..3).chain(0..3).map(|i| a.proof(i).unwrap()).collect();
+        assert_eq!(proofs.len(), 3);
+        assert_eq!(c.proof(2).unwrap(), d.proof(2).unwrap());
+        assert_ne!(a.proof(0).unwrap()[0], a.proof(1).unwrap()[0]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Account-state commitment.
+//!
+//! It builds the [`MerkleTree`] over the `(key, account_data)` pairs,
+//! e.g. the Solana `(Pubkey, AccountInfo::data)` pairs, so that the
+//! state of the accounts can be committed by the merkle root and
+//! proved account by account, e.g. for the light client experiments.
+//!
+//! The leaf is the canonical encoding of the pair hashed by `B`:
+//!
+//! ```text
+//! B(key_len as u32 LE | key | data_len as u64 LE | data)
+//! ```
+//!
+//! and the leaves are sorted by the key bytes, so that the root doesn't
+//! depend on the order of the given accounts.
+use super::{MerkleProof, MerkleTree};
+use digest::{Digest, Output};
+use std::io::{self, Result};
+
+/// Account-state commitment keyed by `K`, e.g. `Pubkey`.
+#[derive(Clone, Debug)]
+pub struct AccountCommitment<B, K>
+where
+    B: Digest,
+{
+    tree: MerkleTree<B>,
+    keys: Vec<K>,
+}
+
+impl<B, K> AccountCommitment<B, K>
+where
+    B: Digest,
+    K: AsRef<[u8]>,
+{
+    /// Builds the commitment of the `accounts`.
+    ///
+    /// It returns the error for the empty accounts as well as
+    /// the duplicate keys.
+    pub fn new<I, D>(accounts: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (K, D)>,
+        D: AsRef<[u8]>,
+    {
+        let mut accounts: Vec<_> = accounts
+            .into_iter()
+            .map(|(key, data)| {
+                let leaf = account_leaf::<B>(key.as_ref(), data.as_ref());
+                (key, leaf)
+            })
+            .collect();
+        if accounts.is_empty() {
+            return Err(invalid_input("empty accounts".to_string()));
+        }
+        accounts.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+        if let Some(i) =
+            (1..accounts.len()).find(|&i| accounts[i - 1].0.as_ref() == accounts[i].0.as_ref())
+        {
+            return Err(invalid_input(format!("duplicate account key at {i}")));
+        }
+        let tree = accounts.iter().map(|(_, leaf)| leaf).collect();
+        let keys = accounts.into_iter().map(|(key, _)| key).collect();
+        Ok(Self { tree, keys })
+    }
+
+    /// Returns the merkle root of the accounts.
+    pub fn root(&self) -> &[u8] {
+        self.tree.root()
+    }
+
+    /// Returns the number of the accounts.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if there is no account, which never happens.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the account keys in the leaf order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.keys.iter()
+    }
+
+    /// Returns the proof of the account.
+    pub fn proof(&self, key: &K) -> Result<MerkleProof<B>> {
+        self.tree.proof(self.try_index(key)? as u64)
+    }
+
+    /// Updates the account data.
+    pub fn update(&mut self, key: &K, data: &[u8]) -> Result<()> {
+        let index = self.try_index(key)?;
+        let leaf = account_leaf::<B>(key.as_ref(), data);
+        if index + 1 != self.keys.len() {
+            return self.tree.set(index as u64, &leaf);
+        }
+
+        // Rebuilds the tree for the last account, as the duplicated
+        // nodes on its path are not updated by `MerkleTree::set`.
+        let mut leaves: Vec<_> = self
+            .tree
+            .leaves()
+            .take(self.keys.len())
+            .map(<[u8]>::to_vec)
+            .collect();
+        leaves[index] = leaf.to_vec();
+        self.tree = leaves.iter().collect();
+        Ok(())
+    }
+
+    fn try_index(&self, key: &K) -> Result<usize> {
+        self.keys
+            .binary_search_by(|probe| probe.as_ref().cmp(key.as_ref()))
+            .map_err(|_| invalid_input("unknown account key".to_string()))
+    }
+}
+
+/// Returns the leaf hash of the account in the canonical encoding.
+pub fn account_leaf<B>(key: &[u8], data: &[u8]) -> Output<B>
+where
+    B: Digest,
+{
+    B::new()
+        .chain_update((key.len() as u32).to_le_bytes())
+        .chain_update(key)
+        .chain_update((data.len() as u64).to_le_bytes())
+        .chain_update(data)
+        .finalize()
+}
+
+/// Verifies the account `(key, data)` against the `root` with the `proof`.
+pub fn verify_account<B>(root: &[u8], key: &[u8], data: &[u8], proof: &MerkleProof<B>) -> bool
+where
+    B: Digest,
+{
+    let leaf = account_leaf::<B>(key, data);
+    // The single account tree has the empty proof.
+    if proof.is_empty() {
+        return leaf.as_slice() == root;
+    }
+    proof.verify(leaf).as_ref() == root
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_account, AccountCommitment};
+    use sha3::Sha3_256;
+
+    fn accounts(n: u8) -> Vec<([u8; 32], Vec<u8>)> {
+        (0..n).map(|i| ([i; 32], vec![i; i as usize])).collect()
+    }
+
+    #[test]
+    fn commitment_proof_verify() {
+        for n in [1, 2, 3, 16, 17] {
+            let accounts = accounts(n);
+            let commitment = AccountCommitment::<Sha3_256, _>::new(accounts.clone()).unwrap();
+            assert_eq!(commitment.len(), n as usize);
+            for (key, data) in &accounts {
+                let proof = commitment.proof(key).unwrap();
+                assert!(verify_account(commitment.root(), key, data, &proof));
+                assert!(!verify_account(commitment.root(), key, b"forged", &proof));
+            }
+        }
+    }
+
+    #[test]
+    fn commitment_canonical_order() {
+        let accounts = accounts(9);
+        let a = AccountCommitment::<Sha3_256, _>::new(accounts.clone()).unwrap();
+        let b = AccountCommitment::<Sha3_256, _>::new(accounts.into_iter().rev()).unwrap();
+        assert_eq!(a.root(), b.root());
+        assert!(a.keys().zip(a.keys().skip(1)).all(|(x, y)| x < y));
+    }
+
+    #[test]
+    fn commitment_update() {
+        let mut accounts = accounts(5);
+        let mut commitment = AccountCommitment::<Sha3_256, _>::new(accounts.clone()).unwrap();
+        for i in [3, 4] {
+            accounts[i].1 = b"updated".to_vec();
+            commitment.update(&accounts[i].0, &accounts[i].1).unwrap();
+
+            let want = AccountCommitment::<Sha3_256, _>::new(accounts.clone()).unwrap();
+            assert_eq!(commitment.root(), want.root(), "i={i}");
+        }
+        assert!(commitment.update(&[0xff; 32], b"unknown").is_err());
+    }
+
+    #[test]
+    fn commitment_invalid_accounts() {
+        let empty: Vec<([u8; 32], Vec<u8>)> = vec![];
+        assert!(AccountCommitment::<Sha3_256, _>::new(empty).is_err());
+        let duplicate = vec![([1; 32], vec![1]), ([1; 32], vec![2])];
+        assert!(AccountCommitment::<Sha3_256, _>::new(duplicate).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Compressed merkle proof encoding.
+//!
+//! The compressed proof replaces the per-level direction enum with the
+//! direction bitmap, followed by the concatenated sibling hashes:
+//!
+//! ```text
+//! +--------+---------------------+----------------------------------+
+//! | levels | direction bitmap    | siblings                         |
+//! | 1 byte | (levels + 7) / 8    | levels * B::output_size() bytes  |
+//! +--------+---------------------+----------------------------------+
+//! ```
+//!
+//! Bit `i % 8` of the bitmap byte `i / 8` is set when the level `i`,
+//! counted from the leaf, is [`MerkleProofDataKind::Left`], e.g. the
+//! hash is on the left and the sibling is on the right.  The unused
+//! bits of the last bitmap byte should be zero.
+use super::{MerkleProof, MerkleProofData, MerkleProofDataKind};
+use digest::{Digest, Output};
+use std::io::{self, Result};
+
+impl<B> MerkleProof<B>
+where
+    B: Digest,
+{
+    /// Returns the compressed proof.
+    pub fn compress(&self) -> Vec<u8> {
+        let levels = self.0.len();
+        assert!(levels <= u8::MAX as usize, "too deep merkle proof");
+        let bitmap_len = levels.div_ceil(8);
+        let mut data = vec![0u8; 1 + bitmap_len];
+        data[0] = levels as u8;
+        data.reserve(levels * <B as Digest>::output_size());
+        for (i, proof) in self.0.iter().enumerate() {
+            if proof.kind() == MerkleProofDataKind::Left {
+                data[1 + i / 8] |= 1 << (i % 8);
+            }
+            data.extend_from_slice(proof.sibling());
+        }
+        data
+    }
+
+    /// Returns the proof decompressed from the [`Self::compress`] output.
+    pub fn decompress(data: &[u8]) -> Result<Self> {
+        let (levels, rest) = data
+            .split_first()
+            .ok_or_else(|| invalid_data("empty compressed proof".to_string()))?;
+        let levels = *levels as usize;
+        let bitmap_len = levels.div_ceil(8);
+        let hash_len = <B as Digest>::output_size();
+        let want = bitmap_len + levels * hash_len;
+        if rest.len() != want {
+            return Err(invalid_data(format!(
+                "invalid compressed proof length: {}!={want}",
+                rest.len(),
+            )));
+        }
+        if levels > u64::BITS as usize {
+            return Err(invalid_data(format!("too deep merkle proof: {levels}")));
+        }
+        let (bitmap, siblings) = rest.split_at(bitmap_len);
+        if !levels.is_multiple_of(8) && bitmap[bitmap_len - 1] >> (levels % 8) != 0 {
+            return Err(invalid_data("invalid direction bitmap".to_string()));
+        }
+        let is_left = |i: usize| bitmap[i / 8] & (1 << (i % 8)) != 0;
+
+        // the leaf index, as the bits of the right hand side levels.
+        let leaf = (0..levels)
+            .filter(|i| !is_left(*i))
+            .fold(0u64, |index, i| index | 1 << i);
+        let proof = siblings
+            .chunks_exact(hash_len)
+            .enumerate()
+            .map(|(i, sibling)| {
+                let kind = if is_left(i) {
+                    MerkleProofDataKind::Left
+                } else {
+                    MerkleProofDataKind::Right
+                };
+                let sibling_index = (leaf >> i) ^ 1;
+                MerkleProofData(kind, Output::<B>::clone_from_slice(sibling), sibling_index)
+            })
+            .collect();
+        Ok(Self(proof))
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MerkleProof, MerkleTree};
+    use sha3::Sha3_256;
+
+    #[test]
+    fn compress_round_trip() {
+        for leaves in [2, 3, 16, 17, 300] {
+            let tree: MerkleTree<Sha3_256> = (0..leaves).map(|i| [i as u8; 32]).collect();
+            for i in 0..leaves {
+                let proof = tree.proof(i).unwrap();
+                let data = proof.compress();
+                assert_eq!(data.len(), 1 + proof.len().div_ceil(8) + proof.len() * 32);
+
+                let got = MerkleProof::<Sha3_256>::decompress(&data).unwrap();
+                assert_eq!(got.len(), proof.len());
+                for (got, want) in got.iter().zip(proof.iter()) {
+                    assert_eq!(got.kind(), want.kind());
+                    assert_eq!(got.sibling(), want.sibling());
+                    assert_eq!(got.sibling_index(), want.sibling_index());
+                }
+                let leaf = tree.leaves().nth(i as usize).unwrap();
+                assert_eq!(got.verify(leaf).as_ref(), tree.root());
+            }
+        }
+    }
+
+    #[test]
+    fn decompress_invalid_data() {
+        let tree: MerkleTree<Sha3_256> = (0..4).map(|i| [i as u8; 32]).collect();
+        let data = tree.proof(1).unwrap().compress();
+
+        // empty, truncated, and extra bytes.
+        assert!(MerkleProof::<Sha3_256>::decompress(&[]).is_err());
+        assert!(MerkleProof::<Sha3_256>::decompress(&data[..data.len() - 1]).is_err());
+        let mut extra = data.clone();
+        extra.push(0);
+        assert!(MerkleProof::<Sha3_256>::decompress(&extra).is_err());
+
+        // unused direction bits.
+        let mut bitmap = data;
+        bitmap[1] |= 0x80;
+        assert!(MerkleProof::<Sha3_256>::decompress(&bitmap).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Hex and base58 display helpers for the merkle roots and proofs.
+use super::{MerkleProof, MerkleProofData, MerkleProofDataKind, MerkleTree};
+use digest::{Digest, OutputSizeUser};
+use std::fmt;
+
+/// HashDisplay to format the hash value in hex or base58.
+///
+/// The `Display` implementation is the lower hex representation,
+/// e.g. `format!("{}", hash)` and `format!("{:x}", hash)` are the same.
+/// The alternate flag, e.g. `{:#x}`, prepends the `0x` prefix.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HashDisplay<'a>(&'a [u8]);
+
+impl<'a> HashDisplay<'a> {
+    pub fn new(hash: &'a [u8]) -> Self {
+        Self(hash)
+    }
+
+    /// Returns the base58 representation, as used by Solana.
+    pub fn to_base58(&self) -> String {
+        bs58::encode(self.0).into_string()
+    }
+}
+
+impl fmt::Display for HashDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl fmt::LowerHex for HashDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str("0x")?;
+        }
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<B> MerkleTree<B>
+where
+    B: Digest,
+{
+    /// Returns the displayable merkle root.
+    pub fn root_display(&self) -> HashDisplay<'_> {
+        HashDisplay(self.root())
+    }
+}
+
+impl<B> MerkleProofData<B>
+where
+    B: OutputSizeUser,
+{
+    /// Returns the base58 representation of the sibling hash.
+    pub fn to_base58(&self) -> String {
+        HashDisplay(self.sibling()).to_base58()
+    }
+}
+
+/// Formats the proof data as `left:<sibling>` or `right:<sibling>`.
+impl<B> fmt::Display for MerkleProofData<B>
+where
+    B: OutputSizeUser,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind() {
+            MerkleProofDataKind::Left => "left",
+            MerkleProofDataKind::Right => "right",
+        };
+        write!(f, "{kind}:{}", HashDisplay(self.sibling()))
+    }
+}
+
+/// Formats the sibling hash in lower hex.
+impl<B> fmt::LowerHex for MerkleProofData<B>
+where
+    B: OutputSizeUser,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&HashDisplay(self.sibling()), f)
+    }
+}
+
+/// Formats the proof as the list of the proof data, from the leaf to the root.
+impl<B> fmt::Display for MerkleProof<B>
+where
+    B: OutputSizeUser,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[")?;
+        for (i, data) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{data}")?;
+        }
+        f.write_str("]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashDisplay;
+    use crate::MerkleTree;
+    use sha3::Sha3_256;
+
+    #[test]
+    fn hash_display_hex() {
+        let hash = HashDisplay::new(&[0x00, 0x0a, 0xff]);
+        assert_eq!(format!("{hash}"), "000aff");
+        assert_eq!(format!("{hash:x}"), "000aff");
+        assert_eq!(format!("{hash:#x}"), "0x000aff");
+    }
+
+    #[test]
+    fn hash_display_base58() {
+        let hash = HashDisplay::new(&[0u8; 32]);
+        assert_eq!(hash.to_base58(), "11111111111111111111111111111111");
+    }
+
+    #[test]
+    fn proof_display() {
+        let tree: MerkleTree<Sha3_256> = (0..4).map(|i| [0x11u8 * i as u8; 32]).collect();
+        let proof = tree.proof(0).unwrap();
+        let got = proof.to_string();
+        assert!(got.starts_with(&format!("[left:{}", "11".repeat(32))));
+        assert_eq!(
+            proof[0].to_base58(),
+            HashDisplay::new(&[0x11; 32]).to_base58()
+        );
+        assert_eq!(format!("{:x}", proof[0]), "11".repeat(32));
+    }
+}
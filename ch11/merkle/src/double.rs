@@ -0,0 +1,133 @@
+//! Double hash digest, e.g. Bitcoin's double SHA-256.
+//!
+//! [`DoubleHash`] wraps the digest `B` and hashes the output once more,
+//! so that the [`MerkleTree`] of the Bitcoin transaction IDs gives the
+//! block merkle root with the default [`Padding::Duplicate`]:
+//!
+//! ```ignore
+//! let tree: MerkleTree<DoubleHash<Sha256>> = txids.iter().collect();
+//! ```
+//!
+//! The transaction IDs and the root are in the internal byte order,
+//! e.g. the reverse of the block explorers.  The single transaction
+//! block is not supported, as the tree always hashes the leaves.
+//!
+//! [`MerkleTree`]: super::MerkleTree
+//! [`Padding::Duplicate`]: super::Padding::Duplicate
+use digest::{
+    Digest, FixedOutput, FixedOutputReset, HashMarker, Output, OutputSizeUser, Reset, Update,
+};
+use std::mem;
+
+/// DoubleHash digest, `B(B(data))`.
+#[derive(Clone, Debug)]
+pub struct DoubleHash<B>(B);
+
+impl<B> Default for DoubleHash<B>
+where
+    B: Digest,
+{
+    fn default() -> Self {
+        Self(B::new())
+    }
+}
+
+impl<B> HashMarker for DoubleHash<B> where B: Digest {}
+
+impl<B> OutputSizeUser for DoubleHash<B>
+where
+    B: Digest,
+{
+    type OutputSize = <B as OutputSizeUser>::OutputSize;
+}
+
+impl<B> Update for DoubleHash<B>
+where
+    B: Digest,
+{
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.0, data);
+    }
+}
+
+impl<B> FixedOutput for DoubleHash<B>
+where
+    B: Digest,
+{
+    fn finalize_into(self, out: &mut Output<Self>) {
+        let first = self.0.finalize();
+        Digest::finalize_into(B::new().chain_update(first), out);
+    }
+}
+
+impl<B> Reset for DoubleHash<B>
+where
+    B: Digest,
+{
+    fn reset(&mut self) {
+        self.0 = B::new();
+    }
+}
+
+impl<B> FixedOutputReset for DoubleHash<B>
+where
+    B: Digest,
+{
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        let first = mem::take(self).0.finalize();
+        Digest::finalize_into(B::new().chain_update(first), out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DoubleHash;
+    use crate::MerkleTree;
+    use digest::Digest;
+    use hex_literal::hex;
+    use sha2::Sha256;
+
+    /// Builds the tree of the transaction IDs in the block explorer
+    /// byte order and returns the root in the same order.
+    fn block_root(txids: &[[u8; 32]]) -> Vec<u8> {
+        let tree: MerkleTree<DoubleHash<Sha256>> = txids
+            .iter()
+            .map(|txid| txid.iter().rev().copied().collect::<Vec<_>>())
+            .collect();
+        tree.root().iter().rev().copied().collect()
+    }
+
+    #[test]
+    fn double_hash_digest() {
+        let want = Sha256::digest(Sha256::digest(b"hello"));
+        assert_eq!(DoubleHash::<Sha256>::digest(b"hello"), want);
+
+        let mut hasher = DoubleHash::<Sha256>::new();
+        Digest::update(&mut hasher, b"hel");
+        Digest::update(&mut hasher, b"lo");
+        assert_eq!(hasher.finalize_reset(), want);
+        assert_eq!(hasher.chain_update(b"hello").finalize(), want);
+    }
+
+    #[test]
+    fn double_hash_bitcoin_block_170() {
+        let txids = [
+            hex!("b1fea52486ce0c62bb442b530a3f0132b826c74e473d1f2c220bfa78111c5082"),
+            hex!("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"),
+        ];
+        let want = hex!("7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff");
+        assert_eq!(block_root(&txids), want);
+    }
+
+    #[test]
+    fn double_hash_bitcoin_block_100000() {
+        let txids = [
+            hex!("8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87"),
+            hex!("fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4"),
+            hex!("6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4"),
+            hex!("e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d"),
+        ];
+        let want = hex!("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766");
+        assert_eq!(block_root(&txids), want);
+    }
+}
@@ -1,39 +1,83 @@
 //! MarkelTree
 use digest::{Digest, Output, OutputSizeUser};
-use generic_array::{ArrayLength, GenericArray};
+use generic_array::GenericArray;
 use std::fmt::{self, Debug};
 use std::io::{self, Result};
 use std::iter::FromIterator;
-use std::mem;
 use std::ops::{Deref, Range};
 
-type Data<B> = <<B as OutputSizeUser>::OutputSize as ArrayLength<u8>>::ArrayType;
+use pages::Pages;
+
+pub use batch::{verify_batch, BatchMode, BatchResult};
+#[cfg(feature = "cache")]
+pub use cache::CachedMerkleTree;
+pub use commitment::{account_leaf, verify_account, AccountCommitment};
+pub use display::HashDisplay;
+pub use double::DoubleHash;
+pub use padding::Padding;
+pub use sorted::{AbsenceProof, SortedMerkleTree};
+pub use stream::StreamVerifier;
+pub use subtree::MerkleSubtree;
+
+mod batch;
+#[cfg(feature = "cache")]
+mod cache;
+mod cmp;
+mod commitment;
+mod compress;
+mod display;
+mod double;
+mod nodes;
+mod padding;
+mod pages;
+mod sorted;
+mod stream;
+mod subtree;
+#[cfg(feature = "testvectors")]
+pub mod testvectors;
 
 /// MerkleTree.
 #[derive(Clone, Debug)]
 pub struct MerkleTree<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
-    data: Vec<NodeData<B>>,
+    data: Pages<NodeData<B>>,
     leaf_range: Range<usize>,
+    padding: Padding,
+    pads: Vec<NodeData<B>>,
 }
 
 impl<B, D> FromIterator<D> for MerkleTree<B>
 where
     B: Digest,
-    Data<B>: Copy,
     D: AsRef<[u8]>,
 {
     fn from_iter<T>(iter: T) -> Self
     where
         T: IntoIterator<Item = D>,
+    {
+        Self::with_padding(iter, Padding::Duplicate)
+    }
+}
+
+impl<B> MerkleTree<B>
+where
+    B: Digest,
+{
+    /// Builds the tree of the leaves with the `padding` strategy for
+    /// the missing right siblings.
+    ///
+    /// The [`FromIterator`] implementation uses [`Padding::Duplicate`].
+    pub fn with_padding<T, D>(iter: T, padding: Padding) -> Self
+    where
+        T: IntoIterator<Item = D>,
+        D: AsRef<[u8]>,
     {
         // assuming size_hint() returns the correct length for now.
         let iter = iter.into_iter();
         let (leaves, _) = iter.size_hint();
-        let mut tree = Self::with_depth(Self::tree_depth(leaves));
+        let mut tree = Self::with_depth(Self::tree_depth(leaves), padding);
 
         // set leaves.
         iter.for_each(|hash| {
@@ -52,7 +96,8 @@ where
 
         // make sure the even leaves.
         if !Self::odd_index(tree.leaf_range.end) {
-            tree.data[tree.leaf_range.end] = tree.data[tree.leaf_range.end - 1].clone();
+            let last = &tree.data[tree.leaf_range.end - 1];
+            tree.data[tree.leaf_range.end] = tree.pads.first().unwrap_or(last).clone();
             tree.leaf_range.end += 1;
         }
 
@@ -60,13 +105,7 @@ where
         for _ in tree.parent_hash_range_iter(tree.leaf_range.clone()) {}
         tree
     }
-}
 
-impl<B> MerkleTree<B>
-where
-    B: Digest,
-    Data<B>: Copy,
-{
     pub fn root(&self) -> &[u8] {
         self.data[0].as_ref()
     }
@@ -75,9 +114,10 @@ where
         self.leaves_iter().map(|node| node.as_ref())
     }
 
-    pub fn set(&mut self, index: usize, hash: &[u8]) -> Result<()> {
-        let node = self.try_leaf_mut(index)?;
-        if let Some(inner) = node.0 {
+    pub fn set(&mut self, index: u64, hash: &[u8]) -> Result<()> {
+        let start = self.try_leaf_index(index)?;
+        let node = &mut self.data[start];
+        if let Some(inner) = &node.0 {
             if inner.as_ref() == hash {
                 // no change.
                 return Ok(());
@@ -86,7 +126,7 @@ where
         *node = NodeData::try_from(hash)?;
 
         // calculate the merkle root.
-        let range = match self.leaf_range.start + index {
+        let range = match start {
             start if Self::odd_index(start) => start..start + 2,
             start => start - 1..start + 1,
         };
@@ -95,51 +135,52 @@ where
         Ok(())
     }
 
-    pub fn proof(&self, index: usize) -> Result<MerkleProof<B>> {
-        let _node = self.try_leaf(index)?;
-        Ok(self.proof_iter(self.leaf_range.start + index).into())
+    /// Returns the padding strategy of the tree.
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    pub fn proof(&self, index: u64) -> Result<MerkleProof<B>> {
+        Ok(self.proof_iter(self.try_leaf_index(index)?).into())
     }
 
-    fn with_depth(depth: usize) -> Self {
+    fn with_depth(depth: usize, padding: Padding) -> Self {
         assert!(depth != 0, "zero depth tree is not supported");
         let tree_size = (1 << depth) - 1;
         let leaf_start = (1 << (depth - 1)) - 1;
         Self {
-            data: vec![NodeData::default(); tree_size],
+            data: Pages::new(tree_size, NodeData::default()),
             leaf_range: leaf_start..leaf_start,
+            padding,
+            pads: padding.nodes(depth),
         }
     }
 
-    fn try_leaf(&self, index: usize) -> Result<&NodeData<B>> {
-        self.leaves_iter().nth(index).ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("invalid leaf index: {index}"),
-            )
-        })
-    }
-
-    fn try_leaf_mut(&mut self, index: usize) -> Result<&mut NodeData<B>> {
-        self.leaves_iter_mut().nth(index).ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("invalid leaf index: {index}"),
-            )
-        })
+    /// Returns the position of the `index`th leaf in the tree.
+    fn try_leaf_index(&self, index: u64) -> Result<usize> {
+        usize::try_from(index)
+            .ok()
+            .filter(|index| *index < self.leaf_range.len())
+            .map(|index| self.leaf_range.start + index)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid leaf index: {index}"),
+                )
+            })
     }
 
     fn leaves_iter(&self) -> impl Iterator<Item = &NodeData<B>> {
-        self.data[self.leaf_range.clone()].iter()
-    }
-
-    fn leaves_iter_mut(&mut self) -> impl Iterator<Item = &mut NodeData<B>> {
-        self.data[self.leaf_range.clone()].iter_mut()
+        self.data.range(self.leaf_range.clone())
     }
 
     fn parent_hash_range_iter(&mut self, range: Range<usize>) -> ParentHashRangeIter<B> {
         ParentHashRangeIter {
             child_start: range.start,
-            data: &mut self.data[..range.end],
+            child_end: range.end,
+            data: &mut self.data,
+            pads: &self.pads,
+            level: 0,
         }
     }
 
@@ -174,21 +215,43 @@ where
 }
 
 /// MerkleProof type to be returned by the MerkleTree::proof function.
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct MerkleProof<B>(Vec<MerkleProofData<B>>)
+where
+    B: OutputSizeUser;
+
+impl<B> Clone for MerkleProof<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy;
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
 
 impl<B> MerkleProof<B>
 where
     B: Digest,
-    Data<B>: Copy,
 {
     pub fn iter(&self) -> impl Iterator<Item = &MerkleProofData<B>> {
         self.0.iter()
     }
 
+    /// Returns the `(level, index)` coordinates of the nodes traversed
+    /// by the proof, from the leaf to the root.
+    ///
+    /// The coordinates are same as [`MerkleTree::nodes`], and derived
+    /// from the [`MerkleProofDataKind`]s, which the proof is verified by.
+    pub fn path(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        let leaf = self
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, data)| data.kind() == MerkleProofDataKind::Right)
+            .fold(0u64, |index, (level, _)| index | 1 << level);
+        (0..=self.0.len()).map(move |level| (level, leaf.checked_shr(level as u32).unwrap_or(0)))
+    }
+
     pub fn verify<T>(&self, leaf: T) -> impl AsRef<[u8]>
     where
         T: AsRef<[u8]>,
@@ -196,6 +259,11 @@ where
         let mut data: Output<B> = GenericArray::default();
         let mut hash = leaf.as_ref();
 
+        // the leaf itself is the root of the empty proof, e.g. the proof
+        // of the leaf subtree.
+        if self.0.is_empty() && hash.len() == data.len() {
+            data.copy_from_slice(hash);
+        }
         for proof in &self.0 {
             match proof.kind() {
                 MerkleProofDataKind::Left => {
@@ -220,7 +288,6 @@ where
 impl<B> Deref for MerkleProof<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     type Target = [MerkleProofData<B>];
 
@@ -232,7 +299,6 @@ where
 impl<'a, B> IntoIterator for &'a MerkleProof<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     type Item = &'a MerkleProofData<B>;
     type IntoIter = std::slice::Iter<'a, MerkleProofData<B>>;
@@ -245,7 +311,6 @@ where
 impl<B> IntoIterator for MerkleProof<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     type Item = MerkleProofData<B>;
     type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -258,30 +323,38 @@ where
 impl<'a, B> From<ProofIter<'a, B>> for MerkleProof<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     fn from(iter: ProofIter<'a, B>) -> Self {
         Self(iter.collect())
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum MerkleProofDataKind {
     Left,
     Right,
 }
 
 /// MerkleProofData for the merkle proof.
-#[derive(Copy, Clone)]
-pub struct MerkleProofData<B>(MerkleProofDataKind, Output<B>)
+///
+/// It carries the index of the sibling node in its level as well, so
+/// that the external verifiers can fetch the node by the coordinates.
+pub struct MerkleProofData<B>(MerkleProofDataKind, Output<B>, u64)
+where
+    B: OutputSizeUser;
+
+impl<B> Clone for MerkleProofData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy;
+{
+    fn clone(&self) -> Self {
+        Self(self.0, self.1.clone(), self.2)
+    }
+}
 
 impl<B> MerkleProofData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     #[inline]
     pub fn kind(&self) -> MerkleProofDataKind {
@@ -292,17 +365,25 @@ where
     pub fn sibling(&self) -> &[u8] {
         self.1.as_ref()
     }
+
+    /// Returns the index of the sibling node, counted from the left
+    /// of its level, e.g. the `(level, index)` coordinates of
+    /// [`MerkleTree::nodes`] for the `level`th proof data.
+    #[inline]
+    pub fn sibling_index(&self) -> u64 {
+        self.2
+    }
 }
 
 impl<B> Debug for MerkleProofData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("MerkleProofData")
             .field("kind", &self.0)
             .field("sibling", &format_args!("{:02x?}", self.1.as_ref()))
+            .field("sibling_index", &self.2)
             .finish()
     }
 }
@@ -312,16 +393,14 @@ where
 struct ProofIter<'a, B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     index: usize,
-    data: &'a [NodeData<B>],
+    data: &'a Pages<NodeData<B>>,
 }
 
 impl<'a, B> Iterator for ProofIter<'a, B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     type Item = MerkleProofData<B>;
 
@@ -330,45 +409,53 @@ where
             return None;
         }
         let (kind, sibling) = if self.index & 1 == 1 {
-            (MerkleProofDataKind::Left, &self.data[self.index + 1])
+            (MerkleProofDataKind::Left, self.index + 1)
         } else {
-            (MerkleProofDataKind::Right, &self.data[self.index - 1])
+            (MerkleProofDataKind::Right, self.index - 1)
         };
         self.index = (self.index - 1) / 2;
-        Some(MerkleProofData(kind, sibling.into()))
+
+        // the index in the level, e.g. from the leftmost node of the level.
+        let level_start = (1 << (sibling + 1).ilog2()) - 1;
+        let sibling_index = (sibling - level_start) as u64;
+        Some(MerkleProofData(
+            kind,
+            (&self.data[sibling]).into(),
+            sibling_index,
+        ))
     }
 }
 
 struct ParentHashRangeIter<'a, B>
 where
     B: Digest,
-    Data<B>: Copy,
 {
     child_start: usize,
-    data: &'a mut [NodeData<B>],
+    child_end: usize,
+    data: &'a mut Pages<NodeData<B>>,
+    pads: &'a [NodeData<B>],
+    level: usize,
 }
 
 impl<'a, B> Iterator for ParentHashRangeIter<'a, B>
 where
     B: Digest,
-    Data<B>: Copy,
 {
     type Item = Range<usize>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.data.len() == 1 {
+        if self.child_end == 1 {
             return None;
         }
         // update the parent hashes.
         let parent_start = (self.child_start - 1) / 2;
-        let parent_end = (self.data.len() - 1) / 2;
-        let (data, children) = mem::take(&mut self.data).split_at_mut(self.child_start);
-        for (i, hashes) in children.chunks(2).enumerate() {
+        let parent_end = (self.child_end - 1) / 2;
+        for (i, child) in (self.child_start..self.child_end).step_by(2).enumerate() {
             let mut hasher = B::new();
-            for hash in hashes {
+            for hash in self.data.range(child..self.child_end.min(child + 2)) {
                 hasher.update(hash);
             }
-            data[parent_start + i] = NodeData::from(hasher.finalize());
+            self.data[parent_start + i] = NodeData::from(hasher.finalize());
         }
         // adjust the start and the end index for the next calculation.
         self.child_start = if parent_start != 0 && parent_start & 1 == 0 {
@@ -382,10 +469,12 @@ where
             parent_end
         };
         // Make sure there is no hole.
-        if data[child_end - 1].0.is_none() {
-            data[child_end - 1] = data[child_end - 2].clone();
+        self.level += 1;
+        if self.data[child_end - 1].0.is_none() {
+            let last = &self.data[child_end - 2];
+            self.data[child_end - 1] = self.pads.get(self.level).unwrap_or(last).clone();
         }
-        self.data = &mut data[..child_end];
+        self.child_end = child_end;
         Some(parent_start..parent_end)
     }
 }
@@ -398,26 +487,22 @@ where
 ///
 /// It's a private type to provide AsRef<[u8]> to the actual
 /// data.
-#[derive(Copy)]
 struct NodeData<B>(Option<Output<B>>)
 where
-    B: OutputSizeUser,
-    Data<B>: Copy;
+    B: OutputSizeUser;
 
 impl<B> Clone for NodeData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     fn clone(&self) -> Self {
-        Self(self.0)
+        Self(self.0.clone())
     }
 }
 
 impl<B> Default for NodeData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     fn default() -> Self {
         Self(None)
@@ -427,11 +512,10 @@ where
 impl<B> Debug for NodeData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut f = f.debug_struct("NodeData");
-        match self.0 {
+        match &self.0 {
             Some(data) => f.field("data", &format_args!("{:02x?}", data.as_ref())),
             None => f.field("data", &"uninitialized"),
         }
@@ -442,7 +526,6 @@ where
 impl<B> AsRef<[u8]> for NodeData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     fn as_ref(&self) -> &[u8] {
         assert!(self.0.is_some(), "accessing uninitialized node");
@@ -453,7 +536,6 @@ where
 impl<B> TryFrom<&[u8]> for NodeData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     type Error = io::Error;
 
@@ -476,18 +558,16 @@ where
 impl<B> From<&NodeData<B>> for Output<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     fn from(node: &NodeData<B>) -> Output<B> {
         assert!(node.0.is_some(), "accessing uninitialized node");
-        node.0.unwrap()
+        node.0.clone().unwrap()
     }
 }
 
 impl<B> From<Output<B>> for NodeData<B>
 where
     B: OutputSizeUser,
-    Data<B>: Copy,
 {
     fn from(inner: Output<B>) -> Self {
         Self(Some(inner))
@@ -497,6 +577,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::{MerkleProofDataKind, MerkleTree};
+    use blake2::Blake2b512;
     use hex_literal::hex;
     use sha3::Sha3_256;
     use std::iter;
@@ -507,7 +588,10 @@ mod tests {
         let want = hex!("57054e43fa56333fd51343b09460d48b9204999c376624f52480c5593b91eff4");
 
         for i in 0..tree.leaves().count() {
-            let got = tree.proof(i).unwrap().verify(tree.leaves().nth(i).unwrap());
+            let got = tree
+                .proof(i as u64)
+                .unwrap()
+                .verify(tree.leaves().nth(i).unwrap());
             assert_eq!(got.as_ref(), want);
         }
     }
@@ -537,6 +621,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn tree_proof_verify_blake2b512() {
+        let tree: MerkleTree<Blake2b512> = (0..17).map(|i| [i as u8; 64]).collect();
+
+        for i in 0..tree.leaves().count() {
+            let got = tree
+                .proof(i as u64)
+                .unwrap()
+                .verify(tree.leaves().nth(i).unwrap());
+            assert_eq!(got.as_ref(), tree.root());
+        }
+    }
+
     #[test]
     fn tree_root_from_iter_depth_5() {
         const LEAF: [u8; 32] = [0xabu8; 32];
@@ -0,0 +1,212 @@
+//! MerkleTree node export and import.
+//!
+//! Every initialized node is identified by its `(level, index)`
+//! coordinates, where the level is counted from the leaves, e.g. zero
+//! for the leaves, and the index is counted from the left of the level,
+//! so that the external systems, e.g. databases and explorers, can
+//! persist the full tree.
+use super::{MerkleTree, NodeData, Padding};
+use digest::Digest;
+use std::io::{self, Result};
+
+impl<B> MerkleTree<B>
+where
+    B: Digest,
+{
+    /// Returns every initialized node as `(level, index, hash)`,
+    /// from the root to the leaves.
+    pub fn nodes(&self) -> impl Iterator<Item = (usize, usize, &[u8])> {
+        let depth = self.depth();
+        self.data.iter().enumerate().filter_map(move |(i, node)| {
+            node.0.as_ref().map(|hash| {
+                let (level, index) = coordinates(depth, i);
+                (level, index, hash.as_ref())
+            })
+        })
+    }
+
+    /// Reconstructs the tree from the [`Self::nodes`] output.
+    ///
+    /// It validates the internal consistency of the nodes, e.g. every
+    /// parent is the hash of its children, and the `padding` is recorded
+    /// as given.
+    pub fn from_nodes<I, D>(nodes: I, padding: Padding) -> Result<Self>
+    where
+        I: IntoIterator<Item = (usize, usize, D)>,
+        D: AsRef<[u8]>,
+    {
+        let nodes: Vec<_> = nodes.into_iter().collect();
+        let depth = nodes
+            .iter()
+            .map(|(level, _, _)| level + 1)
+            .max()
+            .ok_or_else(|| invalid_data("empty nodes".to_string()))?;
+        if depth >= usize::BITS as usize {
+            return Err(invalid_data(format!("too deep tree: {depth}")));
+        }
+
+        // places the nodes.
+        let mut tree = Self::with_depth(depth, padding);
+        for (level, index, hash) in &nodes {
+            let i = position(depth, *level, *index)
+                .ok_or_else(|| invalid_data(format!("invalid node: ({level}, {index})")))?;
+            if tree.data[i].0.is_some() {
+                return Err(invalid_data(format!("duplicate node: ({level}, {index})")));
+            }
+            tree.data[i] = NodeData::try_from(hash.as_ref())?;
+        }
+
+        // the leaves should be contiguous from the left.
+        let leaves = tree
+            .data
+            .range(tree.leaf_range.start..tree.data.len())
+            .take_while(|node| node.0.is_some())
+            .count();
+        tree.leaf_range.end += leaves;
+        if tree
+            .data
+            .range(tree.leaf_range.end..tree.data.len())
+            .any(|node| node.0.is_some())
+        {
+            return Err(invalid_data("non-contiguous leaves".to_string()));
+        }
+
+        // every internal node should be the hash of its children, or
+        // the padding node of the level without children.
+        if tree.data[0].0.is_none() {
+            return Err(invalid_data("missing root".to_string()));
+        }
+        for i in 0..tree.leaf_range.start {
+            let (left, right) = (2 * i + 1, 2 * i + 2);
+            let (level, index) = coordinates(depth, i);
+            let want = match (&tree.data[i].0, &tree.data[left].0, &tree.data[right].0) {
+                (None, None, None) => continue,
+                (Some(_), Some(_), Some(_)) => B::new()
+                    .chain_update(&tree.data[left])
+                    .chain_update(&tree.data[right])
+                    .finalize(),
+                (Some(_), None, None) if i != 0 && i & 1 == 0 && tree.data[i - 1].0.is_some() => {
+                    let last = &tree.data[i - 1];
+                    tree.pads.get(level).unwrap_or(last).into()
+                }
+                _ => {
+                    return Err(invalid_data(format!("incomplete node: ({level}, {index})")));
+                }
+            };
+            if tree.data[i].as_ref() != want.as_slice() {
+                return Err(invalid_data(format!(
+                    "inconsistent node: ({level}, {index})"
+                )));
+            }
+        }
+
+        Ok(tree)
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        (self.data.len() + 1).trailing_zeros() as usize
+    }
+}
+
+/// Returns the `(level, index)` coordinates of the `i`th node.
+fn coordinates(depth: usize, i: usize) -> (usize, usize) {
+    let from_root = (i + 1).ilog2() as usize;
+    (depth - 1 - from_root, i + 1 - (1 << from_root))
+}
+
+/// Returns the position of the `(level, index)` node.
+pub(crate) fn position(depth: usize, level: usize, index: usize) -> Option<usize> {
+    let from_root = depth.checked_sub(level + 1)?;
+    if index >= 1 << from_root {
+        return None;
+    }
+    Some((1 << from_root) - 1 + index)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MerkleTree, Padding};
+    use sha3::Sha3_256;
+    use std::collections::HashMap;
+
+    #[test]
+    fn nodes_round_trip() {
+        for n in [1, 2, 3, 5, 16, 17] {
+            let leaves: Vec<_> = (0..n).map(|i| [i as u8; 32]).collect();
+            let tree = MerkleTree::<Sha3_256>::with_padding(&leaves, Padding::EmptySubtree);
+            assert_eq!(
+                tree.nodes().next().unwrap(),
+                (tree.depth() - 1, 0, tree.root())
+            );
+
+            let got = MerkleTree::<Sha3_256>::from_nodes(tree.nodes(), tree.padding()).unwrap();
+            assert_eq!(got.root(), tree.root(), "n={n}");
+            assert!(got.leaves().eq(tree.leaves()), "n={n}");
+            assert!(got.nodes().eq(tree.nodes()), "n={n}");
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = got.proof(i as u64).unwrap();
+                assert_eq!(proof.verify(leaf).as_ref(), got.root(), "n={n} i={i}");
+            }
+        }
+    }
+
+    #[test]
+    fn nodes_proof_path() {
+        let tree: MerkleTree<Sha3_256> = (0..11).map(|i| [i as u8; 32]).collect();
+        let nodes: HashMap<_, _> = tree
+            .nodes()
+            .map(|(level, index, hash)| ((level, index as u64), hash))
+            .collect();
+        for (i, leaf) in tree.leaves().enumerate() {
+            let proof = tree.proof(i as u64).unwrap();
+            let path: Vec<_> = proof.path().collect();
+            assert_eq!(path.len(), proof.len() + 1);
+            assert_eq!(path[0], (0, i as u64));
+            assert_eq!(nodes[&path[0]], leaf);
+            assert_eq!(nodes[path.last().unwrap()], tree.root());
+            for (level, data) in proof.iter().enumerate() {
+                assert_eq!(data.sibling_index(), path[level].1 ^ 1);
+                assert_eq!(nodes[&(level, data.sibling_index())], data.sibling());
+            }
+        }
+    }
+
+    #[test]
+    fn nodes_invalid() {
+        let tree: MerkleTree<Sha3_256> = (0..5).map(|i| [i as u8; 32]).collect();
+        let nodes: Vec<_> = tree
+            .nodes()
+            .map(|(level, index, hash)| (level, index, hash.to_vec()))
+            .collect();
+        let load = |nodes: Vec<(usize, usize, Vec<u8>)>| {
+            MerkleTree::<Sha3_256>::from_nodes(nodes, Padding::Duplicate)
+        };
+
+        assert!(load(vec![]).is_err());
+        assert!(load(vec![(1, 0, vec![0; 32])]).is_err());
+
+        // forged leaf.
+        let mut forged = nodes.clone();
+        forged.last_mut().unwrap().2[0] ^= 1;
+        assert!(load(forged).is_err());
+
+        // missing leaf.
+        let mut missing = nodes.clone();
+        missing.pop();
+        assert!(load(missing).is_err());
+
+        // duplicate node.
+        let mut duplicate = nodes.clone();
+        duplicate.push(nodes[0].clone());
+        assert!(load(duplicate).is_err());
+
+        // out of range node.
+        let mut out_of_range = nodes;
+        out_of_range.push((0, 8, vec![0; 32]));
+        assert!(load(out_of_range).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! Padding strategy for the missing right siblings.
+//!
+//! The different ecosystems expect the different roots for the same
+//! leaves, e.g. Bitcoin duplicates the last node while the Ethereum
+//! deposit contract pads with the empty subtree roots.
+use super::NodeData;
+use digest::{Digest, Output};
+use std::iter;
+
+/// Padding strategy of the [`MerkleTree`], chosen at construction
+/// with [`MerkleTree::with_padding`].
+///
+/// [`MerkleTree`]: super::MerkleTree
+/// [`MerkleTree::with_padding`]: super::MerkleTree::with_padding
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Padding {
+    /// Duplicates the last node of the level, e.g. Bitcoin.
+    #[default]
+    Duplicate,
+
+    /// Pads with the all-zero hash on every level.
+    ZeroHash,
+
+    /// Pads with the root of the empty subtree of the level, e.g. the
+    /// Ethereum deposit contract, where the leaf level is padded with
+    /// the all-zero hash.
+    EmptySubtree,
+}
+
+impl Padding {
+    /// Returns the precomputed padding nodes of each level, leaves first.
+    ///
+    /// It's empty for [`Padding::Duplicate`], as the padding node
+    /// depends on the last node of the level.
+    pub(crate) fn nodes<B>(self, depth: usize) -> Vec<NodeData<B>>
+    where
+        B: Digest,
+    {
+        let zero = Output::<B>::default();
+        match self {
+            Self::Duplicate => Vec::new(),
+            Self::ZeroHash => vec![NodeData::from(zero); depth],
+            Self::EmptySubtree => {
+                // `successors` has no size hint to pre-size the nodes.
+                let mut nodes = Vec::with_capacity(depth);
+                nodes.extend(
+                    iter::successors(Some(zero), |hash| {
+                        Some(B::new().chain_update(hash).chain_update(hash).finalize())
+                    })
+                    .take(depth)
+                    .map(NodeData::from),
+                );
+                nodes
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Padding;
+    use crate::MerkleTree;
+    use digest::Digest;
+    use sha3::Sha3_256;
+
+    fn hash(a: &[u8], b: &[u8]) -> Vec<u8> {
+        Sha3_256::new()
+            .chain_update(a)
+            .chain_update(b)
+            .finalize()
+            .to_vec()
+    }
+
+    #[test]
+    fn padding_root() {
+        let leaves: Vec<_> = (1..=5).map(|i| [i as u8; 32]).collect();
+        let zero = [0u8; 32];
+        let h01 = hash(&leaves[0], &leaves[1]);
+        let h23 = hash(&leaves[2], &leaves[3]);
+        let left = hash(&h01, &h23);
+
+        let h44 = hash(&leaves[4], &leaves[4]);
+        let h4z = hash(&leaves[4], &zero);
+        let zz = hash(&zero, &zero);
+        for (padding, right) in [
+            (Padding::Duplicate, hash(&h44, &h44)),
+            (Padding::ZeroHash, hash(&h4z, &zero)),
+            (Padding::EmptySubtree, hash(&h4z, &zz)),
+        ] {
+            let tree = MerkleTree::<Sha3_256>::with_padding(&leaves, padding);
+            assert_eq!(tree.padding(), padding);
+            assert_eq!(tree.root(), hash(&left, &right), "{padding:?}");
+        }
+    }
+
+    #[test]
+    fn padding_proof_verify() {
+        for padding in [Padding::Duplicate, Padding::ZeroHash, Padding::EmptySubtree] {
+            for n in [1, 2, 3, 5, 17] {
+                let leaves: Vec<_> = (0..n).map(|i| [i as u8; 32]).collect();
+                let tree = MerkleTree::<Sha3_256>::with_padding(&leaves, padding);
+                for (i, leaf) in leaves.iter().enumerate() {
+                    let got = tree.proof(i as u64).unwrap().verify(leaf);
+                    assert_eq!(got.as_ref(), tree.root(), "{padding:?} n={n} i={i}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Chunked backing store of the tree nodes.
+//!
+//! The nodes are kept in the fixed-size pages instead of the single
+//! contiguous `Vec`, so that the trees with billions of leaves can be
+//! built without one huge allocation.
+use std::ops::{Index, IndexMut, Range};
+
+/// Number of the nodes in a page.
+const PAGE_LEN: usize = 1 << 16;
+
+/// Fixed-size pages of `T`, indexed as a flat array.
+#[derive(Clone, Debug)]
+pub(crate) struct Pages<T> {
+    pages: Vec<Box<[T]>>,
+    len: usize,
+}
+
+impl<T> Pages<T>
+where
+    T: Clone,
+{
+    /// Allocates `len` elements of `value`, page by page.
+    pub(crate) fn new(len: usize, value: T) -> Self {
+        let pages = (0..len)
+            .step_by(PAGE_LEN)
+            .map(|start| vec![value.clone(); PAGE_LEN.min(len - start)].into_boxed_slice())
+            .collect();
+        Self { pages, len }
+    }
+}
+
+impl<T> Pages<T> {
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.pages.iter().flat_map(|page| page.iter())
+    }
+
+    /// Returns the iterator of the elements in the `range`.
+    pub(crate) fn range(&self, range: Range<usize>) -> impl Iterator<Item = &T> {
+        self.pages
+            .iter()
+            .enumerate()
+            .flat_map(move |(i, page)| &page[page_range(i, page.len(), &range)])
+    }
+}
+
+impl<T> Index<usize> for Pages<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        assert!(index < self.len, "index out of range: {index}");
+        &self.pages[index / PAGE_LEN][index % PAGE_LEN]
+    }
+}
+
+impl<T> IndexMut<usize> for Pages<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        assert!(index < self.len, "index out of range: {index}");
+        &mut self.pages[index / PAGE_LEN][index % PAGE_LEN]
+    }
+}
+
+/// Returns the part of the `range` within the `i`th page.
+fn page_range(i: usize, page_len: usize, range: &Range<usize>) -> Range<usize> {
+    let start = i * PAGE_LEN;
+    let end = start + page_len;
+    range.start.clamp(start, end) - start..range.end.clamp(start, end) - start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pages, PAGE_LEN};
+
+    #[test]
+    fn pages_index_across_pages() {
+        let len = 2 * PAGE_LEN + 3;
+        let mut pages = Pages::new(len, 0usize);
+        assert_eq!(pages.len(), len);
+        (0..len).for_each(|i| pages[i] = i);
+
+        for i in [0, PAGE_LEN - 1, PAGE_LEN, 2 * PAGE_LEN, len - 1] {
+            assert_eq!(pages[i], i);
+        }
+        assert!(pages.iter().copied().eq(0..len));
+        let range = PAGE_LEN - 2..2 * PAGE_LEN + 1;
+        assert!(pages.range(range.clone()).copied().eq(range));
+        assert_eq!(pages.range(len..len).count(), 0);
+    }
+}
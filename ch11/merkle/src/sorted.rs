@@ -0,0 +1,248 @@
+//! Sorted-leaf MerkleTree with the proof of absence.
+//!
+//! The leaves are sorted by the hash bytes and enclosed by the all-zero
+//! and the all-`0xff` sentinel leaves, so that any missing hash sits
+//! between the two adjacent leaves, e.g. for the allowlist and the
+//! denylist, without moving to the sparse merkle tree.
+//!
+//! The adjacency is proved by the leaf positions recovered from the
+//! [`MerkleProofDataKind`] of the two membership proofs.  The duplicated
+//! padding nodes only repeat the sorted tail ending with the `0xff`
+//! sentinel, which can't be the lower side of the absence proof.
+use super::{MerkleProof, MerkleProofDataKind, MerkleTree};
+use digest::{Digest, Output};
+use std::io::{self, Result};
+use std::iter::{self, FromIterator};
+
+/// MerkleTree of the sorted and deduplicated leaves.
+#[derive(Clone, Debug)]
+pub struct SortedMerkleTree<B>
+where
+    B: Digest,
+{
+    tree: MerkleTree<B>,
+    len: usize,
+}
+
+impl<B, D> FromIterator<D> for SortedMerkleTree<B>
+where
+    B: Digest,
+    D: AsRef<[u8]>,
+{
+    /// Builds the tree of the sorted leaves.
+    ///
+    /// The duplicate leaves as well as the sentinel values, e.g. the
+    /// all-zero and the all-`0xff` hashes, are dropped.
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = D>,
+    {
+        let (min, max) = (min::<B>(), max::<B>());
+        let mut leaves: Vec<_> = iter
+            .into_iter()
+            .map(|hash| hash.as_ref().to_vec())
+            .filter(|hash| hash.as_slice() != min.as_slice() && hash.as_slice() != max.as_slice())
+            .collect();
+        leaves.sort();
+        leaves.dedup();
+        let len = leaves.len();
+        let tree = iter::once(min.to_vec())
+            .chain(leaves)
+            .chain(iter::once(max.to_vec()))
+            .collect();
+        Self { tree, len }
+    }
+}
+
+impl<B> SortedMerkleTree<B>
+where
+    B: Digest,
+{
+    pub fn root(&self) -> &[u8] {
+        self.tree.root()
+    }
+
+    /// Returns the sorted leaves without the sentinels.
+    pub fn leaves(&self) -> impl Iterator<Item = &[u8]> {
+        self.tree.leaves().skip(1).take(self.len)
+    }
+
+    /// Returns the number of the leaves without the sentinels.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there is no leaf other than the sentinels.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the underlying tree, including the sentinel leaves.
+    pub fn tree(&self) -> &MerkleTree<B> {
+        &self.tree
+    }
+
+    /// Returns the membership proof of the `hash`.
+    pub fn proof(&self, hash: &[u8]) -> Result<MerkleProof<B>> {
+        match self.search(hash)? {
+            Ok(index) => self.tree.proof(index as u64),
+            Err(_) => Err(invalid_input("missing leaf".to_string())),
+        }
+    }
+
+    /// Returns the non-membership proof of the `hash`.
+    pub fn absence_proof(&self, hash: &[u8]) -> Result<AbsenceProof<B>> {
+        let upper = match self.search(hash)? {
+            Ok(_) => return Err(invalid_input("existing leaf".to_string())),
+            Err(index) => index,
+        };
+        let leaf = |index| Output::<B>::clone_from_slice(self.tree.leaves().nth(index).unwrap());
+        Ok(AbsenceProof {
+            lower: leaf(upper - 1),
+            lower_proof: self.tree.proof(upper as u64 - 1)?,
+            upper: leaf(upper),
+            upper_proof: self.tree.proof(upper as u64)?,
+        })
+    }
+
+    /// Binary-searches the `hash` in the leaves, including the sentinels.
+    ///
+    /// It returns the error for the invalid hash length as well as
+    /// the sentinel values.
+    fn search(&self, hash: &[u8]) -> Result<std::result::Result<usize, usize>> {
+        if hash.len() != <B as Digest>::output_size() {
+            return Err(invalid_input(format!(
+                "invalid hash length: {}",
+                hash.len()
+            )));
+        }
+        if hash == min::<B>().as_slice() || hash == max::<B>().as_slice() {
+            return Err(invalid_input("sentinel leaf".to_string()));
+        }
+        let leaves: Vec<_> = self.tree.leaves().take(self.len + 2).collect();
+        Ok(leaves.binary_search(&hash))
+    }
+}
+
+/// Non-membership proof of the [`SortedMerkleTree`].
+///
+/// It's the membership proofs of the two adjacent leaves enclosing the
+/// missing hash.
+#[derive(Clone, Debug)]
+pub struct AbsenceProof<B>
+where
+    B: Digest,
+{
+    lower: Output<B>,
+    lower_proof: MerkleProof<B>,
+    upper: Output<B>,
+    upper_proof: MerkleProof<B>,
+}
+
+impl<B> AbsenceProof<B>
+where
+    B: Digest,
+{
+    /// Returns the leaf right before the missing hash.
+    pub fn lower(&self) -> &[u8] {
+        &self.lower
+    }
+
+    /// Returns the leaf right after the missing hash.
+    pub fn upper(&self) -> &[u8] {
+        &self.upper
+    }
+
+    /// Verifies the absence of the `hash` against the `root`.
+    pub fn verify(&self, root: &[u8], hash: &[u8]) -> bool {
+        self.lower.as_slice() < hash
+            && hash < self.upper.as_slice()
+            && self.lower_proof.len() == self.upper_proof.len()
+            && position(&self.lower_proof) + 1 == position(&self.upper_proof)
+            && self.lower_proof.verify(&self.lower).as_ref() == root
+            && self.upper_proof.verify(&self.upper).as_ref() == root
+    }
+}
+
+/// Returns the leaf position proved by the `proof`.
+fn position<B>(proof: &MerkleProof<B>) -> usize
+where
+    B: Digest,
+{
+    proof
+        .iter()
+        .enumerate()
+        .filter(|(_, data)| data.kind() == MerkleProofDataKind::Right)
+        .fold(0, |index, (level, _)| index | 1 << level)
+}
+
+fn min<B: Digest>() -> Output<B> {
+    Output::<B>::default()
+}
+
+fn max<B: Digest>() -> Output<B> {
+    let mut max = Output::<B>::default();
+    max.iter_mut().for_each(|byte| *byte = 0xff);
+    max
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortedMerkleTree;
+    use sha3::Sha3_256;
+
+    #[test]
+    fn sorted_absence_proof_verify() {
+        for n in [0, 1, 2, 3, 5, 16, 17] {
+            // the even leaves, in the reverse order.
+            let tree: SortedMerkleTree<Sha3_256> =
+                (0..n).rev().map(|i| [2 * i as u8 + 2; 32]).collect();
+            assert_eq!(tree.len(), n);
+            assert!(tree.leaves().is_sorted());
+
+            // the odd ones are missing.
+            for i in 0..=n {
+                let missing = [2 * i as u8 + 1; 32];
+                let proof = tree.absence_proof(&missing).unwrap();
+                assert!(proof.lower() < &missing[..] && &missing[..] < proof.upper());
+                assert!(proof.verify(tree.root(), &missing), "n={n} i={i}");
+            }
+            for leaf in tree.leaves() {
+                assert!(tree.absence_proof(leaf).is_err());
+                let proof = tree.proof(leaf).unwrap();
+                assert_eq!(proof.verify(leaf).as_ref(), tree.root());
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_absence_proof_forged() {
+        let tree: SortedMerkleTree<Sha3_256> = (1..=8).map(|i| [4 * i as u8; 32]).collect();
+        let missing = [10u8; 32];
+        let proof = tree.absence_proof(&missing).unwrap();
+
+        // the other value out of the range.
+        assert!(!proof.verify(tree.root(), &[12u8; 32]));
+        // the other root.
+        assert!(!proof.verify(&[0u8; 32], &missing));
+
+        // the non-adjacent leaves enclosing the existing leaf.
+        let lower = tree.absence_proof(&[6u8; 32]).unwrap();
+        let upper = tree.absence_proof(&[14u8; 32]).unwrap();
+        let forged = super::AbsenceProof {
+            upper: upper.upper,
+            upper_proof: upper.upper_proof.clone(),
+            ..lower
+        };
+        assert!(!forged.verify(tree.root(), &[12u8; 32]));
+
+        // the sentinels and the invalid length.
+        assert!(tree.absence_proof(&[0u8; 32]).is_err());
+        assert!(tree.absence_proof(&[0xffu8; 32]).is_err());
+        assert!(tree.absence_proof(&[10u8; 31]).is_err());
+    }
+}
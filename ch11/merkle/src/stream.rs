@@ -0,0 +1,173 @@
+//! Streaming MerkleProof verification.
+//!
+//! [`StreamVerifier`] consumes the proof data one by one, from the leaf
+//! to the root, instead of the collected [`MerkleProof`], and keeps only
+//! the running hash in between.  It's for the proof arriving across the
+//! multiple instruction invocations on-chain, where the running hash is
+//! saved in the account by [`StreamVerifier::state`] and restored by
+//! [`StreamVerifier::resume`] in the next invocation.
+//!
+//! [`MerkleProof`]: super::MerkleProof
+use super::{MerkleProofData, MerkleProofDataKind};
+use digest::{Digest, Output, OutputSizeUser};
+use std::fmt::{self, Debug};
+use std::io::{self, Result};
+
+/// StreamVerifier to verify the proof data streamed from the leaf to
+/// the root.
+pub struct StreamVerifier<B>(Output<B>)
+where
+    B: OutputSizeUser;
+
+impl<B> Clone for StreamVerifier<B>
+where
+    B: OutputSizeUser,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<B> Debug for StreamVerifier<B>
+where
+    B: OutputSizeUser,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("StreamVerifier")
+            .field(&format_args!("{:02x?}", self.0.as_ref()))
+            .finish()
+    }
+}
+
+impl<B> StreamVerifier<B>
+where
+    B: Digest,
+{
+    /// Starts the verification of the `leaf`.
+    pub fn new(leaf: &[u8]) -> Result<Self> {
+        Self::resume(leaf)
+    }
+
+    /// Resumes the verification from the running hash returned by
+    /// [`Self::state`], e.g. in the next instruction invocation.
+    pub fn resume(state: &[u8]) -> Result<Self> {
+        if state.len() != <B as Digest>::output_size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid hash length: {}!={}",
+                    state.len(),
+                    <B as Digest>::output_size()
+                ),
+            ));
+        }
+        Ok(Self(Output::<B>::clone_from_slice(state)))
+    }
+
+    /// Returns the running hash, which is the root once all the proof
+    /// data are consumed.
+    pub fn state(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+
+    /// Consumes the next proof data, the `sibling` on the `kind` side
+    /// of the current node.
+    pub fn update(&mut self, kind: MerkleProofDataKind, sibling: &[u8]) -> Result<()> {
+        if sibling.len() != self.0.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid sibling length: {}!={}",
+                    sibling.len(),
+                    self.0.len()
+                ),
+            ));
+        }
+        let mut hasher = B::new();
+        match kind {
+            MerkleProofDataKind::Left => {
+                hasher.update(&self.0);
+                hasher.update(sibling);
+            }
+            MerkleProofDataKind::Right => {
+                hasher.update(sibling);
+                hasher.update(&self.0);
+            }
+        }
+        hasher.finalize_into(&mut self.0);
+        Ok(())
+    }
+
+    /// Consumes the proof data from the `iter`, e.g. the chunk of the
+    /// proof given to the current invocation.
+    ///
+    /// The callback can be given through [`std::iter::from_fn`].
+    pub fn update_iter<'a, I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = &'a MerkleProofData<B>>,
+        B: 'a,
+    {
+        for data in iter {
+            // The proof data always has the matching sibling length.
+            self.update(data.kind(), data.sibling()).unwrap();
+        }
+    }
+
+    /// Finishes the verification and returns `true` if the running
+    /// hash matches the `root`.
+    pub fn verify(self, root: &[u8]) -> bool {
+        self.state() == root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamVerifier;
+    use crate::{MerkleProofDataKind, MerkleTree};
+    use sha3::Sha3_256;
+    use std::iter;
+
+    #[test]
+    fn stream_verify_chunks() {
+        let tree: MerkleTree<Sha3_256> = (0..13).map(|i| [i as u8; 32]).collect();
+
+        for (i, leaf) in tree.leaves().enumerate() {
+            let proof = tree.proof(i as u64).unwrap();
+
+            // One proof data per invocation, with the state in between.
+            let mut state = leaf.to_vec();
+            for data in &proof {
+                let mut verifier = StreamVerifier::<Sha3_256>::resume(&state).unwrap();
+                verifier.update(data.kind(), data.sibling()).unwrap();
+                state = verifier.state().to_vec();
+            }
+            assert_eq!(state, tree.root());
+            assert_eq!(state, proof.verify(leaf).as_ref());
+        }
+    }
+
+    #[test]
+    fn stream_verify_callback() {
+        let tree: MerkleTree<Sha3_256> = (0..8).map(|i| [i as u8; 32]).collect();
+        let proof = tree.proof(5).unwrap();
+
+        let mut data = proof.iter();
+        let mut verifier = StreamVerifier::new(tree.leaves().nth(5).unwrap()).unwrap();
+        verifier.update_iter(iter::from_fn(|| data.next()));
+        assert!(verifier.verify(tree.root()));
+
+        // The other leaf doesn't verify with the proof.
+        let mut verifier = StreamVerifier::new(tree.leaves().nth(4).unwrap()).unwrap();
+        verifier.update_iter(&proof);
+        assert!(!verifier.verify(tree.root()));
+    }
+
+    #[test]
+    fn stream_verify_invalid_length() {
+        assert!(StreamVerifier::<Sha3_256>::new(&[0u8; 31]).is_err());
+        let mut verifier = StreamVerifier::<Sha3_256>::new(&[0u8; 32]).unwrap();
+        assert!(verifier
+            .update(MerkleProofDataKind::Left, &[0u8; 33])
+            .is_err());
+    }
+}
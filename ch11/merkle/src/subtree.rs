@@ -0,0 +1,176 @@
+//! Read-only subtree views of the MerkleTree.
+//!
+//! The subtree is rooted at the `(level, index)` node of
+//! [`MerkleTree::nodes`] and produces the proofs relative to its root,
+//! so that the verification work can be sharded across the machines,
+//! each holding one subtree.
+use super::{nodes, MerkleProof, MerkleProofData, MerkleTree};
+use digest::Digest;
+use std::io::{self, Result};
+use std::ops::Range;
+
+/// A read-only view of the subtree of [`MerkleTree`].
+#[derive(Debug)]
+pub struct MerkleSubtree<'a, B>
+where
+    B: Digest,
+{
+    tree: &'a MerkleTree<B>,
+    level: usize,
+    index: usize,
+    leaf_range: Range<usize>,
+}
+
+impl<B> MerkleTree<B>
+where
+    B: Digest,
+{
+    /// Returns the subtree rooted at the `(level, index)` node.
+    ///
+    /// The node should have at least one leaf under it, e.g. not the
+    /// padding node of the level.
+    pub fn subtree(&self, level: usize, index: usize) -> Result<MerkleSubtree<'_, B>> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid subtree: ({level}, {index})"),
+            )
+        };
+        nodes::position(self.depth(), level, index).ok_or_else(invalid)?;
+
+        // the leaves under the node, e.g. without the padding leaves.
+        let start = self.leaf_range.start + (index << level);
+        let end = self.leaf_range.end.min(start + (1 << level));
+        if start >= end {
+            return Err(invalid());
+        }
+
+        Ok(MerkleSubtree {
+            tree: self,
+            level,
+            index,
+            leaf_range: start..end,
+        })
+    }
+}
+
+impl<B> MerkleSubtree<'_, B>
+where
+    B: Digest,
+{
+    /// Returns the `(level, index)` coordinates of the subtree root in
+    /// the tree.
+    pub fn coordinates(&self) -> (usize, usize) {
+        (self.level, self.index)
+    }
+
+    pub fn root(&self) -> &[u8] {
+        let i = nodes::position(self.tree.depth(), self.level, self.index).unwrap();
+        self.tree.data[i].as_ref()
+    }
+
+    pub fn leaves(&self) -> impl Iterator<Item = &[u8]> {
+        self.tree
+            .data
+            .range(self.leaf_range.clone())
+            .map(|node| node.as_ref())
+    }
+
+    /// Returns the proof of the `index`th leaf of the subtree, which
+    /// verifies against the subtree [`root`].
+    ///
+    /// The sibling indices are counted from the left of the subtree
+    /// level, same as the [`MerkleProof::path`].
+    ///
+    /// [`root`]: Self::root
+    pub fn proof(&self, index: u64) -> Result<MerkleProof<B>> {
+        let leaf = usize::try_from(index)
+            .ok()
+            .filter(|index| *index < self.leaf_range.len())
+            .map(|index| self.leaf_range.start + index)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid leaf index: {index}"),
+                )
+            })?;
+        let proof = self
+            .tree
+            .proof_iter(leaf)
+            .take(self.level)
+            .enumerate()
+            .map(|(level, MerkleProofData(kind, sibling, sibling_index))| {
+                let offset = (self.index << (self.level - level)) as u64;
+                MerkleProofData(kind, sibling, sibling_index - offset)
+            })
+            .collect();
+        Ok(MerkleProof(proof))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MerkleTree;
+    use sha3::Sha3_256;
+    use std::collections::HashMap;
+
+    #[test]
+    fn subtree_proof_verify() {
+        let tree: MerkleTree<Sha3_256> = (0..11).map(|i| [i as u8; 32]).collect();
+        let nodes: HashMap<_, _> = tree
+            .nodes()
+            .map(|(level, index, hash)| ((level, index), hash))
+            .collect();
+        let leaves: Vec<_> = tree.leaves().collect();
+
+        for level in 0..tree.depth() {
+            for index in 0..1 << (tree.depth() - 1 - level) {
+                let start = index << level;
+                let subtree = match tree.subtree(level, index) {
+                    Ok(subtree) => subtree,
+                    Err(_) => {
+                        assert!(start >= leaves.len(), "({level}, {index})");
+                        continue;
+                    }
+                };
+                assert_eq!(subtree.coordinates(), (level, index));
+                assert_eq!(subtree.root(), nodes[&(level, index)]);
+                let got: Vec<_> = subtree.leaves().collect();
+                let end = leaves.len().min(start + (1 << level));
+                assert_eq!(got, leaves[start..end], "({level}, {index})");
+
+                for (i, leaf) in got.iter().enumerate() {
+                    let proof = subtree.proof(i as u64).unwrap();
+                    assert_eq!(proof.len(), level);
+                    assert_eq!(proof.verify(leaf).as_ref(), subtree.root());
+                    let path: Vec<_> = proof.path().collect();
+                    assert_eq!(path[0], (0, i as u64));
+                    assert_eq!(path[level], (level, 0));
+                }
+                assert!(subtree.proof(got.len() as u64).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn subtree_of_root() {
+        let tree: MerkleTree<Sha3_256> = (0..5).map(|i| [i as u8; 32]).collect();
+        let subtree = tree.subtree(tree.depth() - 1, 0).unwrap();
+        assert_eq!(subtree.root(), tree.root());
+        assert!(subtree.leaves().eq(tree.leaves()));
+        for i in 0..tree.leaves().count() as u64 {
+            let got = subtree.proof(i).unwrap();
+            let want = tree.proof(i).unwrap();
+            assert_eq!(format!("{got:?}"), format!("{want:?}"));
+        }
+    }
+
+    #[test]
+    fn subtree_invalid() {
+        let tree: MerkleTree<Sha3_256> = (0..5).map(|i| [i as u8; 32]).collect();
+        assert!(tree.subtree(tree.depth(), 0).is_err());
+        assert!(tree.subtree(0, 8).is_err());
+        // the padding node without the leaves.
+        assert!(tree.subtree(1, 3).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! Deterministic test vector generation.
+//!
+//! It produces the canonical trees and proofs for the given seeds,
+//! emitted as JSON, so that other implementations, e.g. TypeScript
+//! clients of the airdrop program, can cross-check the compatibility.
+//!
+//! Two modes are supported:
+//!
+//! - [`Mode::Legacy`]: The [`MerkleTree`] of this crate, which hashes
+//!   the concatenated children without the domain separation and
+//!   duplicates the last leaf for the odd number of leaves.
+//! - [`Mode::Rfc6962`]: The [RFC 6962] merkle tree hash, which
+//!   prefixes the leaves with `0x00` and the nodes with `0x01` and
+//!   splits the unbalanced tree at the largest power of two.
+//!
+//! The proof sibling `kind` follows [`MerkleProofDataKind`], e.g.
+//! `left` means the current hash is on the left and the sibling is
+//! on the right.
+//!
+//! [RFC 6962]: https://www.rfc-editor.org/rfc/rfc6962#section-2.1
+use super::{HashDisplay, MerkleProofDataKind, MerkleTree};
+use digest::Digest;
+use serde::Serialize;
+
+/// Tree construction mode of the test vector.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Legacy,
+    Rfc6962,
+}
+
+/// TestVector of the tree for a seed.
+///
+/// All the hashes and the leaves are lower hex encoded.
+#[derive(Clone, Debug, Serialize)]
+pub struct TestVector {
+    pub mode: Mode,
+    pub seed: u64,
+    pub leaves: Vec<String>,
+    pub root: String,
+    pub proofs: Vec<Proof>,
+}
+
+/// Proof of the leaf at `index`, from the leaf to the root.
+#[derive(Clone, Debug, Serialize)]
+pub struct Proof {
+    pub index: usize,
+    pub siblings: Vec<Sibling>,
+}
+
+/// Sibling of the proof.
+#[derive(Clone, Debug, Serialize)]
+pub struct Sibling {
+    pub kind: &'static str,
+    pub hash: String,
+}
+
+impl TestVector {
+    /// Returns the pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("test vector serialization")
+    }
+}
+
+/// Generates the test vector with `leaves` leaves for the `seed`.
+///
+/// Leaf `i` is the digest of the little endian `seed` followed by the
+/// little endian `i` as `u64`.
+pub fn generate<B>(mode: Mode, seed: u64, leaves: usize) -> TestVector
+where
+    B: Digest,
+{
+    assert!(leaves != 0, "zero length leaf is not supported");
+    let data: Vec<Vec<u8>> = (0..leaves as u64)
+        .map(|i| {
+            B::new()
+                .chain_update(seed.to_le_bytes())
+                .chain_update(i.to_le_bytes())
+                .finalize()
+                .to_vec()
+        })
+        .collect();
+    let (root, proofs) = match mode {
+        Mode::Legacy => legacy::<B>(&data),
+        Mode::Rfc6962 => rfc6962::<B>(&data),
+    };
+    TestVector {
+        mode,
+        seed,
+        leaves: data.iter().map(|leaf| hex(leaf)).collect(),
+        root,
+        proofs,
+    }
+}
+
+fn legacy<B>(data: &[Vec<u8>]) -> (String, Vec<Proof>)
+where
+    B: Digest,
+{
+    let tree: MerkleTree<B> = data.iter().collect();
+    let proofs = (0..data.len())
+        .map(|index| Proof {
+            index,
+            siblings: tree
+                .proof(index as u64)
+                .unwrap()
+                .iter()
+                .map(|proof| sibling(proof.kind(), proof.sibling()))
+                .collect(),
+        })
+        .collect();
+    (hex(tree.root()), proofs)
+}
+
+fn rfc6962<B: Digest>(data: &[Vec<u8>]) -> (String, Vec<Proof>) {
+    let proofs = (0..data.len())
+        .map(|index| {
+            let mut siblings = vec![];
+            rfc6962_path::<B>(index, data, &mut siblings);
+            Proof { index, siblings }
+        })
+        .collect();
+    (hex(&rfc6962_hash::<B>(data)), proofs)
+}
+
+/// MTH(D[n]) in RFC 6962.
+fn rfc6962_hash<B: Digest>(data: &[Vec<u8>]) -> Vec<u8> {
+    if data.len() == 1 {
+        return B::new()
+            .chain_update([0x00])
+            .chain_update(&data[0])
+            .finalize()
+            .to_vec();
+    }
+    let k = split(data.len());
+    B::new()
+        .chain_update([0x01])
+        .chain_update(rfc6962_hash::<B>(&data[..k]))
+        .chain_update(rfc6962_hash::<B>(&data[k..]))
+        .finalize()
+        .to_vec()
+}
+
+/// PATH(m, D[n]) in RFC 6962, pushed from the leaf to the root.
+fn rfc6962_path<B: Digest>(m: usize, data: &[Vec<u8>], siblings: &mut Vec<Sibling>) {
+    if data.len() == 1 {
+        return;
+    }
+    let k = split(data.len());
+    if m < k {
+        rfc6962_path::<B>(m, &data[..k], siblings);
+        let hash = rfc6962_hash::<B>(&data[k..]);
+        siblings.push(sibling(MerkleProofDataKind::Left, &hash));
+    } else {
+        rfc6962_path::<B>(m - k, &data[k..], siblings);
+        let hash = rfc6962_hash::<B>(&data[..k]);
+        siblings.push(sibling(MerkleProofDataKind::Right, &hash));
+    }
+}
+
+/// Returns the largest power of two smaller than `n`.
+#[inline]
+const fn split(n: usize) -> usize {
+    1 << (usize::BITS - (n - 1).leading_zeros() - 1)
+}
+
+fn sibling(kind: MerkleProofDataKind, hash: &[u8]) -> Sibling {
+    let kind = match kind {
+        MerkleProofDataKind::Left => "left",
+        MerkleProofDataKind::Right => "right",
+    };
+    Sibling {
+        kind,
+        hash: hex(hash),
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    HashDisplay::new(data).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, split, Mode, TestVector};
+    use sha3::{Digest, Sha3_256};
+
+    fn verify(vector: &TestVector, prefix: Option<(u8, u8)>) {
+        for proof in &vector.proofs {
+            let leaf = hex_decode(&vector.leaves[proof.index]);
+            let mut hash = match prefix {
+                Some((leaf_prefix, _)) => Sha3_256::new()
+                    .chain_update([leaf_prefix])
+                    .chain_update(&leaf)
+                    .finalize()
+                    .to_vec(),
+                None => leaf,
+            };
+            for sibling in &proof.siblings {
+                let mut hasher = Sha3_256::new();
+                if let Some((_, node_prefix)) = prefix {
+                    hasher.update([node_prefix]);
+                }
+                let other = hex_decode(&sibling.hash);
+                if sibling.kind == "left" {
+                    hasher.update(&hash);
+                    hasher.update(&other);
+                } else {
+                    hasher.update(&other);
+                    hasher.update(&hash);
+                }
+                hash = hasher.finalize().to_vec();
+            }
+            assert_eq!(hash, hex_decode(&vector.root), "index={}", proof.index);
+        }
+    }
+
+    fn hex_decode(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn split_power_of_two() {
+        let tests = [(2, 1), (3, 2), (4, 2), (5, 4), (8, 4), (9, 8), (17, 16)];
+        for (n, want) in tests {
+            assert_eq!(split(n), want, "n={n}");
+        }
+    }
+
+    #[test]
+    fn legacy_proofs_verify() {
+        for leaves in 1..=17 {
+            let vector = generate::<Sha3_256>(Mode::Legacy, 7, leaves);
+            assert_eq!(vector.leaves.len(), leaves);
+            verify(&vector, None);
+        }
+    }
+
+    #[test]
+    fn rfc6962_proofs_verify() {
+        for leaves in 1..=17 {
+            let vector = generate::<Sha3_256>(Mode::Rfc6962, 7, leaves);
+            assert_eq!(vector.proofs.len(), leaves);
+            verify(&vector, Some((0x00, 0x01)));
+        }
+    }
+
+    #[test]
+    fn deterministic_json() {
+        let a = generate::<Sha3_256>(Mode::Rfc6962, 42, 5).to_json();
+        let b = generate::<Sha3_256>(Mode::Rfc6962, 42, 5).to_json();
+        let c = generate::<Sha3_256>(Mode::Rfc6962, 43, 5).to_json();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.contains(r#""mode": "rfc6962""#));
+    }
+}
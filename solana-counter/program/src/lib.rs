@@ -10,26 +10,138 @@ use solana_program::{
     entrypoint,
     entrypoint::ProgramResult as Result,
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError as Error,
     pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
 };
 
+/// A tag of the instruction data to create the counter.
+pub const CREATE: u8 = 0;
+
 /// On-chain counter data account.
+///
+/// The legacy counter account, created before the greeting limit, only
+/// has the `u8` count.  It's still greeted up to `u8::MAX` but can't be
+/// configured, as it has no creator.
 #[derive(Debug, BorshSerialize, BorshDeserialize)]
 pub struct Counter {
     /// Number of greetings it received.
-    pub count: u8,
+    pub count: u32,
+
+    /// Maximum number of greetings it accepts, or zero for no limit
+    /// other than `u32::MAX`.
+    pub max_greetings: u32,
+
+    /// A creator who can set the `max_greetings`, or the default
+    /// pubkey for the counter not created by the `CREATE` instruction.
+    pub creator: Pubkey,
+}
+
+/// Custom errors of the counter program.
+#[derive(Debug)]
+pub enum CounterError {
+    /// The counter reached the `max_greetings`.
+    GreetingLimitReached,
+
+    /// The `max_greetings` can't be changed once it's greeted.
+    AlreadyGreeted,
+
+    /// The `max_greetings` can only be changed by the creator.
+    NotCreator,
+}
+
+/// Converts the counter errors into native `ProgramError`.
+impl From<CounterError> for Error {
+    fn from(e: CounterError) -> Error {
+        Error::Custom(e as u32)
+    }
+}
+
+impl Counter {
+    /// Size of the counter account.
+    pub const LEN: usize = 40;
+
+    /// Size of the legacy counter account.
+    pub const LEGACY_LEN: usize = 1;
+
+    /// A seed of the counter PDA, along with the creator.
+    pub const SEED: &'static [u8] = b"counter";
+
+    /// Returns the counter PDA of the `creator`.
+    pub fn address(creator: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, creator.as_ref()], program_id)
+    }
+
+    /// Deserializes the counter either in the current or in the legacy
+    /// layout, chosen by the account data length.
+    fn load(data: &[u8]) -> std::result::Result<Self, Error> {
+        match data {
+            [count] => Ok(Self {
+                count: *count as u32,
+                max_greetings: u8::MAX as u32,
+                creator: Pubkey::default(),
+            }),
+            _ => Ok(Self::try_from_slice(data)?),
+        }
+    }
+
+    /// Serializes the counter in the layout of the `data`.
+    fn store(&self, data: &mut [u8]) -> Result {
+        match data {
+            [count] => *count = self.count as u8,
+            _ => self.serialize(&mut &mut data[..])?,
+        }
+        Ok(())
+    }
+
+    /// Counts up the greeting.
+    fn greet(&mut self) -> std::result::Result<(), CounterError> {
+        let max = match self.max_greetings {
+            0 => u32::MAX,
+            max => max,
+        };
+        if self.count >= max {
+            return Err(CounterError::GreetingLimitReached);
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Checks if the `signer` is the creator of the counter.
+    fn authorize(&self, signer: &AccountInfo) -> Result {
+        if !signer.is_signer {
+            msg!("Creator should sign the configuration.");
+            return Err(Error::MissingRequiredSignature);
+        }
+        if self.creator == Pubkey::default() || self.creator != *signer.key {
+            return Err(CounterError::NotCreator.into());
+        }
+        Ok(())
+    }
 }
 
 // Declares and export the program's entrypoint.
 entrypoint!(process_instruction);
 
 /// Implements the program's entrypoint.
+///
+/// The empty instruction data greets the counter.  The four bytes
+/// little endian instruction data sets the `Counter::max_greetings`,
+/// which is only allowed to the `Counter::creator`, given as the second
+/// account, before the first greeting.  The `CREATE` tag followed by
+/// the four bytes `Counter::max_greetings` creates the counter, see
+/// `create`.
 pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    _instruction_data: &[u8],
+    instruction_data: &[u8],
 ) -> Result {
+    if let [CREATE, a, b, c, d] = instruction_data {
+        return create(program_id, accounts, u32::from_le_bytes([*a, *b, *c, *d]));
+    }
+
     // get the data account info, which is the first account.
     let iter = &mut accounts.iter();
 
@@ -52,10 +164,24 @@ pub fn process_instruction(
         return Err(Error::InvalidAccountData);
     }
 
-    // Increments and store the number of times the account has been greeted.
-    let mut counter = Counter::try_from_slice(&counter_info.data.borrow())?;
-    counter.count += 1;
-    counter.serialize(&mut &mut counter_info.data.borrow_mut()[..])?;
+    let mut counter = Counter::load(&counter_info.data.borrow())?;
+    match instruction_data {
+        [] => {
+            // Increments the number of times the account has been greeted.
+            counter.greet()?;
+        }
+        [a, b, c, d] => {
+            // Sets the greeting limit.
+            counter.authorize(next_account_info(iter)?)?;
+            if counter.count != 0 {
+                return Err(CounterError::AlreadyGreeted.into());
+            }
+            counter.max_greetings = u32::from_le_bytes([*a, *b, *c, *d]);
+            msg!("counter.max_greetings={}", counter.max_greetings);
+        }
+        _ => return Err(Error::InvalidInstructionData),
+    }
+    counter.store(&mut counter_info.data.borrow_mut())?;
 
     // I think this macro returns from the function, because
     // nothing happen, at least no counter update, if this
@@ -69,3 +195,218 @@ pub fn process_instruction(
 
     Ok(())
 }
+
+/// Creates the counter PDA of the creator with the `max_greetings`.
+///
+/// The creator is bound at the creation, so that no one can configure
+/// the counter before the creator.  The PDA is funded, allocated, and
+/// assigned separately, so that no one can block the creation by funding
+/// the PDA in advance.
+///
+/// Accounts expected:
+///
+/// 0. `[writable]` The counter PDA of the creator.
+/// 1. `[signer, writable]` The creator, who pays the rent.
+/// 2. `[]` The system program.
+fn create(program_id: &Pubkey, accounts: &[AccountInfo], max_greetings: u32) -> Result {
+    let iter = &mut accounts.iter();
+
+    let counter_info = next_account_info(iter)?;
+    let creator_info = next_account_info(iter)?;
+    if !creator_info.is_signer {
+        msg!("Creator should sign the creation.");
+        return Err(Error::MissingRequiredSignature);
+    }
+    let system_program_info = next_account_info(iter)?;
+    let (counter_key, bump) = Counter::address(creator_info.key, program_id);
+    if *counter_info.key != counter_key {
+        msg!("Counter account should be the creator's counter PDA.");
+        return Err(Error::InvalidSeeds);
+    }
+    let seeds: &[&[u8]] = &[Counter::SEED, creator_info.key.as_ref(), &[bump]];
+
+    let lamports = Rent::get()?
+        .minimum_balance(Counter::LEN)
+        .saturating_sub(counter_info.lamports());
+    if lamports > 0 {
+        invoke(
+            &system_instruction::transfer(creator_info.key, counter_info.key, lamports),
+            &[
+                creator_info.clone(),
+                counter_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(counter_info.key, Counter::LEN as u64),
+        &[counter_info.clone(), system_program_info.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(counter_info.key, program_id),
+        &[counter_info.clone(), system_program_info.clone()],
+        &[seeds],
+    )?;
+
+    let counter = Counter {
+        count: 0,
+        max_greetings,
+        creator: *creator_info.key,
+    };
+    counter.store(&mut counter_info.data.borrow_mut())?;
+    msg!("counter.max_greetings={}", counter.max_greetings);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{process_instruction, Counter, CounterError, Error};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use solana_program::{account_info::AccountInfo, clock::Epoch, pubkey::Pubkey};
+
+    fn counter_data(count: u32, max_greetings: u32) -> Vec<u8> {
+        Counter {
+            count,
+            max_greetings,
+            creator: CREATOR,
+        }
+        .try_to_vec()
+        .unwrap()
+    }
+
+    fn process(program_id: &Pubkey, data: &mut [u8], instruction_data: &[u8]) -> super::Result {
+        process_by(program_id, data, instruction_data, &CREATOR, true)
+    }
+
+    /// The creator of the counter in the tests.
+    const CREATOR: Pubkey = Pubkey::new_from_array([1; 32]);
+
+    /// Processes the instruction with the `signer` as the second account.
+    fn process_by(
+        program_id: &Pubkey,
+        data: &mut [u8],
+        instruction_data: &[u8],
+        signer: &Pubkey,
+        is_signer: bool,
+    ) -> super::Result {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            data,
+            program_id,
+            false,
+            Epoch::default(),
+        );
+        let owner = Pubkey::default();
+        let mut signer_lamports = 0;
+        let signer = AccountInfo::new(
+            signer,
+            is_signer,
+            false,
+            &mut signer_lamports,
+            &mut [],
+            &owner,
+            false,
+            Epoch::default(),
+        );
+        process_instruction(program_id, &[account, signer], instruction_data)
+    }
+
+    #[test]
+    fn greet_up_to_max_greetings() {
+        let program_id = Pubkey::new_unique();
+        let mut data = counter_data(0, 0);
+
+        // configure the limit.
+        process(&program_id, &mut data, &3u32.to_le_bytes()).unwrap();
+        for want in 1..=3 {
+            process(&program_id, &mut data, &[]).unwrap();
+            assert_eq!(Counter::try_from_slice(&data).unwrap().count, want);
+        }
+
+        // past the limit.
+        let got = process(&program_id, &mut data, &[]);
+        assert_eq!(got, Err(CounterError::GreetingLimitReached.into()));
+        assert_eq!(Counter::try_from_slice(&data).unwrap().count, 3);
+    }
+
+    #[test]
+    fn greet_without_limit_stops_at_u32_max() {
+        let program_id = Pubkey::new_unique();
+        let mut data = counter_data(u32::MAX - 1, 0);
+
+        process(&program_id, &mut data, &[]).unwrap();
+        assert_eq!(Counter::try_from_slice(&data).unwrap().count, u32::MAX);
+
+        let got = process(&program_id, &mut data, &[]);
+        assert_eq!(got, Err(CounterError::GreetingLimitReached.into()));
+    }
+
+    #[test]
+    fn max_greetings_after_greeting() {
+        let program_id = Pubkey::new_unique();
+        let mut data = counter_data(1, 0);
+
+        let got = process(&program_id, &mut data, &10u32.to_le_bytes());
+        assert_eq!(got, Err(CounterError::AlreadyGreeted.into()));
+
+        let got = process(&program_id, &mut data, &[0, 1]);
+        assert_eq!(got, Err(Error::InvalidInstructionData));
+    }
+    #[test]
+    fn max_greetings_by_creator() {
+        let program_id = Pubkey::new_unique();
+        let mut data = counter_data(0, 0);
+
+        // Not signed.
+        let got = process_by(
+            &program_id,
+            &mut data,
+            &10u32.to_le_bytes(),
+            &CREATOR,
+            false,
+        );
+        assert_eq!(got, Err(Error::MissingRequiredSignature));
+
+        // Not by others.
+        let other = Pubkey::new_unique();
+        let got = process_by(&program_id, &mut data, &10u32.to_le_bytes(), &other, true);
+        assert_eq!(got, Err(CounterError::NotCreator.into()));
+
+        process(&program_id, &mut data, &10u32.to_le_bytes()).unwrap();
+        assert_eq!(Counter::try_from_slice(&data).unwrap().max_greetings, 10);
+    }
+
+    #[test]
+    fn max_greetings_without_creator() {
+        let program_id = Pubkey::new_unique();
+        let mut data = vec![0; Counter::LEN];
+
+        // The zeroed counter, not created by the `CREATE` instruction.
+        let got = process(&program_id, &mut data, &10u32.to_le_bytes());
+        assert_eq!(got, Err(CounterError::NotCreator.into()));
+        process(&program_id, &mut data, &[]).unwrap();
+        assert_eq!(Counter::try_from_slice(&data).unwrap().count, 1);
+    }
+
+    #[test]
+    fn greet_legacy_counter_up_to_u8_max() {
+        let program_id = Pubkey::new_unique();
+        let mut data = vec![u8::MAX - 1];
+
+        process(&program_id, &mut data, &[]).unwrap();
+        assert_eq!(data, [u8::MAX]);
+
+        let got = process(&program_id, &mut data, &[]);
+        assert_eq!(got, Err(CounterError::GreetingLimitReached.into()));
+        let got = process(&program_id, &mut data, &10u32.to_le_bytes());
+        assert_eq!(got, Err(CounterError::NotCreator.into()));
+        assert_eq!(data, [u8::MAX]);
+    }
+}
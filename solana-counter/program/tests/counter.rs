@@ -0,0 +1,322 @@
+//! The counter program tests through the `solana-program-test` banks client.
+
+use borsh::BorshDeserialize;
+use solana_counter::{process_instruction, Counter, CounterError, CREATE};
+use solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestBanksClientExt};
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction::SystemError;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_sdk::{system_instruction, system_program};
+
+struct Env {
+    banks: BanksClient,
+    payer: Keypair,
+    program_id: Pubkey,
+    counter: Pubkey,
+    blockhash: Hash,
+}
+
+impl Env {
+    /// Deploys the program and creates the payer's greeting account
+    /// without the greeting limit.
+    async fn new() -> Self {
+        let program_id = Pubkey::new_unique();
+        let program = ProgramTest::new(
+            "solana_counter",
+            program_id,
+            processor!(process_instruction),
+        );
+        let (banks, payer, blockhash) = program.start().await;
+        let mut env = Self {
+            banks,
+            payer,
+            program_id,
+            counter: Pubkey::default(),
+            blockhash,
+        };
+        let ix = env.create(0, &env.payer.pubkey());
+        env.process(ix, &[]).await.unwrap();
+        env.counter = Counter::address(&env.payer.pubkey(), &program_id).0;
+        env
+    }
+
+    /// Creates the counter PDA of the `creator` with the `max_greetings`.
+    fn create(&self, max_greetings: u32, creator: &Pubkey) -> Instruction {
+        let mut data = vec![CREATE];
+        data.extend_from_slice(&max_greetings.to_le_bytes());
+        let (counter, _bump) = Counter::address(creator, &self.program_id);
+        Instruction::new_with_bytes(
+            self.program_id,
+            &data,
+            vec![
+                AccountMeta::new(counter, false),
+                AccountMeta::new(*creator, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+
+    /// Creates the zeroed `Counter` account owned by the `owner`.
+    async fn create_account(&mut self, owner: &Pubkey) -> Pubkey {
+        let account = Keypair::new();
+        let ix = system_instruction::create_account(
+            &self.payer.pubkey(),
+            &account.pubkey(),
+            Rent::default().minimum_balance(Counter::LEN),
+            Counter::LEN as u64,
+            owner,
+        );
+        self.process(ix, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    fn greet(&self, counter: &Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            self.program_id,
+            &[],
+            vec![AccountMeta::new(*counter, false)],
+        )
+    }
+
+    /// Sets the `max_greetings`, signed by the `creator`.
+    fn set_max_greetings(&self, max_greetings: u32, creator: &Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            self.program_id,
+            &max_greetings.to_le_bytes(),
+            vec![
+                AccountMeta::new(self.counter, false),
+                AccountMeta::new_readonly(*creator, true),
+            ],
+        )
+    }
+
+    /// Signs the transaction with the new blockhash, so that the same
+    /// instruction can be sent multiple times.
+    async fn transaction(&mut self, ix: Instruction, signers: &[&Keypair]) -> Transaction {
+        self.blockhash = self
+            .banks
+            .get_new_latest_blockhash(&self.blockhash)
+            .await
+            .unwrap();
+        Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.payer.pubkey()),
+            &[&[&self.payer], signers].concat(),
+            self.blockhash,
+        )
+    }
+
+    async fn process(
+        &mut self,
+        ix: Instruction,
+        signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        let tx = self.transaction(ix, signers).await;
+        self.banks
+            .process_transaction(tx)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    /// Returns the program logs of the transaction through the simulation.
+    async fn logs(&mut self, ix: Instruction) -> Vec<String> {
+        self.logs_by(ix, &[]).await
+    }
+
+    /// Returns the program logs of the transaction signed by the `signers`
+    /// through the simulation.
+    async fn logs_by(&mut self, ix: Instruction, signers: &[&Keypair]) -> Vec<String> {
+        let tx = self.transaction(ix, signers).await;
+        let got = self.banks.simulate_transaction(tx).await.unwrap();
+        got.simulation_details.unwrap().logs
+    }
+
+    async fn counter(&mut self) -> Counter {
+        let account = self.banks.get_account(self.counter).await.unwrap().unwrap();
+        Counter::try_from_slice(&account.data).unwrap()
+    }
+
+    /// Creates the new keypair funded by the payer.
+    async fn funded_keypair(&mut self) -> Keypair {
+        let keypair = Keypair::new();
+        let ix =
+            system_instruction::transfer(&self.payer.pubkey(), &keypair.pubkey(), 1_000_000_000);
+        self.process(ix, &[]).await.unwrap();
+        keypair
+    }
+}
+
+fn custom_error(error: CounterError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn greet() {
+    let mut env = Env::new().await;
+    assert_eq!(env.counter().await.count, 0);
+
+    for want in 1..=3 {
+        let ix = env.greet(&env.counter);
+        env.process(ix, &[]).await.unwrap();
+        assert_eq!(env.counter().await.count, want);
+    }
+
+    let ix = env.greet(&env.counter);
+    let logs = env.logs(ix).await;
+    assert!(
+        logs.contains(&"Program log: counter.count=4".to_string()),
+        "{logs:?}"
+    );
+    // The simulation doesn't update the account.
+    assert_eq!(env.counter().await.count, 3);
+}
+
+#[tokio::test]
+async fn create() {
+    let mut env = Env::new().await;
+
+    let creator = env.funded_keypair().await;
+    let ix = env.create(3, &creator.pubkey());
+    let logs = env.logs_by(ix.clone(), &[&creator]).await;
+    assert!(
+        logs.contains(&"Program log: counter.max_greetings=3".to_string()),
+        "{logs:?}"
+    );
+    env.process(ix, &[&creator]).await.unwrap();
+
+    env.counter = Counter::address(&creator.pubkey(), &env.program_id).0;
+    let account = env.banks.get_account(env.counter).await.unwrap().unwrap();
+    assert_eq!(account.owner, env.program_id);
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(Counter::LEN)
+    );
+    let counter = env.counter().await;
+    assert_eq!(
+        (counter.count, counter.max_greetings, counter.creator),
+        (0, 3, creator.pubkey())
+    );
+}
+
+#[tokio::test]
+async fn create_funded_counter() {
+    let mut env = Env::new().await;
+
+    // Someone funds the creator's counter PDA in advance.
+    let creator = env.funded_keypair().await;
+    let (counter, _bump) = Counter::address(&creator.pubkey(), &env.program_id);
+    let lamports = Rent::default().minimum_balance(0);
+    let ix = system_instruction::transfer(&env.payer.pubkey(), &counter, lamports);
+    env.process(ix, &[]).await.unwrap();
+
+    // The creator only tops up the rent.
+    let ix = env.create(3, &creator.pubkey());
+    env.process(ix, &[&creator]).await.unwrap();
+    env.counter = counter;
+    let account = env.banks.get_account(counter).await.unwrap().unwrap();
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(Counter::LEN)
+    );
+    assert_eq!(env.counter().await.creator, creator.pubkey());
+}
+
+#[tokio::test]
+async fn create_twice() {
+    let mut env = Env::new().await;
+
+    let payer = env.payer.pubkey();
+    let ix = env.create(3, &payer);
+    let got = env.process(ix, &[]).await;
+    assert_eq!(
+        got,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(SystemError::AccountAlreadyInUse as u32),
+        ))
+    );
+    assert_eq!(env.counter().await.max_greetings, 0);
+}
+
+#[tokio::test]
+async fn create_counter_of_other_creator() {
+    let mut env = Env::new().await;
+
+    let creator = env.funded_keypair().await;
+    let mut ix = env.create(3, &creator.pubkey());
+    ix.accounts[0].pubkey = Counter::address(&Pubkey::new_unique(), &env.program_id).0;
+    let got = env.process(ix, &[&creator]).await;
+    assert_eq!(
+        got,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::InvalidSeeds,
+        ))
+    );
+}
+
+#[tokio::test]
+async fn greet_up_to_max_greetings() {
+    let mut env = Env::new().await;
+
+    let payer = env.payer.pubkey();
+    let ix = env.set_max_greetings(2, &payer);
+    let logs = env.logs(ix.clone()).await;
+    assert!(
+        logs.contains(&"Program log: counter.max_greetings=2".to_string()),
+        "{logs:?}"
+    );
+    env.process(ix, &[]).await.unwrap();
+    let counter = env.counter().await;
+    assert_eq!((counter.max_greetings, counter.creator), (2, payer));
+
+    for _ in 0..2 {
+        let ix = env.greet(&env.counter);
+        env.process(ix, &[]).await.unwrap();
+    }
+    let ix = env.greet(&env.counter);
+    let got = env.process(ix, &[]).await;
+    assert_eq!(got, Err(custom_error(CounterError::GreetingLimitReached)));
+
+    let ix = env.set_max_greetings(10, &payer);
+    let got = env.process(ix, &[]).await;
+    assert_eq!(got, Err(custom_error(CounterError::AlreadyGreeted)));
+
+    let counter = env.counter().await;
+    assert_eq!((counter.count, counter.max_greetings), (2, 2));
+}
+
+#[tokio::test]
+async fn greet_other_program_account() {
+    let mut env = Env::new().await;
+
+    let other = env.create_account(&Pubkey::new_unique()).await;
+    let ix = env.greet(&other);
+    let got = env.process(ix, &[]).await;
+    assert_eq!(
+        got,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::IncorrectProgramId,
+        ))
+    );
+}
+
+#[tokio::test]
+async fn set_max_greetings_by_other_than_creator() {
+    let mut env = Env::new().await;
+
+    let payer = env.payer.pubkey();
+    let ix = env.set_max_greetings(2, &payer);
+    env.process(ix, &[]).await.unwrap();
+
+    let other = Keypair::new();
+    let ix = env.set_max_greetings(10, &other.pubkey());
+    let got = env.process(ix, &[&other]).await;
+    assert_eq!(got, Err(custom_error(CounterError::NotCreator)));
+    assert_eq!(env.counter().await.max_greetings, 2);
+}
@@ -20,20 +20,30 @@ import * as yaml from "yaml";
 
 class Counter {
   count = 0;
-  constructor(fields: {count: number} | undefined = undefined) {
+  max_greetings = 0;
+  creator = new Uint8Array(32);
+  constructor(fields: {count: number, max_greetings: number, creator: Uint8Array} | undefined = undefined) {
     if (fields) {
       this.count = fields.count;
+      this.max_greetings = fields.max_greetings;
+      this.creator = fields.creator;
     }
   }
   //@ts-expect-error missing types
   static SCHEMA = new Map([
-    [Counter, {kind: 'struct', fields: [['count', 'u8']]}],
+    [Counter, {kind: 'struct', fields: [
+      ['count', 'u32'],
+      ['max_greetings', 'u32'],
+      ['creator', [32]],
+    ]}],
   ]);
   static SPACE = borsh.serialize(
     Counter.SCHEMA,
     new Counter(),
   ).length;
   static SEED = "counter";
+  static CREATE = 0;
+  static MAX_GREETINGS = 300;
   static NUMBER_OF_SIGNATURES = 1;
 }
 
@@ -72,7 +82,7 @@ async function main() {
   console.log("program is loaded on-chain and is a valid executable");
 
   // Gets the counter Id.
-  const counterId = await getCounterId(payer, Counter.SEED, programId)
+  const counterId = await getCounterId(payer, Counter.SEED, programId);
   console.log("counterId:", counterId.toBase58());
 
   // airdrop the payer in case there is not enough balance.
@@ -85,19 +95,20 @@ async function main() {
     console.log("counter state is on-chain");
   } else {
     console.log(`counter state (Id=${counterId}) need to be created`);
-    const tx = await createCounter(conn, payer, Counter.SPACE,
-                                   Counter.SEED, counterId, programId);
+    const tx = await createCounter(conn, payer, counterId, programId,
+                                   Counter.MAX_GREETINGS);
     console.log("transaction sig:", tx);
   }
 
-  // call the counter program until it's wrap around.
+  // call the counter program until it reaches the greeting limit.
   let counter = await getCounter(conn, counterId);
   console.log(`start of the counter=${counter}`);
   while (true) {
-    await incrementCounter(conn, payer, counterId, programId);
-    counter = await getCounter(conn, counterId);
-    if (counter == 0) {
-      console.log("counter wrapped around. Let's finish the call.");
+    try {
+      await incrementCounter(conn, payer, counterId, programId);
+    } catch (e) {
+      counter = await getCounter(conn, counterId);
+      console.log(`\ncounter reached the limit(${counter}). Let's finish the call.`);
       break;
     }
     process.stdout.write(".");
@@ -162,11 +173,11 @@ async function getCounterId(
   seed: string,
   programId: PublicKey,
 ): Promise<PublicKey> {
-  return await PublicKey.createWithSeed(
-    payer.publicKey,
-    seed,
+  const [counterId] = await PublicKey.findProgramAddress(
+    [Buffer.from(seed), payer.publicKey.toBuffer()],
     programId,
   );
+  return counterId;
 }
 
 async function checkCounter(
@@ -190,20 +201,20 @@ async function airdropPayer(conn: Connection, payer: Keypair, amount: number) {
 }
 
 async function createCounter(
-  conn: Connection, payer: Keypair, space: number,
-  seed: string, counterId: PublicKey, programId: PublicKey,
+  conn: Connection, payer: Keypair, counterId: PublicKey,
+  programId: PublicKey, maxGreetings: number,
 ): Promise<TransactionSignature> {
-  const lamports = await conn.getMinimumBalanceForRentExemption(space);
-
+  // Creates the counter PDA with the greeting limit, which makes the
+  // payer the creator of the counter.
   const tx = new Transaction().add(
-    SystemProgram.createAccountWithSeed({
-      fromPubkey: payer.publicKey,
-      basePubkey: payer.publicKey,
-      seed,
-      newAccountPubkey: counterId,
-      lamports,
-      space,
+    new TransactionInstruction({
+      keys: [
+        {pubkey: counterId, isSigner: false, isWritable: true},
+        {pubkey: payer.publicKey, isSigner: true, isWritable: true},
+        {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+      ],
       programId,
+      data: Buffer.concat([Buffer.from([Counter.CREATE]), u32(maxGreetings)]),
     }),
   );
   const signers = [payer];
@@ -242,6 +253,12 @@ async function incrementCounter(
   );
 }
 
+function u32(value: number): Buffer {
+  const data = Buffer.alloc(4);
+  data.writeUInt32LE(value);
+  return data;
+}
+
 async function getConfig(): Promise<any> {
   const CONFIG_FILE_PATH = path.resolve(
     os.homedir(),
@@ -6,7 +6,8 @@ use crate::processor::Processor;
 
 entrypoint!(process_instruction);
 
-fn process_instruction(
+/// Processes the escrow program instruction.
+pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
@@ -6,6 +6,18 @@ pub(crate) enum EscrowError {
     NotRentExempt,
     ExpectedAmountMismatch,
     AmountOverflow,
+    MakerNotApproved,
+    RegistryFull,
+    InvalidFee,
+    EscrowExpired,
+    TakerNotAllowed,
+    FeeTooHigh,
+    InvalidDelegate,
+    InsufficientDelegatedAmount,
+    InvalidTimelock,
+    TimelockNotElapsed,
+    PaymentMismatch,
+    PaymentRequired,
 }
 
 /// Converts the escrow errors into native `ProgramError`.
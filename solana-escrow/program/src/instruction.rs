@@ -1,11 +1,22 @@
 use std::convert::TryInto;
 use std::fmt::{self, Debug};
 
-use solana_program::{msg, program_error::ProgramError};
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
 
 use crate::error::EscrowError::InvalidInstruction;
+use crate::state::Registry;
+
+/// A marker bit of the instruction version byte, e.g. `0x81` for v1.
+///
+/// The version byte is placed ahead of the tag.  The legacy clients
+/// send the tag as the first byte, which is always below the marker,
+/// and it's parsed as v0.
+const VERSION_MARKER: u8 = 0x80;
 
 /// Instructions of the escrow program.
+///
+/// The fields only available in the v1 encoding take the default
+/// values for the v0 encoding, e.g. no expiry, any taker, and any fee.
 pub(crate) enum Instruction {
     /// Start the trade by creating and populating an escrow account and
     /// transferring authority of the given temp token account to the PDA.
@@ -13,72 +24,266 @@ pub(crate) enum Instruction {
     /// Accounts expected:
     ///
     /// 0. `[signer]`   The account of the person initiating the escrow.
-    /// 1. `[writable]` Temporary token account that should be created
+    /// 1. `[signer]`   The payer of the rent and the fees, e.g. the
+    ///                 relayer.  It can be the same as the initializer.
+    ///                 The rent is given back to the payer on exchange.
+    /// 2. `[writable]` Temporary token account that should be created
     ///                 prior to this instruction and authorized by the
     ///                 initializer., e.g. Alice.
-    /// 2. `[]`         The initializer's token account for the token
+    /// 3. `[]`         The initializer's token account for the token
     ///                 they will receive should the trade go through.
-    /// 3. `[writable]` The escrow account, it will hold all necesssary
+    /// 4. `[writable]` The escrow account, it will hold all necesssary
     ///                 info about the trade.
-    /// 4. `[]`         The rent sysvar.
-    /// 5. `[]`         The token program.
+    /// 5. `[]`         The rent sysvar.
+    /// 6. `[]`         The token program.
+    /// 7. `[]`         (Optional) The maker registry of the permissioned
+    ///                 market.  Only the listed makers can initialize
+    ///                 the escrow under the registry.
     InitEscrow {
         /// The amount party A expects to receive of token *Y*.
         amount: u64,
+        /// (v1) The unix timestamp after which the escrow can't be
+        /// taken, or zero for no expiry.
+        expiry: i64,
+        /// (v1) The only taker allowed to take the escrow, or the
+        /// default pubkey for any taker.
+        taker: Pubkey,
     },
     /// Accepts a trace.
     ///
     /// Accounts expected:
     ///
-    /// 0. `[signer]`   The account of the person taking the trade. e.g. Bob.
+    /// 0. `[signer]`   The account of the person taking the trade. e.g. Bob,
+    ///                 or the SPL token delegate of the account 1, e.g.
+    ///                 the custodial wallet settling on behalf of Bob.
     /// 1. `[writable]` The taker's token account for the token they send.
     /// 2. `[writable]` The taker's token account for the token they will
     ///                 receive.
     /// 3. `[writable]` The PDA's temp token account to get tokens from
     ///                 and eventually close.
-    /// 4. `[writable]` The initializer's main account to send their rent
-    ///                 fees back.
+    /// 4. `[writable]` The rent payer's account given at `InitEscrow` to
+    ///                 send the rent fees back.
     /// 5. `[writable]` The initializer's token account that will receive
     ///                 tokens.
     /// 6. `[writable]` The escrow account holding the escrow state.
     /// 7. `[]`         The token program.
     /// 8. `[]`         The PDA account.
+    /// 9. `[]`         (Registry only) The maker registry of the escrow.
+    /// 10. `[writable]` (Registry only) The fee collector's token account
+    ///                 for the token *Y* to receive the registry fee.
     Exchange {
         /// The amount the taker expects to be paid in the other token,
         /// as a u64 because that's the max possible supply of a token.
         amount: u64,
+        /// (v1) The maximum registry fee in basis points the taker
+        /// accepts.
+        max_fee_bps: u16,
+        /// (v1) The amount of token *Y* the taker agreed to pay, which
+        /// should match the current escrow terms, e.g. not to be
+        /// front-run by `UpdateEscrow`.  The v0 taker, without the
+        /// payment, can't take the updated escrow.
+        payment: Option<u64>,
+    },
+    /// Initializes the maker registry of the permissioned market.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]`   The admin of the registry.
+    /// 1. `[writable]` The registry account, owned by this program.
+    /// 2. `[]`         The fee collector, who owns the fee token accounts.
+    /// 3. `[]`         The rent sysvar.
+    InitRegistry {
+        /// The registry fee in basis points of the token *Y*.
+        fee_bps: u16,
+    },
+    /// Adds the maker to the registry.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]`   The admin of the registry.
+    /// 1. `[writable]` The registry account.
+    AddMaker {
+        /// The maker to be listed.
+        maker: Pubkey,
+    },
+    /// Removes the maker from the registry.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]`   The admin of the registry.
+    /// 1. `[writable]` The registry account.
+    RemoveMaker {
+        /// The maker to be delisted.
+        maker: Pubkey,
+    },
+    /// Updates the amount the initializer expects, before any taker
+    /// takes the escrow.  Only the v1 `Exchange` with the payment can
+    /// take the updated escrow.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]`   The initializer of the escrow.
+    /// 1. `[writable]` The escrow account holding the escrow state.
+    UpdateEscrow {
+        /// The new amount party A expects to receive of token *Y*.
+        new_expected_amount: u64,
+    },
+    /// Initializes the program-wide config PDA with the admin who can
+    /// force-close the stuck escrows.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The admin, who pays the config rent.
+    ///                 It should be the upgrade authority of the program.
+    /// 1. `[writable]` The config PDA derived from `Config::SEED`.
+    /// 2. `[]`         The system program.
+    /// 3. `[]`         The program data account of this program.
+    InitConfig {
+        /// The seconds after the escrow creation the admin needs to
+        /// wait before force-closing it, at least `Config::MIN_TIMELOCK`.
+        timelock: i64,
     },
+    /// Force-closes the escrow stuck past the config timelock, e.g.
+    /// due to the frozen token account of the initializer, by giving
+    /// the temp token account back to the initializer.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]`   The admin of the config.
+    /// 1. `[]`         The config PDA.
+    /// 2. `[writable]` The escrow account holding the escrow state.
+    /// 3. `[writable]` The PDA's temp token account to be given back
+    ///                 to the initializer.
+    /// 4. `[writable]` The rent payer's account given at `InitEscrow` to
+    ///                 send the rent fees back.
+    /// 5. `[]`         The token program.
+    /// 6. `[]`         The PDA account.
+    ForceClose,
 }
 
 impl Debug for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::InitEscrow { amount } => f
+            Self::InitEscrow {
+                amount,
+                expiry,
+                taker,
+            } => f
                 .debug_struct("Instruction: InitEscrow")
                 .field("amount", &amount)
+                .field("expiry", &expiry)
+                .field("taker", &taker)
                 .finish(),
-            Self::Exchange { amount } => f
+            Self::Exchange {
+                amount,
+                max_fee_bps,
+                payment,
+            } => f
                 .debug_struct("Instruction: Exchange")
                 .field("amount", &amount)
+                .field("max_fee_bps", &max_fee_bps)
+                .field("payment", &payment)
+                .finish(),
+            Self::InitRegistry { fee_bps } => f
+                .debug_struct("Instruction: InitRegistry")
+                .field("fee_bps", &fee_bps)
+                .finish(),
+            Self::AddMaker { maker } => f
+                .debug_struct("Instruction: AddMaker")
+                .field("maker", &maker)
                 .finish(),
+            Self::RemoveMaker { maker } => f
+                .debug_struct("Instruction: RemoveMaker")
+                .field("maker", &maker)
+                .finish(),
+            Self::UpdateEscrow {
+                new_expected_amount,
+            } => f
+                .debug_struct("Instruction: UpdateEscrow")
+                .field("new_expected_amount", &new_expected_amount)
+                .finish(),
+            Self::InitConfig { timelock } => f
+                .debug_struct("Instruction: InitConfig")
+                .field("timelock", &timelock)
+                .finish(),
+            Self::ForceClose => f.debug_struct("Instruction: ForceClose").finish(),
         }
     }
 }
 
 impl Instruction {
     pub(crate) fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
+        let (first, rest) = input.split_first().ok_or(InvalidInstruction)?;
+
+        let ix = if *first < VERSION_MARKER {
+            Self::unpack_v0(*first, rest)?
+        } else {
+            let (tag, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+            match first & !VERSION_MARKER {
+                0 => Self::unpack_v0(*tag, rest)?,
+                1 => Self::unpack_v1(*tag, rest)?,
+                _ => return Err(InvalidInstruction.into()),
+            }
+        };
+        msg!("{:?}", ix);
+        Ok(ix)
+    }
 
+    /// Unpacks the v0 instruction.
+    fn unpack_v0(tag: u8, rest: &[u8]) -> Result<Self, ProgramError> {
         let ix = match tag {
             0 => Self::InitEscrow {
                 amount: Self::unpack_amount(rest)?,
+                expiry: 0,
+                taker: Pubkey::default(),
             },
             1 => Self::Exchange {
                 amount: Self::unpack_amount(rest)?,
+                max_fee_bps: Registry::MAX_FEE_BPS,
+                payment: None,
+            },
+            2 => Self::InitRegistry {
+                fee_bps: Self::unpack_fee_bps(rest)?,
+            },
+            3 => Self::AddMaker {
+                maker: Self::unpack_pubkey(rest)?,
+            },
+            4 => Self::RemoveMaker {
+                maker: Self::unpack_pubkey(rest)?,
+            },
+            5 => Self::UpdateEscrow {
+                new_expected_amount: Self::unpack_amount(rest)?,
             },
+            6 => Self::InitConfig {
+                timelock: Self::unpack_amount(rest)? as i64,
+            },
+            7 => Self::ForceClose,
             _ => return Err(InvalidInstruction.into()),
         };
-        msg!("{:?}", ix);
+        Ok(ix)
+    }
+
+    /// Unpacks the v1 instruction.
+    ///
+    /// `InitEscrow` takes the amount, the expiry, and the taker, and
+    /// `Exchange` takes the amount, the maximum fee, and the payment.
+    /// Others are same as v0.
+    fn unpack_v1(tag: u8, rest: &[u8]) -> Result<Self, ProgramError> {
+        let ix = match tag {
+            0 => Self::InitEscrow {
+                amount: Self::unpack_amount(rest)?,
+                expiry: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)? as i64,
+                taker: Self::unpack_pubkey(rest.get(16..).ok_or(InvalidInstruction)?)?,
+            },
+            1 => Self::Exchange {
+                amount: Self::unpack_amount(rest)?,
+                max_fee_bps: Self::unpack_fee_bps(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                payment: Some(Self::unpack_amount(
+                    rest.get(10..).ok_or(InvalidInstruction)?,
+                )?),
+            },
+            _ => return Self::unpack_v0(tag, rest),
+        };
         Ok(ix)
     }
 
@@ -91,4 +296,24 @@ impl Instruction {
 
         Ok(amount)
     }
+
+    fn unpack_fee_bps(input: &[u8]) -> Result<u16, ProgramError> {
+        let fee_bps = input
+            .get(..2)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(InvalidInstruction)?;
+
+        Ok(fee_bps)
+    }
+
+    fn unpack_pubkey(input: &[u8]) -> Result<Pubkey, ProgramError> {
+        let pubkey = input
+            .get(..32)
+            .and_then(|slice| slice.try_into().ok())
+            .map(Pubkey::new_from_array)
+            .ok_or(InvalidInstruction)?;
+
+        Ok(pubkey)
+    }
 }
@@ -8,6 +8,7 @@ mod error;
 mod instruction;
 mod processor;
 mod state;
+pub mod summary;
 
 /// An entry point of this program.
 #[cfg(not(feature = "no-entrypoint"))]
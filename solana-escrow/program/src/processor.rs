@@ -1,17 +1,23 @@
+use borsh::BorshSerialize;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
     entrypoint::ProgramResult,
     msg,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
+    program_option::COption,
     program_pack::{IsInitialized, Pack},
+    program_utils::limited_deserialize,
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
+    system_instruction,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
 use crate::error::EscrowError;
 use crate::instruction::Instruction;
-use crate::state::Escrow;
+use crate::state::{Config, Escrow, Registry};
+use crate::summary::SettlementSummary;
 
 /// A processor to handle the incoming transactions.
 pub(crate) struct Processor;
@@ -26,12 +32,43 @@ impl Processor {
         instruction_data: &[u8],
     ) -> ProgramResult {
         match Instruction::unpack(instruction_data)? {
-            Instruction::InitEscrow { amount } => {
-                Self::process_init_escrow(accounts, amount, program_id)
+            Instruction::InitEscrow {
+                amount,
+                expiry,
+                taker,
+            } => Self::process_init_escrow(accounts, amount, expiry, taker, program_id),
+            Instruction::Exchange {
+                amount,
+                max_fee_bps,
+                payment,
+            } => Self::process_exchange(accounts, amount, max_fee_bps, payment, program_id),
+            Instruction::InitRegistry { fee_bps } => {
+                Self::process_init_registry(accounts, fee_bps, program_id)
             }
-            Instruction::Exchange { amount } => {
-                Self::process_exchange(accounts, amount, program_id)
+            Instruction::AddMaker { maker } => {
+                Self::process_update_registry(accounts, program_id, |registry| {
+                    if !registry.is_listed(&maker) {
+                        if registry.makers.len() == Registry::MAX_MAKERS {
+                            return Err(EscrowError::RegistryFull.into());
+                        }
+                        registry.makers.push(maker);
+                    }
+                    Ok(())
+                })
             }
+            Instruction::RemoveMaker { maker } => {
+                Self::process_update_registry(accounts, program_id, |registry| {
+                    registry.makers.retain(|listed| *listed != maker);
+                    Ok(())
+                })
+            }
+            Instruction::UpdateEscrow {
+                new_expected_amount,
+            } => Self::process_update_escrow(accounts, new_expected_amount, program_id),
+            Instruction::InitConfig { timelock } => {
+                Self::process_init_config(accounts, timelock, program_id)
+            }
+            Instruction::ForceClose => Self::process_force_close(accounts, program_id),
         }
     }
 
@@ -39,6 +76,8 @@ impl Processor {
     fn process_init_escrow(
         accounts: &[AccountInfo],
         amount: u64,
+        expiry: i64,
+        taker: Pubkey,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
@@ -48,6 +87,13 @@ impl Processor {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
+        // The rent payer, e.g. the relayer, funds the accounts
+        // created by the client and gets the rent back on exchange.
+        let rent_payer = next_account_info(accounts_iter)?;
+        if !rent_payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
         // temp_token_account owner will be checked when we
         // transfer the token to the escrow account below.
         let temp_token_account = next_account_info(accounts_iter)?;
@@ -61,6 +107,20 @@ impl Processor {
         if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
             return Err(EscrowError::NotRentExempt.into());
         }
+        let token_program = next_account_info(accounts_iter)?;
+
+        // Only the listed makers can initialize the escrow under
+        // the registry, e.g. the permissioned market.
+        let registry_pubkey = match accounts_iter.next() {
+            None => Pubkey::default(),
+            Some(registry_account) => {
+                let registry_info = Self::unpack_registry(registry_account, program_id)?;
+                if !registry_info.is_listed(initializer.key) {
+                    return Err(EscrowError::MakerNotApproved.into());
+                }
+                *registry_account.key
+            }
+        };
 
         // Populates the escrow account as PDA.
         let mut escrow_info = Escrow::unpack_unchecked(&escrow_account.try_borrow_data()?)?;
@@ -73,6 +133,12 @@ impl Processor {
         escrow_info.temp_token_account_pubkey = *temp_token_account.key;
         escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
         escrow_info.expected_amount = amount;
+        escrow_info.registry_pubkey = registry_pubkey;
+        escrow_info.expiry = expiry;
+        escrow_info.taker_pubkey = taker;
+        escrow_info.rent_payer_pubkey = *rent_payer.key;
+        escrow_info.created_at = Clock::get()?.unix_timestamp;
+        escrow_info.is_updated = false;
 
         Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
 
@@ -87,7 +153,6 @@ impl Processor {
         // as the example of [CPI].
         //
         // [cpi]: https://paulx.dev/blog/2021/01/14/programming-on-solana-an-introduction/#cpis-part-1
-        let token_program = next_account_info(accounts_iter)?;
         let token_authority_change_ix = spl_token::instruction::set_authority(
             token_program.key,
             temp_token_account.key,
@@ -112,6 +177,8 @@ impl Processor {
     fn process_exchange(
         accounts: &[AccountInfo],
         amount_expected_by_taker: u64,
+        max_fee_bps: u16,
+        payment: Option<u64>,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
@@ -122,6 +189,8 @@ impl Processor {
             return Err(ProgramError::MissingRequiredSignature);
         }
         let takers_sending_token_account = next_account_info(accounts_iter)?;
+        let takers_sending_token_account_info =
+            spl_token::state::Account::unpack(&takers_sending_token_account.try_borrow_data()?)?;
         let takers_token_to_receive_account = next_account_info(accounts_iter)?;
 
         // Making sure the temp token account holds the exact same amount
@@ -134,14 +203,17 @@ impl Processor {
         }
 
         // Making sure the account info matches to the escrow state.
-        let initializers_main_account = next_account_info(accounts_iter)?;
+        let rent_payers_account = next_account_info(accounts_iter)?;
         let initializers_token_to_receive_account = next_account_info(accounts_iter)?;
         let escrow_account = next_account_info(accounts_iter)?;
-        let escrow_info = Escrow::unpack(&escrow_account.try_borrow_data()?)?;
+        if escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let escrow_info = Escrow::unpack_versioned(&escrow_account.try_borrow_data()?)?;
         if *pdas_temp_token_account.key != escrow_info.temp_token_account_pubkey {
             return Err(ProgramError::InvalidAccountData);
         }
-        if *initializers_main_account.key != escrow_info.initializer_pubkey {
+        if *rent_payers_account.key != escrow_info.rent_payer_pubkey {
             return Err(ProgramError::InvalidAccountData);
         }
         if *initializers_token_to_receive_account.key
@@ -150,17 +222,89 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        // Initiates CPI to [transfer] Y tokens from Bob to Alice.
+        // Making sure the taker pays what they agreed to, in case the
+        // initializer updated the escrow in between.  The v0 taker doesn't
+        // tell the payment, so it can only take the escrow never updated.
+        match payment {
+            Some(payment) if payment != escrow_info.expected_amount => {
+                return Err(EscrowError::PaymentMismatch.into());
+            }
+            None if escrow_info.is_updated => {
+                return Err(EscrowError::PaymentRequired.into());
+            }
+            _ => (),
+        }
+
+        // Checks the escrow terms set by the v1 `InitEscrow` instruction.
+        if escrow_info.expiry != 0 && Clock::get()?.unix_timestamp > escrow_info.expiry {
+            return Err(EscrowError::EscrowExpired.into());
+        }
+        // The taker pays the expected amount, including the registry fee,
+        // either as the owner or as the delegate of the sending account.
+        let taker_pubkey = Self::validate_sending_authority(
+            taker.key,
+            &takers_sending_token_account_info,
+            escrow_info.expected_amount,
+        )?;
+        if escrow_info.taker_pubkey != Pubkey::default() && taker_pubkey != escrow_info.taker_pubkey
+        {
+            return Err(EscrowError::TakerNotAllowed.into());
+        }
+
+        let token_program = next_account_info(accounts_iter)?;
+        let pda_account = next_account_info(accounts_iter)?;
+
+        // Splits the registry fee for the escrow under the registry.
+        let mut fee = 0;
+        if escrow_info.registry_pubkey != Pubkey::default() {
+            let registry_account = next_account_info(accounts_iter)?;
+            if *registry_account.key != escrow_info.registry_pubkey {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let registry_info = Self::unpack_registry(registry_account, program_id)?;
+            if registry_info.fee_bps > max_fee_bps {
+                return Err(EscrowError::FeeTooHigh.into());
+            }
+            let fee_account = next_account_info(accounts_iter)?;
+            let fee_account_info =
+                spl_token::state::Account::unpack(&fee_account.try_borrow_data()?)?;
+            if fee_account_info.owner != registry_info.fee_collector_pubkey {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            fee = registry_info.fee(escrow_info.expected_amount);
+            if fee > 0 {
+                let transfer_fee_ix = spl_token::instruction::transfer(
+                    token_program.key,
+                    takers_sending_token_account.key,
+                    fee_account.key,
+                    taker.key,
+                    &[taker.key],
+                    fee,
+                )?;
+                invoke(
+                    &transfer_fee_ix,
+                    &[
+                        takers_sending_token_account.clone(),
+                        fee_account.clone(),
+                        taker.clone(),
+                        token_program.clone(),
+                    ],
+                )?;
+            }
+        }
+        let amount_to_initializer = escrow_info.expected_amount - fee;
+
+        // Initiates CPI to [transfer] Y tokens from Bob to Alice, signed
+        // by the taker either as the owner or as the delegate.
         //
         // [transfer]: https://docs.rs/spl-token/latest/spl_token/instruction/fn.transfer.html
-        let token_program = next_account_info(accounts_iter)?;
         let transfer_to_initializer_ix = spl_token::instruction::transfer(
             token_program.key,
             takers_sending_token_account.key,
             initializers_token_to_receive_account.key,
             taker.key,
             &[taker.key],
-            escrow_info.expected_amount,
+            amount_to_initializer,
         )?;
         invoke(
             &transfer_to_initializer_ix,
@@ -175,7 +319,6 @@ impl Processor {
         // Initiates CPI to [transfer] X tokens from Alice, escrow, to Bob.
         //
         // [transfer]: https://docs.rs/spl-token/latest/spl_token/instruction/fn.transfer.html
-        let pda_account = next_account_info(accounts_iter)?;
         let (pda, bump) = Pubkey::find_program_address(&[b"escrow"], program_id);
         let transfer_to_taker_ix = spl_token::instruction::transfer(
             token_program.key,
@@ -200,7 +343,7 @@ impl Processor {
         let close_pdas_temp_acct_ix = spl_token::instruction::close_account(
             token_program.key,
             pdas_temp_token_account.key,
-            initializers_main_account.key,
+            rent_payers_account.key,
             &pda,
             &[&pda],
         )?;
@@ -208,7 +351,7 @@ impl Processor {
             &close_pdas_temp_acct_ix,
             &[
                 pdas_temp_token_account.clone(),
-                initializers_main_account.clone(),
+                rent_payers_account.clone(),
                 pda_account.clone(),
                 token_program.clone(),
             ],
@@ -216,14 +359,855 @@ impl Processor {
         )?;
 
         // Finally, close the escrow state account and retrun
-        // back the rent lamports back to the initializer.
-        **initializers_main_account.try_borrow_mut_lamports()? = initializers_main_account
+        // back the rent lamports back to the rent payer.
+        Self::close_escrow_account(escrow_account, rent_payers_account)?;
+
+        // Returns the settlement summary to the caller.
+        let summary = SettlementSummary {
+            amount_to_initializer,
+            amount_to_taker: pdas_temp_token_account_info.amount,
+            fee,
+            closed_accounts: vec![*pdas_temp_token_account.key, *escrow_account.key],
+        };
+        set_return_data(&summary.try_to_vec()?);
+
+        Ok(())
+    }
+
+    /// Closes the escrow state account and returns the rent lamports
+    /// back to the rent payer.
+    fn close_escrow_account(
+        escrow_account: &AccountInfo,
+        rent_payers_account: &AccountInfo,
+    ) -> ProgramResult {
+        **rent_payers_account.try_borrow_mut_lamports()? = rent_payers_account
             .lamports()
             .checked_add(escrow_account.lamports())
             .ok_or(EscrowError::AmountOverflow)?;
         **escrow_account.try_borrow_mut_lamports()? = 0;
         *escrow_account.try_borrow_mut_data()? = &mut [];
+        Ok(())
+    }
+
+    /// Validates the `taker` authority over the taker's sending token
+    /// account, either as the owner or as the delegate approved for the
+    /// `amount`, and returns the owner of the account.
+    fn validate_sending_authority(
+        taker: &Pubkey,
+        sending_account: &spl_token::state::Account,
+        amount: u64,
+    ) -> Result<Pubkey, ProgramError> {
+        if sending_account.owner != *taker {
+            if sending_account.delegate != COption::Some(*taker) {
+                return Err(EscrowError::InvalidDelegate.into());
+            }
+            if sending_account.delegated_amount < amount {
+                return Err(EscrowError::InsufficientDelegatedAmount.into());
+            }
+        }
+        Ok(sending_account.owner)
+    }
+
+    /// `Instruction::UpdateEscrow` processor.
+    ///
+    /// The escrow is untouched as long as it's initialized, as the
+    /// `Instruction::Exchange` closes it.  The legacy escrow can't be
+    /// updated, as it has no room to record the update.
+    fn process_update_escrow(
+        accounts: &[AccountInfo],
+        new_expected_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(accounts_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let escrow_account = next_account_info(accounts_iter)?;
+        if escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut escrow_info = Escrow::unpack(&escrow_account.try_borrow_data()?)?;
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        escrow_info.expected_amount = new_expected_amount;
+        escrow_info.is_updated = true;
+
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// `Instruction::InitConfig` processor.
+    ///
+    /// The config lives in the PDA, so there is only one config, and
+    /// one admin, per program.  Only the upgrade authority of the
+    /// program can be the admin, so that no one can front-run the
+    /// initialization after the deployment.
+    fn process_init_config(
+        accounts: &[AccountInfo],
+        timelock: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let admin = next_account_info(accounts_iter)?;
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if timelock < Config::MIN_TIMELOCK {
+            return Err(EscrowError::InvalidTimelock.into());
+        }
+        let config_account = next_account_info(accounts_iter)?;
+        let (config_pubkey, bump) = Pubkey::find_program_address(&[Config::SEED], program_id);
+        if *config_account.key != config_pubkey {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if config_account.owner == program_id {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let system_program = next_account_info(accounts_iter)?;
+        let program_data_account = next_account_info(accounts_iter)?;
+        if Self::upgrade_authority(program_data_account, program_id)? != Some(*admin.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Creates the config PDA, paid by the admin.
+        let create_config_ix = system_instruction::create_account(
+            admin.key,
+            config_account.key,
+            Rent::get()?.minimum_balance(Config::LEN),
+            Config::LEN as u64,
+            program_id,
+        );
+        invoke_signed(
+            &create_config_ix,
+            &[
+                admin.clone(),
+                config_account.clone(),
+                system_program.clone(),
+            ],
+            &[&[Config::SEED, &[bump]]],
+        )?;
+
+        let config_info = Config {
+            is_initialized: true,
+            admin_pubkey: *admin.key,
+            timelock,
+        };
+        Config::pack(config_info, &mut config_account.try_borrow_mut_data()?)?;
 
         Ok(())
     }
+
+    /// Returns the upgrade authority of the program through the
+    /// program data account.
+    fn upgrade_authority(
+        program_data_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Option<Pubkey>, ProgramError> {
+        if *program_data_account.owner != bpf_loader_upgradeable::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (program_data_pubkey, _bump) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        if *program_data_account.key != program_data_pubkey {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let data = program_data_account.try_borrow_data()?;
+        let metadata = data
+            .get(..UpgradeableLoaderState::size_of_programdata_metadata())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match limited_deserialize(metadata, metadata.len() as u64) {
+            Ok(UpgradeableLoaderState::ProgramData {
+                upgrade_authority_address,
+                ..
+            }) => Ok(upgrade_authority_address),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// `Instruction::ForceClose` processor.
+    ///
+    /// The admin can't take the tokens, as the temp token account goes
+    /// back to the initializer, the same as before `InitEscrow`.
+    fn process_force_close(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let admin = next_account_info(accounts_iter)?;
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let config_account = next_account_info(accounts_iter)?;
+        if config_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (config_pubkey, _bump) = Pubkey::find_program_address(&[Config::SEED], program_id);
+        if *config_account.key != config_pubkey {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let config_info = Config::unpack(&config_account.try_borrow_data()?)?;
+        if config_info.admin_pubkey != *admin.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Making sure the account info matches to the escrow state.
+        let escrow_account = next_account_info(accounts_iter)?;
+        if escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let escrow_info = Escrow::unpack_versioned(&escrow_account.try_borrow_data()?)?;
+        let pdas_temp_token_account = next_account_info(accounts_iter)?;
+        if *pdas_temp_token_account.key != escrow_info.temp_token_account_pubkey {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent_payers_account = next_account_info(accounts_iter)?;
+        if *rent_payers_account.key != escrow_info.rent_payer_pubkey {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let token_program = next_account_info(accounts_iter)?;
+        let pda_account = next_account_info(accounts_iter)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        if now < escrow_info.created_at.saturating_add(config_info.timelock) {
+            return Err(EscrowError::TimelockNotElapsed.into());
+        }
+
+        // Transfers the temp token's authority back from PDA to the
+        // initializer, the reverse of `InitEscrow`.
+        let (pda, bump) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let token_authority_change_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            pdas_temp_token_account.key,
+            Some(&escrow_info.initializer_pubkey),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            &pda,
+            &[&pda],
+        )?;
+        invoke_signed(
+            &token_authority_change_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump]]],
+        )?;
+
+        Self::close_escrow_account(escrow_account, rent_payers_account)?;
+
+        msg!(
+            "!!! FORCE CLOSE !!! escrow {} by admin {}: temp token account {} back to initializer {}",
+            escrow_account.key,
+            admin.key,
+            pdas_temp_token_account.key,
+            escrow_info.initializer_pubkey,
+        );
+
+        Ok(())
+    }
+
+    /// `Instruction::InitRegistry` processor.
+    fn process_init_registry(
+        accounts: &[AccountInfo],
+        fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let admin = next_account_info(accounts_iter)?;
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let registry_account = next_account_info(accounts_iter)?;
+        if registry_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let fee_collector = next_account_info(accounts_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(accounts_iter)?)?;
+        if !rent.is_exempt(registry_account.lamports(), registry_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        if fee_bps > Registry::MAX_FEE_BPS {
+            return Err(EscrowError::InvalidFee.into());
+        }
+
+        let mut registry_info = Registry::unpack_unchecked(&registry_account.try_borrow_data()?)?;
+        if registry_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        registry_info.is_initialized = true;
+        registry_info.admin_pubkey = *admin.key;
+        registry_info.fee_collector_pubkey = *fee_collector.key;
+        registry_info.fee_bps = fee_bps;
+        registry_info.makers = Vec::new();
+
+        Registry::pack(registry_info, &mut registry_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// `Instruction::AddMaker` and `Instruction::RemoveMaker` processor.
+    fn process_update_registry<F>(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        update: F,
+    ) -> ProgramResult
+    where
+        F: FnOnce(&mut Registry) -> ProgramResult,
+    {
+        let accounts_iter = &mut accounts.iter();
+
+        let admin = next_account_info(accounts_iter)?;
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let registry_account = next_account_info(accounts_iter)?;
+        let mut registry_info = Self::unpack_registry(registry_account, program_id)?;
+        if registry_info.admin_pubkey != *admin.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        update(&mut registry_info)?;
+
+        Registry::pack(registry_info, &mut registry_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    /// Unpacks the initialized registry account owned by this program.
+    fn unpack_registry(
+        registry_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Registry, ProgramError> {
+        if registry_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Registry::unpack(&registry_account.try_borrow_data()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Processor;
+    use crate::error::EscrowError;
+    use crate::state::{Config, Escrow};
+    use solana_program::{
+        account_info::AccountInfo, bpf_loader_upgradeable, clock::Epoch, entrypoint::ProgramResult,
+        program_error::ProgramError, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+        rent::Rent, sysvar,
+    };
+
+    /// An account backing the `AccountInfo` passed to the processor.
+    struct Account {
+        key: Pubkey,
+        owner: Pubkey,
+        is_signer: bool,
+        lamports: u64,
+        data: Vec<u8>,
+    }
+
+    impl Account {
+        fn new(owner: Pubkey, lamports: u64, data: Vec<u8>) -> Self {
+            Self {
+                key: Pubkey::new_unique(),
+                owner,
+                is_signer: false,
+                lamports,
+                data,
+            }
+        }
+
+        fn signer() -> Self {
+            Self {
+                is_signer: true,
+                ..Self::new(Pubkey::default(), 1_000_000_000, vec![])
+            }
+        }
+
+        /// The account at the `key`, e.g. the PDA.
+        fn at(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+            Self {
+                key,
+                ..Self::new(owner, 1, data)
+            }
+        }
+
+        /// The rent sysvar account with the default rent.
+        fn rent() -> Self {
+            let rent = Rent::default();
+            let mut data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+            data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+            data.push(rent.burn_percent);
+            Self::at(sysvar::rent::id(), sysvar::id(), data)
+        }
+
+        /// The SPL token account holding the `amount` tokens.
+        fn token(amount: u64) -> Self {
+            let mut data = vec![0; spl_token::state::Account::LEN];
+            let token = spl_token::state::Account {
+                mint: Pubkey::new_unique(),
+                owner: Pubkey::new_unique(),
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..Default::default()
+            };
+            spl_token::state::Account::pack(token, &mut data).unwrap();
+            Self::new(spl_token::id(), 1, data)
+        }
+
+        /// Updates the SPL token account state.
+        fn update_token(&mut self, f: impl FnOnce(&mut spl_token::state::Account)) {
+            let mut token = spl_token::state::Account::unpack(&self.data).unwrap();
+            f(&mut token);
+            spl_token::state::Account::pack(token, &mut self.data).unwrap();
+        }
+
+        /// The uninitialized escrow state account, with the rent exempt
+        /// lamports.
+        fn escrow(program_id: Pubkey) -> Self {
+            Self::new(
+                program_id,
+                Rent::default().minimum_balance(Escrow::LEN),
+                vec![0; Escrow::LEN],
+            )
+        }
+
+        /// Updates the escrow state.
+        fn update_escrow(&mut self, f: impl FnOnce(&mut Escrow)) {
+            let mut escrow = Escrow::unpack_unchecked(&self.data).unwrap();
+            f(&mut escrow);
+            Escrow::pack(escrow, &mut self.data).unwrap();
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                self.is_signer,
+                true,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                Epoch::default(),
+            )
+        }
+    }
+
+    /// An instruction to the processor, with the accounts in the order
+    /// of the `Instruction` docs.
+    ///
+    /// The accounts are valid up to the first CPI or sysvar access,
+    /// which isn't available in the unit tests.
+    struct Ix {
+        program_id: Pubkey,
+        accounts: Vec<Account>,
+        data: Vec<u8>,
+    }
+
+    impl Ix {
+        /// `Instruction::InitEscrow` of the 100 tokens.
+        fn init_escrow() -> Self {
+            let program_id = Pubkey::new_unique();
+            Self {
+                program_id,
+                accounts: vec![
+                    Account::signer(),
+                    Account::signer(),
+                    Account::token(100),
+                    Account::token(0),
+                    Account::escrow(program_id),
+                    Account::rent(),
+                    Account::new(Pubkey::default(), 1, vec![]),
+                ],
+                data: data(0, 100),
+            }
+        }
+
+        /// v0 `Instruction::Exchange` of the escrow holding the 50 tokens
+        /// for the 100 tokens.
+        fn exchange(amount: u64) -> Self {
+            let program_id = Pubkey::new_unique();
+            let taker = Account::signer();
+            let mut takers_sending_token_account = Account::token(100);
+            takers_sending_token_account.update_token(|token| token.owner = taker.key);
+            let pdas_temp_token_account = Account::token(50);
+            let rent_payers_account = Account::signer();
+            let initializers_token_to_receive_account = Account::token(0);
+            let mut escrow_account = Account::escrow(program_id);
+            escrow_account.update_escrow(|escrow| {
+                escrow.is_initialized = true;
+                escrow.initializer_pubkey = Pubkey::new_unique();
+                escrow.temp_token_account_pubkey = pdas_temp_token_account.key;
+                escrow.initializer_token_to_receive_account_pubkey =
+                    initializers_token_to_receive_account.key;
+                escrow.expected_amount = 100;
+                escrow.rent_payer_pubkey = rent_payers_account.key;
+            });
+            Self {
+                program_id,
+                accounts: vec![
+                    taker,
+                    takers_sending_token_account,
+                    Account::token(0),
+                    pdas_temp_token_account,
+                    rent_payers_account,
+                    initializers_token_to_receive_account,
+                    escrow_account,
+                ],
+                data: data(1, amount),
+            }
+        }
+
+        /// `Instruction::UpdateEscrow` of the initialized escrow.
+        fn update_escrow(new_expected_amount: u64) -> Self {
+            let program_id = Pubkey::new_unique();
+            let initializer = Account::signer();
+            let mut escrow_account = Account::escrow(program_id);
+            escrow_account.update_escrow(|escrow| {
+                escrow.is_initialized = true;
+                escrow.initializer_pubkey = initializer.key;
+            });
+            Self {
+                program_id,
+                accounts: vec![initializer, escrow_account],
+                data: data(5, new_expected_amount),
+            }
+        }
+
+        /// `Instruction::InitConfig` by the upgrade authority.
+        fn init_config(timelock: i64) -> Self {
+            let program_id = Pubkey::new_unique();
+            let (config, _bump) = Pubkey::find_program_address(&[Config::SEED], &program_id);
+            let mut ix = Self {
+                program_id,
+                accounts: vec![
+                    Account::signer(),
+                    Account::at(config, Pubkey::default(), vec![]),
+                    Account::new(Pubkey::default(), 1, vec![]),
+                ],
+                data: data(6, timelock as u64),
+            };
+            let admin = ix.accounts[0].key;
+            ix.accounts.push(ix.program_data(admin));
+            ix
+        }
+
+        /// The program data account with the `upgrade_authority`.
+        fn program_data(&self, upgrade_authority: Pubkey) -> Account {
+            let (program_data, _bump) = Pubkey::find_program_address(
+                &[self.program_id.as_ref()],
+                &bpf_loader_upgradeable::id(),
+            );
+            // The bincode encoding of `UpgradeableLoaderState::ProgramData`.
+            let mut data = 3u32.to_le_bytes().to_vec();
+            data.extend_from_slice(&0u64.to_le_bytes());
+            data.push(1);
+            data.extend_from_slice(upgrade_authority.as_ref());
+            Account::at(program_data, bpf_loader_upgradeable::id(), data)
+        }
+
+        /// `Instruction::ForceClose` by the config admin.
+        fn force_close() -> Self {
+            let program_id = Pubkey::new_unique();
+            let admin = Account::signer();
+            let (config, _bump) = Pubkey::find_program_address(&[Config::SEED], &program_id);
+            let mut config_account = Account::at(config, program_id, vec![0; Config::LEN]);
+            let config_info = Config {
+                is_initialized: true,
+                admin_pubkey: admin.key,
+                timelock: Config::MIN_TIMELOCK,
+            };
+            Config::pack(config_info, &mut config_account.data).unwrap();
+            let pdas_temp_token_account = Account::token(50);
+            let rent_payers_account = Account::signer();
+            let mut escrow_account = Account::escrow(program_id);
+            escrow_account.update_escrow(|escrow| {
+                escrow.is_initialized = true;
+                escrow.initializer_pubkey = Pubkey::new_unique();
+                escrow.temp_token_account_pubkey = pdas_temp_token_account.key;
+                escrow.expected_amount = 100;
+                escrow.rent_payer_pubkey = rent_payers_account.key;
+            });
+            Self {
+                program_id,
+                accounts: vec![
+                    admin,
+                    config_account,
+                    escrow_account,
+                    pdas_temp_token_account,
+                    rent_payers_account,
+                ],
+                data: vec![7],
+            }
+        }
+
+        fn process(&mut self) -> ProgramResult {
+            let accounts: Vec<_> = self.accounts.iter_mut().map(Account::info).collect();
+            Processor::process(&self.program_id, &accounts, &self.data)
+        }
+    }
+
+    /// The v0 instruction data of the `tag` and the `amount`.
+    fn data(tag: u8, amount: u64) -> Vec<u8> {
+        let mut data = vec![tag];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data
+    }
+
+    /// The v1 `Instruction::Exchange` data with the `payment`.
+    fn exchange_v1(amount: u64, payment: u64) -> Vec<u8> {
+        let mut data = vec![0x81, 1];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&payment.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn init_escrow_non_signer_initializer() {
+        let mut ix = Ix::init_escrow();
+        ix.accounts[0].is_signer = false;
+        assert_eq!(ix.process(), Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn init_escrow_non_signer_rent_payer() {
+        let mut ix = Ix::init_escrow();
+        ix.accounts[1].is_signer = false;
+        assert_eq!(ix.process(), Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn init_escrow_wrong_token_account_owner() {
+        let mut ix = Ix::init_escrow();
+        ix.accounts[3].owner = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn init_escrow_not_rent_exempt() {
+        let mut ix = Ix::init_escrow();
+        ix.accounts[4].lamports -= 1;
+        assert_eq!(ix.process(), Err(EscrowError::NotRentExempt.into()));
+    }
+
+    #[test]
+    fn init_escrow_already_initialized() {
+        let mut ix = Ix::init_escrow();
+        ix.accounts[4].data[0] = 1;
+        assert_eq!(ix.process(), Err(ProgramError::AccountAlreadyInitialized));
+    }
+
+    #[test]
+    fn exchange_non_signer_taker() {
+        let mut ix = Ix::exchange(50);
+        ix.accounts[0].is_signer = false;
+        assert_eq!(ix.process(), Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn exchange_amount_mismatch() {
+        let mut ix = Ix::exchange(49);
+        assert_eq!(
+            ix.process(),
+            Err(EscrowError::ExpectedAmountMismatch.into())
+        );
+    }
+
+    #[test]
+    fn exchange_payment_mismatch() {
+        let mut ix = Ix::exchange(50);
+        ix.data = exchange_v1(50, 99);
+        assert_eq!(ix.process(), Err(EscrowError::PaymentMismatch.into()));
+
+        // The agreed payment passes the validation.
+        ix.data = exchange_v1(50, 100);
+        assert_eq!(ix.process(), Err(ProgramError::NotEnoughAccountKeys));
+    }
+
+    #[test]
+    fn exchange_updated_escrow_without_payment() {
+        let mut ix = Ix::exchange(50);
+        ix.accounts[6].update_escrow(|escrow| escrow.is_updated = true);
+        assert_eq!(ix.process(), Err(EscrowError::PaymentRequired.into()));
+
+        // The v1 taker agreeing to the updated payment.
+        ix.data = exchange_v1(50, 100);
+        assert_eq!(ix.process(), Err(ProgramError::NotEnoughAccountKeys));
+    }
+
+    #[test]
+    fn exchange_uninitialized_escrow() {
+        let mut ix = Ix::exchange(50);
+        ix.accounts[6].data[0] = 0;
+        assert_eq!(ix.process(), Err(ProgramError::UninitializedAccount));
+    }
+
+    #[test]
+    fn exchange_fake_escrow() {
+        let mut ix = Ix::exchange(50);
+        ix.accounts[6].owner = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::IncorrectProgramId));
+    }
+
+    /// Makes the taker the delegate of the sending token account
+    /// approved for the `amount`.
+    fn delegate(ix: &mut Ix, amount: u64) {
+        let taker = ix.accounts[0].key;
+        ix.accounts[1].update_token(|token| {
+            token.owner = Pubkey::new_unique();
+            token.delegate = COption::Some(taker);
+            token.delegated_amount = amount;
+        });
+    }
+
+    #[test]
+    fn exchange_not_delegate_taker() {
+        let mut ix = Ix::exchange(50);
+        delegate(&mut ix, 100);
+        ix.accounts[0].key = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(EscrowError::InvalidDelegate.into()));
+    }
+
+    #[test]
+    fn exchange_insufficient_delegated_amount() {
+        let mut ix = Ix::exchange(50);
+        delegate(&mut ix, 99);
+        assert_eq!(
+            ix.process(),
+            Err(EscrowError::InsufficientDelegatedAmount.into())
+        );
+    }
+
+    #[test]
+    fn update_escrow_expected_amount() {
+        let mut ix = Ix::update_escrow(200);
+        assert_eq!(ix.process(), Ok(()));
+        let escrow = Escrow::unpack(&ix.accounts[1].data).unwrap();
+        assert_eq!(escrow.expected_amount, 200);
+        assert!(escrow.is_updated);
+    }
+
+    #[test]
+    fn update_escrow_non_signer_initializer() {
+        let mut ix = Ix::update_escrow(200);
+        ix.accounts[0].is_signer = false;
+        assert_eq!(ix.process(), Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn update_escrow_other_initializer() {
+        let mut ix = Ix::update_escrow(200);
+        ix.accounts[0].key = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn update_escrow_legacy_escrow() {
+        let mut ix = Ix::update_escrow(200);
+        ix.accounts[1].data.truncate(Escrow::LEGACY_LEN);
+        assert_eq!(ix.process(), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn update_escrow_taken_escrow() {
+        let mut ix = Ix::update_escrow(200);
+        ix.accounts[1].data[0] = 0;
+        assert_eq!(ix.process(), Err(ProgramError::UninitializedAccount));
+    }
+
+    #[test]
+    fn init_config_non_signer_admin() {
+        let mut ix = Ix::init_config(Config::MIN_TIMELOCK);
+        ix.accounts[0].is_signer = false;
+        assert_eq!(ix.process(), Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn init_config_short_timelock() {
+        let mut ix = Ix::init_config(Config::MIN_TIMELOCK - 1);
+        assert_eq!(ix.process(), Err(EscrowError::InvalidTimelock.into()));
+    }
+
+    #[test]
+    fn init_config_wrong_address() {
+        let mut ix = Ix::init_config(Config::MIN_TIMELOCK);
+        ix.accounts[1].key = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::InvalidSeeds));
+    }
+
+    #[test]
+    fn init_config_already_initialized() {
+        let mut ix = Ix::init_config(Config::MIN_TIMELOCK);
+        ix.accounts[1].owner = ix.program_id;
+        assert_eq!(ix.process(), Err(ProgramError::AccountAlreadyInitialized));
+    }
+
+    #[test]
+    fn init_config_not_upgrade_authority() {
+        let mut ix = Ix::init_config(Config::MIN_TIMELOCK);
+        ix.accounts[3] = ix.program_data(Pubkey::new_unique());
+        assert_eq!(ix.process(), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn init_config_fake_program_data() {
+        let mut ix = Ix::init_config(Config::MIN_TIMELOCK);
+        ix.accounts[3].key = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::InvalidSeeds));
+    }
+
+    #[test]
+    fn init_config_program_data_of_other_loader() {
+        let mut ix = Ix::init_config(Config::MIN_TIMELOCK);
+        ix.accounts[3].owner = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn force_close_non_signer_admin() {
+        let mut ix = Ix::force_close();
+        ix.accounts[0].is_signer = false;
+        assert_eq!(ix.process(), Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn force_close_other_admin() {
+        let mut ix = Ix::force_close();
+        ix.accounts[0].key = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn force_close_fake_config() {
+        let mut ix = Ix::force_close();
+        ix.accounts[1].key = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::InvalidSeeds));
+    }
+
+    #[test]
+    fn force_close_other_temp_token_account() {
+        let mut ix = Ix::force_close();
+        ix.accounts[3].key = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn force_close_other_rent_payer() {
+        let mut ix = Ix::force_close();
+        ix.accounts[4].key = Pubkey::new_unique();
+        assert_eq!(ix.process(), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn force_close_taken_escrow() {
+        let mut ix = Ix::force_close();
+        ix.accounts[2].data[0] = 0;
+        assert_eq!(ix.process(), Err(ProgramError::UninitializedAccount));
+    }
 }
@@ -11,6 +11,61 @@ pub(crate) struct Escrow {
     pub(crate) temp_token_account_pubkey: Pubkey,
     pub(crate) initializer_token_to_receive_account_pubkey: Pubkey,
     pub(crate) expected_amount: u64,
+    pub(crate) registry_pubkey: Pubkey,
+    pub(crate) expiry: i64,
+    pub(crate) taker_pubkey: Pubkey,
+    pub(crate) rent_payer_pubkey: Pubkey,
+    pub(crate) created_at: i64,
+    pub(crate) is_updated: bool,
+}
+
+impl Escrow {
+    /// Size of the legacy escrow account, created before the maker
+    /// registry and the v1 terms.
+    pub(crate) const LEGACY_LEN: usize = 105;
+
+    /// Unpacks the initialized escrow either in the current or in the
+    /// legacy layout, chosen by the account data length.
+    ///
+    /// The legacy escrow takes the v0 terms, e.g. no registry, no expiry,
+    /// and any taker, and the initializer paid the rent.  It predates the
+    /// config, so the timelock is counted from the epoch.
+    pub(crate) fn unpack_versioned(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != Escrow::LEGACY_LEN {
+            return Escrow::unpack(src);
+        }
+        let src = array_ref![src, 0, Escrow::LEGACY_LEN];
+
+        let (
+            is_initialized,
+            initializer_pubkey,
+            temp_token_account_pubkey,
+            initializer_token_to_receive_account_pubkey,
+            expected_amount,
+        ) = array_refs![src, 1, 32, 32, 32, 8];
+
+        match is_initialized {
+            [0] => return Err(ProgramError::UninitializedAccount),
+            [1] => (),
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+
+        Ok(Self {
+            is_initialized: true,
+            initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
+            temp_token_account_pubkey: Pubkey::new_from_array(*temp_token_account_pubkey),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(
+                *initializer_token_to_receive_account_pubkey,
+            ),
+            expected_amount: u64::from_le_bytes(*expected_amount),
+            registry_pubkey: Pubkey::default(),
+            expiry: 0,
+            taker_pubkey: Pubkey::default(),
+            rent_payer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
+            created_at: 0,
+            is_updated: false,
+        })
+    }
 }
 
 impl Sealed for Escrow {}
@@ -22,7 +77,7 @@ impl IsInitialized for Escrow {
 }
 
 impl Pack for Escrow {
-    const LEN: usize = 105;
+    const LEN: usize = 218;
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, Escrow::LEN];
@@ -33,7 +88,13 @@ impl Pack for Escrow {
             temp_token_account_pubkey_dst,
             initializer_token_to_receive_account_pubkey_dst,
             expected_amount_dst,
-        ) = mut_array_refs![dst, 1, 32, 32, 32, 8];
+            registry_pubkey_dst,
+            expiry_dst,
+            taker_pubkey_dst,
+            rent_payer_pubkey_dst,
+            created_at_dst,
+            is_updated_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 32, 8, 32, 32, 8, 1];
 
         let Self {
             is_initialized,
@@ -41,6 +102,12 @@ impl Pack for Escrow {
             temp_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
+            registry_pubkey,
+            expiry,
+            taker_pubkey,
+            rent_payer_pubkey,
+            created_at,
+            is_updated,
         } = self;
 
         is_initialized_dst[0] = *is_initialized as u8;
@@ -49,6 +116,12 @@ impl Pack for Escrow {
         initializer_token_to_receive_account_pubkey_dst
             .copy_from_slice(initializer_token_to_receive_account_pubkey.as_ref());
         *expected_amount_dst = expected_amount.to_le_bytes();
+        registry_pubkey_dst.copy_from_slice(registry_pubkey.as_ref());
+        *expiry_dst = expiry.to_le_bytes();
+        taker_pubkey_dst.copy_from_slice(taker_pubkey.as_ref());
+        rent_payer_pubkey_dst.copy_from_slice(rent_payer_pubkey.as_ref());
+        *created_at_dst = created_at.to_le_bytes();
+        is_updated_dst[0] = *is_updated as u8;
     }
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
@@ -60,13 +133,24 @@ impl Pack for Escrow {
             temp_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
-        ) = array_refs![src, 1, 32, 32, 32, 8];
+            registry_pubkey,
+            expiry,
+            taker_pubkey,
+            rent_payer_pubkey,
+            created_at,
+            is_updated,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 32, 8, 32, 32, 8, 1];
 
         let is_initialized = match is_initialized {
             [0] => false,
             [1] => true,
             _ => return Err(ProgramError::InvalidAccountData),
         };
+        let is_updated = match is_updated {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
 
         Ok(Self {
             is_initialized,
@@ -76,6 +160,166 @@ impl Pack for Escrow {
                 *initializer_token_to_receive_account_pubkey,
             ),
             expected_amount: u64::from_le_bytes(*expected_amount),
+            registry_pubkey: Pubkey::new_from_array(*registry_pubkey),
+            expiry: i64::from_le_bytes(*expiry),
+            taker_pubkey: Pubkey::new_from_array(*taker_pubkey),
+            rent_payer_pubkey: Pubkey::new_from_array(*rent_payer_pubkey),
+            created_at: i64::from_le_bytes(*created_at),
+            is_updated,
+        })
+    }
+}
+
+/// A maker registry of the permissioned market.
+///
+/// Only the listed makers can initialize the escrow under the
+/// registry, and the registry takes `fee_bps` of the token *Y*
+/// on the exchange.
+pub(crate) struct Registry {
+    pub(crate) is_initialized: bool,
+    pub(crate) admin_pubkey: Pubkey,
+    pub(crate) fee_collector_pubkey: Pubkey,
+    pub(crate) fee_bps: u16,
+    pub(crate) makers: Vec<Pubkey>,
+}
+
+impl Registry {
+    /// A maximum number of the listed makers.
+    pub(crate) const MAX_MAKERS: usize = 16;
+
+    /// A denominator of the `fee_bps`.
+    pub(crate) const MAX_FEE_BPS: u16 = 10_000;
+
+    /// Checks if the maker is listed in the registry.
+    pub(crate) fn is_listed(&self, maker: &Pubkey) -> bool {
+        self.makers.contains(maker)
+    }
+
+    /// Returns the registry fee for the `amount`.
+    pub(crate) fn fee(&self, amount: u64) -> u64 {
+        (amount as u128 * self.fee_bps as u128 / Self::MAX_FEE_BPS as u128) as u64
+    }
+}
+
+impl Sealed for Registry {}
+
+impl IsInitialized for Registry {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Registry {
+    const LEN: usize = 580;
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Registry::LEN];
+
+        let (
+            is_initialized_dst,
+            admin_pubkey_dst,
+            fee_collector_pubkey_dst,
+            fee_bps_dst,
+            makers_len_dst,
+            makers_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 2, 1, 32 * Registry::MAX_MAKERS];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        admin_pubkey_dst.copy_from_slice(self.admin_pubkey.as_ref());
+        fee_collector_pubkey_dst.copy_from_slice(self.fee_collector_pubkey.as_ref());
+        *fee_bps_dst = self.fee_bps.to_le_bytes();
+        makers_len_dst[0] = self.makers.len() as u8;
+        makers_dst.fill(0);
+        for (dst, maker) in makers_dst.chunks_exact_mut(32).zip(&self.makers) {
+            dst.copy_from_slice(maker.as_ref());
+        }
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Registry::LEN];
+
+        let (is_initialized, admin_pubkey, fee_collector_pubkey, fee_bps, makers_len, makers) =
+            array_refs![src, 1, 32, 32, 2, 1, 32 * Registry::MAX_MAKERS];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let makers_len = makers_len[0] as usize;
+        if makers_len > Registry::MAX_MAKERS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            is_initialized,
+            admin_pubkey: Pubkey::new_from_array(*admin_pubkey),
+            fee_collector_pubkey: Pubkey::new_from_array(*fee_collector_pubkey),
+            fee_bps: u16::from_le_bytes(*fee_bps),
+            makers: makers
+                .chunks_exact(32)
+                .take(makers_len)
+                .map(|maker| Pubkey::new_from_array(*array_ref![maker, 0, 32]))
+                .collect(),
+        })
+    }
+}
+
+/// A program-wide config, stored in the PDA derived from
+/// `Config::SEED`.
+///
+/// The admin can force-close the escrow stuck for longer than
+/// the `timelock` seconds since its creation.
+pub(crate) struct Config {
+    pub(crate) is_initialized: bool,
+    pub(crate) admin_pubkey: Pubkey,
+    pub(crate) timelock: i64,
+}
+
+impl Config {
+    /// A seed of the config PDA.
+    pub(crate) const SEED: &'static [u8] = b"config";
+
+    /// A minimum timelock in seconds, e.g. 30 days.
+    pub(crate) const MIN_TIMELOCK: i64 = 30 * 24 * 60 * 60;
+}
+
+impl Sealed for Config {}
+
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Config {
+    const LEN: usize = 41;
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Config::LEN];
+
+        let (is_initialized_dst, admin_pubkey_dst, timelock_dst) = mut_array_refs![dst, 1, 32, 8];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        admin_pubkey_dst.copy_from_slice(self.admin_pubkey.as_ref());
+        *timelock_dst = self.timelock.to_le_bytes();
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Config::LEN];
+
+        let (is_initialized, admin_pubkey, timelock) = array_refs![src, 1, 32, 8];
+
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Self {
+            is_initialized,
+            admin_pubkey: Pubkey::new_from_array(*admin_pubkey),
+            timelock: i64::from_le_bytes(*timelock),
         })
     }
 }
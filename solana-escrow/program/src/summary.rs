@@ -0,0 +1,25 @@
+//! A settlement summary returned by the `Exchange` instruction.
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+/// A settlement summary of the `Exchange` instruction.
+///
+/// It's borsh-encoded and returned through the [return data], so that
+/// CPI callers and simulators can consume the result programmatically.
+///
+/// [return data]: https://docs.rs/solana-program/latest/solana_program/program/fn.set_return_data.html
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct SettlementSummary {
+    /// The amount of the token *Y* sent from the taker to the initializer,
+    /// net of the `fee`.
+    pub amount_to_initializer: u64,
+
+    /// The amount of the token *X* sent from the escrow to the taker.
+    pub amount_to_taker: u64,
+
+    /// The registry fee of the token *Y* taken in the permissioned market.
+    pub fee: u64,
+
+    /// The accounts closed by the exchange.
+    pub closed_accounts: Vec<Pubkey>,
+}
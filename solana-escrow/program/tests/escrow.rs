@@ -0,0 +1,806 @@
+//! The escrow program tests through the `solana-program-test` banks
+//! client, with the real SPL token program.
+
+use borsh::BorshDeserialize;
+use solana_escrow::entrypoint::process_instruction;
+use solana_escrow::summary::SettlementSummary;
+use solana_program_test::{processor, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::clock::Clock;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::program_option::COption;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_sdk::transaction_context::TransactionReturnData;
+use solana_sdk::{system_instruction, system_program, sysvar};
+
+/// Size of the `Escrow` account data.
+const ESCROW_LEN: usize = 218;
+
+/// Size of the legacy `Escrow` account data.
+const LEGACY_ESCROW_LEN: usize = 105;
+
+/// Size of the `Registry` account data.
+const REGISTRY_LEN: usize = 580;
+
+/// A seed of the config PDA.
+const CONFIG_SEED: &[u8] = b"config";
+
+/// A minimum config timelock in seconds.
+const MIN_TIMELOCK: i64 = 30 * 24 * 60 * 60;
+
+/// `EscrowError::MakerNotApproved`.
+const MAKER_NOT_APPROVED: u32 = 4;
+
+/// `EscrowError::EscrowExpired`.
+const ESCROW_EXPIRED: u32 = 7;
+
+/// `EscrowError::TakerNotAllowed`.
+const TAKER_NOT_ALLOWED: u32 = 8;
+
+/// `EscrowError::FeeTooHigh`.
+const FEE_TOO_HIGH: u32 = 9;
+
+/// `EscrowError::TimelockNotElapsed`.
+const TIMELOCK_NOT_ELAPSED: u32 = 13;
+
+/// `EscrowError::PaymentRequired`.
+const PAYMENT_REQUIRED: u32 = 15;
+
+/// The amount of token *X* in the escrow.
+const AMOUNT_X: u64 = 50;
+
+/// The amount of token *Y* the initializer expects.
+const AMOUNT_Y: u64 = 100;
+
+struct Env {
+    context: ProgramTestContext,
+    program_id: Pubkey,
+    admin: Keypair,
+    initializer: Keypair,
+    taker: Keypair,
+    mint_x: Pubkey,
+    mint_y: Pubkey,
+    temp_token_account: Pubkey,
+    token_to_receive_account: Pubkey,
+    escrow: Pubkey,
+    rent_payer: Pubkey,
+    /// The maker registry and the fee collector's token account of
+    /// the token *Y*, for the escrow under the registry.
+    registry: Option<(Pubkey, Pubkey)>,
+}
+
+impl Env {
+    /// Initializes the escrow of `AMOUNT_X` token *X* for `AMOUNT_Y`
+    /// token *Y*.
+    async fn new() -> Self {
+        let mut env = Self::start().await;
+        let ix = env.init_escrow(AMOUNT_Y);
+        env.process_by_initializer(ix).await.unwrap();
+        env
+    }
+
+    /// Deploys the program with the `admin` as the upgrade authority,
+    /// and creates the escrow accounts, but not yet initialized.
+    async fn start() -> Self {
+        let program_id = Pubkey::new_unique();
+        let admin = Keypair::new();
+        let mut program =
+            ProgramTest::new("solana_escrow", program_id, processor!(process_instruction));
+        program.add_program(
+            "spl_token",
+            spl_token::id(),
+            processor!(spl_token::processor::Processor::process),
+        );
+        let (program_data, _bump) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        let program_data_state = UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(admin.pubkey()),
+        };
+        program.add_account(
+            program_data,
+            Account::new_data(
+                1_000_000_000,
+                &program_data_state,
+                &bpf_loader_upgradeable::id(),
+            )
+            .unwrap(),
+        );
+        let context = program.start_with_context().await;
+        let mut env = Self {
+            context,
+            program_id,
+            admin,
+            initializer: Keypair::new(),
+            taker: Keypair::new(),
+            mint_x: Pubkey::default(),
+            mint_y: Pubkey::default(),
+            temp_token_account: Pubkey::default(),
+            token_to_receive_account: Pubkey::default(),
+            escrow: Pubkey::default(),
+            rent_payer: Pubkey::default(),
+            registry: None,
+        };
+        let (mint_x, mint_y) = (env.create_mint().await, env.create_mint().await);
+        let initializer = env.initializer.pubkey();
+        let temp_token_account = env.create_token_account(&mint_x, &initializer).await;
+        env.mint_to(&mint_x, &temp_token_account, AMOUNT_X).await;
+        env.token_to_receive_account = env.create_token_account(&mint_y, &initializer).await;
+        (env.mint_x, env.mint_y) = (mint_x, mint_y);
+        env.temp_token_account = temp_token_account;
+        env.escrow = env.create_account(ESCROW_LEN, &program_id).await;
+        env.rent_payer = env.payer();
+        env
+    }
+
+    fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    /// Creates the account of the `len` bytes owned by the `owner`.
+    async fn create_account(&mut self, len: usize, owner: &Pubkey) -> Pubkey {
+        let account = Keypair::new();
+        let ix = system_instruction::create_account(
+            &self.payer(),
+            &account.pubkey(),
+            Rent::default().minimum_balance(len),
+            len as u64,
+            owner,
+        );
+        self.process(ix, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    /// Creates the mint with the payer as the mint authority.
+    async fn create_mint(&mut self) -> Pubkey {
+        let mint = self
+            .create_account(spl_token::state::Mint::LEN, &spl_token::id())
+            .await;
+        let ix = spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &mint,
+            &self.payer(),
+            None,
+            0,
+        )
+        .unwrap();
+        self.process(ix, &[]).await.unwrap();
+        mint
+    }
+
+    async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let account = self
+            .create_account(spl_token::state::Account::LEN, &spl_token::id())
+            .await;
+        let ix =
+            spl_token::instruction::initialize_account(&spl_token::id(), &account, mint, owner)
+                .unwrap();
+        self.process(ix, &[]).await.unwrap();
+        account
+    }
+
+    async fn mint_to(&mut self, mint: &Pubkey, account: &Pubkey, amount: u64) {
+        let ix = spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint,
+            account,
+            &self.payer(),
+            &[],
+            amount,
+        )
+        .unwrap();
+        self.process(ix, &[]).await.unwrap();
+    }
+
+    /// Creates the taker's token accounts, the one of the `mint` holding
+    /// `AMOUNT_Y` tokens to send and the one of the token *X* to receive.
+    async fn create_taker_accounts(&mut self, mint: &Pubkey) -> (Pubkey, Pubkey) {
+        let (taker, mint_x) = (self.taker.pubkey(), self.mint_x);
+        let sending = self.create_token_account(mint, &taker).await;
+        self.mint_to(mint, &sending, AMOUNT_Y).await;
+        let receiving = self.create_token_account(&mint_x, &taker).await;
+        (sending, receiving)
+    }
+
+    /// Initializes the maker registry with the `fee_bps`, and the fee
+    /// collector's token account of the token *Y*.
+    async fn init_registry(&mut self, fee_bps: u16) {
+        let (program_id, mint_y) = (self.program_id, self.mint_y);
+        let registry = self.create_account(REGISTRY_LEN, &program_id).await;
+        let fee_collector = Pubkey::new_unique();
+        let fee_account = self.create_token_account(&mint_y, &fee_collector).await;
+        let mut data = vec![2];
+        data.extend_from_slice(&fee_bps.to_le_bytes());
+        let ix = Instruction::new_with_bytes(
+            program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(self.admin.pubkey(), true),
+                AccountMeta::new(registry, false),
+                AccountMeta::new_readonly(fee_collector, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+        );
+        let admin = Keypair::from_bytes(&self.admin.to_bytes()).unwrap();
+        self.process(ix, &[&admin]).await.unwrap();
+        self.registry = Some((registry, fee_account));
+    }
+
+    /// Lists the `maker` in the registry.
+    async fn add_maker(&mut self, maker: &Pubkey) {
+        let (registry, _fee_account) = self.registry.unwrap();
+        let mut data = vec![3];
+        data.extend_from_slice(maker.as_ref());
+        let ix = Instruction::new_with_bytes(
+            self.program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(self.admin.pubkey(), true),
+                AccountMeta::new(registry, false),
+            ],
+        );
+        let admin = Keypair::from_bytes(&self.admin.to_bytes()).unwrap();
+        self.process(ix, &[&admin]).await.unwrap();
+    }
+
+    fn config(&self) -> Pubkey {
+        Pubkey::find_program_address(&[CONFIG_SEED], &self.program_id).0
+    }
+
+    fn pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"escrow"], &self.program_id).0
+    }
+
+    fn init_escrow(&self, amount: u64) -> Instruction {
+        let mut data = vec![0];
+        data.extend_from_slice(&amount.to_le_bytes());
+        self.init_escrow_with(data)
+    }
+
+    /// `InitEscrow` with the instruction `data`, under the registry
+    /// if any.
+    fn init_escrow_with(&self, data: Vec<u8>) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.initializer.pubkey(), true),
+            AccountMeta::new(self.payer(), true),
+            AccountMeta::new(self.temp_token_account, false),
+            AccountMeta::new_readonly(self.token_to_receive_account, false),
+            AccountMeta::new(self.escrow, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ];
+        if let Some((registry, _fee_account)) = self.registry {
+            accounts.push(AccountMeta::new_readonly(registry, false));
+        }
+        Instruction::new_with_bytes(self.program_id, &data, accounts)
+    }
+
+    fn exchange(&self, sending: Pubkey, receiving: Pubkey, amount: u64) -> Instruction {
+        let mut data = vec![1];
+        data.extend_from_slice(&amount.to_le_bytes());
+        self.exchange_with(sending, receiving, data)
+    }
+
+    /// `Exchange` with the instruction `data`, paying the registry
+    /// fee if any.
+    fn exchange_with(&self, sending: Pubkey, receiving: Pubkey, data: Vec<u8>) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.taker.pubkey(), true),
+            AccountMeta::new(sending, false),
+            AccountMeta::new(receiving, false),
+            AccountMeta::new(self.temp_token_account, false),
+            AccountMeta::new(self.rent_payer, false),
+            AccountMeta::new(self.token_to_receive_account, false),
+            AccountMeta::new(self.escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(self.pda(), false),
+        ];
+        if let Some((registry, fee_account)) = self.registry {
+            accounts.push(AccountMeta::new_readonly(registry, false));
+            accounts.push(AccountMeta::new(fee_account, false));
+        }
+        Instruction::new_with_bytes(self.program_id, &data, accounts)
+    }
+
+    fn update_escrow(&self, new_expected_amount: u64) -> Instruction {
+        let mut data = vec![5];
+        data.extend_from_slice(&new_expected_amount.to_le_bytes());
+        Instruction::new_with_bytes(
+            self.program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(self.initializer.pubkey(), true),
+                AccountMeta::new(self.escrow, false),
+            ],
+        )
+    }
+
+    fn init_config(&self, admin: &Pubkey, timelock: i64) -> Instruction {
+        let mut data = vec![6];
+        data.extend_from_slice(&timelock.to_le_bytes());
+        let (program_data, _bump) = Pubkey::find_program_address(
+            &[self.program_id.as_ref()],
+            &bpf_loader_upgradeable::id(),
+        );
+        Instruction::new_with_bytes(
+            self.program_id,
+            &data,
+            vec![
+                AccountMeta::new(*admin, true),
+                AccountMeta::new(self.config(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(program_data, false),
+            ],
+        )
+    }
+
+    fn force_close(&self) -> Instruction {
+        Instruction::new_with_bytes(
+            self.program_id,
+            &[7],
+            vec![
+                AccountMeta::new_readonly(self.admin.pubkey(), true),
+                AccountMeta::new_readonly(self.config(), false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.temp_token_account, false),
+                AccountMeta::new(self.rent_payer, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(self.pda(), false),
+            ],
+        )
+    }
+
+    /// Signs the transaction with the new blockhash, so that the same
+    /// instruction can be sent multiple times.
+    async fn process(
+        &mut self,
+        ix: Instruction,
+        signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        let tx = self.transaction(ix, signers).await;
+        self.context
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    /// Simulates the transaction and returns the return data.
+    async fn simulate(
+        &mut self,
+        ix: Instruction,
+        signers: &[&Keypair],
+    ) -> Option<TransactionReturnData> {
+        let tx = self.transaction(ix, signers).await;
+        let simulation = self
+            .context
+            .banks_client
+            .simulate_transaction(tx)
+            .await
+            .unwrap();
+        assert_eq!(simulation.result, Some(Ok(())));
+        simulation.simulation_details.unwrap().return_data
+    }
+
+    async fn transaction(&mut self, ix: Instruction, signers: &[&Keypair]) -> Transaction {
+        let blockhash = self
+            .context
+            .banks_client
+            .get_new_latest_blockhash(&self.context.last_blockhash)
+            .await
+            .unwrap();
+        self.context.last_blockhash = blockhash;
+        Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.payer()),
+            &[&[&self.context.payer], signers].concat(),
+            blockhash,
+        )
+    }
+
+    async fn process_by_initializer(&mut self, ix: Instruction) -> Result<(), TransactionError> {
+        let initializer = Keypair::from_bytes(&self.initializer.to_bytes()).unwrap();
+        self.process(ix, &[&initializer]).await
+    }
+
+    /// Moves the clock forward by the `seconds`.
+    async fn warp(&mut self, seconds: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.context.set_sysvar(&clock);
+    }
+
+    async fn account(&mut self, address: Pubkey) -> Option<Account> {
+        self.context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+    }
+
+    async fn token_account(&mut self, address: Pubkey) -> spl_token::state::Account {
+        let account = self.account(address).await.unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap()
+    }
+}
+
+fn instruction_error(error: InstructionError) -> TransactionError {
+    TransactionError::InstructionError(0, error)
+}
+
+/// The v1 `InitEscrow` instruction data.
+fn init_escrow_v1(amount: u64, expiry: i64, taker: &Pubkey) -> Vec<u8> {
+    let mut data = vec![0x81, 0];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&expiry.to_le_bytes());
+    data.extend_from_slice(taker.as_ref());
+    data
+}
+
+/// The v1 `Exchange` instruction data.
+fn exchange_v1(amount: u64, max_fee_bps: u16, payment: u64) -> Vec<u8> {
+    let mut data = vec![0x81, 1];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&max_fee_bps.to_le_bytes());
+    data.extend_from_slice(&payment.to_le_bytes());
+    data
+}
+
+#[tokio::test]
+async fn init_escrow() {
+    let mut env = Env::new().await;
+
+    let temp = env.token_account(env.temp_token_account).await;
+    assert_eq!((temp.owner, temp.amount), (env.pda(), AMOUNT_X));
+    let escrow = env.account(env.escrow).await.unwrap();
+    assert_eq!(escrow.owner, env.program_id);
+    assert_eq!(escrow.data[0], 1);
+}
+
+#[tokio::test]
+async fn exchange() {
+    let mut env = Env::new().await;
+
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    env.process(ix, &[&taker]).await.unwrap();
+
+    assert_eq!(env.token_account(sending).await.amount, 0);
+    assert_eq!(env.token_account(receiving).await.amount, AMOUNT_X);
+    let token_to_receive_account = env.token_account(env.token_to_receive_account).await;
+    assert_eq!(token_to_receive_account.amount, AMOUNT_Y);
+    assert!(env.account(env.temp_token_account).await.is_none());
+    assert!(env.account(env.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn exchange_by_delegate() {
+    let mut env = Env::new().await;
+
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let custodian = Keypair::new();
+    let ix = spl_token::instruction::approve(
+        &spl_token::id(),
+        &sending,
+        &custodian.pubkey(),
+        &env.taker.pubkey(),
+        &[],
+        AMOUNT_Y + 1,
+    )
+    .unwrap();
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    env.process(ix, &[&taker]).await.unwrap();
+
+    // The custodian settles on behalf of the taker.
+    env.taker = custodian;
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    let custodian = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    env.process(ix, &[&custodian]).await.unwrap();
+
+    let sending = env.token_account(sending).await;
+    assert_eq!(sending.amount, 0);
+    assert_eq!(sending.delegate, COption::Some(custodian.pubkey()));
+    assert_eq!(sending.delegated_amount, 1);
+    let receiving = env.token_account(receiving).await;
+    assert_eq!(
+        (receiving.owner, receiving.amount),
+        (taker.pubkey(), AMOUNT_X)
+    );
+    let token_to_receive_account = env.token_account(env.token_to_receive_account).await;
+    assert_eq!(token_to_receive_account.amount, AMOUNT_Y);
+    assert!(env.account(env.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn exchange_legacy_escrow() {
+    let mut env = Env::new().await;
+
+    // The escrow written by the program before the maker registry, with
+    // the temp token account already owned by the PDA.
+    let (mint_x, pda, initializer) = (env.mint_x, env.pda(), env.initializer.pubkey());
+    env.temp_token_account = env.create_token_account(&mint_x, &pda).await;
+    let temp_token_account = env.temp_token_account;
+    env.mint_to(&mint_x, &temp_token_account, AMOUNT_X).await;
+    let mut data = vec![1];
+    data.extend_from_slice(initializer.as_ref());
+    data.extend_from_slice(temp_token_account.as_ref());
+    data.extend_from_slice(env.token_to_receive_account.as_ref());
+    data.extend_from_slice(&AMOUNT_Y.to_le_bytes());
+    let mut escrow = Account::new(
+        Rent::default().minimum_balance(LEGACY_ESCROW_LEN),
+        LEGACY_ESCROW_LEN,
+        &env.program_id,
+    );
+    escrow.data = data;
+    let lamports = escrow.lamports;
+    env.escrow = Pubkey::new_unique();
+    env.context.set_account(&env.escrow, &escrow.into());
+
+    // The rent goes back to the initializer.
+    env.rent_payer = initializer;
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    env.process(ix, &[&taker]).await.unwrap();
+
+    assert_eq!(env.token_account(sending).await.amount, 0);
+    assert_eq!(env.token_account(receiving).await.amount, AMOUNT_X);
+    let token_to_receive_account = env.token_account(env.token_to_receive_account).await;
+    assert_eq!(token_to_receive_account.amount, AMOUNT_Y);
+    assert!(env.account(env.temp_token_account).await.is_none());
+    assert!(env.account(env.escrow).await.is_none());
+    let rent = lamports + Rent::default().minimum_balance(spl_token::state::Account::LEN);
+    assert_eq!(env.account(initializer).await.unwrap().lamports, rent);
+}
+
+#[tokio::test]
+async fn exchange_under_registry() {
+    let mut env = Env::start().await;
+    env.init_registry(1_000).await;
+    let initializer = env.initializer.pubkey();
+    env.add_maker(&initializer).await;
+    let ix = env.init_escrow(AMOUNT_Y);
+    env.process_by_initializer(ix).await.unwrap();
+
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    env.process(ix, &[&taker]).await.unwrap();
+
+    // 10% of the token *Y* goes to the fee collector.
+    let (_registry, fee_account) = env.registry.unwrap();
+    assert_eq!(env.token_account(sending).await.amount, 0);
+    assert_eq!(env.token_account(receiving).await.amount, AMOUNT_X);
+    let token_to_receive_account = env.token_account(env.token_to_receive_account).await;
+    assert_eq!(token_to_receive_account.amount, AMOUNT_Y - 10);
+    assert_eq!(env.token_account(fee_account).await.amount, 10);
+    assert!(env.account(env.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn exchange_settlement_summary() {
+    let mut env = Env::start().await;
+    env.init_registry(1_000).await;
+    let initializer = env.initializer.pubkey();
+    env.add_maker(&initializer).await;
+    let ix = env.init_escrow(AMOUNT_Y);
+    env.process_by_initializer(ix).await.unwrap();
+
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    let return_data = env.simulate(ix, &[&taker]).await.unwrap();
+
+    assert_eq!(return_data.program_id, env.program_id);
+    let summary = SettlementSummary::try_from_slice(&return_data.data).unwrap();
+    assert_eq!(
+        summary,
+        SettlementSummary {
+            amount_to_initializer: AMOUNT_Y - 10,
+            amount_to_taker: AMOUNT_X,
+            fee: 10,
+            closed_accounts: vec![env.temp_token_account, env.escrow],
+        }
+    );
+}
+
+#[tokio::test]
+async fn init_escrow_by_unlisted_maker() {
+    let mut env = Env::start().await;
+    env.init_registry(1_000).await;
+
+    let ix = env.init_escrow(AMOUNT_Y);
+    let got = env.process_by_initializer(ix).await;
+    assert_eq!(
+        got,
+        Err(instruction_error(InstructionError::Custom(
+            MAKER_NOT_APPROVED
+        )))
+    );
+    let temp = env.token_account(env.temp_token_account).await;
+    assert_eq!(temp.owner, env.initializer.pubkey());
+
+    let initializer = env.initializer.pubkey();
+    env.add_maker(&initializer).await;
+    let ix = env.init_escrow(AMOUNT_Y);
+    env.process_by_initializer(ix).await.unwrap();
+    let temp = env.token_account(env.temp_token_account).await;
+    assert_eq!(temp.owner, env.pda());
+}
+
+#[tokio::test]
+async fn exchange_over_max_fee() {
+    let mut env = Env::start().await;
+    env.init_registry(1_000).await;
+    let initializer = env.initializer.pubkey();
+    env.add_maker(&initializer).await;
+    let ix = env.init_escrow(AMOUNT_Y);
+    env.process_by_initializer(ix).await.unwrap();
+
+    // The taker accepts up to 5% of the fee.
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange_with(sending, receiving, exchange_v1(AMOUNT_X, 500, AMOUNT_Y));
+    let got = env.process(ix, &[&taker]).await;
+    assert_eq!(
+        got,
+        Err(instruction_error(InstructionError::Custom(FEE_TOO_HIGH)))
+    );
+
+    let (_registry, fee_account) = env.registry.unwrap();
+    assert_eq!(env.token_account(sending).await.amount, AMOUNT_Y);
+    assert_eq!(env.token_account(fee_account).await.amount, 0);
+    assert!(env.account(env.escrow).await.is_some());
+}
+
+#[tokio::test]
+async fn exchange_after_expiry() {
+    let mut env = Env::start().await;
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    let expiry = clock.unix_timestamp + 60;
+    let ix = env.init_escrow_with(init_escrow_v1(AMOUNT_Y, expiry, &Pubkey::default()));
+    env.process_by_initializer(ix).await.unwrap();
+
+    env.warp(61).await;
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    let got = env.process(ix, &[&taker]).await;
+    assert_eq!(
+        got,
+        Err(instruction_error(InstructionError::Custom(ESCROW_EXPIRED)))
+    );
+    assert_eq!(env.token_account(sending).await.amount, AMOUNT_Y);
+    assert!(env.account(env.escrow).await.is_some());
+}
+
+#[tokio::test]
+async fn exchange_by_not_allowed_taker() {
+    let mut env = Env::start().await;
+    let allowed = Pubkey::new_unique();
+    let ix = env.init_escrow_with(init_escrow_v1(AMOUNT_Y, 0, &allowed));
+    env.process_by_initializer(ix).await.unwrap();
+
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    let got = env.process(ix, &[&taker]).await;
+    assert_eq!(
+        got,
+        Err(instruction_error(InstructionError::Custom(
+            TAKER_NOT_ALLOWED
+        )))
+    );
+    assert_eq!(env.token_account(sending).await.amount, AMOUNT_Y);
+    assert!(env.account(env.escrow).await.is_some());
+}
+
+#[tokio::test]
+async fn exchange_wrong_mint() {
+    let mut env = Env::new().await;
+
+    // The taker pays in the token other than *Y*.
+    let mint_z = env.create_mint().await;
+    let (sending, receiving) = env.create_taker_accounts(&mint_z).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    let got = env.process(ix, &[&taker]).await;
+    assert_eq!(
+        got,
+        Err(instruction_error(InstructionError::Custom(
+            spl_token::error::TokenError::MintMismatch as u32
+        )))
+    );
+
+    assert_eq!(env.token_account(sending).await.amount, AMOUNT_Y);
+    assert_eq!(env.token_account(receiving).await.amount, 0);
+    let temp = env.token_account(env.temp_token_account).await;
+    assert_eq!((temp.owner, temp.amount), (env.pda(), AMOUNT_X));
+    assert!(env.account(env.escrow).await.is_some());
+}
+
+#[tokio::test]
+async fn exchange_updated_escrow_without_payment() {
+    let mut env = Env::new().await;
+
+    // The initializer lowers the ask, but the v0 taker can't tell.
+    let ix = env.update_escrow(AMOUNT_Y - 1);
+    env.process_by_initializer(ix).await.unwrap();
+
+    let mint_y = env.mint_y;
+    let (sending, receiving) = env.create_taker_accounts(&mint_y).await;
+    let taker = Keypair::from_bytes(&env.taker.to_bytes()).unwrap();
+    let ix = env.exchange(sending, receiving, AMOUNT_X);
+    let got = env.process(ix, &[&taker]).await;
+    assert_eq!(
+        got,
+        Err(instruction_error(InstructionError::Custom(
+            PAYMENT_REQUIRED
+        )))
+    );
+    assert_eq!(env.token_account(sending).await.amount, AMOUNT_Y);
+    assert!(env.account(env.escrow).await.is_some());
+}
+
+#[tokio::test]
+async fn init_config_by_other_than_upgrade_authority() {
+    let mut env = Env::new().await;
+
+    let other = Keypair::new();
+    let ix = system_instruction::transfer(&env.payer(), &other.pubkey(), 1_000_000_000);
+    env.process(ix, &[]).await.unwrap();
+    let ix = env.init_config(&other.pubkey(), MIN_TIMELOCK);
+    let got = env.process(ix, &[&other]).await;
+    assert_eq!(
+        got,
+        Err(instruction_error(InstructionError::InvalidAccountData))
+    );
+    assert!(env.account(env.config()).await.is_none());
+}
+
+#[tokio::test]
+async fn force_close_after_timelock() {
+    let mut env = Env::new().await;
+
+    let admin = Keypair::from_bytes(&env.admin.to_bytes()).unwrap();
+    let ix = system_instruction::transfer(&env.payer(), &admin.pubkey(), 1_000_000_000);
+    env.process(ix, &[]).await.unwrap();
+    let ix = env.init_config(&admin.pubkey(), MIN_TIMELOCK);
+    env.process(ix, &[&admin]).await.unwrap();
+
+    // Not yet.
+    env.warp(MIN_TIMELOCK - 1).await;
+    let ix = env.force_close();
+    let got = env.process(ix, &[&admin]).await;
+    assert_eq!(
+        got,
+        Err(instruction_error(InstructionError::Custom(
+            TIMELOCK_NOT_ELAPSED
+        )))
+    );
+
+    env.warp(1).await;
+    let ix = env.force_close();
+    env.process(ix, &[&admin]).await.unwrap();
+
+    let temp = env.token_account(env.temp_token_account).await;
+    assert_eq!(
+        (temp.owner, temp.amount),
+        (env.initializer.pubkey(), AMOUNT_X)
+    );
+    assert!(env.account(env.escrow).await.is_none());
+}
@@ -82,6 +82,11 @@ const alice = async() => {
         isSigner: true,
         isWritable: false,
       },
+      { // Rent payer, which could be a relayer instead of alice.
+        pubkey: aliceKeypair.publicKey,
+        isSigner: true,
+        isWritable: false,
+      },
       { // temp token X account to transfer to bob's Y token.
         pubkey: tempXTokenAccountKeypair.publicKey,
         isSigner: false,
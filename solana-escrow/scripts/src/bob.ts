@@ -52,6 +52,9 @@ const bob = async () => {
     initializerYTokenAccount: new PublicKey(
       decodedEscrowLayout.initializerReceivingTokenAccountPubkey,
     ),
+    rentPayerAccountPubkey: new PublicKey(
+      decodedEscrowLayout.rentPayerPubkey,
+    ),
     expectedAmount: new BN(decodedEscrowLayout.expectedAmount, 10, "le"),
   };
   const PDA = await PublicKey.findProgramAddress(
@@ -86,8 +89,8 @@ const bob = async () => {
         isSigner: false,
         isWritable: true,
       },
-      {
-        pubkey: escrowState.initializerAccountPubkey,
+      { // Rent payer to get the rent back.
+        pubkey: escrowState.rentPayerAccountPubkey,
         isSigner: false,
         isWritable: true,
       },
@@ -23,6 +23,18 @@ export const ESCROW_ACCOUNT_DATA_LAYOUT = BufferLayout.struct([
   publicKey("initializerReceivingTokenAccountPubkey"),
   //@ts-expect-error missing types
   uint64("expectedAmount"),
+  //@ts-expect-error missing types
+  publicKey("registryPubkey"),
+  //@ts-expect-error missing types
+  uint64("expiry"),
+  //@ts-expect-error missing types
+  publicKey("takerPubkey"),
+  //@ts-expect-error missing types
+  publicKey("rentPayerPubkey"),
+  //@ts-expect-error missing types
+  uint64("createdAt"),
+  //@ts-expect-error missing types
+  BufferLayout.u8("isUpdated"),
 ]);
 
 export interface EscrowLayout {
@@ -31,6 +43,12 @@ export interface EscrowLayout {
   initializerReceivingTokenAccountPubkey: Uint8Array,
   initializerTempTokenAccountPubkey: Uint8Array,
   expectedAmount: Uint8Array,
+  registryPubkey: Uint8Array,
+  expiry: Uint8Array,
+  takerPubkey: Uint8Array,
+  rentPayerPubkey: Uint8Array,
+  createdAt: Uint8Array,
+  isUpdated: number,
 }
 
 export const getEscrowTerms = (): {